@@ -1,4 +1,4 @@
-use celrix_client::{Client, Result};
+use celrix_client::{Pool, Result};
 use hdrhistogram::Histogram;
 use rand::Rng;
 use std::sync::{Arc, Mutex};
@@ -27,6 +27,7 @@ async fn main() -> Result<()> {
     println!("Duration: {} seconds", DURATION_SECS);
     println!("--------------------------------------------------");
 
+    let pool = Pool::new("127.0.0.1:6380", TOTAL_CLIENTS);
     let start_time = Instant::now();
     let mut tasks = Vec::with_capacity(TOTAL_CLIENTS);
 
@@ -54,6 +55,7 @@ async fn main() -> Result<()> {
         let errors = errors.clone();
         let ops = ops_total.clone();
         let client_id = i;
+        let pool = pool.clone();
         
         // Determine Client Role
         // 0..100 = Heavy KV
@@ -68,7 +70,7 @@ async fn main() -> Result<()> {
         };
 
         tasks.push(tokio::spawn(async move {
-            let mut client = match Client::connect("127.0.0.1:6380").await {
+            let mut client = match pool.acquire().await {
                 Ok(c) => c,
                 Err(e) => {
                     println!("Client {} failed to connect: {}", client_id, e);
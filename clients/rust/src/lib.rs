@@ -1,12 +1,20 @@
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::io::Cursor;
+use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
 use tokio::net::TcpStream;
 use thiserror::Error;
 
+mod pipeline;
+mod pool;
+
+pub use pipeline::Pipeline;
+pub use pool::{Pool, PooledClient};
+
 const MAGIC: [u8; 4] = [0x43, 0x45, 0x4C, 0x58]; // "CELX"
 const VERSION: u8 = 1;
 const HEADER_SIZE: usize = 22;
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(5);
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -16,8 +24,44 @@ pub enum Error {
     Protocol(String),
     #[error("Server error: {0}")]
     Server(String),
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Permission denied: {0}")]
+    Permission(String),
+    #[error("Temporarily unavailable: {0}")]
+    Unavailable(String),
+    #[error("Internal server error: {0}")]
+    Internal(String),
     #[error("Connection closed")]
     ConnectionClosed,
+    #[error("Server busy, retry after {retry_after_ms}ms")]
+    Busy { retry_after_ms: u32 },
+    #[error("Operation timed out")]
+    Timeout,
+    #[error("Connection unusable after an earlier error")]
+    Poisoned,
+}
+
+impl Error {
+    /// Map a server error reply to a variant by its leading code. Command
+    /// failures (ERR, WRONGTYPE, ...) stay `Server`.
+    fn from_server(message: String) -> Self {
+        match message.split(' ').next().unwrap_or_default() {
+            "PROTOCOL" | "NOPROTO" => Error::BadRequest(message),
+            "NOTFOUND" => Error::NotFound(message),
+            "NOPERM" | "NOAUTH" | "WRONGPASS" => Error::Permission(message),
+            "RATELIMITED" | "NOQUORUM" | "STALE" => Error::Unavailable(message),
+            "INTERNAL" => Error::Internal(message),
+            _ => Error::Server(message),
+        }
+    }
+
+    /// Whether the same request may succeed if sent again later
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Error::Busy { .. } | Error::Unavailable(_) | Error::Timeout | Error::ConnectionClosed)
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -31,7 +75,17 @@ pub enum OpCode {
     Set = 0x04,
     Del = 0x05,
     Exists = 0x06,
-    
+
+    // Extended
+    MGet = 0x07,
+    MSet = 0x08,
+    MDel = 0x09,
+    Incr = 0x0A,
+    Decr = 0x0B,
+    IncrBy = 0x0C,
+    DecrBy = 0x0D,
+    Scan = 0x0E,
+
     // Responses
     Ok = 0x10,
     Error = 0x11,
@@ -39,10 +93,14 @@ pub enum OpCode {
     Nil = 0x13,
     Integer = 0x14,
     Array = 0x15,
+    Busy = 0x18,
+    Values = 0x19,
+    BatchAdded = 0x1B,
 
     // Vector
     VAdd = 0x20,
     VSearch = 0x21,
+    VAddBatch = 0x22,
 }
 
 impl OpCode {
@@ -54,17 +112,37 @@ impl OpCode {
             0x04 => Some(OpCode::Set),
             0x05 => Some(OpCode::Del),
             0x06 => Some(OpCode::Exists),
+            0x07 => Some(OpCode::MGet),
+            0x08 => Some(OpCode::MSet),
+            0x09 => Some(OpCode::MDel),
+            0x0A => Some(OpCode::Incr),
+            0x0B => Some(OpCode::Decr),
+            0x0C => Some(OpCode::IncrBy),
+            0x0D => Some(OpCode::DecrBy),
+            0x0E => Some(OpCode::Scan),
             0x10 => Some(OpCode::Ok),
             0x11 => Some(OpCode::Error),
             0x12 => Some(OpCode::Value),
             0x13 => Some(OpCode::Nil),
             0x14 => Some(OpCode::Integer),
             0x15 => Some(OpCode::Array),
+            0x18 => Some(OpCode::Busy),
+            0x19 => Some(OpCode::Values),
+            0x1B => Some(OpCode::BatchAdded),
             0x20 => Some(OpCode::VAdd),
             0x21 => Some(OpCode::VSearch),
+            0x22 => Some(OpCode::VAddBatch),
             _ => None,
         }
     }
+
+    /// Safe to resend after a connection drop without risking a double apply
+    fn is_idempotent(self) -> bool {
+        matches!(
+            self,
+            OpCode::Ping | OpCode::Get | OpCode::Exists | OpCode::MGet | OpCode::Scan | OpCode::VSearch
+        )
+    }
 }
 
 #[derive(Debug)]
@@ -76,91 +154,266 @@ pub enum Response {
     Integer(i64),
     Error(String),
     Array(Vec<Response>), // Recursive support
+    Busy { retry_after_ms: u32 },
+    Values(Vec<Option<Bytes>>), // MGET: None for missing keys
+    BatchAdded { added: u32, failed: Vec<u32> },
+}
+
+// Payload builders shared by `Client` and `Pipeline`
+
+/// [key_len][key]
+fn key_payload(key: &str) -> Bytes {
+    let mut payload = BytesMut::with_capacity(4 + key.len());
+    payload.put_u32(key.len() as u32);
+    payload.put_slice(key.as_bytes());
+    payload.freeze()
+}
+
+/// [key_len][key][value_len][value][ttl: u64, 0 = none]
+fn set_payload(key: &str, value: &str, ttl: Option<u64>) -> Bytes {
+    let mut payload = BytesMut::with_capacity(16 + key.len() + value.len());
+    payload.put_u32(key.len() as u32);
+    payload.put_slice(key.as_bytes());
+    payload.put_u32(value.len() as u32);
+    payload.put_slice(value.as_bytes());
+    payload.put_u64(ttl.unwrap_or(0));
+    payload.freeze()
+}
+
+/// [key_len][key][delta: i64]
+fn incr_by_payload(key: &str, delta: i64) -> Bytes {
+    let mut payload = BytesMut::with_capacity(12 + key.len());
+    payload.put_u32(key.len() as u32);
+    payload.put_slice(key.as_bytes());
+    payload.put_i64(delta);
+    payload.freeze()
 }
 
 pub struct Client {
+    addr: String,
     stream: BufWriter<TcpStream>,
     buffer: BytesMut,
     next_req_id: u64,
+    // Retries for BUSY responses (0 = surface Error::Busy immediately)
+    busy_retries: u32,
+    busy_backoff: Duration,
+    // Set after a transport or framing error; the stream can't be reused
+    poisoned: bool,
+    // Re-dial attempts and initial backoff after a connection error (None = off)
+    reconnect: Option<(u32, Duration)>,
+    retry_writes: bool,
+    // Limit on each request's round trip (None = wait forever)
+    timeout: Option<Duration>,
+    // One-shot override consumed by the next request
+    next_timeout: Option<Duration>,
 }
 
 impl Client {
     pub async fn connect(addr: &str) -> Result<Self> {
         let stream = TcpStream::connect(addr).await?;
         Ok(Self {
+            addr: addr.to_string(),
             stream: BufWriter::new(stream),
             buffer: BytesMut::with_capacity(8192),
             next_req_id: 1,
+            busy_retries: 0,
+            busy_backoff: Duration::from_millis(5),
+            poisoned: false,
+            reconnect: None,
+            retry_writes: false,
+            timeout: None,
+            next_timeout: None,
         })
     }
 
+    /// Re-dial the server after a connection error, trying up to `attempts`
+    /// times with exponential backoff starting at `backoff`. Idempotent
+    /// requests (PING, GET, EXISTS, MGET, SCAN, VSEARCH) are retried once on
+    /// the new connection; other requests surface the error and reconnect on
+    /// the next call.
+    pub fn with_reconnect(mut self, attempts: u32, backoff: Duration) -> Self {
+        self.reconnect = Some((attempts, backoff));
+        self
+    }
+
+    /// Also retry writes after reconnecting. Only enable this if repeating a
+    /// write that may already have been applied is harmless for your data.
+    pub fn with_retry_writes(mut self, retry: bool) -> Self {
+        self.retry_writes = retry;
+        self
+    }
+
+    /// Fail requests with `Error::Timeout` if the server hasn't answered
+    /// within `timeout`. A timed-out connection is poisoned, since the
+    /// request may have been cut off mid-frame.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Change the request timeout (None = wait forever)
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Use `timeout` for the next request only, e.g.
+    /// `client.next_timeout(Duration::from_secs(5)).vsearch(&v, 10)`
+    pub fn next_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.next_timeout = Some(timeout);
+        self
+    }
+
+    /// Address this client connects to
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// Whether a previous transport error left this connection unusable
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    /// Retry requests rejected with BUSY up to `retries` times, doubling
+    /// `backoff` each attempt (never waiting less than the server's hint)
+    pub fn with_busy_retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.busy_retries = retries;
+        self.busy_backoff = backoff;
+        self
+    }
+
+    /// Start a batch of commands sent in one write; see [`Pipeline`]
+    pub fn pipeline(&mut self) -> Pipeline<'_> {
+        Pipeline::new(self)
+    }
+
     pub async fn ping(&mut self) -> Result<()> {
-        self.send_frame(OpCode::Ping, Bytes::new()).await?;
-        match self.read_response().await? {
+        match self.request(OpCode::Ping, Bytes::new()).await? {
             Response::Pong => Ok(()),
-            Response::Error(e) => Err(Error::Server(e)),
+            Response::Error(e) => Err(Error::from_server(e)),
             _ => Err(Error::Protocol("Expected PONG".into())),
         }
     }
 
     pub async fn set(&mut self, key: &str, value: &str, ttl: Option<u64>) -> Result<()> {
-        let key_bytes = key.as_bytes();
-        let val_bytes = value.as_bytes();
-        
-        let mut payload = BytesMut::new();
-        payload.put_u32(key_bytes.len() as u32);
-        payload.put_slice(key_bytes);
-        payload.put_u32(val_bytes.len() as u32);
-        payload.put_slice(val_bytes);
-        payload.put_u64(ttl.unwrap_or(0));
-
-        self.send_frame(OpCode::Set, payload.freeze()).await?;
-        self.expect_ok().await
+        let response = self.request(OpCode::Set, set_payload(key, value, ttl)).await?;
+        Self::expect_ok(response)
     }
 
     pub async fn get(&mut self, key: &str) -> Result<Option<String>> {
-        let key_bytes = key.as_bytes();
-        let mut payload = BytesMut::new();
-        payload.put_u32(key_bytes.len() as u32);
-        payload.put_slice(key_bytes);
-
-        self.send_frame(OpCode::Get, payload.freeze()).await?;
-        match self.read_response().await? {
+        match self.request(OpCode::Get, key_payload(key)).await? {
             Response::Value(bytes) => Ok(Some(String::from_utf8_lossy(&bytes).into())),
             Response::Nil => Ok(None),
-            Response::Error(e) => Err(Error::Server(e)),
+            Response::Error(e) => Err(Error::from_server(e)),
             _ => Err(Error::Protocol("Expected Value or Nil".into())),
         }
     }
 
     pub async fn del(&mut self, key: &str) -> Result<bool> {
-        let key_bytes = key.as_bytes();
-        let mut payload = BytesMut::new();
-        payload.put_u32(key_bytes.len() as u32);
-        payload.put_slice(key_bytes);
-
-        self.send_frame(OpCode::Del, payload.freeze()).await?;
-        match self.read_response().await? {
+        match self.request(OpCode::Del, key_payload(key)).await? {
             Response::Integer(n) => Ok(n > 0),
-            Response::Error(e) => Err(Error::Server(e)),
+            Response::Error(e) => Err(Error::from_server(e)),
             _ => Err(Error::Protocol("Expected Integer".into())),
         }
     }
 
     pub async fn exists(&mut self, key: &str) -> Result<bool> {
-        let key_bytes = key.as_bytes();
+        match self.request(OpCode::Exists, key_payload(key)).await? {
+            Response::Integer(n) => Ok(n > 0),
+            Response::Error(e) => Err(Error::from_server(e)),
+            _ => Err(Error::Protocol("Expected Integer".into())),
+        }
+    }
+
+    // Multi-key and counter operations
+
+    pub async fn mget(&mut self, keys: &[&str]) -> Result<Vec<Option<String>>> {
+        // [count][key_len][key]...
         let mut payload = BytesMut::new();
-        payload.put_u32(key_bytes.len() as u32);
-        payload.put_slice(key_bytes);
+        Self::put_key_list(&mut payload, keys);
+
+        match self.request(OpCode::MGet, payload.freeze()).await? {
+            Response::Values(items) => Ok(items
+                .into_iter()
+                .map(|item| item.map(|bytes| String::from_utf8_lossy(&bytes).into()))
+                .collect()),
+            Response::Error(e) => Err(Error::from_server(e)),
+            _ => Err(Error::Protocol("Expected Values".into())),
+        }
+    }
 
-        self.send_frame(OpCode::Exists, payload.freeze()).await?;
-        match self.read_response().await? {
-            Response::Integer(n) => Ok(n > 0),
-            Response::Error(e) => Err(Error::Server(e)),
+    pub async fn mset(&mut self, pairs: &[(&str, &str)]) -> Result<()> {
+        // [count][key_len][key][value_len][value]...
+        let mut payload = BytesMut::new();
+        payload.put_u32(pairs.len() as u32);
+        for (key, value) in pairs {
+            payload.put_u32(key.len() as u32);
+            payload.put_slice(key.as_bytes());
+            payload.put_u32(value.len() as u32);
+            payload.put_slice(value.as_bytes());
+        }
+
+        let response = self.request(OpCode::MSet, payload.freeze()).await?;
+        Self::expect_ok(response)
+    }
+
+    /// Delete several keys, returning how many existed
+    pub async fn mdel(&mut self, keys: &[&str]) -> Result<u64> {
+        let mut payload = BytesMut::new();
+        Self::put_key_list(&mut payload, keys);
+
+        match self.request(OpCode::MDel, payload.freeze()).await? {
+            Response::Integer(n) => Ok(n as u64),
+            Response::Error(e) => Err(Error::from_server(e)),
             _ => Err(Error::Protocol("Expected Integer".into())),
         }
     }
 
+    pub async fn incr(&mut self, key: &str) -> Result<i64> {
+        let response = self.request(OpCode::Incr, key_payload(key)).await?;
+        Self::expect_integer(response)
+    }
+
+    pub async fn incr_by(&mut self, key: &str, delta: i64) -> Result<i64> {
+        let response = self.request(OpCode::IncrBy, incr_by_payload(key, delta)).await?;
+        Self::expect_integer(response)
+    }
+
+    /// One page of keys matching an optional glob pattern. Start with cursor 0
+    /// and stop when the returned cursor is 0; pages may be empty mid-scan.
+    pub async fn scan(
+        &mut self,
+        cursor: u64,
+        pattern: Option<&str>,
+        count: u32,
+    ) -> Result<(u64, Vec<String>)> {
+        // [cursor: u64][count: u32]([pattern_len][pattern])?
+        let mut payload = BytesMut::new();
+        payload.put_u64(cursor);
+        payload.put_u32(count);
+        if let Some(pattern) = pattern {
+            payload.put_u32(pattern.len() as u32);
+            payload.put_slice(pattern.as_bytes());
+        }
+
+        // Reply: [next_cursor, key...]
+        match self.request(OpCode::Scan, payload.freeze()).await? {
+            Response::Array(items) => {
+                let mut items = items.into_iter().map(|item| match item {
+                    Response::Value(bytes) => Ok(String::from_utf8_lossy(&bytes).into_owned()),
+                    _ => Err(Error::Protocol("Expected Value in Array".into())),
+                });
+                let next = items
+                    .next()
+                    .ok_or_else(|| Error::Protocol("Missing scan cursor".into()))??
+                    .parse()
+                    .map_err(|_| Error::Protocol("Invalid scan cursor".into()))?;
+                Ok((next, items.collect::<Result<_>>()?))
+            }
+            Response::Error(e) => Err(Error::from_server(e)),
+            _ => Err(Error::Protocol("Expected Array".into())),
+        }
+    }
+
     // Vector operations
 
     pub async fn vadd(&mut self, key: &str, vector: &[f32]) -> Result<()> {
@@ -175,8 +428,31 @@ impl Client {
             payload.put_f32(f);
         }
 
-        self.send_frame(OpCode::VAdd, payload.freeze()).await?;
-        self.expect_ok().await
+        let response = self.request(OpCode::VAdd, payload.freeze()).await?;
+        Self::expect_ok(response)
+    }
+
+    /// Add many embeddings in one request. Returns how many were added and
+    /// the indices of entries the server rejected (wrong dimension).
+    pub async fn vadd_batch(&mut self, entries: &[(&str, &[f32])]) -> Result<(u32, Vec<u32>)> {
+        let mut payload = BytesMut::new();
+
+        // [count] then [key_len][key][dim][f32...] per entry
+        payload.put_u32(entries.len() as u32);
+        for (key, vector) in entries {
+            payload.put_u32(key.len() as u32);
+            payload.put_slice(key.as_bytes());
+            payload.put_u32(vector.len() as u32);
+            for &f in vector.iter() {
+                payload.put_f32(f);
+            }
+        }
+
+        match self.request(OpCode::VAddBatch, payload.freeze()).await? {
+            Response::BatchAdded { added, failed } => Ok((added, failed)),
+            Response::Error(e) => Err(Error::from_server(e)),
+            _ => Err(Error::Protocol("Expected batch result".into())),
+        }
     }
 
     pub async fn vsearch(&mut self, vector: &[f32], k: usize) -> Result<Vec<String>> {
@@ -189,8 +465,7 @@ impl Client {
         }
         payload.put_u32(k as u32);
 
-        self.send_frame(OpCode::VSearch, payload.freeze()).await?;
-        match self.read_response().await? {
+        match self.request(OpCode::VSearch, payload.freeze()).await? {
             Response::Array(items) => {
                 let mut keys = Vec::with_capacity(items.len());
                 for item in items {
@@ -201,126 +476,527 @@ impl Client {
                 }
                 Ok(keys)
             },
-            Response::Error(e) => Err(Error::Server(e)),
+            Response::Error(e) => Err(Error::from_server(e)),
             _ => Err(Error::Protocol("Expected Array".into())),
         }
     }
 
     // Internal helpers
 
-    async fn expect_ok(&mut self) -> Result<()> {
-        match self.read_response().await? {
+    fn expect_integer(response: Response) -> Result<i64> {
+        match response {
+            Response::Integer(n) => Ok(n),
+            Response::Error(e) => Err(Error::from_server(e)),
+            _ => Err(Error::Protocol("Expected Integer".into())),
+        }
+    }
+
+    fn put_key_list(payload: &mut BytesMut, keys: &[&str]) {
+        payload.put_u32(keys.len() as u32);
+        for key in keys {
+            payload.put_u32(key.len() as u32);
+            payload.put_slice(key.as_bytes());
+        }
+    }
+
+    fn expect_ok(response: Response) -> Result<()> {
+        match response {
             Response::Ok => Ok(()),
-            Response::Error(e) => Err(Error::Server(e)),
+            Response::Error(e) => Err(Error::from_server(e)),
             _ => Err(Error::Protocol("Expected OK".into())),
         }
     }
 
-    async fn send_frame(&mut self, opcode: OpCode, payload: Bytes) -> Result<()> {
-        let req_id = self.next_req_id;
-        self.next_req_id += 1;
+    /// Send a request and read its response, retrying BUSY replies
+    async fn request(&mut self, opcode: OpCode, payload: Bytes) -> Result<Response> {
+        let timeout = self.next_timeout.take().or(self.timeout);
+        self.ensure_usable().await?;
+
+        let result = self.exchange_within(timeout, opcode, payload.clone()).await;
+        let connection_lost = matches!(result, Err(Error::Io(_) | Error::ConnectionClosed));
+
+        let retryable = opcode.is_idempotent() || self.retry_writes;
+        if connection_lost && retryable && self.reconnect.is_some() {
+            self.reconnect().await?;
+            return self.exchange_within(timeout, opcode, payload).await;
+        }
+        result
+    }
+
+    /// Reconnect a poisoned connection if allowed, otherwise refuse to use it
+    async fn ensure_usable(&mut self) -> Result<()> {
+        match (self.poisoned, self.reconnect.is_some()) {
+            (false, _) => Ok(()),
+            (true, true) => self.reconnect().await,
+            (true, false) => Err(Error::Poisoned),
+        }
+    }
+
+    /// `exchange` under an optional deadline, poisoning the connection on
+    /// any error that may leave it mid-frame
+    async fn exchange_within(
+        &mut self,
+        timeout: Option<Duration>,
+        opcode: OpCode,
+        payload: Bytes,
+    ) -> Result<Response> {
+        let result = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.exchange(opcode, payload))
+                .await
+                .unwrap_or(Err(Error::Timeout)),
+            None => self.exchange(opcode, payload).await,
+        };
+        if let Err(Error::Io(_) | Error::Protocol(_) | Error::ConnectionClosed | Error::Timeout) =
+            &result
+        {
+            // A partial frame or a late reply may be left on the stream
+            self.poisoned = true;
+        }
+        result
+    }
+
+    /// Replace the connection, backing off between failed dials
+    async fn reconnect(&mut self) -> Result<()> {
+        let (attempts, mut backoff) = self.reconnect.unwrap_or((1, Duration::ZERO));
+        let mut last_err = None;
+        for attempt in 0..attempts.max(1) {
+            if attempt > 0 {
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+            }
+            match TcpStream::connect(&self.addr).await {
+                Ok(stream) => {
+                    self.stream = BufWriter::new(stream);
+                    self.buffer.clear();
+                    self.poisoned = false;
+                    return Ok(());
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(Error::Io(last_err.unwrap()))
+    }
+
+    async fn exchange(&mut self, opcode: OpCode, payload: Bytes) -> Result<Response> {
+        let mut attempt = 0;
+        loop {
+            let req_id = self.send_frame(opcode, payload.clone()).await?;
+            match self.read_response(req_id).await? {
+                Response::Busy { retry_after_ms } => {
+                    if attempt >= self.busy_retries {
+                        return Err(Error::Busy { retry_after_ms });
+                    }
+                    let backoff = self.busy_backoff.saturating_mul(1 << attempt.min(16));
+                    let hint = Duration::from_millis(retry_after_ms as u64);
+                    tokio::time::sleep(backoff.max(hint)).await;
+                    attempt += 1;
+                }
+                response => return Ok(response),
+            }
+        }
+    }
+
+    /// Write one request frame, returning its request id
+    async fn send_frame(&mut self, opcode: OpCode, payload: Bytes) -> Result<u64> {
+        let req_id = self.next_request_id();
 
         let mut header = BytesMut::with_capacity(HEADER_SIZE);
-        header.put_slice(&MAGIC);
-        header.put_u8(VERSION);
-        header.put_u8(opcode as u8);
-        header.put_u16(0); // flags
-        header.put_u32(payload.len() as u32);
-        header.put_u64(req_id);
-        header.put_u16(0); // reserved
+        Self::put_header(&mut header, opcode, req_id, payload.len());
 
         self.stream.write_all(&header).await?;
         if !payload.is_empty() {
             self.stream.write_all(&payload).await?;
         }
         self.stream.flush().await?;
-        Ok(())
+        Ok(req_id)
+    }
+
+    fn next_request_id(&mut self) -> u64 {
+        let req_id = self.next_req_id;
+        self.next_req_id += 1;
+        req_id
     }
 
-    async fn read_response(&mut self) -> Result<Response> {
+    fn put_header(buf: &mut BytesMut, opcode: OpCode, req_id: u64, payload_len: usize) {
+        buf.put_slice(&MAGIC);
+        buf.put_u8(VERSION);
+        buf.put_u8(opcode as u8);
+        buf.put_u16(0); // flags
+        buf.put_u32(payload_len as u32);
+        buf.put_u64(req_id);
+        buf.put_u16(0); // reserved
+    }
+
+    /// Read until the response to `req_id` arrives. The server answers in
+    /// completion order, so replies to other requests (e.g. ones abandoned
+    /// after a timeout) are skipped.
+    async fn read_response(&mut self, req_id: u64) -> Result<Response> {
         loop {
-            // Check if we have enough for header
-            if self.buffer.len() >= HEADER_SIZE {
-                let mut buf = Cursor::new(&self.buffer[..]);
-                
-                // Parse header
-                let mut magic = [0u8; 4];
-                buf.copy_to_slice(&mut magic);
-                if magic != MAGIC {
-                    return Err(Error::Protocol("Invalid magic bytes".into()));
-                }
-                let _version = buf.get_u8();
-                let opcode_byte = buf.get_u8();
-                let _flags = buf.get_u16();
-                let payload_len = buf.get_u32() as usize;
-                let _req_id = buf.get_u64();
-                let _reserved = buf.get_u16();
-
-                // Check payload availability
-                if self.buffer.len() >= HEADER_SIZE + payload_len {
-                    // Consume header + payload
-                    self.buffer.advance(HEADER_SIZE);
-                    let payload = self.buffer.split_to(payload_len).freeze();
-                    
-                    let opcode = OpCode::from_u8(opcode_byte)
-                        .ok_or_else(|| Error::Protocol(format!("Unknown opcode: {}", opcode_byte)))?;
-
-                    return match opcode {
-                        OpCode::Ok => Ok(Response::Ok),
-                        OpCode::Nil => Ok(Response::Nil),
-                        OpCode::Pong => Ok(Response::Pong),
-                        OpCode::Error => {
-                            let msg = String::from_utf8_lossy(&payload).into();
-                            Ok(Response::Error(msg))
-                        },
-                        OpCode::Value => Ok(Response::Value(payload)),
-                        OpCode::Integer => {
-                            if payload.len() < 8 { return Err(Error::Protocol("Invalid integer len".into())); }
-                            let mut p = payload.clone(); // Implements Buf
-                            use bytes::Buf; // Ensure Buf trait is used
-                            let val = p.get_i64();
-                            Ok(Response::Integer(val))
-                        },
-                        OpCode::Array => {
-                           // Basic array parsing: [count: u32][len1: u32][bytes1]...
-                           // Note: Recursive parsing of generic Responses is harder with flat payload structure.
-                           // Server currently sends OpCode::Array with payload structure:
-                           // [count: u32] then items.
-                           // BUT items in current server impl (Response::Array) are encoded as: 
-                           // [item_len: u32][item_bytes]. This assumes items are just Bytes (Response::Value).
-                           // If we need recursive types, server encoding needs to be richer.
-                           // For Phase 9 verification, `VSearch` returns `Array(Vec<Bytes>)` (keys). 
-                           // So this simple parsing is sufficient for now.
-                           
-                           let mut p = payload.clone();
-                           if p.remaining() < 4 { return Ok(Response::Array(vec![])); }
-                           let count = p.get_u32() as usize;
-                           let mut items = Vec::with_capacity(count);
-                           
-                           for _ in 0..count {
-                               if p.remaining() < 4 { return Err(Error::Protocol("Incomplete array".into())); }
-                               let item_len = p.get_u32() as usize;
-                               if p.remaining() < item_len { return Err(Error::Protocol("Incomplete array item".into())); }
-                               let item_data = p.copy_to_bytes(item_len);
-                               // Knowing our server only sends values in arrays for now:
-                               items.push(Response::Value(item_data));
-                               // To be robust we might want generic parsing but protocol above implies flat bytes for array items
-                           }
-                           Ok(Response::Array(items))
-                        },
-                        _ => Err(Error::Protocol(format!("Unexpected response opcode: {:?}", opcode))),
-                    };
+            if let Some((id, response)) = Self::parse_frame(&mut self.buffer)? {
+                if id == req_id {
+                    return Ok(response);
                 }
+                continue;
             }
 
             // Need more data
             if 0 == self.stream.read_buf(&mut self.buffer).await? {
-                if self.buffer.is_empty() {
-                    return Err(Error::ConnectionClosed);
-                } else {
-                    return Err(Error::ConnectionClosed); // Unexpected EOF
-                }
+                return Err(Error::ConnectionClosed);
+            }
+        }
+    }
+
+    /// Take one complete response frame off the front of `buffer`, if present
+    fn parse_frame(buffer: &mut BytesMut) -> Result<Option<(u64, Response)>> {
+        // Check if we have enough for header
+        if buffer.len() >= HEADER_SIZE {
+            let mut buf = Cursor::new(&buffer[..]);
+            
+            // Parse header
+            let mut magic = [0u8; 4];
+            buf.copy_to_slice(&mut magic);
+            if magic != MAGIC {
+                return Err(Error::Protocol("Invalid magic bytes".into()));
             }
+            let _version = buf.get_u8();
+            let opcode_byte = buf.get_u8();
+            let _flags = buf.get_u16();
+            let payload_len = buf.get_u32() as usize;
+            let req_id = buf.get_u64();
+            let _reserved = buf.get_u16();
+
+            // Check payload availability
+            if buffer.len() >= HEADER_SIZE + payload_len {
+                // Consume header + payload
+                buffer.advance(HEADER_SIZE);
+                let payload = buffer.split_to(payload_len).freeze();
+                
+                let opcode = OpCode::from_u8(opcode_byte)
+                    .ok_or_else(|| Error::Protocol(format!("Unknown opcode: {}", opcode_byte)))?;
+
+                let response = match opcode {
+                    OpCode::Ok => Ok(Response::Ok),
+                    OpCode::Nil => Ok(Response::Nil),
+                    OpCode::Pong => Ok(Response::Pong),
+                    OpCode::Error => {
+                        let msg = String::from_utf8_lossy(&payload).into();
+                        Ok(Response::Error(msg))
+                    },
+                    OpCode::Value => Ok(Response::Value(payload)),
+                    OpCode::Integer => {
+                        if payload.len() < 8 { return Err(Error::Protocol("Invalid integer len".into())); }
+                        let mut p = payload.clone(); // Implements Buf
+                        use bytes::Buf; // Ensure Buf trait is used
+                        let val = p.get_i64();
+                        Ok(Response::Integer(val))
+                    },
+                    OpCode::Array => {
+                       // Basic array parsing: [count: u32][len1: u32][bytes1]...
+                       // Note: Recursive parsing of generic Responses is harder with flat payload structure.
+                       // Server currently sends OpCode::Array with payload structure:
+                       // [count: u32] then items.
+                       // BUT items in current server impl (Response::Array) are encoded as: 
+                       // [item_len: u32][item_bytes]. This assumes items are just Bytes (Response::Value).
+                       // If we need recursive types, server encoding needs to be richer.
+                       // For Phase 9 verification, `VSearch` returns `Array(Vec<Bytes>)` (keys). 
+                       // So this simple parsing is sufficient for now.
+                       
+                       let mut p = payload.clone();
+                       if p.remaining() < 4 { return Ok(Some((req_id, Response::Array(vec![])))); }
+                       let count = p.get_u32() as usize;
+                       let mut items = Vec::with_capacity(count);
+                       
+                       for _ in 0..count {
+                           if p.remaining() < 4 { return Err(Error::Protocol("Incomplete array".into())); }
+                           let item_len = p.get_u32() as usize;
+                           if p.remaining() < item_len { return Err(Error::Protocol("Incomplete array item".into())); }
+                           let item_data = p.copy_to_bytes(item_len);
+                           // Knowing our server only sends values in arrays for now:
+                           items.push(Response::Value(item_data));
+                           // To be robust we might want generic parsing but protocol above implies flat bytes for array items
+                       }
+                       Ok(Response::Array(items))
+                    },
+                    OpCode::Values => {
+                        // [count: u32] then [len: u32][bytes] per item, len u32::MAX = nil
+                        let mut p = payload.clone();
+                        if p.remaining() < 4 { return Err(Error::Protocol("Invalid values payload".into())); }
+                        let count = p.get_u32() as usize;
+                        let mut items = Vec::with_capacity(count.min(p.remaining() / 4));
+                        for _ in 0..count {
+                            if p.remaining() < 4 { return Err(Error::Protocol("Incomplete values".into())); }
+                            let item_len = p.get_u32();
+                            if item_len == u32::MAX {
+                                items.push(None);
+                                continue;
+                            }
+                            if p.remaining() < item_len as usize { return Err(Error::Protocol("Incomplete values item".into())); }
+                            items.push(Some(p.copy_to_bytes(item_len as usize)));
+                        }
+                        Ok(Response::Values(items))
+                    },
+                    OpCode::BatchAdded => {
+                        // [added: u32][count: u32][index: u32...]
+                        let mut p = payload.clone();
+                        if p.remaining() < 8 { return Err(Error::Protocol("Invalid batch payload".into())); }
+                        let added = p.get_u32();
+                        let count = p.get_u32() as usize;
+                        if p.remaining() / 4 < count { return Err(Error::Protocol("Incomplete batch payload".into())); }
+                        let failed = (0..count).map(|_| p.get_u32()).collect();
+                        Ok(Response::BatchAdded { added, failed })
+                    },
+                    OpCode::Busy => {
+                        let mut p = payload.clone();
+                        let retry_after_ms = if p.remaining() >= 4 { p.get_u32() } else { 0 };
+                        Ok(Response::Busy { retry_after_ms })
+                    },
+                    _ => Err(Error::Protocol(format!("Unexpected response opcode: {:?}", opcode))),
+                };
+                return response.map(|r| Some((req_id, r)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use celrix::{ConcurrentServer, Config};
+    use tokio::net::TcpListener;
+
+    fn config() -> Config {
+        Config { kv_workers: 2, vector_workers: 1, ..Config::default() }
+    }
+
+    /// Start an in-process server on an ephemeral port and return its address
+    pub async fn spawn_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(ConcurrentServer::new(config()).serve(listener));
+        addr.to_string()
+    }
+
+    /// Relay connections to `target`, holding each client-to-server chunk for
+    /// `delay` to stand in for network latency. Returns the relay's address.
+    pub async fn spawn_latency_proxy(target: String, delay: std::time::Duration) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((client, _)) = listener.accept().await {
+                let server = tokio::net::TcpStream::connect(&target).await.unwrap();
+                let (mut client_rd, mut client_wr) = client.into_split();
+                let (mut server_rd, mut server_wr) = server.into_split();
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 64 * 1024];
+                    while let Ok(n @ 1..) = client_rd.read(&mut buf).await {
+                        tokio::time::sleep(delay).await;
+                        if server_wr.write_all(&buf[..n]).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+                tokio::spawn(async move {
+                    let _ = tokio::io::copy(&mut server_rd, &mut client_wr).await;
+                });
+            }
+        });
+        addr.to_string()
+    }
+
+    /// Server on its own thread and runtime, so stopping it drops every connection
+    pub struct TestServer {
+        stop: tokio::sync::oneshot::Sender<()>,
+        thread: std::thread::JoinHandle<()>,
+    }
+
+    impl TestServer {
+        /// Start on `addr` ("127.0.0.1:0" for any port), returning the bound address
+        pub fn start(addr: &str) -> (Self, String) {
+            let listener = std::net::TcpListener::bind(addr).unwrap();
+            listener.set_nonblocking(true).unwrap();
+            let addr = listener.local_addr().unwrap().to_string();
+            let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+
+            let thread = std::thread::spawn(move || {
+                let runtime = tokio::runtime::Builder::new_multi_thread()
+                    .worker_threads(1)
+                    .enable_all()
+                    .build()
+                    .unwrap();
+                runtime.block_on(async move {
+                    let listener = TcpListener::from_std(listener).unwrap();
+                    tokio::select! {
+                        _ = ConcurrentServer::new(config()).serve(listener) => {}
+                        _ = stopped => {}
+                    }
+                });
+                // Dropping the runtime closes every connection task's socket
+            });
+            (Self { stop, thread }, addr)
+        }
+
+        /// Kill the server and all of its connections
+        pub fn stop(self) {
+            let _ = self.stop.send(());
+            self.thread.join().unwrap();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{spawn_server, TestServer};
+
+    #[tokio::test]
+    async fn test_multi_key_commands() {
+        let mut client = Client::connect(&spawn_server().await).await.unwrap();
+
+        client.mset(&[("mk:a", "1"), ("mk:b", "two")]).await.unwrap();
+        assert_eq!(
+            client.mget(&["mk:a", "missing", "mk:b"]).await.unwrap(),
+            vec![Some("1".to_string()), None, Some("two".to_string())]
+        );
+        assert_eq!(client.mdel(&["mk:a", "missing", "mk:b"]).await.unwrap(), 2);
+        assert_eq!(client.mget(&["mk:a"]).await.unwrap(), vec![None]);
+    }
+
+    #[tokio::test]
+    async fn test_counter_commands() {
+        let mut client = Client::connect(&spawn_server().await).await.unwrap();
+
+        assert_eq!(client.incr("counter").await.unwrap(), 1);
+        assert_eq!(client.incr_by("counter", 41).await.unwrap(), 42);
+        assert_eq!(client.incr_by("counter", -50).await.unwrap(), -8);
+        client.set("text", "abc", None).await.unwrap();
+        assert!(matches!(client.incr("text").await, Err(Error::Server(_))));
+    }
+
+    #[tokio::test]
+    async fn test_error_categories() {
+        let mut client = Client::connect(&spawn_server().await).await.unwrap();
+
+        // GET whose key length runs past the payload
+        let malformed = client.request(OpCode::Get, Bytes::from_static(&[0, 0, 0, 9, b'k'])).await.unwrap();
+        let Response::Error(message) = malformed else { panic!("expected an error reply") };
+        let error = Error::from_server(message);
+        assert!(matches!(error, Error::BadRequest(_)), "{}", error);
+        assert!(!error.is_retryable());
+
+        // Well-formed, but the command itself fails
+        client.set("text", "abc", None).await.unwrap();
+        let error = client.incr("text").await.unwrap_err();
+        assert!(matches!(error, Error::Server(_)), "{}", error);
+
+        // The connection is still usable after either
+        assert!(!client.is_poisoned());
+        client.ping().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_vadd_batch() {
+        let mut client = Client::connect(&spawn_server().await).await.unwrap();
+        // Server default embedding dimension
+        let dim = 1536;
+
+        let keys: Vec<String> = (0..1000).map(|i| format!("emb:{}", i)).collect();
+        let vectors: Vec<Vec<f32>> = (0..1000)
+            .map(|i| {
+                let mut v = vec![0.0; dim];
+                v[i % dim] = 1.0;
+                v
+            })
+            .collect();
+        let mut entries: Vec<(&str, &[f32])> =
+            keys.iter().zip(&vectors).map(|(k, v)| (k.as_str(), v.as_slice())).collect();
+        let short = [1.0, 2.0];
+        entries[10] = ("bad", &short);
+
+        let (added, failed) = client.vadd_batch(&entries).await.unwrap();
+        assert_eq!(added, 999);
+        assert_eq!(failed, vec![10]);
+        assert_eq!(client.vsearch(&vectors[500], 1).await.unwrap(), vec!["emb:500"]);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_on_silent_server() {
+        // Accepts connections but never reads or replies
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+
+        let mut client = Client::connect(&addr)
+            .await
+            .unwrap()
+            .with_timeout(Duration::from_secs(30));
+        let start = std::time::Instant::now();
+        let result = client.next_timeout(Duration::from_millis(100)).ping().await;
+        assert!(matches!(result, Err(Error::Timeout)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(client.is_poisoned());
+
+        // Without reconnect a poisoned connection refuses further requests
+        assert!(matches!(client.get("k").await, Err(Error::Poisoned)));
+
+        // With reconnect it re-dials and times out afresh
+        let mut client = client.with_reconnect(1, Duration::ZERO);
+        client.set_timeout(Some(Duration::from_millis(50)));
+        assert!(matches!(client.get("k").await, Err(Error::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn test_scan() {
+        let mut client = Client::connect(&spawn_server().await).await.unwrap();
+        for i in 0..50 {
+            client.set(&format!("scan:{}", i), "v", None).await.unwrap();
+        }
+        client.set("other", "v", None).await.unwrap();
+
+        let mut cursor = 0;
+        let mut keys = Vec::new();
+        loop {
+            let (next, page) = client.scan(cursor, Some("scan:*"), 10).await.unwrap();
+            keys.extend(page);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        keys.sort();
+        keys.dedup();
+        assert_eq!(keys.len(), 50);
+        assert!(keys.iter().all(|k| k.starts_with("scan:")));
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_after_server_restart() {
+        let (server, addr) = TestServer::start("127.0.0.1:0");
+        let mut client = Client::connect(&addr)
+            .await
+            .unwrap()
+            .with_reconnect(5, Duration::from_millis(20));
+        client.set("k", "v", None).await.unwrap();
+        assert_eq!(client.get("k").await.unwrap().as_deref(), Some("v"));
+
+        // GET is retried transparently on a fresh connection
+        server.stop();
+        let (server, _) = TestServer::start(&addr);
+        assert_eq!(client.get("k").await.unwrap(), None);
+        client.set("k", "v2", None).await.unwrap();
+
+        // DEL isn't idempotent: the error surfaces, then the next call re-dials
+        server.stop();
+        let (_server, _) = TestServer::start(&addr);
+        assert!(client.del("k").await.is_err());
+        assert!(client.is_poisoned());
+        client.ping().await.unwrap();
+        assert!(!client.is_poisoned());
+    }
+}
@@ -0,0 +1,234 @@
+//! Request pipelining
+//!
+//! Queues commands locally and sends them in a single write, then matches
+//! the responses, which the server sends in completion order, back to their
+//! commands by request id. Saves a round trip per command when many
+//! independent requests go to the same server.
+
+use bytes::{Bytes, BytesMut};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{incr_by_payload, key_payload, set_payload, Client, Error, OpCode, Response, Result};
+
+/// Batch of queued commands bound to one client
+pub struct Pipeline<'a> {
+    client: &'a mut Client,
+    frames: BytesMut,
+    first_req_id: u64,
+    count: usize,
+}
+
+impl<'a> Pipeline<'a> {
+    pub(crate) fn new(client: &'a mut Client) -> Self {
+        let first_req_id = client.next_req_id;
+        Self {
+            client,
+            frames: BytesMut::new(),
+            first_req_id,
+            count: 0,
+        }
+    }
+
+    /// Number of queued commands
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn ping(&mut self) -> &mut Self {
+        self.push(OpCode::Ping, Bytes::new())
+    }
+
+    pub fn get(&mut self, key: &str) -> &mut Self {
+        self.push(OpCode::Get, key_payload(key))
+    }
+
+    pub fn set(&mut self, key: &str, value: &str, ttl: Option<u64>) -> &mut Self {
+        self.push(OpCode::Set, set_payload(key, value, ttl))
+    }
+
+    pub fn del(&mut self, key: &str) -> &mut Self {
+        self.push(OpCode::Del, key_payload(key))
+    }
+
+    pub fn exists(&mut self, key: &str) -> &mut Self {
+        self.push(OpCode::Exists, key_payload(key))
+    }
+
+    pub fn incr(&mut self, key: &str) -> &mut Self {
+        self.push(OpCode::Incr, key_payload(key))
+    }
+
+    pub fn incr_by(&mut self, key: &str, delta: i64) -> &mut Self {
+        self.push(OpCode::IncrBy, incr_by_payload(key, delta))
+    }
+
+    fn push(&mut self, opcode: OpCode, payload: Bytes) -> &mut Self {
+        let req_id = self.client.next_request_id();
+        Client::put_header(&mut self.frames, opcode, req_id, payload.len());
+        self.frames.extend_from_slice(&payload);
+        self.count += 1;
+        self
+    }
+
+    /// Send every queued command and collect the responses in queue order.
+    ///
+    /// Server errors come back as `Response::Error` entries rather than
+    /// failing the batch; a transport error or timeout fails the whole batch
+    /// and leaves the connection poisoned. The client's timeout covers the
+    /// batch as a whole. Pipelines are never retried after a
+    /// reconnect, since some of the commands may already have been applied.
+    pub async fn execute(self) -> Result<Vec<Response>> {
+        let Pipeline { client, frames, first_req_id, count } = self;
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        let timeout = client.next_timeout.take().or(client.timeout);
+        client.ensure_usable().await?;
+
+        let exchange = Self::exchange(client, &frames, first_req_id, count);
+        let result = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, exchange)
+                .await
+                .unwrap_or(Err(Error::Timeout)),
+            None => exchange.await,
+        };
+        if result.is_err() {
+            // Unread responses may be left on the stream
+            client.poisoned = true;
+        }
+        result
+    }
+
+    async fn exchange(
+        client: &mut Client,
+        frames: &[u8],
+        first_req_id: u64,
+        count: usize,
+    ) -> Result<Vec<Response>> {
+        client.stream.flush().await?;
+        let buffer = &mut client.buffer;
+        let (mut reader, mut writer) = client.stream.get_mut().split();
+
+        // Write and read concurrently so a large batch can't stall with
+        // both sides' socket buffers full
+        let write = async {
+            writer.write_all(frames).await?;
+            writer.flush().await?;
+            Ok::<_, Error>(())
+        };
+        let read = async {
+            let mut responses: Vec<Option<Response>> = (0..count).map(|_| None).collect();
+            let mut received = 0;
+            while received < count {
+                match Client::parse_frame(buffer)? {
+                    Some((req_id, response)) => {
+                        let slot = req_id
+                            .checked_sub(first_req_id)
+                            .and_then(|i| responses.get_mut(i as usize))
+                            .ok_or_else(|| Error::Protocol(format!("Unexpected response to request {}", req_id)))?;
+                        if slot.replace(response).is_some() {
+                            return Err(Error::Protocol(format!("Duplicate response to request {}", req_id)));
+                        }
+                        received += 1;
+                    }
+                    None => {
+                        if 0 == reader.read_buf(buffer).await? {
+                            return Err(Error::ConnectionClosed);
+                        }
+                    }
+                }
+            }
+            Ok(responses.into_iter().flatten().collect())
+        };
+
+        let ((), responses) = tokio::try_join!(write, read)?;
+        Ok(responses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use bytes::BytesMut;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use crate::testing::{spawn_latency_proxy, spawn_server};
+    use crate::{Client, OpCode, Response, HEADER_SIZE};
+
+    #[tokio::test]
+    async fn test_pipeline_is_faster_than_sequential() {
+        // Loopback round trips are nearly free; add latency so they aren't
+        let addr = spawn_latency_proxy(spawn_server().await, Duration::from_millis(1)).await;
+        let mut client = Client::connect(&addr).await.unwrap();
+        const N: usize = 1000;
+
+        let start = Instant::now();
+        for i in 0..N {
+            client.set(&format!("seq:{}", i), "v", None).await.unwrap();
+        }
+        let sequential = start.elapsed();
+
+        let start = Instant::now();
+        let mut pipeline = client.pipeline();
+        for i in 0..N {
+            pipeline.set(&format!("pipe:{}", i), "v", None);
+        }
+        let responses = pipeline.execute().await.unwrap();
+        let pipelined = start.elapsed();
+
+        assert_eq!(responses.len(), N);
+        assert!(responses.iter().all(|r| matches!(r, Response::Ok)));
+        assert!(
+            pipelined * 10 < sequential,
+            "pipelined {:?} vs sequential {:?}",
+            pipelined,
+            sequential
+        );
+
+        // Responses map back to their commands and the client stays usable
+        let mut pipeline = client.pipeline();
+        pipeline.get("pipe:0").get("missing").incr("counter").ping();
+        let responses = pipeline.execute().await.unwrap();
+        assert!(matches!(&responses[0], Response::Value(v) if &v[..] == b"v"));
+        assert!(matches!(responses[1], Response::Nil));
+        assert!(matches!(responses[2], Response::Integer(1)));
+        assert!(matches!(responses[3], Response::Pong));
+        assert_eq!(client.get("seq:999").await.unwrap().as_deref(), Some("v"));
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_routes_out_of_order_responses() {
+        // Server that answers a slow request after the fast one behind it
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut req_ids = Vec::new();
+            for _ in 0..2 {
+                let mut header = [0u8; HEADER_SIZE];
+                socket.read_exact(&mut header).await.unwrap();
+                let payload_len = u32::from_be_bytes(header[8..12].try_into().unwrap()) as usize;
+                socket.read_exact(&mut vec![0u8; payload_len]).await.unwrap();
+                req_ids.push(u64::from_be_bytes(header[12..20].try_into().unwrap()));
+            }
+            let mut replies = BytesMut::new();
+            Client::put_header(&mut replies, OpCode::Value, req_ids[1], 4);
+            replies.extend_from_slice(b"fast");
+            Client::put_header(&mut replies, OpCode::Ok, req_ids[0], 0);
+            socket.write_all(&replies).await.unwrap();
+        });
+
+        let mut client = Client::connect(&addr).await.unwrap();
+        let mut pipeline = client.pipeline();
+        pipeline.set("slow", "v", None).get("fast");
+        let responses = pipeline.execute().await.unwrap();
+        assert!(matches!(responses[0], Response::Ok));
+        assert!(matches!(&responses[1], Response::Value(v) if &v[..] == b"fast"));
+    }
+}
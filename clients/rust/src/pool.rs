@@ -0,0 +1,141 @@
+//! Connection pool
+//!
+//! Shares a bounded set of connections between tasks. Connections that hit
+//! a transport error are dropped on release and replaced on a later acquire.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{Client, Error, Result};
+
+struct Shared {
+    addr: String,
+    idle: Mutex<Vec<Client>>,
+    permits: Arc<Semaphore>,
+    acquire_timeout: Duration,
+}
+
+/// Pool of up to `max_size` connections to one server
+#[derive(Clone)]
+pub struct Pool {
+    shared: Arc<Shared>,
+}
+
+impl Pool {
+    /// Create a pool; connections are opened lazily on acquire
+    pub fn new(addr: impl Into<String>, max_size: usize) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                addr: addr.into(),
+                idle: Mutex::new(Vec::with_capacity(max_size)),
+                permits: Arc::new(Semaphore::new(max_size)),
+                acquire_timeout: Duration::from_secs(30),
+            }),
+        }
+    }
+
+    /// Set how long `acquire` waits for a free connection
+    pub fn with_acquire_timeout(mut self, timeout: Duration) -> Self {
+        Arc::get_mut(&mut self.shared)
+            .expect("configure the pool before cloning it")
+            .acquire_timeout = timeout;
+        self
+    }
+
+    /// Check out a connection, waiting up to the acquire timeout for one to free up
+    pub async fn acquire(&self) -> Result<PooledClient> {
+        let permit = tokio::time::timeout(
+            self.shared.acquire_timeout,
+            self.shared.permits.clone().acquire_owned(),
+        )
+        .await
+        .map_err(|_| Error::Timeout)?
+        .expect("pool semaphore is never closed");
+
+        let idle = self.shared.idle.lock().unwrap().pop();
+        let client = match idle {
+            Some(client) => client,
+            None => Client::connect(&self.shared.addr).await?,
+        };
+
+        Ok(PooledClient {
+            client: Some(client),
+            shared: self.shared.clone(),
+            _permit: permit,
+        })
+    }
+
+    /// Number of open connections not currently checked out
+    pub fn idle_count(&self) -> usize {
+        self.shared.idle.lock().unwrap().len()
+    }
+}
+
+/// Connection checked out of a `Pool`; returned to it on drop
+pub struct PooledClient {
+    client: Option<Client>,
+    shared: Arc<Shared>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Deref for PooledClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledClient {
+    fn deref_mut(&mut self) -> &mut Client {
+        self.client.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledClient {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            // Broken connections are discarded; the freed permit lets a new one be dialed
+            if !client.is_poisoned() {
+                self.shared.idle.lock().unwrap().push(client);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::spawn_server;
+
+    #[tokio::test]
+    async fn test_pool_concurrent_get_set() {
+        let addr = spawn_server().await;
+        let pool = Pool::new(addr, 4).with_acquire_timeout(Duration::from_secs(5));
+
+        let mut tasks = Vec::new();
+        for task in 0..16 {
+            let pool = pool.clone();
+            tasks.push(tokio::spawn(async move {
+                for i in 0..20 {
+                    let mut conn = pool.acquire().await.unwrap();
+                    let key = format!("pool:{}:{}", task, i);
+                    conn.set(&key, "value", None).await.unwrap();
+                    assert_eq!(conn.get(&key).await.unwrap().as_deref(), Some("value"));
+                }
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert!(pool.idle_count() <= 4);
+
+        // Every connection checked out: the next acquire times out
+        let short = Pool::new(pool.shared.addr.clone(), 1)
+            .with_acquire_timeout(Duration::from_millis(50));
+        let _held = short.acquire().await.unwrap();
+        assert!(matches!(short.acquire().await, Err(Error::Timeout)));
+    }
+}
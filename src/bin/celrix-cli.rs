@@ -3,7 +3,7 @@
 //! Interactive command-line client for CELRIX.
 
 use bytes::Bytes;
-use celrix::protocol::{Command, Frame, Response, VcpCodec};
+use celrix::protocol::{Command, Response, SetOptions, VcpCodec, CAP_COMPRESSION};
 use clap::Parser;
 use futures::{SinkExt, StreamExt};
 use std::io::{self, Write};
@@ -38,7 +38,8 @@ async fn main() -> anyhow::Result<()> {
     println!("Connecting to CELRIX at {}...", addr);
 
     let stream = TcpStream::connect(&addr).await?;
-    let mut framed = Framed::new(stream, VcpCodec::new());
+    // Large values are compressed both ways if the server supports it
+    let mut framed = Framed::new(stream, VcpCodec::new().with_compression(1024));
 
     println!("Connected! Type 'help' for available commands, 'quit' to exit.\n");
 
@@ -60,15 +61,26 @@ async fn main() -> anyhow::Result<()> {
         }
 
         if input.eq_ignore_ascii_case("help") {
-            print_help();
+            framed.send(Command::CommandList.to_frame(next_request_id())).await?;
+            let supported = match framed.next().await {
+                Some(Ok(frame)) => match Response::from_frame(&frame)? {
+                    Response::Array(records) => Some(records),
+                    _ => None,
+                },
+                Some(Err(e)) => return Err(e.into()),
+                None => {
+                    eprintln!("Connection closed by server");
+                    break;
+                }
+            };
+            print_help(supported.as_deref());
             continue;
         }
 
         match parse_command(input) {
             Ok(cmd) => {
                 let request_id = next_request_id();
-                let (opcode, payload) = cmd.encode();
-                let frame = Frame::new(opcode, request_id, payload);
+                let frame = cmd.to_frame(request_id);
 
                 framed.send(frame).await?;
 
@@ -106,28 +118,56 @@ fn parse_command(input: &str) -> anyhow::Result<Command> {
 
     match cmd.as_str() {
         "PING" => Ok(Command::Ping),
+        "RESET" => Ok(Command::Reset),
+        "ASKING" => Ok(Command::Asking),
+        "RANDOMKEY" => Ok(Command::RandomKey),
+        "DBSIZE" => Ok(Command::DbSize),
+        "MULTI" => Ok(Command::Multi),
+        "EXEC" => Ok(Command::Exec { commands: Vec::new(), watched: Vec::new() }),
+        "DISCARD" => Ok(Command::Discard),
+
+        "WATCH" => {
+            if parts.len() < 2 {
+                anyhow::bail!("WATCH requires at least one key: WATCH <key>...");
+            }
+            let keys = parts[1..].iter().map(|k| Bytes::copy_from_slice(k.as_bytes())).collect();
+            Ok(Command::Watch { keys })
+        }
 
         "GET" => {
             if parts.len() < 2 {
                 anyhow::bail!("GET requires a key: GET <key>");
             }
-            Ok(Command::Get {
-                key: Bytes::copy_from_slice(parts[1].as_bytes()),
-            })
+            let key = Bytes::copy_from_slice(parts[1].as_bytes());
+            match parts.get(2).map(|s| s.to_uppercase()).as_deref() {
+                None => Ok(Command::Get { key }),
+                Some("WITHTTL") => Ok(Command::GetWithTtl { key }),
+                Some("EXPIRED") => Ok(Command::GetOrExpired { key }),
+                Some(_) => anyhow::bail!("Usage: GET <key> [WITHTTL|EXPIRED]"),
+            }
         }
 
         "SET" => {
             if parts.len() < 3 {
-                anyhow::bail!("SET requires key and value: SET <key> <value> [ttl_seconds]");
+                anyhow::bail!("SET requires key and value: SET <key> <value> [ttl] [PX] [NX|XX] [GET]");
             }
             let key = Bytes::copy_from_slice(parts[1].as_bytes());
             let value = Bytes::copy_from_slice(parts[2].as_bytes());
-            let ttl = if parts.len() > 3 {
-                Some(parts[3].parse::<u64>()?)
-            } else {
-                None
-            };
-            Ok(Command::Set { key, value, ttl })
+            let mut ttl = None;
+            let mut options = SetOptions::default();
+            for arg in &parts[3..] {
+                match arg.to_uppercase().as_str() {
+                    "NX" => options.nx = true,
+                    "XX" => options.xx = true,
+                    "GET" => options.get = true,
+                    "PX" => options.px = true,
+                    _ => ttl = Some(arg.parse::<u64>()?),
+                }
+            }
+            if options.nx && options.xx {
+                anyhow::bail!("NX and XX options are mutually exclusive");
+            }
+            Ok(Command::Set { key, value, ttl, options })
         }
 
         "DEL" => {
@@ -139,6 +179,15 @@ fn parse_command(input: &str) -> anyhow::Result<Command> {
             })
         }
 
+        "UNLINK" => {
+            if parts.len() < 2 {
+                anyhow::bail!("UNLINK requires a key: UNLINK <key>");
+            }
+            Ok(Command::Unlink {
+                key: Bytes::copy_from_slice(parts[1].as_bytes()),
+            })
+        }
+
         "EXISTS" => {
             if parts.len() < 2 {
                 anyhow::bail!("EXISTS requires a key: EXISTS <key>");
@@ -148,27 +197,274 @@ fn parse_command(input: &str) -> anyhow::Result<Command> {
             })
         }
 
+        "GETDEL" => {
+            if parts.len() < 2 {
+                anyhow::bail!("GETDEL requires a key: GETDEL <key>");
+            }
+            Ok(Command::GetDel {
+                key: Bytes::copy_from_slice(parts[1].as_bytes()),
+            })
+        }
+
+        "GETSET" => {
+            if parts.len() < 3 {
+                anyhow::bail!("GETSET requires key and value: GETSET <key> <value>");
+            }
+            Ok(Command::GetSet {
+                key: Bytes::copy_from_slice(parts[1].as_bytes()),
+                value: Bytes::copy_from_slice(parts[2].as_bytes()),
+            })
+        }
+
+        "APPEND" => {
+            if parts.len() < 3 {
+                anyhow::bail!("APPEND requires key and value: APPEND <key> <value>");
+            }
+            Ok(Command::Append {
+                key: Bytes::copy_from_slice(parts[1].as_bytes()),
+                value: Bytes::copy_from_slice(parts[2].as_bytes()),
+            })
+        }
+
+        "STRLEN" => {
+            if parts.len() < 2 {
+                anyhow::bail!("STRLEN requires a key: STRLEN <key>");
+            }
+            Ok(Command::Strlen { key: Bytes::copy_from_slice(parts[1].as_bytes()) })
+        }
+
+        "DELPATTERN" => {
+            if parts.len() < 2 {
+                anyhow::bail!("DELPATTERN requires a pattern: DELPATTERN <pattern>");
+            }
+            Ok(Command::DelPattern { pattern: Bytes::copy_from_slice(parts[1].as_bytes()) })
+        }
+
+        "SWAPKEY" => {
+            if parts.len() < 3 {
+                anyhow::bail!("SWAPKEY requires two keys: SWAPKEY <key1> <key2>");
+            }
+            Ok(Command::SwapKey {
+                key1: Bytes::copy_from_slice(parts[1].as_bytes()),
+                key2: Bytes::copy_from_slice(parts[2].as_bytes()),
+            })
+        }
+
+        "RENAME" => {
+            if parts.len() < 3 {
+                anyhow::bail!("RENAME requires two keys: RENAME <src> <dst>");
+            }
+            Ok(Command::Rename {
+                src: Bytes::copy_from_slice(parts[1].as_bytes()),
+                dst: Bytes::copy_from_slice(parts[2].as_bytes()),
+            })
+        }
+
+        "COPY" => {
+            let replace = match parts.get(3).map(|s| s.to_uppercase()).as_deref() {
+                None => false,
+                Some("REPLACE") if parts.len() == 4 => true,
+                Some(_) => anyhow::bail!("Usage: COPY <src> <dst> [REPLACE]"),
+            };
+            if parts.len() < 3 {
+                anyhow::bail!("COPY requires two keys: COPY <src> <dst> [REPLACE]");
+            }
+            Ok(Command::Copy {
+                src: Bytes::copy_from_slice(parts[1].as_bytes()),
+                dst: Bytes::copy_from_slice(parts[2].as_bytes()),
+                replace,
+            })
+        }
+
+        "PEXPIRE" => {
+            if parts.len() < 3 {
+                anyhow::bail!("PEXPIRE requires key and milliseconds: PEXPIRE <key> <ms>");
+            }
+            Ok(Command::PExpire {
+                key: Bytes::copy_from_slice(parts[1].as_bytes()),
+                ms: parts[2].parse()?,
+            })
+        }
+
+        "TTL" | "PTTL" => {
+            if parts.len() < 2 {
+                anyhow::bail!("{} requires a key: {} <key>", cmd, cmd);
+            }
+            let key = Bytes::copy_from_slice(parts[1].as_bytes());
+            Ok(if cmd == "TTL" { Command::Ttl { key } } else { Command::PTtl { key } })
+        }
+
+        "TYPE" => {
+            if parts.len() < 2 {
+                anyhow::bail!("TYPE requires a key: TYPE <key>");
+            }
+            Ok(Command::Type { key: Bytes::copy_from_slice(parts[1].as_bytes()) })
+        }
+
+        "SELECT" => {
+            let db = parts
+                .get(1)
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| anyhow::anyhow!("SELECT requires a database index: SELECT <n>"))?;
+            Ok(Command::Select { db })
+        }
+
+        "FLUSHDB" => Ok(Command::FlushDb),
+
+        "FLUSHALL" => match parts.get(1).map(|s| s.to_uppercase()).as_deref() {
+            None => Ok(Command::FlushAll { vectors: false }),
+            Some("VECTORS") => Ok(Command::FlushAll { vectors: true }),
+            Some(_) => anyhow::bail!("Usage: FLUSHALL [VECTORS]"),
+        },
+
+        "AUTH" => {
+            let (username, password) = match parts.len() {
+                2 => ("", parts[1]),
+                3 => (parts[1], parts[2]),
+                _ => anyhow::bail!("AUTH requires a password: AUTH [username] <password>"),
+            };
+            Ok(Command::Auth {
+                username: Bytes::copy_from_slice(username.as_bytes()),
+                password: Bytes::copy_from_slice(password.as_bytes()),
+            })
+        }
+
+        "HELLO" => Ok(Command::Hello {
+            versions: parts[1..]
+                .iter()
+                .map(|v| v.parse::<u8>().map_err(|_| anyhow::anyhow!("Invalid protocol version: {}", v)))
+                .collect::<anyhow::Result<_>>()?,
+            capabilities: CAP_COMPRESSION,
+        }),
+
+        "COMMAND" => match parts.get(1).map(|s| s.to_uppercase()).as_deref() {
+            Some("COUNT") => Ok(Command::CommandCount),
+            Some("LIST") => Ok(Command::CommandList),
+            Some("INFO") => Ok(Command::CommandInfo {
+                names: parts[2..]
+                    .iter()
+                    .map(|n| Bytes::copy_from_slice(n.as_bytes()))
+                    .collect(),
+            }),
+            _ => anyhow::bail!("COMMAND requires a subcommand: COMMAND COUNT | COMMAND INFO <name>... | COMMAND LIST"),
+        },
+
+        "WAIT" => {
+            if parts.len() != 3 {
+                anyhow::bail!("Usage: WAIT <numreplicas> <timeout_ms>");
+            }
+            Ok(Command::Wait {
+                num_replicas: parts[1].parse()?,
+                timeout_ms: parts[2].parse()?,
+            })
+        }
+
+        "SLOWLOG" => match parts.get(1).map(|s| s.to_uppercase()).as_deref() {
+            Some("GET") => Ok(Command::SlowlogGet {
+                count: parts.get(2).map(|n| n.parse()).transpose()?.unwrap_or(10),
+            }),
+            _ => anyhow::bail!("Usage: SLOWLOG GET [count]"),
+        },
+
+        "CLIENT" => match (parts.get(1).map(|s| s.to_uppercase()).as_deref(), parts.get(2)) {
+            (Some("LIST"), None) => Ok(Command::ClientList),
+            (Some("KILL"), Some(id)) => Ok(Command::ClientKill { id: id.parse()? }),
+            _ => anyhow::bail!("Usage: CLIENT LIST | CLIENT KILL <id>"),
+        },
+
+        "DEBUG" => match (parts.get(1).map(|s| s.to_uppercase()).as_deref(), parts.get(2)) {
+            (Some("SLEEP"), Some(ms)) => Ok(Command::DebugSleep { ms: ms.parse()? }),
+            (Some("OBJECT"), Some(key)) => Ok(Command::DebugObject { key: Bytes::copy_from_slice(key.as_bytes()) }),
+            _ => anyhow::bail!("Usage: DEBUG SLEEP <ms> | DEBUG OBJECT <key>"),
+        },
+
+        "CLUSTER" => match parts.get(1).map(|s| s.to_uppercase()).as_deref() {
+            Some("SLOTS") => Ok(Command::ClusterSlots),
+            _ => anyhow::bail!("CLUSTER requires a subcommand: CLUSTER SLOTS"),
+        },
+
         _ => anyhow::bail!("Unknown command: {}. Type 'help' for available commands.", cmd),
     }
 }
 
-fn print_help() {
-    println!(
-        r#"
-Available commands:
+/// Usage lines for the commands this CLI can parse, keyed by command name
+const USAGE: &[(&str, &str)] = &[
+    ("PING", "PING              - Check server connectivity"),
+    ("RESET", "RESET             - Log out, leave subscriptions and select database 0"),
+    ("GET", "GET <key> [WITHTTL|EXPIRED] - Get value for key (and its remaining TTL in ms, or (expired) if it lapsed)"),
+    ("SET", "SET <key> <value> [ttl] [PX] [NX|XX] [GET] - Set key-value pair with optional TTL in seconds (ms with PX)"),
+    ("DEL", "DEL <key>         - Delete a key"),
+    ("UNLINK", "UNLINK <key>      - Delete a key, freeing its value in the background"),
+    ("EXISTS", "EXISTS <key>      - Check if key exists"),
+    ("GETDEL", "GETDEL <key>      - Get value and delete key"),
+    ("GETSET", "GETSET <key> <value> - Set value and return the previous one"),
+    ("APPEND", "APPEND <key> <value> - Append to a key's value, returning the new length"),
+    ("STRLEN", "STRLEN <key>      - Length of a key's value"),
+    ("DELPATTERN", "DELPATTERN <pattern> - Delete all keys matching a glob pattern"),
+    ("SWAPKEY", "SWAPKEY <key1> <key2> - Atomically swap two keys' values and TTLs"),
+    ("RENAME", "RENAME <src> <dst> - Move a key's value and TTL, overwriting dst"),
+    ("COPY", "COPY <src> <dst> [REPLACE] - Copy a key's value and TTL (1 if copied)"),
+    ("PEXPIRE", "PEXPIRE <key> <ms> - Set a key's TTL in milliseconds"),
+    ("TTL", "TTL <key>         - Remaining TTL in seconds (-1 = none, -2 = missing)"),
+    ("PTTL", "PTTL <key>        - Remaining TTL in milliseconds"),
+    ("TYPE", "TYPE <key>        - Kind of value stored at key (string, or none if missing)"),
+    ("RANDOMKEY", "RANDOMKEY         - A random key from the current database"),
+    ("DBSIZE", "DBSIZE            - Number of keys in the current database"),
+    ("SELECT", "SELECT <n>        - Switch to logical database n"),
+    ("FLUSHDB", "FLUSHDB           - Remove all keys from the current database"),
+    ("FLUSHALL", "FLUSHALL [VECTORS] - Remove all keys from every database (and all vectors)"),
+    ("AUTH", "AUTH [user] <password> - Authenticate the connection"),
+    ("HELLO", "HELLO [version ...] - Negotiate the protocol version"),
+    ("COMMAND", "COMMAND COUNT     - Number of supported commands"),
+    ("COMMAND", "COMMAND INFO <name>... - Command metadata"),
+    ("COMMAND", "COMMAND LIST      - Every command with its opcode, arity and permission"),
+    ("CLUSTER", "CLUSTER SLOTS     - Slot ranges with their owner (and migration target)"),
+    ("WAIT", "WAIT <n> <timeout_ms> - Wait until n replicas ack this connection's writes (0 = no timeout)"),
+    ("SLOWLOG", "SLOWLOG GET [count] - Latest slow commands: id time duration_us command request_id [key]"),
+    ("MULTI", "MULTI             - Queue the following commands until EXEC or DISCARD"),
+    ("EXEC", "EXEC              - Run queued commands atomically (nil if a watched key changed)"),
+    ("DISCARD", "DISCARD           - Drop queued commands and watched keys"),
+    ("WATCH", "WATCH <key>...    - Abort the next EXEC if any of these keys is written first"),
+    ("CLIENT", "CLIENT LIST | CLIENT KILL <id> - List connections (id addr user age cmd), or close one"),
+    ("DEBUG", "DEBUG SLEEP <ms> | DEBUG OBJECT <key> - Stall a worker, or show a key's encoding and size"),
+];
 
-  PING              - Check server connectivity
-  GET <key>         - Get value for key
-  SET <key> <value> [ttl] - Set key-value pair with optional TTL in seconds
-  DEL <key>         - Delete a key
-  EXISTS <key>      - Check if key exists
+/// Print help for the commands the server reports via COMMAND LIST, or
+/// every command this CLI knows if the server couldn't be asked
+fn print_help(supported: Option<&[Bytes]>) {
+    let names: Option<Vec<String>> = supported.map(|records| {
+        records
+            .iter()
+            .filter_map(|r| String::from_utf8_lossy(r).split(' ').next().map(str::to_uppercase))
+            .collect()
+    });
 
+    println!("\nAvailable commands:\n");
+    for (name, usage) in USAGE {
+        if names.as_ref().is_none_or(|names| names.iter().any(|n| n == name)) {
+            println!("  {}", usage);
+        }
+    }
+    if let Some(names) = &names {
+        let server_only: Vec<&str> = names
+            .iter()
+            .filter(|n| !USAGE.iter().any(|(name, _)| name == n))
+            .map(String::as_str)
+            .collect();
+        if !server_only.is_empty() {
+            println!("\n  Also supported by the server: {}", server_only.join(", "));
+        }
+    }
+
+    println!(
+        r#"
   help              - Show this help
   quit / exit       - Exit the CLI
 
 Examples:
   SET mykey myvalue
   SET tempkey value 60   (expires in 60 seconds)
+  SET bucket 1 PX 250    (expires in 250 milliseconds)
   GET mykey
   EXISTS mykey
   DEL mykey
@@ -3,16 +3,25 @@
 //! High-performance in-memory cache server.
 //! Supports both single-threaded and multi-threaded concurrent modes.
 
-use celrix::server::{Config, WorkerPoolConfig};
-use celrix::{ConcurrentServer, Server};
+use celrix::cluster::{ReplicationConfig, ReplicationFollower, ReplicationLeader};
+use celrix::server::{Config, ConnectionLimitPolicy, WorkerPoolConfig};
+use celrix::{ConcurrentServer, ReplicationManager, Server};
 use clap::Parser;
-use tracing::info;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tracing::{info, warn};
 use tracing_subscriber::{fmt, EnvFilter};
 
 /// CELRIX Server - High-Performance In-Memory Cache
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
+    /// Load settings from this TOML file instead of the flags below
+    /// (CELRIX_* environment variables override it)
+    #[arg(long)]
+    config: Option<std::path::PathBuf>,
+
     /// Bind address
     #[arg(short, long, default_value = "0.0.0.0")]
     bind: String,
@@ -21,6 +30,14 @@ struct Args {
     #[arg(short, long, default_value_t = 6380)]
     port: u16,
 
+    /// Also accept clients on a Unix domain socket at this path
+    #[arg(long)]
+    unix_socket: Option<std::path::PathBuf>,
+
+    /// Expect a PROXY protocol v1 header from a load balancer on each connection
+    #[arg(long)]
+    proxy_protocol: bool,
+
     /// TTL cleaner interval in seconds
     #[arg(long, default_value_t = 10)]
     ttl_interval: u64,
@@ -40,6 +57,66 @@ struct Args {
     /// Command queue capacity
     #[arg(long, default_value_t = 10000)]
     queue_capacity: usize,
+
+    /// Milliseconds to wait on a full command queue before replying BUSY
+    #[arg(long, default_value_t = 5)]
+    queue_send_timeout: u64,
+
+    /// Maximum simultaneous client connections (0 = unlimited)
+    #[arg(long, default_value_t = 10000)]
+    max_connections: usize,
+
+    /// Queue new connections at the limit instead of rejecting them
+    #[arg(long)]
+    wait_for_connection_slot: bool,
+
+    /// Close connections idle for this many seconds (0 = disabled)
+    #[arg(long, default_value_t = 0)]
+    idle_timeout: u64,
+
+    /// Commands per second allowed per user or client IP (0 = unlimited)
+    #[arg(long, default_value_t = 0.0)]
+    rate_limit: f64,
+
+    /// Commands a client may burst above the rate limit
+    #[arg(long, default_value_t = 100)]
+    rate_limit_burst: u32,
+
+    /// Number of logical databases
+    #[arg(long, default_value_t = 16)]
+    databases: usize,
+
+    /// Publish keyspace notifications on writes
+    #[arg(long)]
+    notify_keyspace_events: bool,
+
+    /// Free large values removed by DEL in the background, like UNLINK
+    #[arg(long)]
+    lazy_free: bool,
+
+    /// Route keyed commands to a worker by key slot instead of a shared queue
+    #[arg(long)]
+    route_by_key: bool,
+
+    /// DashMap shards per database, a power of two (0 = four per KV worker)
+    #[arg(long, default_value_t = 0)]
+    shard_count: usize,
+
+    /// Disable a command for all clients (repeatable, e.g. --disable-command KEYS)
+    #[arg(long = "disable-command")]
+    disabled_commands: Vec<String>,
+
+    /// Serve the replication stream to followers on this port (0 = disabled)
+    #[arg(long, default_value_t = 0)]
+    replication_port: u16,
+
+    /// Follow the leader's replication stream at this address (host:port)
+    #[arg(long)]
+    replicaof: Option<String>,
+
+    /// This node's id when following a leader
+    #[arg(long, default_value_t = 1)]
+    node_id: u64,
 }
 
 #[tokio::main]
@@ -51,34 +128,63 @@ async fn main() -> anyhow::Result<()> {
 
     let args = Args::parse();
 
-    let mut config = Config::default()
-        .with_bind(&args.bind)
-        .with_port(args.port)
-        .with_ttl_interval(args.ttl_interval);
-
-    config.kv_workers = args.kv_workers;
-    config.vector_workers = args.vector_workers;
+    let config = match &args.config {
+        Some(path) => Config::from_file(path)?,
+        None => {
+            let config = config_from_args(&args);
+            config.validate()?;
+            config
+        }
+    };
+    let (bind, port) = (config.bind.clone(), config.port);
+    let (kv_workers, vector_workers) = (config.kv_workers, config.vector_workers);
 
     if args.concurrent {
         info!(
             "Starting CELRIX concurrent server on {}:{} with {} KV workers and {} Vector workers",
-            args.bind, args.port, 
-            if args.kv_workers == 0 { num_cpus::get() } else { args.kv_workers },
-            args.vector_workers
+            bind, port,
+            if kv_workers == 0 { num_cpus::get() } else { kv_workers },
+            vector_workers
         );
 
         let worker_config = WorkerPoolConfig {
-            num_workers: args.kv_workers, // Ignored
+            num_workers: kv_workers, // Ignored
             pin_to_cores: true, // Ignored
-            queue_capacity: args.queue_capacity,
+            queue_capacity: config.queue_capacity,
         };
 
-        let server = ConcurrentServer::with_worker_config(config, worker_config);
-        server.run().await?;
+        let mut server = ConcurrentServer::with_worker_config(config, worker_config);
+
+        if args.replication_port != 0 {
+            let manager = Arc::new(ReplicationManager::new(ReplicationConfig::default()));
+            server = server.with_replication(manager.clone());
+            let listener = TcpListener::bind((bind.as_str(), args.replication_port)).await?;
+            tokio::spawn(ReplicationLeader::new(manager).serve(listener));
+        }
+
+        if let Some(leader) = args.replicaof {
+            let mut follower = ReplicationFollower::new(args.node_id, server.databases().clone());
+            server = server.with_follower(follower.state());
+            tokio::spawn(async move {
+                loop {
+                    match follower.run(&leader).await {
+                        Ok(()) => warn!("Replication stream from {} closed", leader),
+                        Err(e) => warn!("Replication from {} failed: {}", leader, e),
+                    }
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            });
+        }
+
+        // Stopping the server on Ctrl-C removes its Unix socket file
+        tokio::select! {
+            result = server.run() => result?,
+            _ = tokio::signal::ctrl_c() => info!("Shutting down"),
+        }
     } else {
         info!(
             "Starting CELRIX single-threaded server on {}:{}",
-            args.bind, args.port
+            bind, port
         );
 
         let server = Server::new(config);
@@ -87,3 +193,35 @@ async fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Server config from command-line flags
+fn config_from_args(args: &Args) -> Config {
+    let mut config = Config::default()
+        .with_bind(&args.bind)
+        .with_port(args.port)
+        .with_ttl_interval(args.ttl_interval)
+        .with_disabled_commands(&args.disabled_commands)
+        .with_keyspace_notifications(args.notify_keyspace_events)
+        .with_lazy_free(args.lazy_free)
+        .with_route_by_key(args.route_by_key)
+        .with_shard_count(args.shard_count)
+        .with_databases(args.databases)
+        .with_max_connections(args.max_connections)
+        .with_idle_timeout(args.idle_timeout)
+        .with_queue_send_timeout(args.queue_send_timeout)
+        .with_rate_limit(args.rate_limit, args.rate_limit_burst)
+        .with_proxy_protocol(args.proxy_protocol)
+        .with_connection_limit_policy(if args.wait_for_connection_slot {
+            ConnectionLimitPolicy::Wait
+        } else {
+            ConnectionLimitPolicy::Reject
+        });
+
+    if let Some(path) = &args.unix_socket {
+        config = config.with_unix_socket(path);
+    }
+    config.kv_workers = args.kv_workers;
+    config.vector_workers = args.vector_workers;
+    config.queue_capacity = args.queue_capacity;
+    config
+}
@@ -0,0 +1,54 @@
+//! Quorum Fencing
+//!
+//! Stops a node that may be on the minority side of a partition from
+//! accepting writes, so two sides of a split brain can't diverge.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+use super::node::ClusterTopology;
+
+/// Error returned for commands refused by the fence
+pub const NO_QUORUM_ERROR: &str = "NOQUORUM this node can't confirm it's in the majority partition";
+
+/// Decides whether this node may serve writes (and reads) based on
+/// cluster quorum and split-brain detection
+#[derive(Debug)]
+pub struct QuorumFence {
+    topology: Arc<RwLock<ClusterTopology>>,
+    /// Set while more than one primary is visible
+    split_brain: AtomicBool,
+    /// Serve possibly stale reads while fenced
+    stale_reads: bool,
+}
+
+impl QuorumFence {
+    pub fn new(topology: Arc<RwLock<ClusterTopology>>) -> Self {
+        Self {
+            topology,
+            split_brain: AtomicBool::new(false),
+            stale_reads: true,
+        }
+    }
+
+    /// Whether reads are served while writes are fenced (default: true)
+    pub fn with_stale_reads(mut self, allow: bool) -> Self {
+        self.stale_reads = allow;
+        self
+    }
+
+    /// Record whether a split brain is currently detected
+    pub fn set_split_brain(&self, detected: bool) {
+        self.split_brain.store(detected, Ordering::Relaxed);
+    }
+
+    /// Whether this node can confirm it's in the majority partition
+    pub fn can_write(&self) -> bool {
+        !self.split_brain.load(Ordering::Relaxed) && self.topology.read().unwrap().has_quorum()
+    }
+
+    /// Whether reads may be served
+    pub fn can_read(&self) -> bool {
+        self.stale_reads || self.can_write()
+    }
+}
@@ -0,0 +1,173 @@
+//! Slot Migration
+//!
+//! Moves the keys of a slot to its new owner before handing it over.
+//!
+//! While a slot is migrating, the source keeps serving reads for keys it
+//! still holds and ASK-redirects writes (and reads of keys already moved)
+//! to the target. Keys are copied in batches with `SET NX`, so a write that
+//! already landed on the target is never overwritten by the older copy, and
+//! each batch is deleted locally once the target has confirmed it. Ownership
+//! only flips after every key has been moved. Like a redirected client, the
+//! migrator sends ASKING before each copy, since until then the target
+//! MOVED-redirects requests for the slot back to its owner.
+
+use futures::{SinkExt, StreamExt};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio_util::codec::Framed;
+use tracing::{info, warn};
+
+use super::node::NodeId;
+use super::sharding::{ShardManager, Slot};
+use crate::protocol::{Command, Response, SetOptions, VcpCodec};
+use crate::storage::ConcurrentStore;
+
+/// Moves slots from the local store to other nodes
+pub struct SlotMigrator {
+    shards: Arc<ShardManager>,
+    /// Keys sent per round-trip
+    batch_size: usize,
+}
+
+impl SlotMigrator {
+    pub fn new(shards: Arc<ShardManager>) -> Self {
+        Self { shards, batch_size: 100 }
+    }
+
+    /// Keys sent to the target per round-trip
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Move every key of `slot` from `store` to `target` at `addr`, then
+    /// hand the slot over. On failure the migration is cancelled; keys
+    /// already moved stay on the target, the rest stay local.
+    pub async fn migrate_slot(
+        &self,
+        slot: u16,
+        store: &ConcurrentStore,
+        target: NodeId,
+        addr: SocketAddr,
+    ) -> io::Result<usize> {
+        self.shards.start_migration(slot, target);
+        match self.transfer(slot, store, addr).await {
+            Ok(moved) => {
+                self.shards.complete_migration(slot);
+                info!("Migrated slot {} to node {} ({} keys)", slot, target, moved);
+                Ok(moved)
+            }
+            Err(e) => {
+                self.shards.cancel_migration(slot);
+                warn!("Migration of slot {} to node {} failed: {}", slot, target, e);
+                Err(e)
+            }
+        }
+    }
+
+    async fn transfer(&self, slot: u16, store: &ConcurrentStore, addr: SocketAddr) -> io::Result<usize> {
+        let mut entries = Vec::new();
+        store.for_each_live(|key, value, ttl| {
+            if Slot::from_key(key).0 == slot {
+                entries.push((key.clone(), value.clone(), ttl));
+            }
+        });
+
+        let socket = TcpStream::connect(addr).await?;
+        socket.set_nodelay(true)?;
+        let mut framed = Framed::new(socket, VcpCodec::new());
+
+        let mut moved = 0;
+        for batch in entries.chunks(self.batch_size) {
+            for (i, (key, value, ttl)) in batch.iter().enumerate() {
+                let set = Command::Set {
+                    key: key.clone(),
+                    value: value.clone(),
+                    ttl: ttl.map(|t| (t.as_millis() as u64).max(1)),
+                    options: SetOptions { nx: true, px: true, ..Default::default() },
+                };
+                framed.feed(Command::Asking.to_frame(i as u64)).await?;
+                framed.feed(set.to_frame(i as u64)).await?;
+            }
+            framed.flush().await?;
+
+            // One reply to each ASKING and each SET
+            for _ in 0..batch.len() * 2 {
+                let frame = framed
+                    .next()
+                    .await
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))??;
+                match Response::from_frame(&frame)? {
+                    // Nil: the key was already written on the target
+                    Response::Ok | Response::Nil => {}
+                    Response::Error(e) => return Err(io::Error::other(e)),
+                    other => return Err(io::Error::other(format!("unexpected reply during migration: {:?}", other))),
+                }
+            }
+
+            for (key, _, _) in batch {
+                store.del(key);
+            }
+            moved += batch.len();
+        }
+        Ok(moved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::ClusterRouter;
+    use crate::server::{Config, ConcurrentServer};
+    use bytes::Bytes;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_migrate_slot_moves_keys() {
+        let slot = Slot::from_key(b"{user}").0;
+
+        // The target sees the slot as importing from node 1, so it serves
+        // the copies only because each follows ASKING
+        let target_shards = Arc::new(ShardManager::new());
+        target_shards.distribute_slots(&[1]);
+        target_shards.start_migration(slot, 2);
+        let router = ClusterRouter::new(2, target_shards).with_node_addr(1, "127.0.0.1:1".parse().unwrap());
+        let target = ConcurrentServer::new(Config { kv_workers: 1, vector_workers: 1, ..Default::default() })
+            .with_cluster(Arc::new(router));
+        let target_dbs = target.databases().clone();
+        let target_store = target_dbs.get(0).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(target.serve(listener));
+
+        let source = ConcurrentStore::new();
+        for i in 0..250 {
+            source.set(Bytes::from(format!("{{user}}:{}", i)), Bytes::from(format!("v{}", i)), None);
+        }
+        source.set_with_ttl(Bytes::from_static(b"{user}:ttl"), Bytes::from_static(b"t"), Some(Duration::from_secs(60)));
+        source.set(Bytes::from_static(b"other"), Bytes::from_static(b"stays"), None);
+        // A write redirected to the target mid-migration isn't overwritten
+        target_store.set(Bytes::from_static(b"{user}:0"), Bytes::from_static(b"newer"), None);
+
+        let shards = Arc::new(ShardManager::new());
+        shards.distribute_slots(&[1]);
+        let migrator = SlotMigrator::new(shards.clone()).with_batch_size(64);
+        assert_eq!(migrator.migrate_slot(slot, &source, 2, addr).await.unwrap(), 251);
+
+        assert_eq!(shards.get_node_for_slot(Slot(slot)), Some(2));
+        assert_eq!(shards.is_migrating(slot), None);
+        assert_eq!(source.len(), 1);
+        assert!(source.exists(&Bytes::from_static(b"other")));
+
+        assert_eq!(target_store.get(&Bytes::from_static(b"{user}:0")).unwrap().as_ref(), b"newer");
+        for i in 1..250 {
+            let value = target_store.get(&Bytes::from(format!("{{user}}:{}", i))).unwrap();
+            assert_eq!(value, Bytes::from(format!("v{}", i)));
+        }
+        let ttl = target_store.pttl(&Bytes::from_static(b"{user}:ttl"));
+        assert!(matches!(ttl, Some(Some(d)) if d > Duration::from_secs(50)));
+    }
+}
@@ -2,12 +2,22 @@
 //!
 //! Distributed cluster support with replication and consensus.
 
+pub mod fencing;
+pub mod migration;
 pub mod node;
 pub mod raft;
+pub mod raft_storage;
 pub mod replication;
+pub mod replication_stream;
+pub mod routing;
 pub mod sharding;
 
-pub use node::{Node, NodeId, NodeRole, NodeState};
-pub use raft::{RaftNode, RaftConfig, RaftState};
+pub use fencing::{QuorumFence, NO_QUORUM_ERROR};
+pub use migration::SlotMigrator;
+pub use node::{ClusterTopology, Node, NodeId, NodeRole, NodeState};
+pub use raft::{RaftNode, RaftConfig, RaftPeers, RaftState, SnapshotMeta};
+pub use raft_storage::{FileRaftStorage, HardState, RaftStorage};
 pub use replication::{ReplicationManager, ReplicationConfig, ReplicationMode};
-pub use sharding::{ShardManager, Slot, SlotRange};
+pub use replication_stream::{FollowerState, ReplicationFollower, ReplicationLeader};
+pub use routing::{ClusterRouter, Route};
+pub use sharding::{hash_tag, ShardManager, Slot, SlotAssignment, SlotRange};
@@ -9,11 +9,12 @@ use std::time::Instant;
 pub type NodeId = u64;
 
 /// Node role in the cluster
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum NodeRole {
     /// Leader node (handles writes)
     Leader,
     /// Follower node (replica)
+    #[default]
     Follower,
     /// Candidate (during election)
     Candidate,
@@ -21,16 +22,11 @@ pub enum NodeRole {
     Learner,
 }
 
-impl Default for NodeRole {
-    fn default() -> Self {
-        Self::Follower
-    }
-}
-
 /// Node health state
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum NodeState {
     /// Node is healthy and responding
+    #[default]
     Healthy,
     /// Node is suspected to be down
     Suspect,
@@ -42,12 +38,6 @@ pub enum NodeState {
     Maintenance,
 }
 
-impl Default for NodeState {
-    fn default() -> Self {
-        Self::Healthy
-    }
-}
-
 /// Cluster node information
 #[derive(Debug, Clone)]
 pub struct Node {
@@ -3,11 +3,15 @@
 //! Leader election and log replication using Raft protocol.
 
 use std::collections::HashMap;
+use std::io;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::RwLock;
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tracing::error;
 
 use super::node::NodeId;
+use super::raft_storage::{HardState, RaftStorage};
 
 /// Raft configuration
 #[derive(Debug, Clone)]
@@ -34,20 +38,15 @@ impl Default for RaftConfig {
 }
 
 /// Raft state
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum RaftState {
+    #[default]
     Follower,
     Candidate,
     Leader,
     PreCandidate,
 }
 
-impl Default for RaftState {
-    fn default() -> Self {
-        Self::Follower
-    }
-}
-
 /// Log entry
 #[derive(Debug, Clone)]
 pub struct LogEntry {
@@ -109,6 +108,57 @@ pub struct AppendEntriesResponse {
     pub match_index: u64,
 }
 
+/// Install snapshot request (leader -> lagging follower)
+#[derive(Debug, Clone)]
+pub struct InstallSnapshotRequest {
+    pub term: u64,
+    pub leader_id: NodeId,
+    pub last_included_index: u64,
+    pub last_included_term: u64,
+    pub data: Vec<u8>,
+}
+
+/// Install snapshot response
+#[derive(Debug, Clone)]
+pub struct InstallSnapshotResponse {
+    pub term: u64,
+}
+
+/// Position of the latest snapshot in the log
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SnapshotMeta {
+    /// Index of the last entry covered by the snapshot
+    pub last_included_index: u64,
+    /// Term of that entry
+    pub last_included_term: u64,
+}
+
+/// Outbound RPCs to cluster peers.
+///
+/// Injected into `RaftNode::tick` so the election driver can run without a
+/// network transport (e.g. in-process nodes in tests). `None` means the peer
+/// could not be reached.
+pub trait RaftPeers {
+    /// IDs of all other voting members
+    fn peer_ids(&self) -> Vec<NodeId>;
+
+    /// Send a RequestVote (or pre-vote) RPC
+    fn request_vote(&self, peer: NodeId, req: &VoteRequest) -> Option<VoteResponse>;
+
+    /// Send an AppendEntries RPC
+    fn append_entries(&self, peer: NodeId, req: &AppendEntriesRequest)
+        -> Option<AppendEntriesResponse>;
+
+    /// Send an InstallSnapshot RPC
+    fn install_snapshot(
+        &self,
+        _peer: NodeId,
+        _req: &InstallSnapshotRequest,
+    ) -> Option<InstallSnapshotResponse> {
+        None
+    }
+}
+
 /// Raft node state machine
 pub struct RaftNode {
     /// Node ID
@@ -119,8 +169,12 @@ pub struct RaftNode {
     pub state: RwLock<RaftState>,
     /// Voted for in current term
     pub voted_for: RwLock<Option<NodeId>>,
-    /// Log entries
+    /// Log entries after the snapshot point
     pub log: RwLock<Vec<LogEntry>>,
+    /// Latest snapshot position (lock after `log`)
+    pub snapshot_meta: RwLock<SnapshotMeta>,
+    /// Latest snapshot state
+    snapshot_data: RwLock<Vec<u8>>,
     /// Commit index
     pub commit_index: AtomicU64,
     /// Last applied
@@ -137,17 +191,28 @@ pub struct RaftNode {
     pub last_heartbeat: RwLock<Instant>,
     /// Election deadline
     pub election_deadline: RwLock<Instant>,
+    /// State for the election timeout jitter
+    rng: AtomicU64,
+    /// Durable storage (None = in-memory only)
+    storage: Option<Box<dyn RaftStorage>>,
 }
 
 impl RaftNode {
     pub fn new(id: NodeId, config: RaftConfig) -> Self {
         let now = Instant::now();
-        Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0)
+            ^ id.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        let node = Self {
             id,
             current_term: AtomicU64::new(0),
             state: RwLock::new(RaftState::Follower),
             voted_for: RwLock::new(None),
             log: RwLock::new(Vec::new()),
+            snapshot_meta: RwLock::new(SnapshotMeta::default()),
+            snapshot_data: RwLock::new(Vec::new()),
             commit_index: AtomicU64::new(0),
             last_applied: AtomicU64::new(0),
             leader_id: RwLock::new(None),
@@ -156,7 +221,77 @@ impl RaftNode {
             config,
             last_heartbeat: RwLock::new(now),
             election_deadline: RwLock::new(now),
+            rng: AtomicU64::new(seed | 1),
+            storage: None,
+        };
+        node.reset_election_deadline();
+        node
+    }
+
+    /// Rebuild a node from durable storage; later state changes are
+    /// persisted to the same storage before being acknowledged
+    pub fn restore(
+        id: NodeId,
+        config: RaftConfig,
+        storage: Box<dyn RaftStorage>,
+    ) -> io::Result<Self> {
+        let (hard_state, log) = storage.load()?;
+
+        let mut node = Self::new(id, config);
+        node.current_term.store(hard_state.term, Ordering::SeqCst);
+        *node.voted_for.write().unwrap() = hard_state.voted_for;
+        *node.log.write().unwrap() = log;
+        if let Some((meta, data)) = storage.load_snapshot()? {
+            node.log
+                .write()
+                .unwrap()
+                .retain(|e| e.index > meta.last_included_index);
+            node.commit_index.store(meta.last_included_index, Ordering::SeqCst);
+            node.last_applied.store(meta.last_included_index, Ordering::SeqCst);
+            *node.snapshot_meta.write().unwrap() = meta;
+            *node.snapshot_data.write().unwrap() = data;
         }
+        node.storage = Some(storage);
+        Ok(node)
+    }
+
+    /// Persist term and vote
+    fn persist_hard_state(&self, term: u64, voted_for: Option<NodeId>) -> io::Result<()> {
+        match &self.storage {
+            Some(storage) => storage.save_hard_state(HardState { term, voted_for }),
+            None => Ok(()),
+        }
+    }
+
+    /// Persist `entries` as the log suffix, replacing any entries from the
+    /// first one's index on
+    fn persist_log_tail(&self, entries: &[LogEntry]) -> io::Result<()> {
+        match (&self.storage, entries.first()) {
+            (Some(storage), Some(first)) => {
+                storage.truncate_from(first.index)?;
+                storage.append(entries)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Pick a random election timeout within `config.election_timeout`
+    fn random_election_timeout(&self) -> Duration {
+        // xorshift64
+        let mut x = self.rng.load(Ordering::Relaxed);
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng.store(x, Ordering::Relaxed);
+
+        let (min, max) = self.config.election_timeout;
+        let span = max.saturating_sub(min) + 1;
+        Duration::from_millis(min + x % span)
+    }
+
+    /// Push the election deadline out by a fresh randomized timeout
+    pub fn reset_election_deadline(&self) {
+        *self.election_deadline.write().unwrap() = Instant::now() + self.random_election_timeout();
     }
 
     /// Get current term
@@ -174,22 +309,161 @@ impl RaftNode {
         self.get_state() == RaftState::Leader
     }
 
-    /// Get last log index
+    /// Get last log index (the snapshot point if the log is empty)
     pub fn last_log_index(&self) -> u64 {
-        self.log.read().unwrap().last().map(|e| e.index).unwrap_or(0)
+        let log = self.log.read().unwrap();
+        log.last()
+            .map(|e| e.index)
+            .unwrap_or_else(|| self.snapshot_meta.read().unwrap().last_included_index)
     }
 
-    /// Get last log term
+    /// Get last log term (the snapshot term if the log is empty)
     pub fn last_log_term(&self) -> u64 {
-        self.log.read().unwrap().last().map(|e| e.term).unwrap_or(0)
+        let log = self.log.read().unwrap();
+        log.last()
+            .map(|e| e.term)
+            .unwrap_or_else(|| self.snapshot_meta.read().unwrap().last_included_term)
+    }
+
+    /// Term of the entry at `index`, if it's in the log or at the snapshot point
+    pub fn term_at(&self, index: u64) -> Option<u64> {
+        let log = self.log.read().unwrap();
+        let meta = self.snapshot_meta.read().unwrap();
+        if index == meta.last_included_index {
+            return Some(meta.last_included_term);
+        }
+        if index < meta.last_included_index {
+            return None;
+        }
+        log.get((index - meta.last_included_index - 1) as usize)
+            .map(|e| e.term)
+    }
+
+    /// Latest snapshot state
+    pub fn snapshot_data(&self) -> Vec<u8> {
+        self.snapshot_data.read().unwrap().clone()
+    }
+
+    /// Compact the log: record a snapshot of the state machine up to
+    /// `last_included_index` and drop the entries it covers
+    pub fn snapshot(
+        &self,
+        last_included_index: u64,
+        last_included_term: u64,
+        state: Vec<u8>,
+    ) -> io::Result<()> {
+        let mut log = self.log.write().unwrap();
+        let mut meta = self.snapshot_meta.write().unwrap();
+        if last_included_index <= meta.last_included_index {
+            return Ok(());
+        }
+
+        let new_meta = SnapshotMeta {
+            last_included_index,
+            last_included_term,
+        };
+        if let Some(storage) = &self.storage {
+            storage.save_snapshot(new_meta, &state)?;
+        }
+
+        let covered = log
+            .iter()
+            .position(|e| e.index > last_included_index)
+            .unwrap_or(log.len());
+        log.drain(..covered);
+        *meta = new_meta;
+        *self.snapshot_data.write().unwrap() = state;
+        Ok(())
+    }
+
+    /// Build an InstallSnapshot request from the latest snapshot
+    pub fn snapshot_request(&self) -> InstallSnapshotRequest {
+        let meta = *self.snapshot_meta.read().unwrap();
+        InstallSnapshotRequest {
+            term: self.term(),
+            leader_id: self.id,
+            last_included_index: meta.last_included_index,
+            last_included_term: meta.last_included_term,
+            data: self.snapshot_data(),
+        }
+    }
+
+    /// Handle install snapshot request
+    pub fn handle_install_snapshot(&self, req: &InstallSnapshotRequest) -> InstallSnapshotResponse {
+        let current_term = self.term();
+        if req.term < current_term {
+            return InstallSnapshotResponse { term: current_term };
+        }
+        if req.term > current_term {
+            if self.become_follower(req.term, Some(req.leader_id)).is_err() {
+                return InstallSnapshotResponse { term: current_term };
+            }
+        } else if self.get_state() != RaftState::Follower {
+            *self.state.write().unwrap() = RaftState::Follower;
+        }
+        *self.leader_id.write().unwrap() = Some(req.leader_id);
+        *self.last_heartbeat.write().unwrap() = Instant::now();
+        self.reset_election_deadline();
+
+        let mut log = self.log.write().unwrap();
+        let mut meta = self.snapshot_meta.write().unwrap();
+        if req.last_included_index <= meta.last_included_index {
+            return InstallSnapshotResponse { term: self.term() };
+        }
+
+        let new_meta = SnapshotMeta {
+            last_included_index: req.last_included_index,
+            last_included_term: req.last_included_term,
+        };
+
+        // Keep our suffix only if it continues from the snapshot point
+        let matching = log.iter().position(|e| {
+            e.index == req.last_included_index && e.term == req.last_included_term
+        });
+
+        if let Some(storage) = &self.storage {
+            let persisted = storage.save_snapshot(new_meta, &req.data).and_then(|_| {
+                match matching {
+                    Some(_) => Ok(()),
+                    None => storage.truncate_from(req.last_included_index + 1),
+                }
+            });
+            if let Err(e) = persisted {
+                error!("Failed to persist Raft snapshot: {}", e);
+                return InstallSnapshotResponse { term: self.term() };
+            }
+        }
+
+        match matching {
+            Some(pos) => {
+                log.drain(..=pos);
+            }
+            None => log.clear(),
+        }
+        *meta = new_meta;
+        *self.snapshot_data.write().unwrap() = req.data.clone();
+        self.commit_index
+            .fetch_max(req.last_included_index, Ordering::SeqCst);
+        self.last_applied
+            .fetch_max(req.last_included_index, Ordering::SeqCst);
+
+        InstallSnapshotResponse { term: self.term() }
     }
 
-    /// Transition to candidate state
-    pub fn become_candidate(&self) {
+    /// Transition to candidate state, voting for ourselves in the next
+    /// term. The term and vote are persisted first; if that fails nothing
+    /// changes and the candidacy is abandoned.
+    pub fn become_candidate(&self) -> io::Result<()> {
         let mut state = self.state.write().unwrap();
-        *state = RaftState::Candidate;
-        self.current_term.fetch_add(1, Ordering::SeqCst);
+        let term = self.term() + 1;
+        if let Err(e) = self.persist_hard_state(term, Some(self.id)) {
+            error!("Failed to persist Raft term {}: {}", term, e);
+            return Err(e);
+        }
+        self.current_term.store(term, Ordering::SeqCst);
         *self.voted_for.write().unwrap() = Some(self.id);
+        *state = RaftState::Candidate;
+        Ok(())
     }
 
     /// Transition to leader state
@@ -199,13 +473,165 @@ impl RaftNode {
         *self.leader_id.write().unwrap() = Some(self.id);
     }
 
-    /// Transition to follower state
-    pub fn become_follower(&self, term: u64, leader: Option<NodeId>) {
-        self.current_term.store(term, Ordering::SeqCst);
+    /// Reset per-follower replication progress after winning an election
+    fn init_leader_state(&self, peers: &[NodeId]) {
+        let next = self.last_log_index() + 1;
+        let mut next_index = self.next_index.write().unwrap();
+        let mut match_index = self.match_index.write().unwrap();
+        next_index.clear();
+        match_index.clear();
+        for &peer in peers {
+            next_index.insert(peer, next);
+            match_index.insert(peer, 0);
+        }
+    }
+
+    /// Drive timers: start an election when the deadline passes, or send
+    /// heartbeats when leader. Call periodically (e.g. every few ms).
+    pub fn tick(&self, peers: &impl RaftPeers) {
+        if self.is_leader() {
+            let interval = Duration::from_millis(self.config.heartbeat_interval);
+            if self.last_heartbeat.read().unwrap().elapsed() >= interval {
+                self.broadcast_heartbeat(peers);
+            }
+            return;
+        }
+
+        if Instant::now() >= *self.election_deadline.read().unwrap() {
+            self.run_election(peers);
+        }
+    }
+
+    /// Run one election round (with an optional pre-vote phase)
+    fn run_election(&self, peers: &impl RaftPeers) {
+        self.reset_election_deadline();
+        let peer_ids = peer_ids_excluding(peers, self.id);
+        let cluster_size = peer_ids.len() + 1;
+        let quorum = cluster_size / 2 + 1;
+
+        if self.config.pre_vote {
+            *self.state.write().unwrap() = RaftState::PreCandidate;
+            let req = self.vote_request(self.term() + 1, true);
+            let (granted, higher_term) = self.collect_votes(peers, &peer_ids, &req);
+            if let Some(term) = higher_term {
+                let _ = self.become_follower(term, None);
+                return;
+            }
+            if granted < quorum {
+                *self.state.write().unwrap() = RaftState::Follower;
+                return;
+            }
+        }
+
+        if self.become_candidate().is_err() {
+            return;
+        }
+        let term = self.term();
+        let req = self.vote_request(term, false);
+        let (granted, higher_term) = self.collect_votes(peers, &peer_ids, &req);
+        if let Some(term) = higher_term {
+            let _ = self.become_follower(term, None);
+            return;
+        }
+
+        // Only win if nothing moved us on while votes were outstanding
+        if granted >= quorum && self.term() == term && self.get_state() == RaftState::Candidate {
+            self.become_leader();
+            self.init_leader_state(&peer_ids);
+            self.broadcast_heartbeat(peers);
+        }
+    }
+
+    fn vote_request(&self, term: u64, pre_vote: bool) -> VoteRequest {
+        VoteRequest {
+            term,
+            candidate_id: self.id,
+            last_log_index: self.last_log_index(),
+            last_log_term: self.last_log_term(),
+            pre_vote,
+        }
+    }
+
+    /// Count granted votes (including our own) and report any higher term seen
+    fn collect_votes(
+        &self,
+        peers: &impl RaftPeers,
+        peer_ids: &[NodeId],
+        req: &VoteRequest,
+    ) -> (usize, Option<u64>) {
+        let mut granted = 1;
+        let mut higher_term = None;
+        for &peer in peer_ids {
+            if let Some(resp) = peers.request_vote(peer, req) {
+                if resp.term > self.term() && !resp.vote_granted {
+                    higher_term = Some(higher_term.map_or(resp.term, |t: u64| t.max(resp.term)));
+                } else if resp.vote_granted {
+                    granted += 1;
+                }
+            }
+        }
+        (granted, higher_term)
+    }
+
+    /// Send an empty AppendEntries to every peer to assert leadership
+    fn broadcast_heartbeat(&self, peers: &impl RaftPeers) {
+        *self.last_heartbeat.write().unwrap() = Instant::now();
+
+        let req = AppendEntriesRequest {
+            term: self.term(),
+            leader_id: self.id,
+            prev_log_index: self.last_log_index(),
+            prev_log_term: self.last_log_term(),
+            entries: Vec::new(),
+            leader_commit: self.commit_index.load(Ordering::SeqCst),
+        };
+
+        let snapshot_index = self.snapshot_meta.read().unwrap().last_included_index;
+        for peer in peer_ids_excluding(peers, self.id) {
+            let resp = match peers.append_entries(peer, &req) {
+                Some(resp) => resp,
+                None => continue,
+            };
+            if resp.term > self.term() {
+                if self.become_follower(resp.term, None).is_ok() {
+                    self.reset_election_deadline();
+                }
+                return;
+            }
+
+            let mut next = resp.match_index + 1;
+            if resp.success {
+                self.match_index.write().unwrap().insert(peer, resp.match_index);
+            } else if resp.match_index < snapshot_index {
+                // Entries the follower needs are compacted: ship the snapshot
+                if let Some(snap) = peers.install_snapshot(peer, &self.snapshot_request()) {
+                    if snap.term > self.term() {
+                        if self.become_follower(snap.term, None).is_ok() {
+                            self.reset_election_deadline();
+                        }
+                        return;
+                    }
+                    self.match_index.write().unwrap().insert(peer, snapshot_index);
+                    next = snapshot_index + 1;
+                }
+            }
+            self.next_index.write().unwrap().insert(peer, next);
+        }
+    }
+
+    /// Transition to follower state in a newer `term`. The term is
+    /// persisted first; if that fails nothing changes.
+    pub fn become_follower(&self, term: u64, leader: Option<NodeId>) -> io::Result<()> {
         let mut state = self.state.write().unwrap();
+        if let Err(e) = self.persist_hard_state(term, None) {
+            error!("Failed to persist Raft term {}: {}", term, e);
+            return Err(e);
+        }
+        self.current_term.store(term, Ordering::SeqCst);
         *state = RaftState::Follower;
         *self.voted_for.write().unwrap() = None;
         *self.leader_id.write().unwrap() = leader;
+        Ok(())
     }
 
     /// Handle vote request
@@ -221,13 +647,19 @@ impl RaftNode {
             };
         }
 
-        // If request term is newer, become follower
-        if req.term > current_term && !req.pre_vote {
-            self.become_follower(req.term, None);
+        // If request term is newer, become follower (can't vote in a term
+        // we failed to record)
+        if req.term > current_term && !req.pre_vote && self.become_follower(req.term, None).is_err() {
+            return VoteResponse {
+                term: current_term,
+                vote_granted: false,
+                pre_vote: req.pre_vote,
+            };
         }
 
-        // Check if we can vote (read lock scope)
-        let can_vote = {
+        // Check if we can vote (read lock scope). Pre-votes don't bind us,
+        // so an earlier vote in this term doesn't matter.
+        let can_vote = req.pre_vote || {
             let voted_for = self.voted_for.read().unwrap();
             voted_for.is_none() || *voted_for == Some(req.candidate_id)
         };
@@ -238,11 +670,16 @@ impl RaftNode {
         let log_ok = req.last_log_term > last_term
             || (req.last_log_term == last_term && req.last_log_index >= last_index);
 
-        let vote_granted = can_vote && log_ok;
+        let mut vote_granted = can_vote && log_ok;
 
-        // Record vote if granted (separate write lock scope)
+        // Record vote if granted (separate write lock scope), durably first
         if vote_granted && !req.pre_vote {
-            *self.voted_for.write().unwrap() = Some(req.candidate_id);
+            if self.persist_hard_state(self.term(), Some(req.candidate_id)).is_ok() {
+                *self.voted_for.write().unwrap() = Some(req.candidate_id);
+                self.reset_election_deadline();
+            } else {
+                vote_granted = false;
+            }
         }
 
         VoteResponse {
@@ -267,57 +704,97 @@ impl RaftNode {
 
         // Update term and become follower
         if req.term > current_term {
-            self.become_follower(req.term, Some(req.leader_id));
+            if self.become_follower(req.term, Some(req.leader_id)).is_err() {
+                return AppendEntriesResponse {
+                    term: current_term,
+                    success: false,
+                    match_index: 0,
+                };
+            }
+        } else if self.get_state() != RaftState::Follower {
+            // Lost the election for this term; keep our vote
+            *self.state.write().unwrap() = RaftState::Follower;
         }
 
         // Update leader and heartbeat
         *self.leader_id.write().unwrap() = Some(req.leader_id);
         *self.last_heartbeat.write().unwrap() = Instant::now();
+        self.reset_election_deadline();
 
-        // Check log consistency
+        // Check log consistency. Entries at or below the snapshot point are
+        // committed, so only the snapshot boundary term needs to match there.
         let log = self.log.read().unwrap();
-        if req.prev_log_index > 0 {
-            if let Some(entry) = log.get(req.prev_log_index as usize - 1) {
-                if entry.term != req.prev_log_term {
-                    return AppendEntriesResponse {
-                        term: self.term(),
-                        success: false,
-                        match_index: 0,
-                    };
-                }
-            } else if req.prev_log_index > log.len() as u64 {
-                return AppendEntriesResponse {
-                    term: self.term(),
-                    success: false,
-                    match_index: log.len() as u64,
-                };
-            }
+        let snapshot = *self.snapshot_meta.read().unwrap();
+        let last_index = log.last().map(|e| e.index).unwrap_or(snapshot.last_included_index);
+        if req.prev_log_index > last_index {
+            return AppendEntriesResponse {
+                term: self.term(),
+                success: false,
+                match_index: last_index,
+            };
+        }
+        let prev_term = if req.prev_log_index > snapshot.last_included_index {
+            Some(log[(req.prev_log_index - snapshot.last_included_index - 1) as usize].term)
+        } else if req.prev_log_index == snapshot.last_included_index && req.prev_log_index > 0 {
+            Some(snapshot.last_included_term)
+        } else {
+            None
+        };
+        if prev_term.is_some_and(|t| t != req.prev_log_term) {
+            return AppendEntriesResponse {
+                term: self.term(),
+                success: false,
+                match_index: 0,
+            };
         }
         drop(log);
 
         // Append new entries
         if !req.entries.is_empty() {
             let mut log = self.log.write().unwrap();
-            for entry in &req.entries {
-                if (entry.index as usize) <= log.len() {
-                    // Overwrite conflicting entry
-                    log[entry.index as usize - 1] = entry.clone();
-                } else {
-                    log.push(entry.clone());
+            let snapshot_index = self.snapshot_meta.read().unwrap().last_included_index;
+            // First conflict (or end of our log): everything from there is
+            // replaced by the rest of the leader's entries
+            let first_changed = req.entries.iter().enumerate().find_map(|(i, entry)| {
+                // Already covered by the snapshot
+                if entry.index <= snapshot_index {
+                    return None;
+                }
+                let pos = (entry.index - snapshot_index - 1) as usize;
+                let matches = pos < log.len() && log[pos].term == entry.term;
+                (!matches).then_some((i, pos))
+            });
+
+            // Persist before changing the log, so a failed write leaves no
+            // in-memory entries that a retry would take as already stored
+            if let Some((i, pos)) = first_changed {
+                if let Err(e) = self.persist_log_tail(&req.entries[i..]) {
+                    error!("Failed to persist Raft log: {}", e);
+                    return AppendEntriesResponse {
+                        term: self.term(),
+                        success: false,
+                        match_index: 0,
+                    };
                 }
+                log.truncate(pos);
+                log.extend(req.entries[i..].iter().cloned());
             }
         }
 
+        // Only entries up to the last one in this request are known to match
+        // the leader; anything after may still be stale
+        let last_new_index = req.prev_log_index + req.entries.len() as u64;
+
         // Update commit index
         if req.leader_commit > self.commit_index.load(Ordering::SeqCst) {
-            let new_commit = std::cmp::min(req.leader_commit, self.last_log_index());
+            let new_commit = std::cmp::min(req.leader_commit, last_new_index);
             self.commit_index.store(new_commit, Ordering::SeqCst);
         }
 
         AppendEntriesResponse {
             term: self.term(),
             success: true,
-            match_index: self.last_log_index(),
+            match_index: last_new_index,
         }
     }
 
@@ -328,23 +805,40 @@ impl RaftNode {
         }
 
         let mut log = self.log.write().unwrap();
-        let index = log.len() as u64 + 1;
+        let snapshot_index = self.snapshot_meta.read().unwrap().last_included_index;
+        let index = log.last().map(|e| e.index).unwrap_or(snapshot_index) + 1;
         let term = self.term();
 
-        log.push(LogEntry {
+        let entry = LogEntry {
             term,
             index,
             entry_type: LogEntryType::Command,
             data,
-        });
+        };
+
+        // Persist before acknowledging
+        if let Some(storage) = &self.storage {
+            if let Err(e) = storage.append(std::slice::from_ref(&entry)) {
+                error!("Failed to persist Raft log entry {}: {}", index, e);
+                return None;
+            }
+        }
+        log.push(entry);
 
         Some(index)
     }
 }
 
+/// Peer IDs with our own ID filtered out
+fn peer_ids_excluding(peers: &impl RaftPeers, id: NodeId) -> Vec<NodeId> {
+    peers.peer_ids().into_iter().filter(|&p| p != id).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
 
     #[test]
     fn test_raft_node_creation() {
@@ -357,7 +851,7 @@ mod tests {
     #[test]
     fn test_become_candidate() {
         let node = RaftNode::new(1, RaftConfig::default());
-        node.become_candidate();
+        node.become_candidate().unwrap();
 
         assert_eq!(node.get_state(), RaftState::Candidate);
         assert_eq!(node.term(), 1);
@@ -367,7 +861,7 @@ mod tests {
     #[test]
     fn test_become_leader() {
         let node = RaftNode::new(1, RaftConfig::default());
-        node.become_candidate();
+        node.become_candidate().unwrap();
         node.become_leader();
 
         assert!(node.is_leader());
@@ -389,4 +883,308 @@ mod tests {
         let resp = node.handle_vote_request(&req);
         assert!(resp.vote_granted);
     }
+
+    /// In-process peers that call straight into other nodes
+    struct LocalPeers {
+        nodes: Vec<Arc<RaftNode>>,
+    }
+
+    impl RaftPeers for LocalPeers {
+        fn peer_ids(&self) -> Vec<NodeId> {
+            self.nodes.iter().map(|n| n.id).collect()
+        }
+
+        fn request_vote(&self, peer: NodeId, req: &VoteRequest) -> Option<VoteResponse> {
+            let node = self.nodes.iter().find(|n| n.id == peer)?;
+            Some(node.handle_vote_request(req))
+        }
+
+        fn append_entries(
+            &self,
+            peer: NodeId,
+            req: &AppendEntriesRequest,
+        ) -> Option<AppendEntriesResponse> {
+            let node = self.nodes.iter().find(|n| n.id == peer)?;
+            Some(node.handle_append_entries(req))
+        }
+    }
+
+    #[test]
+    fn test_election_converges() {
+        let config = RaftConfig {
+            election_timeout: (30, 60),
+            heartbeat_interval: 10,
+            ..Default::default()
+        };
+        let nodes: Vec<_> = (1..=3)
+            .map(|id| Arc::new(RaftNode::new(id, config.clone())))
+            .collect();
+        let peers = LocalPeers { nodes: nodes.clone() };
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            for node in &nodes {
+                node.tick(&peers);
+            }
+            if nodes.iter().filter(|n| n.is_leader()).count() == 1 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        // Keep ticking: heartbeats should hold leadership stable
+        for _ in 0..50 {
+            for node in &nodes {
+                node.tick(&peers);
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        let leaders: Vec<_> = nodes.iter().filter(|n| n.is_leader()).collect();
+        assert_eq!(leaders.len(), 1);
+        let leader = leaders[0];
+        for node in &nodes {
+            assert_eq!(node.term(), leader.term());
+            assert_eq!(*node.leader_id.read().unwrap(), Some(leader.id));
+        }
+    }
+
+    #[test]
+    fn test_restore_from_storage() {
+        use crate::cluster::raft_storage::FileRaftStorage;
+
+        let dir = tempfile::tempdir().unwrap();
+        let open = || Box::new(FileRaftStorage::open(dir.path()).unwrap());
+
+        {
+            let node = RaftNode::restore(1, RaftConfig::default(), open()).unwrap();
+            node.become_candidate().unwrap();
+            node.become_leader();
+            for i in 0..5 {
+                node.append_command(format!("cmd-{}", i).into_bytes()).unwrap();
+            }
+            assert_eq!(node.last_log_index(), 5);
+        }
+
+        let node = RaftNode::restore(1, RaftConfig::default(), open()).unwrap();
+        assert_eq!(node.term(), 1);
+        assert_eq!(*node.voted_for.read().unwrap(), Some(1));
+        assert_eq!(node.last_log_index(), 5);
+        assert_eq!(node.last_log_term(), 1);
+        assert_eq!(node.log.read().unwrap()[4].data, b"cmd-4");
+    }
+
+    #[test]
+    fn test_state_unchanged_when_persisting_fails() {
+        use crate::cluster::raft_storage::FileRaftStorage;
+
+        let dir = tempfile::tempdir().unwrap();
+        let node = RaftNode::restore(1, RaftConfig::default(), Box::new(FileRaftStorage::open(dir.path()).unwrap())).unwrap();
+        std::fs::remove_dir_all(dir.path()).unwrap();
+
+        assert!(node.become_candidate().is_err());
+        assert_eq!(node.term(), 0);
+        assert_eq!(node.get_state(), RaftState::Follower);
+        assert_eq!(*node.voted_for.read().unwrap(), None);
+
+        let resp = node.handle_vote_request(&VoteRequest {
+            term: 5,
+            candidate_id: 2,
+            last_log_index: 0,
+            last_log_term: 0,
+            pre_vote: false,
+        });
+        assert!(!resp.vote_granted);
+        assert_eq!(resp.term, 0);
+        assert_eq!(node.term(), 0);
+    }
+
+    #[test]
+    fn test_log_compaction() {
+        let node = RaftNode::new(1, RaftConfig::default());
+        node.become_candidate().unwrap();
+        node.become_leader();
+        for i in 0..10_000 {
+            node.append_command(format!("cmd-{}", i).into_bytes()).unwrap();
+        }
+
+        node.snapshot(9_000, 1, b"state@9000".to_vec()).unwrap();
+        assert_eq!(node.log.read().unwrap().len(), 1_000);
+        assert_eq!(node.last_log_index(), 10_000);
+        assert_eq!(node.term_at(9_000), Some(1));
+        assert_eq!(node.term_at(8_999), None);
+        assert_eq!(node.append_command(b"next".to_vec()), Some(10_001));
+
+        // Compacting everything still leaves the snapshot point as the log tail
+        node.snapshot(10_001, 1, b"state@10001".to_vec()).unwrap();
+        assert!(node.log.read().unwrap().is_empty());
+        assert_eq!(node.last_log_index(), 10_001);
+        assert_eq!(node.last_log_term(), 1);
+    }
+
+    #[test]
+    fn test_install_snapshot_then_append() {
+        let follower = RaftNode::new(2, RaftConfig::default());
+
+        let resp = follower.handle_install_snapshot(&InstallSnapshotRequest {
+            term: 3,
+            leader_id: 1,
+            last_included_index: 500,
+            last_included_term: 2,
+            data: b"state@500".to_vec(),
+        });
+        assert_eq!(resp.term, 3);
+        assert_eq!(follower.last_log_index(), 500);
+        assert_eq!(follower.commit_index.load(Ordering::SeqCst), 500);
+        assert_eq!(follower.snapshot_data(), b"state@500");
+
+        // Normal replication resumes right after the snapshot point
+        let resp = follower.handle_append_entries(&AppendEntriesRequest {
+            term: 3,
+            leader_id: 1,
+            prev_log_index: 500,
+            prev_log_term: 2,
+            entries: vec![LogEntry {
+                term: 3,
+                index: 501,
+                entry_type: LogEntryType::Command,
+                data: b"cmd".to_vec(),
+            }],
+            leader_commit: 501,
+        });
+        assert!(resp.success);
+        assert_eq!(resp.match_index, 501);
+        assert_eq!(follower.term_at(501), Some(3));
+
+        // A mismatched term at the snapshot boundary is rejected
+        let resp = follower.handle_append_entries(&AppendEntriesRequest {
+            term: 3,
+            leader_id: 1,
+            prev_log_index: 500,
+            prev_log_term: 1,
+            entries: Vec::new(),
+            leader_commit: 501,
+        });
+        assert!(!resp.success);
+    }
+
+    #[test]
+    fn test_append_entries_truncates_conflicting_tail() {
+        let entry = |index, term| LogEntry {
+            term,
+            index,
+            entry_type: LogEntryType::Command,
+            data: format!("cmd-{}-{}", index, term).into_bytes(),
+        };
+
+        // Follower holds uncommitted entries 3..=5 from an old leader in term 2
+        let follower = RaftNode::new(2, RaftConfig::default());
+        follower.handle_append_entries(&AppendEntriesRequest {
+            term: 2,
+            leader_id: 1,
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: vec![entry(1, 1), entry(2, 1), entry(3, 2), entry(4, 2), entry(5, 2)],
+            leader_commit: 2,
+        });
+        assert_eq!(follower.last_log_index(), 5);
+
+        // New leader in term 3 only has one entry after index 2
+        let resp = follower.handle_append_entries(&AppendEntriesRequest {
+            term: 3,
+            leader_id: 3,
+            prev_log_index: 2,
+            prev_log_term: 1,
+            entries: vec![entry(3, 3)],
+            leader_commit: 3,
+        });
+        assert!(resp.success);
+        assert_eq!(resp.match_index, 3);
+
+        let log = follower.log.read().unwrap();
+        assert_eq!(log.len(), 3);
+        assert_eq!(log[2].term, 3);
+        assert_eq!(log[2].data, b"cmd-3-3");
+        drop(log);
+        assert_eq!(follower.commit_index.load(Ordering::SeqCst), 3);
+
+        // Re-delivering an already-applied prefix doesn't truncate the tail
+        let resp = follower.handle_append_entries(&AppendEntriesRequest {
+            term: 3,
+            leader_id: 3,
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: vec![entry(1, 1)],
+            leader_commit: 3,
+        });
+        assert!(resp.success);
+        assert_eq!(follower.last_log_index(), 3);
+    }
+
+    /// File storage whose log appends fail while `failing` is set
+    struct FlakyStorage {
+        inner: crate::cluster::raft_storage::FileRaftStorage,
+        failing: Arc<AtomicBool>,
+    }
+
+    impl RaftStorage for FlakyStorage {
+        fn load(&self) -> io::Result<(HardState, Vec<LogEntry>)> {
+            self.inner.load()
+        }
+
+        fn save_hard_state(&self, state: HardState) -> io::Result<()> {
+            self.inner.save_hard_state(state)
+        }
+
+        fn append(&self, entries: &[LogEntry]) -> io::Result<()> {
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(io::Error::other("disk full"));
+            }
+            self.inner.append(entries)
+        }
+
+        fn truncate_from(&self, from_index: u64) -> io::Result<()> {
+            self.inner.truncate_from(from_index)
+        }
+
+        fn save_snapshot(&self, meta: SnapshotMeta, data: &[u8]) -> io::Result<()> {
+            self.inner.save_snapshot(meta, data)
+        }
+
+        fn load_snapshot(&self) -> io::Result<Option<(SnapshotMeta, Vec<u8>)>> {
+            self.inner.load_snapshot()
+        }
+    }
+
+    #[test]
+    fn test_failed_append_is_not_acknowledged_on_retry() {
+        use crate::cluster::raft_storage::FileRaftStorage;
+
+        let dir = tempfile::tempdir().unwrap();
+        let failing = Arc::new(AtomicBool::new(true));
+        let storage = FlakyStorage { inner: FileRaftStorage::open(dir.path()).unwrap(), failing: failing.clone() };
+        let follower = RaftNode::restore(2, RaftConfig::default(), Box::new(storage)).unwrap();
+        let req = AppendEntriesRequest {
+            term: 1,
+            leader_id: 1,
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: (1..=3)
+                .map(|index| LogEntry { term: 1, index, entry_type: LogEntryType::Command, data: Vec::new() })
+                .collect(),
+            leader_commit: 0,
+        };
+
+        // Nothing reached the disk, so nothing is kept in memory either
+        assert!(!follower.handle_append_entries(&req).success);
+        assert_eq!(follower.last_log_index(), 0);
+        assert!(!follower.handle_append_entries(&req).success);
+
+        failing.store(false, Ordering::SeqCst);
+        let resp = follower.handle_append_entries(&req);
+        assert!(resp.success);
+        assert_eq!(resp.match_index, 3);
+        let (_, log) = FileRaftStorage::open(dir.path()).unwrap().load().unwrap();
+        assert_eq!(log.iter().map(|e| e.index).collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
 }
@@ -0,0 +1,309 @@
+//! Raft Storage
+//!
+//! Durable storage for Raft term, vote, and log entries.
+
+use bytes::{Buf, BufMut, BytesMut};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::node::NodeId;
+use super::raft::{LogEntry, LogEntryType, SnapshotMeta};
+
+/// Term and vote that must survive restarts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HardState {
+    pub term: u64,
+    pub voted_for: Option<NodeId>,
+}
+
+/// Pluggable persistence for Raft state.
+///
+/// Every method must be durable (fsynced) before returning `Ok`, since the
+/// node acknowledges RPCs right after calling it.
+pub trait RaftStorage: Send + Sync {
+    /// Load the persisted hard state and log
+    fn load(&self) -> io::Result<(HardState, Vec<LogEntry>)>;
+
+    /// Persist the current term and vote
+    fn save_hard_state(&self, state: HardState) -> io::Result<()>;
+
+    /// Append entries to the end of the log
+    fn append(&self, entries: &[LogEntry]) -> io::Result<()>;
+
+    /// Remove all entries with `index >= from_index`
+    fn truncate_from(&self, from_index: u64) -> io::Result<()>;
+
+    /// Persist a snapshot and drop the log entries it covers
+    fn save_snapshot(&self, meta: SnapshotMeta, data: &[u8]) -> io::Result<()>;
+
+    /// Load the latest snapshot, if any
+    fn load_snapshot(&self) -> io::Result<Option<(SnapshotMeta, Vec<u8>)>>;
+}
+
+const HARD_STATE_FILE: &str = "hard_state";
+const LOG_FILE: &str = "raft.log";
+const SNAPSHOT_FILE: &str = "snapshot";
+
+/// File-backed Raft storage in a single directory
+pub struct FileRaftStorage {
+    dir: PathBuf,
+    log: Mutex<File>,
+}
+
+impl FileRaftStorage {
+    /// Open (or create) storage in the given directory. A torn trailing
+    /// record left by a crash mid-append is cut off, so later appends follow
+    /// the last complete entry.
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .read(true)
+            .open(dir.join(LOG_FILE))?;
+
+        let mut data = Vec::new();
+        (&log).read_to_end(&mut data)?;
+        let (_, valid_len) = Self::decode_entries(&data)?;
+        if valid_len < data.len() {
+            log.set_len(valid_len as u64)?;
+            log.sync_all()?;
+        }
+
+        Ok(Self {
+            dir,
+            log: Mutex::new(log),
+        })
+    }
+
+    fn encode_entry(buf: &mut BytesMut, entry: &LogEntry) {
+        buf.put_u64(entry.index);
+        buf.put_u64(entry.term);
+        buf.put_u8(match entry.entry_type {
+            LogEntryType::Command => 0,
+            LogEntryType::ConfigChange => 1,
+            LogEntryType::NoOp => 2,
+        });
+        buf.put_u32(entry.data.len() as u32);
+        buf.put_slice(&entry.data);
+    }
+
+    fn read_entries(&self) -> io::Result<Vec<LogEntry>> {
+        let mut data = Vec::new();
+        File::open(self.dir.join(LOG_FILE))?.read_to_end(&mut data)?;
+        Ok(Self::decode_entries(&data)?.0)
+    }
+
+    /// Decode log records, returning them with the byte length of the
+    /// complete ones. A torn trailing record (crash mid-append) is ignored.
+    fn decode_entries(bytes: &[u8]) -> io::Result<(Vec<LogEntry>, usize)> {
+        let mut buf = bytes;
+
+        let mut entries = Vec::new();
+        let mut valid_len = 0;
+        while buf.remaining() >= 21 {
+            let index = buf.get_u64();
+            let term = buf.get_u64();
+            let entry_type = match buf.get_u8() {
+                0 => LogEntryType::Command,
+                1 => LogEntryType::ConfigChange,
+                2 => LogEntryType::NoOp,
+                t => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Invalid log entry type: {}", t),
+                    ))
+                }
+            };
+            if buf.remaining() < 4 {
+                break;
+            }
+            let len = buf.get_u32() as usize;
+            if buf.remaining() < len {
+                break;
+            }
+            let data = buf[..len].to_vec();
+            buf.advance(len);
+            entries.push(LogEntry {
+                term,
+                index,
+                entry_type,
+                data,
+            });
+            valid_len = bytes.len() - buf.remaining();
+        }
+        Ok((entries, valid_len))
+    }
+
+    /// Rewrite the log keeping only entries matching `keep`
+    fn rewrite_log(&self, keep: impl Fn(&LogEntry) -> bool) -> io::Result<()> {
+        let mut log = self.log.lock().unwrap();
+        let mut buf = BytesMut::new();
+        for entry in self.read_entries()?.iter().filter(|e| keep(e)) {
+            Self::encode_entry(&mut buf, entry);
+        }
+        self.write_atomic(LOG_FILE, &buf)?;
+
+        *log = OpenOptions::new()
+            .append(true)
+            .read(true)
+            .open(self.dir.join(LOG_FILE))?;
+        Ok(())
+    }
+
+    /// Write a file atomically via temp file + rename
+    fn write_atomic(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        let tmp = self.dir.join(format!("{}.tmp", name));
+        let mut file = File::create(&tmp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&tmp, self.dir.join(name))?;
+        File::open(&self.dir)?.sync_all()
+    }
+}
+
+impl RaftStorage for FileRaftStorage {
+    fn load(&self) -> io::Result<(HardState, Vec<LogEntry>)> {
+        let mut state = HardState::default();
+        match fs::read(self.dir.join(HARD_STATE_FILE)) {
+            Ok(data) if data.len() == 17 => {
+                let mut buf = &data[..];
+                state.term = buf.get_u64();
+                let has_vote = buf.get_u8() != 0;
+                let vote = buf.get_u64();
+                state.voted_for = has_vote.then_some(vote);
+            }
+            // Written atomically, so anything else is damage, not a torn write;
+            // starting over at term 0 could let this node vote twice
+            Ok(data) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Raft hard state is {} bytes, expected 17", data.len()),
+                ))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok((state, self.read_entries()?))
+    }
+
+    fn save_hard_state(&self, state: HardState) -> io::Result<()> {
+        let mut buf = BytesMut::with_capacity(17);
+        buf.put_u64(state.term);
+        buf.put_u8(state.voted_for.is_some() as u8);
+        buf.put_u64(state.voted_for.unwrap_or(0));
+        self.write_atomic(HARD_STATE_FILE, &buf)
+    }
+
+    fn append(&self, entries: &[LogEntry]) -> io::Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut buf = BytesMut::new();
+        for entry in entries {
+            Self::encode_entry(&mut buf, entry);
+        }
+        let mut log = self.log.lock().unwrap();
+        log.write_all(&buf)?;
+        log.sync_data()
+    }
+
+    fn truncate_from(&self, from_index: u64) -> io::Result<()> {
+        self.rewrite_log(|e| e.index < from_index)
+    }
+
+    fn save_snapshot(&self, meta: SnapshotMeta, data: &[u8]) -> io::Result<()> {
+        let mut buf = BytesMut::with_capacity(16 + data.len());
+        buf.put_u64(meta.last_included_index);
+        buf.put_u64(meta.last_included_term);
+        buf.put_slice(data);
+        self.write_atomic(SNAPSHOT_FILE, &buf)?;
+        self.rewrite_log(|e| e.index > meta.last_included_index)
+    }
+
+    fn load_snapshot(&self) -> io::Result<Option<(SnapshotMeta, Vec<u8>)>> {
+        let data = match fs::read(self.dir.join(SNAPSHOT_FILE)) {
+            Ok(data) if data.len() >= 16 => data,
+            Ok(_) => return Ok(None),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut buf = &data[..];
+        let meta = SnapshotMeta {
+            last_included_index: buf.get_u64(),
+            last_included_term: buf.get_u64(),
+        };
+        Ok(Some((meta, buf.to_vec())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(index: u64, term: u64) -> LogEntry {
+        LogEntry {
+            term,
+            index,
+            entry_type: LogEntryType::Command,
+            data: format!("cmd-{}", index).into_bytes(),
+        }
+    }
+
+    #[test]
+    fn test_file_storage_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FileRaftStorage::open(dir.path()).unwrap();
+
+        storage
+            .save_hard_state(HardState { term: 3, voted_for: Some(2) })
+            .unwrap();
+        storage.append(&[entry(1, 1), entry(2, 1), entry(3, 2)]).unwrap();
+        storage.truncate_from(3).unwrap();
+        storage.append(&[entry(3, 3)]).unwrap();
+        drop(storage);
+
+        let storage = FileRaftStorage::open(dir.path()).unwrap();
+        let (state, log) = storage.load().unwrap();
+        assert_eq!(state, HardState { term: 3, voted_for: Some(2) });
+        assert_eq!(log.len(), 3);
+        assert_eq!(log[2].term, 3);
+        assert_eq!(log[1].data, b"cmd-2");
+    }
+
+    #[test]
+    fn test_open_truncates_torn_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FileRaftStorage::open(dir.path()).unwrap();
+        storage.append(&[entry(1, 1), entry(2, 1)]).unwrap();
+        drop(storage);
+
+        // Crash partway through appending a third record
+        let path = dir.path().join(LOG_FILE);
+        let good_len = fs::metadata(&path).unwrap().len();
+        let mut torn = BytesMut::new();
+        FileRaftStorage::encode_entry(&mut torn, &entry(3, 1));
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(&torn[..10]).unwrap();
+
+        let storage = FileRaftStorage::open(dir.path()).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), good_len);
+        storage.append(&[entry(3, 2)]).unwrap();
+        drop(storage);
+
+        let (_, log) = FileRaftStorage::open(dir.path()).unwrap().load().unwrap();
+        assert_eq!(log.iter().map(|e| (e.index, e.term)).collect::<Vec<_>>(), vec![(1, 1), (2, 1), (3, 2)]);
+    }
+
+    #[test]
+    fn test_load_rejects_damaged_hard_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FileRaftStorage::open(dir.path()).unwrap();
+        assert_eq!(storage.load().unwrap().0, HardState::default());
+
+        fs::write(dir.path().join(HARD_STATE_FILE), [0u8; 5]).unwrap();
+        assert_eq!(storage.load().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -2,17 +2,23 @@
 //!
 //! Manages data replication between nodes.
 
-use std::collections::HashMap;
+use bytes::{Buf, BufMut, Bytes};
+use std::collections::{HashMap, VecDeque};
+use std::io;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::RwLock;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use tokio::sync::futures::Notified;
+use tokio::sync::Notify;
 
 use super::node::NodeId;
+use crate::storage::Databases;
 
 /// Replication mode
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum ReplicationMode {
     /// Asynchronous replication (fast, eventual consistency)
+    #[default]
     Async,
     /// Semi-synchronous (wait for at least one replica)
     SemiSync,
@@ -20,12 +26,6 @@ pub enum ReplicationMode {
     Sync,
 }
 
-impl Default for ReplicationMode {
-    fn default() -> Self {
-        Self::Async
-    }
-}
-
 /// Replication configuration
 #[derive(Debug, Clone)]
 pub struct ReplicationConfig {
@@ -37,7 +37,7 @@ pub struct ReplicationConfig {
     pub timeout_ms: u64,
     /// Batch size for replication
     pub batch_size: usize,
-    /// Buffer size for replication stream
+    /// Byte budget for the in-memory replication backlog
     pub buffer_size: usize,
 }
 
@@ -78,12 +78,114 @@ pub struct ReplicationEntry {
     pub data: Vec<u8>,
 }
 
+impl ReplicationEntry {
+    /// Bytes this entry occupies in the backlog and on the wire
+    pub fn encoded_len(&self) -> usize {
+        // seq, op, timestamp and data length prefix
+        8 + 1 + 8 + 4 + self.data.len()
+    }
+
+    /// Payload for a Set: [db u32][key_len u32][key][value_len u32][value][ttl_ms i64, -1 = none]
+    pub fn set_data(db: u32, key: &[u8], value: &[u8], ttl: Option<Duration>) -> Vec<u8> {
+        let mut data = Vec::with_capacity(20 + key.len() + value.len());
+        data.put_u32(db);
+        data.put_u32(key.len() as u32);
+        data.put_slice(key);
+        data.put_u32(value.len() as u32);
+        data.put_slice(value);
+        data.put_i64(ttl.map_or(-1, |ttl| ttl.as_millis() as i64));
+        data
+    }
+
+    /// Payload for a Del: [db u32][key_len u32][key]
+    pub fn del_data(db: u32, key: &[u8]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(8 + key.len());
+        data.put_u32(db);
+        data.put_u32(key.len() as u32);
+        data.put_slice(key);
+        data
+    }
+
+    /// Payload for a FlushDb: [db u32]
+    pub fn flush_data(db: u32) -> Vec<u8> {
+        let mut data = Vec::with_capacity(4);
+        data.put_u32(db);
+        data
+    }
+
+    /// Apply this entry to a follower's databases
+    pub fn apply(&self, databases: &Databases) -> io::Result<()> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        let mut data = Bytes::copy_from_slice(&self.data);
+        let read_bytes = |data: &mut Bytes| -> io::Result<Bytes> {
+            if data.remaining() < 4 {
+                return Err(invalid("truncated replication entry"));
+            }
+            let len = data.get_u32() as usize;
+            if data.remaining() < len {
+                return Err(invalid("truncated replication entry"));
+            }
+            Ok(data.copy_to_bytes(len))
+        };
+
+        if data.remaining() < 4 {
+            return Err(invalid("truncated replication entry"));
+        }
+        let db = data.get_u32() as usize;
+        let store = databases.get(db).ok_or_else(|| invalid("replicated database out of range"))?;
+        match self.op {
+            ReplicationOp::Set => {
+                let key = read_bytes(&mut data)?;
+                let value = read_bytes(&mut data)?;
+                if data.remaining() < 8 {
+                    return Err(invalid("truncated replication entry"));
+                }
+                let ttl = u64::try_from(data.get_i64()).ok().map(Duration::from_millis);
+                store.set_with_ttl(key, value, ttl);
+            }
+            ReplicationOp::Del => {
+                store.del(&read_bytes(&mut data)?);
+            }
+            // The leader's clock decides; don't wait for ours to agree
+            ReplicationOp::Expire => {
+                store.expire_now(&read_bytes(&mut data)?);
+            }
+            ReplicationOp::FlushDb => {
+                store.clear();
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Replication operation
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ReplicationOp {
     Set,
     Del,
     Expire,
+    FlushDb,
+}
+
+impl ReplicationOp {
+    pub fn as_u8(self) -> u8 {
+        match self {
+            ReplicationOp::Set => 1,
+            ReplicationOp::Del => 2,
+            ReplicationOp::Expire => 3,
+            ReplicationOp::FlushDb => 4,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(ReplicationOp::Set),
+            2 => Some(ReplicationOp::Del),
+            3 => Some(ReplicationOp::Expire),
+            4 => Some(ReplicationOp::FlushDb),
+            _ => None,
+        }
+    }
 }
 
 /// Replica state
@@ -114,7 +216,15 @@ impl ReplicaState {
     }
 }
 
+/// Buffered entries, oldest first, with their total encoded size
+#[derive(Debug, Default)]
+struct Backlog {
+    entries: VecDeque<ReplicationEntry>,
+    bytes: usize,
+}
+
 /// Replication manager
+#[derive(Debug)]
 pub struct ReplicationManager {
     /// Configuration
     config: ReplicationConfig,
@@ -123,10 +233,14 @@ pub struct ReplicationManager {
     /// Replica states
     replicas: RwLock<HashMap<NodeId, ReplicaState>>,
     /// Replication buffer
-    buffer: RwLock<Vec<ReplicationEntry>>,
+    buffer: RwLock<Backlog>,
     /// Am I the leader?
     #[allow(dead_code)]
     is_leader: RwLock<bool>,
+    /// Wakes replica streams when entries are recorded
+    appended: Notify,
+    /// Woken whenever a replica acknowledges an offset
+    acked: Notify,
 }
 
 impl ReplicationManager {
@@ -135,11 +249,24 @@ impl ReplicationManager {
             config,
             offset: AtomicU64::new(0),
             replicas: RwLock::new(HashMap::new()),
-            buffer: RwLock::new(Vec::new()),
+            buffer: RwLock::new(Backlog::default()),
             is_leader: RwLock::new(false),
+            appended: Notify::new(),
+            acked: Notify::new(),
         }
     }
 
+    /// Get configuration
+    pub fn config(&self) -> &ReplicationConfig {
+        &self.config
+    }
+
+    /// Future that completes on the next `record`. Enable it before
+    /// checking for entries so a write in between isn't missed.
+    pub fn appended(&self) -> Notified<'_> {
+        self.appended.notified()
+    }
+
     /// Get current offset
     pub fn offset(&self) -> u64 {
         self.offset.load(Ordering::SeqCst)
@@ -151,6 +278,29 @@ impl ReplicationManager {
         replicas.insert(node_id, ReplicaState::new(node_id));
     }
 
+    /// Register a replica streaming from `offset` as connected, if every
+    /// entry after that offset is still buffered. While connected, entries
+    /// it hasn't acked are never trimmed.
+    pub fn connect_replica(&self, node_id: NodeId, offset: u64) -> bool {
+        // Hold the buffer lock so nothing is trimmed before it's registered
+        let buffer = self.buffer.read().unwrap();
+        if offset > self.offset() || buffer.entries.front().is_some_and(|first| offset + 1 < first.seq) {
+            return false;
+        }
+        let mut replicas = self.replicas.write().unwrap();
+        let replica = replicas.entry(node_id).or_insert_with(|| ReplicaState::new(node_id));
+        replica.update_offset(offset, self.offset());
+        replica.connected = true;
+        true
+    }
+
+    /// Mark a replica disconnected; its backlog is no longer retained
+    pub fn disconnect_replica(&self, node_id: NodeId) {
+        if let Some(replica) = self.replicas.write().unwrap().get_mut(&node_id) {
+            replica.connected = false;
+        }
+    }
+
     /// Remove a replica
     pub fn remove_replica(&self, node_id: NodeId) {
         let mut replicas = self.replicas.write().unwrap();
@@ -159,39 +309,63 @@ impl ReplicationManager {
 
     /// Record a write operation
     pub fn record(&self, op: ReplicationOp, data: Vec<u8>) -> u64 {
-        let seq = self.offset.fetch_add(1, Ordering::SeqCst) + 1;
-
-        let entry = ReplicationEntry {
-            seq,
-            op,
-            timestamp_ms: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_millis() as u64,
-            data,
-        };
+        let timestamp_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
 
+        // Sequence under the buffer lock so entries are buffered in order
         let mut buffer = self.buffer.write().unwrap();
-        buffer.push(entry);
-
-        // Trim buffer if too large
-        if buffer.len() > self.config.buffer_size / 100 {
-            let trim_to = buffer.len() - self.config.buffer_size / 200;
-            buffer.drain(0..trim_to);
+        let seq = self.offset.fetch_add(1, Ordering::SeqCst) + 1;
+        let entry = ReplicationEntry { seq, op, timestamp_ms, data };
+        buffer.bytes += entry.encoded_len();
+        buffer.entries.push_back(entry);
+
+        // Trim oldest entries past the byte budget, but keep anything a
+        // connected replica hasn't acked yet
+        if buffer.bytes > self.config.buffer_size {
+            let replicas = self.replicas.read().unwrap();
+            let retain_after = replicas
+                .values()
+                .filter(|r| r.connected)
+                .map(|r| r.offset)
+                .min()
+                .unwrap_or(u64::MAX);
+            while buffer.bytes > self.config.buffer_size {
+                match buffer.entries.front() {
+                    Some(first) if first.seq <= retain_after => {
+                        let first = buffer.entries.pop_front().unwrap();
+                        buffer.bytes -= first.encoded_len();
+                    }
+                    _ => break,
+                }
+            }
         }
+        drop(buffer);
 
+        self.appended.notify_waiters();
         seq
     }
 
     /// Get entries from offset for replication
     pub fn get_entries(&self, from_offset: u64, limit: usize) -> Vec<ReplicationEntry> {
         let buffer = self.buffer.read().unwrap();
-        buffer
-            .iter()
-            .filter(|e| e.seq > from_offset)
-            .take(limit)
-            .cloned()
-            .collect()
+        // Entries are contiguous by seq, so skip straight to the offset
+        let skip = buffer
+            .entries
+            .front()
+            .map_or(0, |first| (from_offset + 1).saturating_sub(first.seq) as usize);
+        buffer.entries.iter().skip(skip).take(limit).cloned().collect()
+    }
+
+    /// Bytes currently held in the replication backlog
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffer.read().unwrap().bytes
+    }
+
+    /// Number of entries currently held in the replication backlog
+    pub fn buffered_entries(&self) -> usize {
+        self.buffer.read().unwrap().entries.len()
     }
 
     /// Acknowledge replication from a replica
@@ -201,6 +375,19 @@ impl ReplicationManager {
         if let Some(replica) = replicas.get_mut(&node_id) {
             replica.update_offset(offset, leader_offset);
         }
+        drop(replicas);
+        self.acked.notify_waiters();
+    }
+
+    /// Resolves on the next acknowledgement from any replica
+    pub fn acked(&self) -> Notified<'_> {
+        self.acked.notified()
+    }
+
+    /// Number of replicas that have acknowledged at least `offset`
+    pub fn acked_count(&self, offset: u64) -> usize {
+        let replicas = self.replicas.read().unwrap();
+        replicas.values().filter(|r| r.offset >= offset).count()
     }
 
     /// Get replication lag for a replica
@@ -285,6 +472,42 @@ mod tests {
         assert_eq!(manager.get_lag(2), Some(2));
     }
 
+    #[test]
+    fn test_buffer_trims_to_byte_budget() {
+        let config = ReplicationConfig { buffer_size: 10_000, ..Default::default() };
+        let manager = ReplicationManager::new(config);
+
+        // With no replicas connected the backlog stays within budget
+        for i in 0..50 {
+            let size = if i % 5 == 0 { 3000 } else { 100 };
+            manager.record(ReplicationOp::Set, vec![0; size]);
+            assert!(manager.buffered_bytes() <= 10_000);
+        }
+        let first = manager.get_entries(0, 1)[0].seq;
+        assert!(first > 1);
+        assert_eq!(manager.get_entries(first - 1, usize::MAX).len(), manager.buffered_entries());
+
+        // A connected replica pins everything it hasn't acked
+        assert!(manager.connect_replica(1, manager.offset()));
+        assert!(!manager.connect_replica(2, 0));
+        let pinned = manager.offset();
+        for _ in 0..10 {
+            manager.record(ReplicationOp::Set, vec![0; 3000]);
+        }
+        assert!(manager.buffered_bytes() > 10_000);
+        assert_eq!(manager.get_entries(pinned, usize::MAX).len(), 10);
+
+        // Once it acks, the next write trims back under budget
+        manager.ack(1, pinned + 8);
+        manager.record(ReplicationOp::Set, vec![0; 100]);
+        assert!(manager.buffered_bytes() <= 10_000);
+        assert_eq!(manager.get_entries(pinned + 8, usize::MAX).len(), 3);
+
+        // A disconnected replica no longer pins the backlog
+        manager.disconnect_replica(1);
+        assert_eq!(manager.healthy_replica_count(), 0);
+    }
+
     #[test]
     fn test_durability_check() {
         let config = ReplicationConfig::default().with_mode(ReplicationMode::SemiSync);
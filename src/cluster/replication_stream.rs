@@ -0,0 +1,401 @@
+//! Replication Transport
+//!
+//! Streams the leader's replication log to followers over VCP frames.
+//!
+//! A follower connects and sends `ReplSync` with its node id and applied
+//! offset. The leader then pushes `ReplEntries` batches from that offset as
+//! writes are recorded, and the follower answers each batch with `ReplAck`
+//! once it's applied. Each batch ends with the leader's current offset, so
+//! the follower knows how far behind it is; the first is sent straight
+//! away, even if empty.
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::{SinkExt, StreamExt};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::{Framed, FramedRead, FramedWrite};
+use tracing::{info, warn};
+
+use super::node::NodeId;
+use super::replication::{ReplicationEntry, ReplicationManager, ReplicationOp};
+use crate::protocol::{Frame, OpCode, VcpCodec};
+use crate::storage::Databases;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// [node_id u64][offset u64]
+fn sync_frame(node_id: NodeId, offset: u64) -> Frame {
+    let mut buf = BytesMut::with_capacity(16);
+    buf.put_u64(node_id);
+    buf.put_u64(offset);
+    Frame::new(OpCode::ReplSync, 0, buf.freeze())
+}
+
+/// [offset u64]
+fn ack_frame(offset: u64) -> Frame {
+    Frame::new(OpCode::ReplAck, 0, Bytes::copy_from_slice(&offset.to_be_bytes()))
+}
+
+/// [count u32] then [seq u64][op u8][timestamp_ms u64][data_len u32][data] per
+/// entry, then [leader_offset u64]
+fn entries_frame(entries: &[ReplicationEntry], leader_offset: u64) -> Frame {
+    let size = entries.iter().map(ReplicationEntry::encoded_len).sum::<usize>();
+    let mut buf = BytesMut::with_capacity(12 + size);
+    buf.put_u32(entries.len() as u32);
+    for entry in entries {
+        buf.put_u64(entry.seq);
+        buf.put_u8(entry.op.as_u8());
+        buf.put_u64(entry.timestamp_ms);
+        buf.put_u32(entry.data.len() as u32);
+        buf.put_slice(&entry.data);
+    }
+    buf.put_u64(leader_offset);
+    Frame::new(OpCode::ReplEntries, 0, buf.freeze())
+}
+
+/// Entries and the leader's offset, which older leaders don't send
+fn decode_entries(mut payload: Bytes) -> io::Result<(Vec<ReplicationEntry>, Option<u64>)> {
+    if payload.remaining() < 4 {
+        return Err(invalid("truncated replication batch"));
+    }
+    let count = payload.get_u32() as usize;
+    let mut entries = Vec::with_capacity(count.min(payload.remaining() / 21));
+    for _ in 0..count {
+        if payload.remaining() < 21 {
+            return Err(invalid("truncated replication batch"));
+        }
+        let seq = payload.get_u64();
+        let op = ReplicationOp::from_u8(payload.get_u8()).ok_or_else(|| invalid("unknown replication op"))?;
+        let timestamp_ms = payload.get_u64();
+        let len = payload.get_u32() as usize;
+        if payload.remaining() < len {
+            return Err(invalid("truncated replication batch"));
+        }
+        let data = payload.copy_to_bytes(len).to_vec();
+        entries.push(ReplicationEntry { seq, op, timestamp_ms, data });
+    }
+    let leader_offset = (payload.remaining() >= 8).then(|| payload.get_u64());
+    Ok((entries, leader_offset))
+}
+
+fn read_u64(payload: &mut Bytes) -> io::Result<u64> {
+    if payload.remaining() < 8 {
+        return Err(invalid("truncated replication frame"));
+    }
+    Ok(payload.get_u64())
+}
+
+/// Leader side: serves the replication log to connecting followers
+pub struct ReplicationLeader {
+    manager: Arc<ReplicationManager>,
+}
+
+impl ReplicationLeader {
+    pub fn new(manager: Arc<ReplicationManager>) -> Self {
+        Self { manager }
+    }
+
+    /// Accept followers on `listener`, streaming to each until it disconnects
+    pub async fn serve(self, listener: TcpListener) -> io::Result<()> {
+        info!("Serving replication stream on {}", listener.local_addr()?);
+        loop {
+            let (socket, peer) = listener.accept().await?;
+            let manager = self.manager.clone();
+            tokio::spawn(async move {
+                if let Err(e) = Self::stream_to(manager, socket).await {
+                    warn!("Replication stream to {} ended: {}", peer, e);
+                }
+            });
+        }
+    }
+
+    async fn stream_to(manager: Arc<ReplicationManager>, socket: TcpStream) -> io::Result<()> {
+        socket.set_nodelay(true)?;
+        let (reader, writer) = socket.into_split();
+        let mut frames = FramedRead::new(reader, VcpCodec::new());
+        let mut sink = FramedWrite::new(writer, VcpCodec::new());
+
+        let hello = frames
+            .next()
+            .await
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))??;
+        if hello.header.opcode != OpCode::ReplSync {
+            return Err(invalid("expected REPLSYNC"));
+        }
+        let mut payload = hello.payload;
+        let node_id = read_u64(&mut payload)?;
+        let mut offset = read_u64(&mut payload)?;
+
+        if !manager.connect_replica(node_id, offset) {
+            let msg = format!("ERR cannot resume replication from offset {}; full resync required", offset);
+            sink.send(Frame::error(0, &msg)).await?;
+            return Err(invalid(&msg));
+        }
+        info!("Replica {} connected at offset {}", node_id, offset);
+
+        let send = async {
+            let batch_size = manager.config().batch_size.max(1);
+            // Tells a caught-up follower its lag without waiting for a write
+            let mut first = true;
+            loop {
+                let appended = manager.appended();
+                tokio::pin!(appended);
+                appended.as_mut().enable();
+
+                let leader_offset = manager.offset();
+                let entries = manager.get_entries(offset, batch_size);
+                match entries.last() {
+                    Some(last) => {
+                        offset = last.seq;
+                        sink.send(entries_frame(&entries, leader_offset)).await?;
+                    }
+                    None if first => sink.send(entries_frame(&[], leader_offset)).await?,
+                    None => appended.await,
+                }
+                first = false;
+            }
+        };
+        let receive = async {
+            while let Some(frame) = frames.next().await {
+                let frame = frame?;
+                if frame.header.opcode != OpCode::ReplAck {
+                    return Err(invalid("expected REPLACK"));
+                }
+                manager.ack(node_id, read_u64(&mut frame.payload.clone())?);
+            }
+            Ok::<_, io::Error>(())
+        };
+
+        let result = tokio::select! {
+            result = send => result,
+            result = receive => result,
+        };
+        manager.disconnect_replica(node_id);
+        result
+    }
+}
+
+/// How far a follower is behind its leader, shared with the connections
+/// serving reads from the follower
+#[derive(Debug, Default)]
+pub struct FollowerState {
+    /// Sequence number of the last applied entry
+    applied: AtomicU64,
+    /// Leader's offset as of its latest batch
+    leader_offset: AtomicU64,
+    /// Streaming from the leader right now
+    connected: AtomicBool,
+    leader_addr: RwLock<Option<String>>,
+}
+
+impl FollowerState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Entries the leader has that this follower hasn't applied, or None
+    /// while it isn't streaming from the leader (the lag is unknown)
+    pub fn lag(&self) -> Option<u64> {
+        if !self.connected.load(Ordering::Acquire) {
+            return None;
+        }
+        let applied = self.applied.load(Ordering::Acquire);
+        Some(self.leader_offset.load(Ordering::Acquire).saturating_sub(applied))
+    }
+
+    /// Address of the leader this follower last streamed from
+    pub fn leader_addr(&self) -> Option<String> {
+        self.leader_addr.read().unwrap().clone()
+    }
+
+    /// Streaming from `leader_addr`, which has reached `leader_offset`
+    pub fn mark_connected(&self, leader_addr: &str, leader_offset: u64) {
+        *self.leader_addr.write().unwrap() = Some(leader_addr.to_string());
+        self.leader_offset.fetch_max(leader_offset, Ordering::AcqRel);
+        self.connected.store(true, Ordering::Release);
+    }
+
+    pub fn mark_disconnected(&self) {
+        self.connected.store(false, Ordering::Release);
+    }
+
+    /// Entries up to `applied` are applied and the leader has reached
+    /// at least `leader_offset`
+    pub fn record(&self, applied: u64, leader_offset: u64) {
+        self.leader_offset.fetch_max(leader_offset.max(applied), Ordering::AcqRel);
+        self.applied.store(applied, Ordering::Release);
+    }
+}
+
+/// Follower side: applies a leader's replication stream to local databases
+pub struct ReplicationFollower {
+    node_id: NodeId,
+    databases: Databases,
+    /// Sequence number of the last applied entry
+    offset: u64,
+    state: Arc<FollowerState>,
+}
+
+impl ReplicationFollower {
+    pub fn new(node_id: NodeId, databases: Databases) -> Self {
+        Self { node_id, databases, offset: 0, state: Arc::new(FollowerState::new()) }
+    }
+
+    /// Sequence number of the last applied entry
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Lag tracking for serving reads, see `ConcurrentServer::with_follower`
+    pub fn state(&self) -> Arc<FollowerState> {
+        self.state.clone()
+    }
+
+    /// Stream from the leader until it disconnects. The applied offset is
+    /// kept, so calling this again resumes where the stream left off.
+    pub async fn run(&mut self, leader_addr: &str) -> io::Result<()> {
+        let result = self.stream(leader_addr).await;
+        self.state.mark_disconnected();
+        result
+    }
+
+    async fn stream(&mut self, leader_addr: &str) -> io::Result<()> {
+        let socket = TcpStream::connect(leader_addr).await?;
+        socket.set_nodelay(true)?;
+        let mut framed = Framed::new(socket, VcpCodec::new());
+        framed.send(sync_frame(self.node_id, self.offset)).await?;
+
+        while let Some(frame) = framed.next().await {
+            let frame = frame?;
+            match frame.header.opcode {
+                OpCode::ReplEntries => {
+                    let (entries, leader_offset) = decode_entries(frame.payload)?;
+                    let leader_offset = leader_offset.unwrap_or(0);
+                    self.state.mark_connected(leader_addr, leader_offset);
+                    for entry in entries {
+                        if entry.seq <= self.offset {
+                            continue;
+                        }
+                        if entry.seq != self.offset + 1 {
+                            return Err(invalid("gap in replication stream"));
+                        }
+                        entry.apply(&self.databases)?;
+                        self.offset = entry.seq;
+                        self.state.record(self.offset, leader_offset);
+                    }
+                    framed.send(ack_frame(self.offset)).await?;
+                }
+                OpCode::Error => {
+                    return Err(io::Error::other(String::from_utf8_lossy(&frame.payload).into_owned()));
+                }
+                _ => return Err(invalid("unexpected frame in replication stream")),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::ReplicationConfig;
+    use crate::storage::ConcurrentStore;
+    use std::time::Duration;
+
+    fn snapshot(databases: &Databases) -> Vec<Vec<(Bytes, Bytes)>> {
+        databases
+            .iter()
+            .map(|store| {
+                let mut pairs = Vec::new();
+                store.for_each_live(|key, value, _| pairs.push((key.clone(), value.clone())));
+                pairs.sort();
+                pairs
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_follower_converges_with_leader() {
+        let manager = Arc::new(ReplicationManager::new(ReplicationConfig::default()));
+        let leader = Databases::from_fn(2, |db| ConcurrentStore::new().with_replication(manager.clone(), db as u32));
+        let follower_dbs = Databases::new(2, 4);
+
+        // Writes before the follower connects are caught up from the log
+        let store = leader.get(0).unwrap();
+        for i in 0..100 {
+            store.set(Bytes::from(format!("k{}", i)), Bytes::from(format!("v{}", i)), None);
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(ReplicationLeader::new(manager.clone()).serve(listener));
+        let mut follower = ReplicationFollower::new(7, follower_dbs.clone());
+        let state = follower.state();
+        tokio::spawn(async move { follower.run(&addr).await });
+
+        // ...and later writes of every kind are streamed
+        for i in 0..10 {
+            store.del(&Bytes::from(format!("k{}", i)));
+        }
+        store.append(Bytes::from_static(b"k50"), b"-more");
+        store.incr_by(Bytes::from_static(b"counter"), 5).unwrap();
+        store.set_with_ttl(Bytes::from_static(b"ttl"), Bytes::from_static(b"t"), Some(Duration::from_secs(60)));
+        let other = leader.get(1).unwrap();
+        other.set(Bytes::from_static(b"flushed"), Bytes::from_static(b"db"), None);
+        other.clear();
+        other.set(Bytes::from_static(b"other"), Bytes::from_static(b"db"), None);
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while manager.get_lag(7) != Some(0) || manager.min_confirmed_offset() < manager.offset() {
+            assert!(tokio::time::Instant::now() < deadline, "follower didn't catch up");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(snapshot(&follower_dbs), snapshot(&leader));
+        assert_eq!(follower_dbs.get(1).unwrap().keys(), vec![Bytes::from_static(b"other")]);
+        assert_eq!(manager.healthy_replica_count(), 1);
+        assert_eq!(state.lag(), Some(0));
+        let ttl = follower_dbs.get(0).unwrap().pttl(&Bytes::from_static(b"ttl"));
+        assert!(matches!(ttl, Some(Some(d)) if d > Duration::from_secs(50)));
+    }
+
+    #[tokio::test]
+    async fn test_follower_applies_leader_expiry() {
+        let manager = Arc::new(ReplicationManager::new(ReplicationConfig::default()));
+        let leader = Databases::from_fn(1, |db| ConcurrentStore::new().with_replication(manager.clone(), db as u32));
+        let follower_dbs = Databases::new(1, 4);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(ReplicationLeader::new(manager.clone()).serve(listener));
+        let mut follower = ReplicationFollower::new(7, follower_dbs.clone());
+        tokio::spawn(async move { follower.run(&addr).await });
+
+        let store = leader.get(0).unwrap();
+        for key in [&b"lazy"[..], b"active"] {
+            store.set_with_ttl(Bytes::from_static(key), Bytes::from_static(b"v"), Some(Duration::from_millis(20)));
+        }
+        store.set(Bytes::from_static(b"kept"), Bytes::from_static(b"v"), None);
+        tokio::time::sleep(Duration::from_millis(30)).await;
+
+        // A read reaps one key, the cleaner's sweep the other
+        assert_eq!(store.get(&Bytes::from_static(b"lazy")), None);
+        assert_eq!(store.cleanup_expired(), 1);
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while manager.get_lag(7) != Some(0) || manager.min_confirmed_offset() < manager.offset() {
+            assert!(tokio::time::Instant::now() < deadline, "follower didn't catch up");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Nothing on the follower sweeps expired keys, so only the leader's
+        // expire ops can have removed them
+        let replica = follower_dbs.get(0).unwrap();
+        assert_eq!(replica.len(), 1);
+        assert_eq!(replica.keys(), vec![Bytes::from_static(b"kept")]);
+    }
+}
@@ -0,0 +1,136 @@
+//! Cluster Routing
+//!
+//! Decides whether a key is served locally or the client must be
+//! redirected to the owning node (MOVED) or a migration target (ASK).
+//!
+//! Only the owner of a migrating slot sends ASK. Other nodes send MOVED to
+//! the owner as usual, and the target serves the slot only to requests
+//! sent after ASKING, so clients keep going to the owner until it hands
+//! the slot over.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+
+use super::node::NodeId;
+use super::sharding::{ShardManager, Slot};
+
+/// Outcome of routing a key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Route {
+    /// Slot is served by this node
+    Local,
+    /// Slot is owned by another node
+    Moved { slot: u16, addr: SocketAddr },
+    /// Slot is migrating away from this node: serve reads of keys still
+    /// held here, ASK-redirect everything else to `addr`
+    Migrating { slot: u16, addr: SocketAddr },
+    /// Slot is migrating to this node: serve requests sent after ASKING,
+    /// MOVED-redirect the rest to the owner at `addr`
+    Importing { slot: u16, addr: SocketAddr },
+    /// Keys of one request hash to different slots
+    CrossSlot,
+    /// Slot has no owner, or the owner's address is unknown
+    Unavailable { slot: u16 },
+}
+
+/// Routes keys against the slot map for the local node
+pub struct ClusterRouter {
+    local_id: NodeId,
+    shards: Arc<ShardManager>,
+    /// Client-facing address of every known node
+    addrs: RwLock<HashMap<NodeId, SocketAddr>>,
+}
+
+impl ClusterRouter {
+    pub fn new(local_id: NodeId, shards: Arc<ShardManager>) -> Self {
+        Self {
+            local_id,
+            shards,
+            addrs: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Register a node's client address
+    pub fn with_node_addr(self, node_id: NodeId, addr: SocketAddr) -> Self {
+        self.set_node_addr(node_id, addr);
+        self
+    }
+
+    /// Register or update a node's client address
+    pub fn set_node_addr(&self, node_id: NodeId, addr: SocketAddr) {
+        self.addrs.write().unwrap().insert(node_id, addr);
+    }
+
+    /// Local node ID
+    pub fn local_id(&self) -> NodeId {
+        self.local_id
+    }
+
+    /// Slot map
+    pub fn shards(&self) -> &Arc<ShardManager> {
+        &self.shards
+    }
+
+    /// Client address of a node, if known
+    pub fn node_addr(&self, node_id: NodeId) -> Option<SocketAddr> {
+        self.addrs.read().unwrap().get(&node_id).copied()
+    }
+
+    /// CLUSTER SLOTS records, one per contiguous range:
+    /// `start end node_id addr [migrating target_id target_addr]`,
+    /// with `-` for an unknown address
+    pub fn slot_records(&self) -> Vec<String> {
+        let addrs = self.addrs.read().unwrap();
+        let addr = |node_id| addrs.get(&node_id).map_or_else(|| "-".to_string(), |a| a.to_string());
+        self.shards
+            .slot_ranges()
+            .into_iter()
+            .map(|a| {
+                let mut record = format!("{} {} {} {}", a.range.start, a.range.end, a.node_id, addr(a.node_id));
+                if let Some(target) = a.migrating_to {
+                    record.push_str(&format!(" migrating {} {}", target, addr(target)));
+                }
+                record
+            })
+            .collect()
+    }
+
+    /// Route a single slot
+    pub fn route_slot(&self, slot: Slot) -> Route {
+        let owner = match self.shards.get_node_for_slot(slot) {
+            Some(owner) => owner,
+            None => return Route::Unavailable { slot: slot.0 },
+        };
+        let addrs = self.addrs.read().unwrap();
+        let migrating_to = self.shards.is_migrating(slot.0);
+        if owner == self.local_id {
+            return match migrating_to.filter(|&target| target != self.local_id) {
+                Some(target) => match addrs.get(&target) {
+                    Some(&addr) => Route::Migrating { slot: slot.0, addr },
+                    None => Route::Local,
+                },
+                None => Route::Local,
+            };
+        }
+
+        match (addrs.get(&owner), migrating_to) {
+            (Some(&addr), Some(target)) if target == self.local_id => Route::Importing { slot: slot.0, addr },
+            (Some(&addr), _) => Route::Moved { slot: slot.0, addr },
+            (None, _) => Route::Unavailable { slot: slot.0 },
+        }
+    }
+
+    /// Route the keys of one request; all keys must share a slot
+    pub fn route_keys<'a>(&self, keys: impl IntoIterator<Item = &'a [u8]>) -> Route {
+        let mut slot: Option<Slot> = None;
+        for key in keys {
+            let key_slot = Slot::from_key(key);
+            match slot {
+                Some(s) if s != key_slot => return Route::CrossSlot,
+                _ => slot = Some(key_slot),
+            }
+        }
+        slot.map_or(Route::Local, |s| self.route_slot(s))
+    }
+}
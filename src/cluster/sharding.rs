@@ -20,13 +20,26 @@ impl Slot {
         Self(slot)
     }
 
-    /// Calculate slot from key using CRC16
+    /// Calculate slot from key using CRC16 of its hash tag
     pub fn from_key(key: &[u8]) -> Self {
-        let hash = crc16(key);
+        let hash = crc16(hash_tag(key));
         Self(hash % TOTAL_SLOTS)
     }
 }
 
+/// Part of the key that is hashed: the content of the first `{...}` if it
+/// is non-empty, otherwise the whole key (Redis hash tag semantics)
+pub fn hash_tag(key: &[u8]) -> &[u8] {
+    if let Some(open) = key.iter().position(|&b| b == b'{') {
+        if let Some(len) = key[open + 1..].iter().position(|&b| b == b'}') {
+            if len > 0 {
+                return &key[open + 1..open + 1 + len];
+            }
+        }
+    }
+    key
+}
+
 /// CRC16 implementation (XMODEM)
 fn crc16(data: &[u8]) -> u16 {
     let mut crc: u16 = 0;
@@ -142,6 +155,40 @@ impl ShardManager {
         }
     }
 
+    /// Distribute slots in proportion to node weights (e.g. `Node.priority`
+    /// or capacity), one contiguous range per node. Leftover slots from
+    /// rounding go to the largest remainders so the total is always exact.
+    pub fn distribute_slots_weighted(&self, nodes: &[(NodeId, u32)]) {
+        let total_weight: u64 = nodes.iter().map(|&(_, w)| w as u64).sum();
+        if total_weight == 0 {
+            let ids: Vec<NodeId> = nodes.iter().map(|&(id, _)| id).collect();
+            return self.distribute_slots(&ids);
+        }
+
+        let shares: Vec<u64> = nodes.iter().map(|&(_, w)| w as u64 * TOTAL_SLOTS as u64).collect();
+        let mut counts: Vec<u64> = shares.iter().map(|s| s / total_weight).collect();
+        let mut leftover = TOTAL_SLOTS as u64 - counts.iter().sum::<u64>();
+        let mut by_remainder: Vec<usize> = (0..nodes.len()).collect();
+        by_remainder.sort_by_key(|&i| std::cmp::Reverse(shares[i] % total_weight));
+        for &i in &by_remainder {
+            if leftover == 0 {
+                break;
+            }
+            counts[i] += 1;
+            leftover -= 1;
+        }
+
+        let mut start = 0u16;
+        for (&(node_id, _), &count) in nodes.iter().zip(&counts) {
+            if count == 0 {
+                continue;
+            }
+            let end = start + count as u16 - 1;
+            self.assign_slots(node_id, SlotRange::new(start, end));
+            start = end + 1;
+        }
+    }
+
     /// Start slot migration
     pub fn start_migration(&self, slot: u16, to_node: NodeId) {
         let mut migrations = self.migrations.write().unwrap();
@@ -170,6 +217,36 @@ impl ShardManager {
         migrations.get(&slot).copied()
     }
 
+    /// Contiguous slot ranges with their owner, split wherever the owner or
+    /// migration target changes. Unassigned slots are left out.
+    pub fn slot_ranges(&self) -> Vec<SlotAssignment> {
+        let slots = self.slots.read().unwrap();
+        let migrations = self.migrations.read().unwrap();
+
+        let mut ranges: Vec<SlotAssignment> = Vec::new();
+        for (slot, owner) in slots.iter().enumerate() {
+            let Some(node_id) = *owner else { continue };
+            let slot = slot as u16;
+            let migrating_to = migrations.get(&slot).copied();
+            match ranges.last_mut() {
+                Some(last)
+                    if last.range.end + 1 == slot
+                        && last.node_id == node_id
+                        && last.migrating_to == migrating_to =>
+                {
+                    last.range.end = slot;
+                }
+                _ => ranges.push(SlotAssignment {
+                    node_id,
+                    range: SlotRange::new(slot, slot),
+                    migrating_to,
+                    importing_from: None,
+                }),
+            }
+        }
+        ranges
+    }
+
     /// Get all slots for a node
     pub fn get_node_slots(&self, node_id: NodeId) -> Vec<u16> {
         let slots = self.slots.read().unwrap();
@@ -214,6 +291,18 @@ mod tests {
         assert!(slot.0 < TOTAL_SLOTS);
     }
 
+    #[test]
+    fn test_hash_tags() {
+        assert_eq!(hash_tag(b"{user1}:name"), b"user1");
+        assert_eq!(hash_tag(b"foo{}{bar}"), b"foo{}{bar}");
+        assert_eq!(hash_tag(b"foo{{bar}}"), b"{bar");
+        assert_eq!(hash_tag(b"foo{bar"), b"foo{bar");
+
+        assert_eq!(Slot::from_key(b"{user}:a"), Slot::from_key(b"{user}:b"));
+        assert_eq!(Slot::from_key(b"{user}:a"), Slot::from_key(b"user"));
+        assert_ne!(Slot::from_key(b"usera"), Slot::from_key(b"userb"));
+    }
+
     #[test]
     fn test_slot_range() {
         let range = SlotRange::new(0, 5460);
@@ -241,6 +330,42 @@ mod tests {
         assert!(node3_slots > 5000);
     }
 
+    #[test]
+    fn test_distribute_slots_weighted() {
+        let manager = ShardManager::new();
+        manager.distribute_slots_weighted(&[(1, 2), (2, 1), (3, 1)]);
+
+        assert_eq!(manager.get_node_slot_count(1), 8192);
+        assert_eq!(manager.get_node_slot_count(2), 4096);
+        assert_eq!(manager.get_node_slot_count(3), 4096);
+        assert_eq!(manager.assigned_slot_count(), TOTAL_SLOTS as usize);
+        assert_eq!(manager.slot_ranges().len(), 3);
+
+        // Weights that don't divide evenly still sum to exactly 16384
+        let manager = ShardManager::new();
+        manager.distribute_slots_weighted(&[(1, 3), (2, 3), (3, 1), (4, 0)]);
+        let counts: Vec<_> = (1..=4).map(|n| manager.get_node_slot_count(n)).collect();
+        assert_eq!(counts.iter().sum::<usize>(), TOTAL_SLOTS as usize);
+        assert_eq!(counts, vec![7022, 7022, 2340, 0]);
+    }
+
+    #[test]
+    fn test_slot_ranges_split_on_migration() {
+        let manager = ShardManager::new();
+        manager.distribute_slots(&[1, 2]);
+        manager.start_migration(100, 2);
+
+        let ranges: Vec<_> = manager
+            .slot_ranges()
+            .iter()
+            .map(|a| (a.range.start, a.range.end, a.node_id, a.migrating_to))
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![(0, 99, 1, None), (100, 100, 1, Some(2)), (101, 8191, 1, None), (8192, 16383, 2, None)]
+        );
+    }
+
     #[test]
     fn test_migration() {
         let manager = ShardManager::new();
@@ -2,8 +2,12 @@
 //!
 //! Automatic failover with quorum voting and split-brain prevention.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::cluster::{ClusterTopology, NodeRole, NodeState, QuorumFence};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FailoverState {
@@ -19,6 +23,8 @@ pub struct FailoverConfig {
     pub timeout: Duration,
     pub cooldown: Duration,
     pub auto_failover: bool,
+    /// Failovers allowed in any rolling hour
+    pub max_failovers_per_hour: usize,
 }
 
 impl Default for FailoverConfig {
@@ -28,6 +34,7 @@ impl Default for FailoverConfig {
             timeout: Duration::from_secs(30),
             cooldown: Duration::from_secs(300),
             auto_failover: true,
+            max_failovers_per_hour: 3,
         }
     }
 }
@@ -45,6 +52,8 @@ pub struct FailoverManager {
     current_primary: Option<u64>,
     votes: HashMap<u64, FailoverVote>,
     last_failover: Option<Instant>,
+    /// Completion times of failovers within the last hour
+    recent_failovers: VecDeque<Instant>,
 }
 
 impl FailoverManager {
@@ -55,6 +64,7 @@ impl FailoverManager {
             current_primary: None,
             votes: HashMap::new(),
             last_failover: None,
+            recent_failovers: VecDeque::new(),
         }
     }
 
@@ -66,6 +76,10 @@ impl FailoverManager {
         if let Some(last) = self.last_failover {
             if last.elapsed() < self.config.cooldown { return false; }
         }
+        let in_last_hour = self.recent_failovers.iter()
+            .filter(|t| t.elapsed() < Duration::from_secs(3600))
+            .count();
+        if in_last_hour >= self.config.max_failovers_per_hour { return false; }
         self.config.auto_failover && self.state == FailoverState::Normal
     }
 
@@ -91,6 +105,16 @@ impl FailoverManager {
         self.state = FailoverState::Normal;
         self.last_failover = Some(Instant::now());
         self.votes.clear();
+        self.recent_failovers.retain(|t| t.elapsed() < Duration::from_secs(3600));
+        self.recent_failovers.push_back(Instant::now());
+    }
+
+    /// Give up an in-progress failover without promoting anyone
+    pub fn abort_failover(&mut self) {
+        if self.state == FailoverState::InProgress {
+            self.state = FailoverState::Normal;
+        }
+        self.votes.clear();
     }
 
     pub fn detect_split_brain(&mut self, primaries: &[u64]) -> bool {
@@ -105,6 +129,110 @@ impl Default for FailoverManager {
     fn default() -> Self { Self::new(FailoverConfig::default()) }
 }
 
+/// Failover vote requests to other nodes.
+///
+/// Injected into `FailoverDriver::tick`, as `RaftPeers` is for Raft, so a
+/// promotion only counts votes that voters actually returned. `None` means
+/// the voter could not be reached.
+pub trait FailoverVoters {
+    /// Ask `voter` to approve `candidate` replacing the unreachable `primary`
+    fn request_vote(&self, voter: u64, primary: u64, candidate: u64) -> Option<FailoverVote>;
+}
+
+/// Drives a `FailoverManager` from node health in the cluster topology:
+/// when the primary is `Suspect` or `Down`, the highest-priority healthy
+/// follower is proposed to the healthy voters and promoted if a quorum of
+/// them approve.
+pub struct FailoverDriver {
+    manager: FailoverManager,
+    topology: Arc<RwLock<ClusterTopology>>,
+    fence: Option<Arc<QuorumFence>>,
+}
+
+impl FailoverDriver {
+    pub fn new(manager: FailoverManager, topology: Arc<RwLock<ClusterTopology>>) -> Self {
+        Self { manager, topology, fence: None }
+    }
+
+    /// Raise `fence` while a split brain is detected
+    pub fn with_fence(mut self, fence: Arc<QuorumFence>) -> Self {
+        self.fence = Some(fence);
+        self
+    }
+
+    pub fn manager(&self) -> &FailoverManager { &self.manager }
+
+    /// Check the primary once, failing over if it's unreachable and
+    /// `voters` approve. Returns the newly promoted primary, if any.
+    pub fn tick(&mut self, voters: &impl FailoverVoters) -> Option<u64> {
+        let mut topology = self.topology.write().unwrap();
+        if let Some(fence) = &self.fence {
+            let primaries: Vec<u64> = topology.nodes.iter()
+                .filter(|n| n.is_leader() && n.is_healthy())
+                .map(|n| n.id)
+                .collect();
+            fence.set_split_brain(primaries.len() > 1);
+        }
+        if self.manager.primary().is_none() {
+            self.manager.current_primary = topology.leader_id;
+        }
+        let primary = self.manager.primary()?;
+        let reachable = topology.get_node(primary)
+            .is_some_and(|n| !matches!(n.state, NodeState::Suspect | NodeState::Down));
+        if reachable || !self.manager.start_failover() {
+            return None;
+        }
+
+        // Highest priority wins, then the most caught-up replica
+        let electorate: Vec<u64> = topology.nodes.iter()
+            .filter(|n| n.id != primary && n.is_healthy() && n.role != NodeRole::Learner)
+            .map(|n| n.id)
+            .collect();
+        let candidate = topology.nodes.iter()
+            .filter(|n| electorate.contains(&n.id))
+            .max_by_key(|n| (n.priority, n.replication_offset, std::cmp::Reverse(n.id)))
+            .map(|n| n.id);
+        let Some(candidate) = candidate else {
+            warn!("Primary {} is unreachable but no healthy candidate is available", primary);
+            self.manager.abort_failover();
+            return None;
+        };
+
+        for voter in electorate {
+            // A reply only ever counts for the voter it was asked of
+            if let Some(vote) = voters.request_vote(voter, primary, candidate) {
+                self.manager.vote(FailoverVote { node_id: voter, ..vote });
+            }
+        }
+        if !self.manager.has_quorum(candidate) {
+            warn!("Failover from {} to {} lacks quorum", primary, candidate);
+            self.manager.abort_failover();
+            return None;
+        }
+
+        self.manager.complete_failover(candidate);
+        if let Some(old) = topology.get_node_mut(primary) {
+            old.role = NodeRole::Follower;
+        }
+        if let Some(new) = topology.get_node_mut(candidate) {
+            new.role = NodeRole::Leader;
+        }
+        topology.leader_id = Some(candidate);
+        topology.epoch += 1;
+        info!("Failed over from node {} to node {}", primary, candidate);
+        Some(candidate)
+    }
+
+    /// Check the primary every `interval`, forever
+    pub async fn run(mut self, voters: impl FailoverVoters, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            self.tick(&voters);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -120,6 +248,75 @@ mod tests {
         assert!(fm.has_quorum(2));
     }
 
+    /// Voters that approve any candidate, except those listed as refusing
+    /// or unreachable
+    #[derive(Default)]
+    struct TestVoters {
+        refuse: Vec<u64>,
+        unreachable: Vec<u64>,
+    }
+
+    impl FailoverVoters for TestVoters {
+        fn request_vote(&self, voter: u64, _primary: u64, candidate: u64) -> Option<FailoverVote> {
+            if self.unreachable.contains(&voter) {
+                return None;
+            }
+            Some(FailoverVote { node_id: voter, candidate_id: candidate, approved: !self.refuse.contains(&voter) })
+        }
+    }
+
+    fn three_node_driver() -> (FailoverDriver, Arc<RwLock<ClusterTopology>>) {
+        use crate::cluster::Node;
+
+        let addr = "127.0.0.1:6380".parse().unwrap();
+        let mut topology = ClusterTopology::new();
+        topology.add_node(Node::leader(1, addr, addr));
+        topology.add_node(Node::new(2, addr, addr).with_priority(100));
+        topology.add_node(Node::new(3, addr, addr).with_priority(200));
+        let topology = Arc::new(RwLock::new(topology));
+        let config = FailoverConfig { cooldown: Duration::ZERO, max_failovers_per_hour: 1, ..Default::default() };
+        (FailoverDriver::new(FailoverManager::new(config), topology.clone()), topology)
+    }
+
+    #[test]
+    fn test_driver_promotes_once() {
+        let (mut driver, topology) = three_node_driver();
+        let voters = TestVoters::default();
+
+        assert_eq!(driver.tick(&voters), None);
+        topology.write().unwrap().get_node_mut(1).unwrap().mark_down();
+        let promoted: Vec<_> = (0..5).filter_map(|_| driver.tick(&voters)).collect();
+        assert_eq!(promoted, vec![3]);
+        assert_eq!(driver.manager().primary(), Some(3));
+        {
+            let topology = topology.read().unwrap();
+            assert_eq!(topology.leader_id, Some(3));
+            assert!(topology.get_node(3).unwrap().is_leader());
+            assert!(!topology.get_node(1).unwrap().is_leader());
+        }
+
+        // The hourly limit holds back a second failover
+        topology.write().unwrap().get_node_mut(3).unwrap().mark_down();
+        assert_eq!(driver.tick(&voters), None);
+        assert_eq!(driver.manager().state(), FailoverState::Normal);
+    }
+
+    #[test]
+    fn test_driver_needs_votes_from_voters() {
+        let (mut driver, topology) = three_node_driver();
+        topology.write().unwrap().get_node_mut(1).unwrap().mark_down();
+
+        // Node 2 refuses and node 3 can't be reached: one approval short
+        let refusing = TestVoters { refuse: vec![2], ..Default::default() };
+        assert_eq!(driver.tick(&refusing), None);
+        let unreachable = TestVoters { unreachable: vec![3], ..Default::default() };
+        assert_eq!(driver.tick(&unreachable), None);
+        assert_eq!(driver.manager().state(), FailoverState::Normal);
+        assert_eq!(topology.read().unwrap().leader_id, Some(1));
+
+        assert_eq!(driver.tick(&TestVoters::default()), Some(3));
+    }
+
     #[test]
     fn test_split_brain() {
         let mut fm = FailoverManager::default();
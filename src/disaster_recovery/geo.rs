@@ -3,7 +3,10 @@
 //! Multi-region and cross-datacenter replication.
 
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tracing::warn;
 
 /// Geographic region
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -85,16 +88,39 @@ impl RegionStatus {
     }
 }
 
+/// Heartbeat intervals a region may miss before it's marked unhealthy
+const MISSED_HEARTBEATS: u32 = 3;
+
+/// Events emitted by the health-check loop
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GeoEvent {
+    /// A standby took over from a primary that stayed unhealthy past the failover timeout
+    PrimaryPromoted {
+        from: Option<String>,
+        to: String,
+        unhealthy_for: Duration,
+    },
+}
+
 /// Geo-replication manager
 pub struct GeoReplication {
     config: GeoConfig,
     regions: HashMap<String, RegionStatus>,
     primary_id: Option<String>,
+    /// When the primary was first seen unhealthy
+    primary_unhealthy_since: Option<Instant>,
+    events: broadcast::Sender<GeoEvent>,
 }
 
 impl GeoReplication {
     pub fn new(config: GeoConfig) -> Self {
-        Self { config, regions: HashMap::new(), primary_id: None }
+        let (events, _) = broadcast::channel(16);
+        Self { config, regions: HashMap::new(), primary_id: None, primary_unhealthy_since: None, events }
+    }
+
+    /// Receive promotion events from the health-check loop
+    pub fn subscribe(&self) -> broadcast::Receiver<GeoEvent> {
+        self.events.subscribe()
     }
 
     pub fn add_region(&mut self, region: GeoRegion) {
@@ -113,8 +139,13 @@ impl GeoReplication {
     }
 
     pub fn heartbeat(&mut self, region_id: &str, lag_ms: u64) {
+        self.heartbeat_at(region_id, lag_ms, Instant::now());
+    }
+
+    /// Record a heartbeat received at `at`
+    pub fn heartbeat_at(&mut self, region_id: &str, lag_ms: u64, at: Instant) {
         if let Some(status) = self.regions.get_mut(region_id) {
-            status.last_heartbeat = Instant::now();
+            status.last_heartbeat = at;
             status.replication_lag_ms = lag_ms;
             status.healthy = lag_ms <= self.config.max_lag_ms;
         }
@@ -123,6 +154,67 @@ impl GeoReplication {
     pub fn region_count(&self) -> usize {
         self.regions.len()
     }
+
+    /// Mark regions that missed several heartbeats as unhealthy
+    pub fn check_health(&mut self, now: Instant) {
+        let deadline = self.config.health_check_interval * MISSED_HEARTBEATS;
+        for status in self.regions.values_mut() {
+            if now.saturating_duration_since(status.last_heartbeat) > deadline {
+                status.healthy = false;
+            }
+        }
+    }
+
+    /// Promote the healthy standby with the lowest priority value (the
+    /// most preferred), returning its id
+    pub fn elect_primary(&mut self) -> Option<String> {
+        let new_id = self.regions.values()
+            .filter(|r| r.healthy && Some(&r.region.id) != self.primary_id.as_ref())
+            .min_by_key(|r| (r.region.priority, r.region.id.clone()))
+            .map(|r| r.region.id.clone())?;
+        for status in self.regions.values_mut() {
+            status.region.is_primary = status.region.id == new_id;
+        }
+        self.primary_id = Some(new_id.clone());
+        self.primary_unhealthy_since = None;
+        Some(new_id)
+    }
+
+    /// One health-check pass at `now`: fail over once the primary has been
+    /// unhealthy for longer than `failover_timeout`
+    pub fn tick(&mut self, now: Instant) -> Option<GeoEvent> {
+        self.check_health(now);
+        if self.primary().is_none_or(|p| p.healthy) {
+            self.primary_unhealthy_since = None;
+            return None;
+        }
+        let since = *self.primary_unhealthy_since.get_or_insert(now);
+        let unhealthy_for = now.saturating_duration_since(since);
+        if unhealthy_for < self.config.failover_timeout {
+            return None;
+        }
+
+        let from = self.primary_id.clone();
+        let Some(to) = self.elect_primary() else {
+            warn!("Primary region {:?} is down but no healthy standby is available", from);
+            return None;
+        };
+        warn!("Promoted region {} after primary {:?} was unhealthy for {:?}", to, from, unhealthy_for);
+        let event = GeoEvent::PrimaryPromoted { from, to, unhealthy_for };
+        let _ = self.events.send(event.clone());
+        Some(event)
+    }
+
+    /// Run health checks every `health_check_interval` until the task is
+    /// dropped. Heartbeats are recorded through the same shared handle.
+    pub async fn run(geo: Arc<Mutex<Self>>) {
+        let interval = geo.lock().unwrap().config.health_check_interval;
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            geo.lock().unwrap().tick(Instant::now());
+        }
+    }
 }
 
 #[cfg(test)]
@@ -137,4 +229,42 @@ mod tests {
         assert_eq!(geo.region_count(), 2);
         assert!(geo.primary().is_some());
     }
+
+    #[test]
+    fn test_standby_promoted_after_timeout() {
+        let config = GeoConfig {
+            health_check_interval: Duration::from_secs(1),
+            failover_timeout: Duration::from_secs(5),
+            ..Default::default()
+        };
+        let mut geo = GeoReplication::new(config);
+        geo.add_region(GeoRegion::new("us-1", "us-east", "dc1").primary());
+        geo.add_region(GeoRegion::new("eu-1", "eu-west", "dc2").with_priority(10));
+        geo.add_region(GeoRegion::new("ap-1", "ap-south", "dc3").with_priority(20));
+        let mut events = geo.subscribe();
+
+        // Only the standbys keep heartbeating
+        let start = Instant::now();
+        geo.heartbeat_at("us-1", 0, start);
+        let mut promoted = Vec::new();
+        for second in 0..15 {
+            let now = start + Duration::from_secs(second);
+            geo.heartbeat_at("eu-1", 0, now);
+            geo.heartbeat_at("ap-1", 0, now);
+            if let Some(event) = geo.tick(now) {
+                promoted.push((second, event));
+            }
+        }
+
+        // Unhealthy after 3 missed heartbeats (t=4), promoted 5s later
+        let expected = GeoEvent::PrimaryPromoted {
+            from: Some("us-1".to_string()),
+            to: "eu-1".to_string(),
+            unhealthy_for: Duration::from_secs(5),
+        };
+        assert_eq!(promoted, vec![(9, expected.clone())]);
+        assert_eq!(events.try_recv().unwrap(), expected);
+        assert_eq!(geo.primary().unwrap().region.id, "eu-1");
+        assert!(geo.primary().unwrap().region.is_primary);
+    }
 }
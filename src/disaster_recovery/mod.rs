@@ -6,6 +6,6 @@ pub mod geo;
 pub mod failover;
 pub mod recovery;
 
-pub use geo::{GeoRegion, GeoReplication, GeoConfig};
-pub use failover::{FailoverManager, FailoverConfig, FailoverState};
+pub use geo::{GeoEvent, GeoRegion, GeoReplication, GeoConfig};
+pub use failover::{FailoverDriver, FailoverManager, FailoverConfig, FailoverState, FailoverVote, FailoverVoters};
 pub use recovery::{PointInTimeRecovery, RecoveryPoint};
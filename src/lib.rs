@@ -9,18 +9,22 @@ pub mod metrics;
 pub mod observability;
 pub mod persistence;
 pub mod protocol;
+pub mod pubsub;
 pub mod security;
 pub mod server;
 pub mod storage;
 pub mod vector;
 
 pub use cluster::{Node, RaftNode, ReplicationManager, ShardManager};
-pub use disaster_recovery::{FailoverManager, GeoReplication, PointInTimeRecovery};
+pub use disaster_recovery::{FailoverDriver, FailoverManager, GeoReplication, PointInTimeRecovery};
 pub use metrics::Metrics;
-pub use observability::{AdminApi, Benchmark, HealthCheck, LoadTestStats, PrometheusExporter};
+pub use observability::{
+    run_load_test, AdminApi, Benchmark, HealthCheck, LoadTestConfig, LoadTestStats, PrometheusExporter,
+};
 pub use persistence::{AofWriter, Snapshot, SnapshotConfig};
 pub use protocol::{Command, ExtendedCommand, Frame, Response, VcpCodec};
+pub use pubsub::{KeyspaceNotifier, PubSub};
 pub use security::{AclManager, AuthManager, AuditLogger, TlsConfig};
 pub use server::{ConcurrentServer, Config, Server, WorkerPoolConfig};
-pub use storage::{ConcurrentStore, EvictionConfig, EvictionPolicy, Store};
+pub use storage::{ConcurrentStore, Databases, EvictionConfig, EvictionPolicy, Store};
 pub use vector::{EmbeddingStore, SemanticCache};
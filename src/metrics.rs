@@ -3,10 +3,31 @@
 //! Operations counters and latency tracking.
 
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::Duration;
 
+use crate::observability::{Histogram, LoadTestStats, DEFAULT_LATENCY_BUCKETS};
+
+/// Latency histograms, so concurrent workers rarely contend on one lock
+const LATENCY_SHARDS: usize = 16;
+
+/// Highest latency tracked exactly (µs); slower commands count as this
+const MAX_TRACKED_LATENCY_US: u64 = 60_000_000;
+
+/// Latency histogram shard used by the current thread
+fn latency_shard() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        static SHARD: usize = NEXT.fetch_add(1, Ordering::Relaxed) % LATENCY_SHARDS;
+    }
+    SHARD.with(|shard| *shard)
+}
+
+fn new_latency_histogram() -> hdrhistogram::Histogram<u64> {
+    hdrhistogram::Histogram::new_with_bounds(1, MAX_TRACKED_LATENCY_US, 3).expect("valid histogram bounds")
+}
+
 /// Metrics collector
 #[derive(Debug)]
 pub struct Metrics {
@@ -21,6 +42,23 @@ pub struct Metrics {
     latency_count: AtomicU64,
     latency_min_us: AtomicU64,
     latency_max_us: AtomicU64,
+
+    /// Command latency distribution (seconds)
+    command_duration: Arc<Histogram>,
+
+    /// Per-thread latency histograms (µs) for percentiles, 3 significant digits
+    latency_hdr: Vec<Mutex<hdrhistogram::Histogram<u64>>>,
+
+    /// Currently open client connections
+    connections_active: AtomicU64,
+    /// Connections accepted since start
+    connections_total: AtomicU64,
+
+    /// Requests rejected with BUSY because a worker queue stayed full
+    busy_rejections: AtomicU64,
+
+    /// Keys removed by the TTL cleaner
+    expired_keys: AtomicU64,
 }
 
 impl Default for Metrics {
@@ -32,6 +70,11 @@ impl Default for Metrics {
 impl Metrics {
     /// Create new metrics collector
     pub fn new() -> Self {
+        Self::with_latency_buckets(DEFAULT_LATENCY_BUCKETS)
+    }
+
+    /// Create metrics collector with custom latency bucket bounds (seconds)
+    pub fn with_latency_buckets(buckets: &[f64]) -> Self {
         Self {
             total_ops: AtomicU64::new(0),
             ops_by_command: RwLock::new(HashMap::new()),
@@ -39,6 +82,12 @@ impl Metrics {
             latency_count: AtomicU64::new(0),
             latency_min_us: AtomicU64::new(u64::MAX),
             latency_max_us: AtomicU64::new(0),
+            command_duration: Arc::new(Histogram::new(buckets)),
+            latency_hdr: (0..LATENCY_SHARDS).map(|_| Mutex::new(new_latency_histogram())).collect(),
+            connections_active: AtomicU64::new(0),
+            connections_total: AtomicU64::new(0),
+            busy_rejections: AtomicU64::new(0),
+            expired_keys: AtomicU64::new(0),
         }
     }
 
@@ -54,9 +103,14 @@ impl Metrics {
         }
 
         // Record latency
+        self.command_duration.observe(latency.as_secs_f64());
         let latency_us = latency.as_micros() as u64;
         self.latency_sum_us.fetch_add(latency_us, Ordering::Relaxed);
         self.latency_count.fetch_add(1, Ordering::Relaxed);
+        self.latency_hdr[latency_shard()]
+            .lock()
+            .unwrap()
+            .saturating_record(latency_us.clamp(1, MAX_TRACKED_LATENCY_US));
 
         // Update min (atomic min)
         let mut current_min = self.latency_min_us.load(Ordering::Relaxed);
@@ -122,13 +176,88 @@ impl Metrics {
         self.latency_max_us.load(Ordering::Relaxed)
     }
 
+    /// Record an accepted connection
+    pub fn connection_opened(&self) {
+        self.connections_active.fetch_add(1, Ordering::Relaxed);
+        self.connections_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a closed connection
+    pub fn connection_closed(&self) {
+        self.connections_active.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Get number of open connections
+    pub fn active_connections(&self) -> u64 {
+        self.connections_active.load(Ordering::Relaxed)
+    }
+
+    /// Get number of connections accepted since start
+    pub fn total_connections(&self) -> u64 {
+        self.connections_total.load(Ordering::Relaxed)
+    }
+
+    /// Record a request rejected with BUSY
+    pub fn record_busy_rejection(&self) {
+        self.busy_rejections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Get number of requests rejected with BUSY
+    pub fn busy_rejections(&self) -> u64 {
+        self.busy_rejections.load(Ordering::Relaxed)
+    }
+
+    /// Record keys removed by a TTL sweep
+    pub fn record_expired(&self, count: u64) {
+        self.expired_keys.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Get number of keys removed by TTL sweeps
+    pub fn expired_total(&self) -> u64 {
+        self.expired_keys.load(Ordering::Relaxed)
+    }
+
+    /// Latency (µs) at percentile `q` (0-100) of every recorded operation,
+    /// accurate to 3 significant digits; 0 before any operation
+    pub fn percentile(&self, q: f64) -> u64 {
+        let mut merged = new_latency_histogram();
+        for shard in &self.latency_hdr {
+            merged.add(&*shard.lock().unwrap()).expect("histograms share bounds");
+        }
+        if merged.is_empty() {
+            return 0;
+        }
+        merged.value_at_percentile(q)
+    }
+
+    /// Live-server counterpart of a load test's stats over `duration`
+    pub fn load_test_stats(&self, duration: Duration) -> LoadTestStats {
+        let completed = self.total_ops();
+        LoadTestStats {
+            completed,
+            success: completed,
+            duration,
+            avg_latency: Duration::from_micros(self.avg_latency_us() as u64),
+            p50_latency: Duration::from_micros(self.percentile(50.0)),
+            p99_latency: Duration::from_micros(self.percentile(99.0)),
+            rps: completed as f64 / duration.as_secs_f64().max(f64::EPSILON),
+        }
+    }
+
+    /// Get the command latency histogram
+    pub fn command_duration(&self) -> &Arc<Histogram> {
+        &self.command_duration
+    }
+
     /// Get a summary of metrics
     pub fn summary(&self) -> String {
         format!(
-            "Operations: {} | Latency (µs): avg={:.1}, min={}, max={}",
+            "Operations: {} | Latency (µs): avg={:.1}, min={}, p50={}, p99={}, max={}",
             self.total_ops(),
             self.avg_latency_us(),
             self.min_latency_us(),
+            self.percentile(50.0),
+            self.percentile(99.0),
             self.max_latency_us()
         )
     }
@@ -155,4 +284,32 @@ mod tests {
         assert_eq!(by_cmd.get("GET"), Some(&2));
         assert_eq!(by_cmd.get("SET"), Some(&1));
     }
+
+    #[test]
+    fn test_latency_percentiles() {
+        let metrics = Arc::new(Metrics::new());
+        assert_eq!(metrics.percentile(99.0), 0);
+
+        // 1..=10_000µs, recorded from several threads
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let metrics = metrics.clone();
+                std::thread::spawn(move || {
+                    for us in (1..=10_000u64).filter(|us| us % 4 == t) {
+                        metrics.record_operation("GET", Duration::from_micros(us));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // 3 significant digits: within 0.1% of the exact rank
+        for (q, exact) in [(50.0, 5_000.0), (99.0, 9_900.0), (99.9, 9_990.0)] {
+            let reported = metrics.percentile(q) as f64;
+            assert!((reported - exact).abs() <= exact * 0.001 + 1.0, "p{} = {}", q, reported);
+        }
+        assert_eq!(metrics.load_test_stats(Duration::from_secs(1)).completed, 10_000);
+    }
 }
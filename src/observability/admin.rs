@@ -1,6 +1,12 @@
 //! Admin HTTP API
 
 use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use super::{HealthCheck, HealthStatus, Slowlog};
+use crate::metrics::Metrics;
+use crate::storage::Databases;
+use crate::vector::SemanticCache;
 
 #[derive(Debug, Clone)]
 pub struct AdminConfig {
@@ -69,15 +75,98 @@ impl AdminApi {
         api
     }
 
+    /// Serve `GET /health` from the registered checks (503 when unhealthy)
+    /// and `GET /ready` (503 unless every check is healthy)
+    pub fn with_health(mut self, health: Arc<RwLock<HealthCheck>>) -> Self {
+        let checks = health.clone();
+        self.register("GET /health", Box::new(move |_| {
+            let report = checks.read().unwrap().check();
+            let status = if report.overall == HealthStatus::Unhealthy { 503 } else { 200 };
+            AdminResponse { status, body: report.to_json() }
+        }));
+        self.register("GET /ready", Box::new(move |_| {
+            let report = health.read().unwrap().check();
+            let status = if report.overall == HealthStatus::Healthy { 200 } else { 503 };
+            AdminResponse { status, body: format!(r#"{{"ready":{},"status":"{}"}}"#, status == 200, report.overall) }
+        }));
+        self
+    }
+
+    /// Serve `POST /cache/flush`, clearing every database and the vector store
+    pub fn with_cache_flush(mut self, databases: Databases, vectors: SemanticCache) -> Self {
+        self.register("POST /cache/flush", Box::new(move |_| {
+            let keys: usize = databases.iter().map(|db| db.len()).sum();
+            let embeddings = vectors.len();
+            databases.flush_all();
+            vectors.clear();
+            AdminResponse::ok(&format!(r#"{{"flushed":true,"keys":{},"vectors":{}}}"#, keys, embeddings))
+        }));
+        self
+    }
+
+    /// Serve `GET /stats`: per-database key counts, how they spread
+    /// across store shards, and command latency percentiles
+    pub fn with_stats(mut self, databases: Databases, metrics: Arc<Metrics>) -> Self {
+        self.register("GET /stats", Box::new(move |_| {
+            let dbs: Vec<String> = databases
+                .iter()
+                .enumerate()
+                .map(|(db, store)| {
+                    let dist = store.shard_distribution();
+                    format!(
+                        r#"{{"db":{},"keys":{},"shards":{},"shard_keys_max":{},"shard_keys_min":{},"shard_keys_stddev":{:.2}}}"#,
+                        db,
+                        store.live_len(),
+                        store.shards(),
+                        dist.max,
+                        dist.min,
+                        dist.stddev
+                    )
+                })
+                .collect();
+            AdminResponse::ok(&format!(
+                r#"{{"databases":[{}],"latency_us":{{"p50":{},"p99":{},"p999":{}}}}}"#,
+                dbs.join(","),
+                metrics.percentile(50.0),
+                metrics.percentile(99.0),
+                metrics.percentile(99.9)
+            ))
+        }));
+        self
+    }
+
+    /// Serve `GET /slowlog`: recent slow commands, newest first
+    pub fn with_slowlog(mut self, slowlog: Arc<Slowlog>) -> Self {
+        self.register("GET /slowlog", Box::new(move |_| {
+            let entries: Vec<String> = slowlog
+                .get(usize::MAX)
+                .iter()
+                .map(|e| {
+                    format!(
+                        r#"{{"id":{},"timestamp":{},"duration_us":{},"command":{},"request_id":{},"key":{}}}"#,
+                        e.id,
+                        e.timestamp,
+                        e.duration_us,
+                        json_string(e.command),
+                        e.request_id,
+                        e.key.as_ref().map_or("null".to_string(), |k| json_string(&String::from_utf8_lossy(k)))
+                    )
+                })
+                .collect();
+            AdminResponse::ok(&format!(r#"{{"entries":[{}]}}"#, entries.join(",")))
+        }));
+        self
+    }
+
     pub fn register(&mut self, route: &str, handler: AdminHandler) {
         self.handlers.insert(route.to_string(), handler);
     }
 
     pub fn handle(&self, req: &AdminRequest) -> AdminResponse {
-        if self.config.require_auth {
-            if req.headers.get("authorization") != self.config.api_key.as_ref().map(|k| format!("Bearer {}", k)).as_ref() {
-                return AdminResponse::unauthorized();
-            }
+        if self.config.require_auth
+            && req.headers.get("authorization") != self.config.api_key.as_ref().map(|k| format!("Bearer {}", k)).as_ref()
+        {
+            return AdminResponse::unauthorized();
         }
         let route = format!("{} {}", req.method, req.path);
         self.handlers.get(&route).map(|h| h(req)).unwrap_or_else(AdminResponse::not_found)
@@ -88,6 +177,25 @@ impl Default for AdminApi {
     fn default() -> Self { Self::new(AdminConfig::default()) }
 }
 
+/// `s` as a quoted JSON string literal
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,4 +206,68 @@ mod tests {
         let resp = api.handle(&AdminRequest::new("GET", "/health"));
         assert_eq!(resp.status, 200);
     }
+
+    #[test]
+    fn test_health_routes() {
+        let health = Arc::new(RwLock::new(HealthCheck::new()));
+        let api = AdminApi::default().with_health(health.clone());
+        assert_eq!(api.handle(&AdminRequest::new("GET", "/ready")).status, 200);
+
+        health.write().unwrap().register("memory", || (HealthStatus::Degraded, None));
+        let resp = api.handle(&AdminRequest::new("GET", "/health"));
+        assert_eq!(resp.status, 200);
+        assert!(resp.body.contains(r#""status":"degraded""#));
+        assert_eq!(api.handle(&AdminRequest::new("GET", "/ready")).status, 503);
+
+        health.write().unwrap().register("store", || (HealthStatus::Unhealthy, None));
+        assert_eq!(api.handle(&AdminRequest::new("GET", "/health")).status, 503);
+    }
+
+    #[test]
+    fn test_cache_flush_route() {
+        use bytes::Bytes;
+
+        let databases = Databases::new(2, 4);
+        let vectors = SemanticCache::with_defaults();
+        databases.get(1).unwrap().set(Bytes::from_static(b"k"), Bytes::from_static(b"v"), None);
+        let api = AdminApi::default().with_cache_flush(databases.clone(), vectors);
+
+        let resp = api.handle(&AdminRequest::new("POST", "/cache/flush"));
+        assert_eq!(resp.status, 200);
+        assert!(resp.body.contains(r#""keys":1"#));
+        assert!(databases.iter().all(|db| db.is_empty()));
+    }
+
+    #[test]
+    fn test_stats_route() {
+        use bytes::Bytes;
+
+        let databases = Databases::new(1, 4);
+        databases.get(0).unwrap().set(Bytes::from_static(b"k"), Bytes::from_static(b"v"), None);
+        let metrics = Arc::new(Metrics::new());
+        metrics.record_operation("GET", std::time::Duration::from_micros(100));
+        let api = AdminApi::default().with_stats(databases, metrics);
+
+        let resp = api.handle(&AdminRequest::new("GET", "/stats"));
+        assert_eq!(resp.status, 200);
+        assert!(resp.body.contains(r#""keys":1,"shards":4,"shard_keys_max":1,"shard_keys_min":0"#), "{}", resp.body);
+        assert!(resp.body.contains(r#""latency_us":{"p50":100,"p99":100,"p999":100}"#), "{}", resp.body);
+    }
+
+    #[test]
+    fn test_slowlog_route_escapes_keys() {
+        use bytes::Bytes;
+        use std::time::Duration;
+
+        let slowlog = Arc::new(Slowlog::new(Duration::ZERO, 10));
+        slowlog.record("GET", 1, Some(Bytes::from_static(b"a\"b\\c\n\x1b")), Duration::from_micros(5));
+        slowlog.record("PING", 2, None, Duration::from_micros(5));
+        let api = AdminApi::default().with_slowlog(slowlog);
+
+        let resp = api.handle(&AdminRequest::new("GET", "/slowlog"));
+        assert_eq!(resp.status, 200);
+        assert!(resp.body.contains(r#""command":"GET","request_id":1,"key":"a\"b\\c\n\u001b"}"#), "{}", resp.body);
+        assert!(resp.body.contains(r#""command":"PING","request_id":2,"key":null}"#), "{}", resp.body);
+        assert_eq!(json_string("tab\there"), r#""tab\there""#);
+    }
 }
@@ -2,9 +2,18 @@
 //!
 //! Server health status and diagnostics.
 
+use bytes::Bytes;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 
+use crate::server::CommandQueue;
+use crate::storage::Databases;
+
+/// Prefix of the keys the store round-trip check writes, reads back and
+/// deletes; each probe appends its own number
+pub const HEALTH_SENTINEL_PREFIX: &str = "__celrix:health:";
+
 /// Health status
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HealthStatus {
@@ -102,6 +111,77 @@ impl HealthCheck {
         self.checks.insert(name.to_string(), Box::new(check));
     }
 
+    /// Register a saturation check for a worker command queue
+    ///
+    /// Reports `Degraded` once the queue depth exceeds `degraded_ratio` of its
+    /// capacity and `Unhealthy` when the queue is full.
+    pub fn register_queue(&mut self, name: &str, queue: CommandQueue, degraded_ratio: f64) {
+        self.register(name, move || {
+            let depth = queue.len();
+            let capacity = queue.capacity();
+            let message = Some(format!("depth {}/{}", depth, capacity));
+
+            if queue.is_full() {
+                (HealthStatus::Unhealthy, message)
+            } else if depth as f64 > capacity as f64 * degraded_ratio {
+                (HealthStatus::Degraded, message)
+            } else {
+                (HealthStatus::Healthy, None)
+            }
+        });
+    }
+
+    /// Register a round-trip check against database 0: set a sentinel key,
+    /// read it back and delete it
+    ///
+    /// Each probe uses its own key, so overlapping probes don't see each
+    /// other's deletes, and writes it without keyspace events or
+    /// replication. Reports `Unhealthy` if the value read back isn't the
+    /// one written.
+    pub fn register_store(&mut self, name: &str, databases: Databases) {
+        let probes = AtomicU64::new(0);
+        self.register(name, move || {
+            let Some(store) = databases.get(0).map(|db| db.unobserved()) else {
+                return (HealthStatus::Unhealthy, Some("no database 0".to_string()));
+            };
+            let probe = probes.fetch_add(1, Ordering::Relaxed);
+            let key = Bytes::from(format!("{}{}", HEALTH_SENTINEL_PREFIX, probe));
+            let value = Bytes::from(probe.to_string());
+
+            store.set(key.clone(), value.clone(), None);
+            let read = store.get(&key);
+            store.del(&key);
+            match read {
+                Some(read) if read == value => (HealthStatus::Healthy, None),
+                Some(_) => (HealthStatus::Unhealthy, Some("sentinel read back a different value".to_string())),
+                None => (HealthStatus::Unhealthy, Some("sentinel missing after set".to_string())),
+            }
+        });
+    }
+
+    /// Register a memory pressure check against `max_memory`, the limit
+    /// each database is held to (0 = unlimited, always healthy)
+    ///
+    /// Reports on the fullest database: `Degraded` once it uses more than
+    /// `degraded_ratio` of the limit and `Unhealthy` when it reaches it.
+    pub fn register_memory(&mut self, name: &str, databases: Databases, max_memory: usize, degraded_ratio: f64) {
+        self.register(name, move || {
+            if max_memory == 0 {
+                return (HealthStatus::Healthy, None);
+            }
+            let used = databases.iter().map(|db| db.memory_used()).max().unwrap_or(0);
+            let message = Some(format!("used {}/{} bytes", used, max_memory));
+
+            if used >= max_memory {
+                (HealthStatus::Unhealthy, message)
+            } else if used as f64 > max_memory as f64 * degraded_ratio {
+                (HealthStatus::Degraded, message)
+            } else {
+                (HealthStatus::Healthy, None)
+            }
+        });
+    }
+
     /// Run all health checks
     pub fn check(&self) -> SystemHealth {
         let mut results = Vec::new();
@@ -183,6 +263,105 @@ mod tests {
         assert!(json.contains("\"version\":"));
     }
 
+    #[test]
+    fn test_queue_saturation() {
+        use crate::protocol::Command;
+        use crate::server::WorkItem;
+
+        let queue = CommandQueue::new(4);
+        let mut health = HealthCheck::new();
+        health.register_queue("kv_queue", queue.clone(), 0.5);
+        assert_eq!(health.check().overall, HealthStatus::Healthy);
+
+        for i in 0..4 {
+            let (tx, _rx) = tokio::sync::oneshot::channel();
+            queue
+                .try_send(WorkItem { command: Command::Ping, request_id: i, db: 0, response_tx: tx })
+                .unwrap();
+            if i == 2 {
+                assert_eq!(health.check().overall, HealthStatus::Degraded);
+            }
+        }
+        assert_eq!(health.check().overall, HealthStatus::Unhealthy);
+        assert!(!health.readiness());
+
+        while queue.try_recv().is_ok() {}
+        assert_eq!(health.check().overall, HealthStatus::Healthy);
+    }
+
+    #[test]
+    fn test_store_round_trip() {
+        let databases = Databases::new(1, 4);
+        let mut health = HealthCheck::new();
+        health.register_store("store", databases.clone());
+
+        assert_eq!(health.check().overall, HealthStatus::Healthy);
+        assert_eq!(health.check().overall, HealthStatus::Healthy);
+        assert!(databases.get(0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_store_probes_overlap_quietly() {
+        use crate::cluster::{ReplicationConfig, ReplicationManager};
+        use std::sync::Arc;
+
+        let manager = Arc::new(ReplicationManager::new(ReplicationConfig::default()));
+        let databases = Databases::from_fn(1, |db| {
+            crate::storage::ConcurrentStore::new().with_replication(manager.clone(), db as u32)
+        });
+        let mut health = HealthCheck::new();
+        health.register_store("store", databases.clone());
+
+        // Concurrent probes never delete each other's sentinel
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..500 {
+                        assert_eq!(health.check().overall, HealthStatus::Healthy);
+                    }
+                });
+            }
+        });
+        assert!(databases.get(0).unwrap().is_empty());
+        assert_eq!(manager.offset(), 0);
+    }
+
+    #[test]
+    fn test_memory_pressure() {
+        use crate::storage::ENTRY_OVERHEAD;
+
+        let databases = Databases::new(2, 4);
+        let store = databases.get(1).unwrap();
+        let entry = ENTRY_OVERHEAD + 1 + 100;
+        let mut health = HealthCheck::new();
+        health.register_memory("memory", databases.clone(), entry * 4, 0.5);
+        assert_eq!(health.check().overall, HealthStatus::Healthy);
+
+        for (i, key) in [b"a", b"b", b"c", b"d"].into_iter().enumerate() {
+            store.set(Bytes::from_static(key), Bytes::from(vec![b'x'; 100]), None);
+            let expected = match i {
+                0 | 1 => HealthStatus::Healthy,
+                2 => HealthStatus::Degraded,
+                _ => HealthStatus::Unhealthy,
+            };
+            assert_eq!(health.check().overall, expected, "after {} keys", i + 1);
+        }
+        assert!(!health.readiness());
+
+        store.del(&Bytes::from_static(b"d"));
+        store.del(&Bytes::from_static(b"c"));
+        assert_eq!(health.check().overall, HealthStatus::Healthy);
+    }
+
+    #[test]
+    fn test_unlimited_memory_is_healthy() {
+        let databases = Databases::new(1, 4);
+        databases.get(0).unwrap().set(Bytes::from_static(b"k"), Bytes::from_static(b"v"), None);
+        let mut health = HealthCheck::new();
+        health.register_memory("memory", databases, 0, 0.5);
+        assert_eq!(health.check().overall, HealthStatus::Healthy);
+    }
+
     #[test]
     fn test_liveness_readiness() {
         let health = HealthCheck::new();
@@ -1,6 +1,55 @@
 //! Load Testing Framework
 
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use std::io;
 use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio_util::codec::Framed;
+
+use crate::protocol::{Command, Response, VcpCodec};
+
+/// Where and how hard `run_load_test` drives a server
+#[derive(Debug, Clone)]
+pub struct LoadTestConfig {
+    pub host: String,
+    pub port: u16,
+    /// Connections, each driven by its own task
+    pub concurrency: usize,
+    /// Requests across all connections, alternating SET and GET
+    pub total_requests: u64,
+    /// Bytes in each SET value
+    pub value_size: usize,
+}
+
+impl Default for LoadTestConfig {
+    fn default() -> Self {
+        Self { host: "127.0.0.1".to_string(), port: 6380, concurrency: 50, total_requests: 100_000, value_size: 64 }
+    }
+}
+
+impl LoadTestConfig {
+    pub fn with_addr(mut self, host: &str, port: u16) -> Self {
+        self.host = host.to_string();
+        self.port = port;
+        self
+    }
+
+    pub fn with_concurrency(mut self, n: usize) -> Self {
+        self.concurrency = n;
+        self
+    }
+
+    pub fn with_total_requests(mut self, n: u64) -> Self {
+        self.total_requests = n;
+        self
+    }
+
+    pub fn with_value_size(mut self, bytes: usize) -> Self {
+        self.value_size = bytes;
+        self
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct LoadTestStats {
@@ -8,6 +57,7 @@ pub struct LoadTestStats {
     pub success: u64,
     pub duration: Duration,
     pub avg_latency: Duration,
+    pub p50_latency: Duration,
     pub p99_latency: Duration,
     pub rps: f64,
 }
@@ -15,31 +65,112 @@ pub struct LoadTestStats {
 impl LoadTestStats {
     pub fn from_latencies(latencies: &[Duration], duration: Duration) -> Self {
         if latencies.is_empty() {
-            return Self { completed: 0, success: 0, duration, avg_latency: Duration::ZERO, p99_latency: Duration::ZERO, rps: 0.0 };
+            return Self {
+                completed: 0,
+                success: 0,
+                duration,
+                avg_latency: Duration::ZERO,
+                p50_latency: Duration::ZERO,
+                p99_latency: Duration::ZERO,
+                rps: 0.0,
+            };
         }
         let mut sorted = latencies.to_vec();
         sorted.sort();
         let sum: Duration = latencies.iter().sum();
         let avg = sum / latencies.len() as u32;
-        let p99_idx = (latencies.len() as f64 * 0.99) as usize;
+        let percentile = |p: f64| sorted[((latencies.len() as f64 * p) as usize).min(sorted.len() - 1)];
         Self {
             completed: latencies.len() as u64,
             success: latencies.len() as u64,
             duration,
             avg_latency: avg,
-            p99_latency: sorted[p99_idx.min(sorted.len() - 1)],
+            p50_latency: percentile(0.5),
+            p99_latency: percentile(0.99),
             rps: latencies.len() as f64 / duration.as_secs_f64(),
         }
     }
 
     pub fn report(&self) -> String {
-        format!("Completed: {}, RPS: {:.0}, Avg: {:.2}ms, P99: {:.2}ms",
-            self.completed, self.rps,
+        format!("Completed: {}, Errors: {}, RPS: {:.0}, Avg: {:.2}ms, P50: {:.2}ms, P99: {:.2}ms",
+            self.completed, self.completed - self.success, self.rps,
             self.avg_latency.as_secs_f64() * 1000.0,
+            self.p50_latency.as_secs_f64() * 1000.0,
             self.p99_latency.as_secs_f64() * 1000.0)
     }
 }
 
+/// Drive the server at `config.host:config.port` over VCP: open
+/// `concurrency` connections, split `total_requests` SET/GET pairs across
+/// them and time every request. Error replies count as completed but not
+/// successful; a connection that fails aborts the run.
+pub async fn run_load_test(config: &LoadTestConfig) -> io::Result<LoadTestStats> {
+    let addr = format!("{}:{}", config.host, config.port);
+    let concurrency = config.concurrency.max(1);
+    let value = Bytes::from(vec![b'x'; config.value_size]);
+
+    let mut connections = Vec::with_capacity(concurrency);
+    for _ in 0..concurrency {
+        connections.push(Framed::new(TcpStream::connect(&addr).await?, VcpCodec::new()));
+    }
+
+    let start = Instant::now();
+    let tasks: Vec<_> = connections
+        .into_iter()
+        .enumerate()
+        .map(|(client, framed)| {
+            // Spread the remainder over the first connections
+            let share = config.total_requests / concurrency as u64
+                + u64::from((client as u64) < config.total_requests % concurrency as u64);
+            tokio::spawn(drive_connection(framed, client, share, value.clone()))
+        })
+        .collect();
+
+    let mut latencies = Vec::with_capacity(config.total_requests as usize);
+    let mut errors = 0;
+    for task in tasks {
+        let (client_latencies, client_errors) = task.await.map_err(io::Error::other)??;
+        latencies.extend(client_latencies);
+        errors += client_errors;
+    }
+
+    let mut stats = LoadTestStats::from_latencies(&latencies, start.elapsed());
+    stats.success -= errors;
+    Ok(stats)
+}
+
+/// Send `requests` alternating SETs and GETs of this client's keys, one at
+/// a time; returns each request's latency and how many got an error reply
+async fn drive_connection(
+    mut framed: Framed<TcpStream, VcpCodec>,
+    client: usize,
+    requests: u64,
+    value: Bytes,
+) -> io::Result<(Vec<Duration>, u64)> {
+    let mut latencies = Vec::with_capacity(requests as usize);
+    let mut errors = 0;
+    for i in 0..requests {
+        let key = Bytes::from(format!("loadtest:{}:{}", client, i / 2));
+        let cmd = if i % 2 == 0 {
+            Command::Set { key, value: value.clone(), ttl: None, options: Default::default() }
+        } else {
+            Command::Get { key }
+        };
+
+        let sent = Instant::now();
+        framed.send(cmd.to_frame(i + 1)).await?;
+        let frame = framed
+            .next()
+            .await
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "server closed the connection"))??;
+        latencies.push(sent.elapsed());
+        if matches!(Response::from_frame(&frame), Ok(Response::Error(_)) | Err(_)) {
+            errors += 1;
+        }
+    }
+    Ok((latencies, errors))
+}
+
 pub struct Benchmark {
     name: String,
     iterations: u64,
@@ -91,4 +222,31 @@ mod tests {
         let result = Benchmark::new("test").iterations(100).run(|| { let _ = 1 + 1; });
         assert!(result.ops_per_sec > 0.0);
     }
+
+    #[tokio::test]
+    async fn test_load_test_against_server() {
+        use crate::server::{Config, ConcurrentServer};
+
+        let server = ConcurrentServer::new(Config { kv_workers: 2, vector_workers: 1, ..Default::default() });
+        let store = server.store().clone();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(server.serve(listener));
+
+        let config = LoadTestConfig::default()
+            .with_addr("127.0.0.1", port)
+            .with_concurrency(4)
+            .with_total_requests(403)
+            .with_value_size(16);
+        let stats = run_load_test(&config).await.unwrap();
+
+        assert_eq!(stats.completed, 403);
+        assert_eq!(stats.success, 403);
+        assert!(stats.rps > 0.0);
+        assert!(stats.p50_latency > Duration::ZERO);
+        assert!(stats.p50_latency <= stats.p99_latency);
+        assert!(stats.p99_latency <= stats.duration);
+        // 3 connections send 101 requests and one sends 100: a key per SET/GET pair
+        assert_eq!(store.len(), 3 * 51 + 50);
+    }
 }
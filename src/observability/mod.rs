@@ -6,8 +6,12 @@ mod admin;
 mod health;
 mod loadtest;
 mod prometheus_metrics;
+mod slowlog;
 
 pub use admin::{AdminApi, AdminConfig, AdminRequest, AdminResponse};
 pub use health::{HealthCheck, HealthStatus, SystemHealth};
-pub use loadtest::{Benchmark, BenchmarkResult, LoadTestStats};
-pub use prometheus_metrics::{MetricsRegistry, PrometheusExporter};
+pub use loadtest::{run_load_test, Benchmark, BenchmarkResult, LoadTestConfig, LoadTestStats};
+pub use prometheus_metrics::{
+    Histogram, Metric, MetricType, MetricsRegistry, PrometheusExporter, DEFAULT_LATENCY_BUCKETS,
+};
+pub use slowlog::{Slowlog, SlowlogEntry};
@@ -4,7 +4,11 @@
 
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
+
+use crate::metrics::Metrics;
+use crate::server::CommandQueue;
+use crate::storage::Databases;
 
 /// Metric type
 #[derive(Debug, Clone, Copy)]
@@ -14,6 +18,88 @@ pub enum MetricType {
     Histogram,
 }
 
+/// Default latency buckets in seconds (10µs .. 100ms)
+pub const DEFAULT_LATENCY_BUCKETS: &[f64] = &[
+    0.00001, 0.000025, 0.00005, 0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025,
+    0.05, 0.1,
+];
+
+/// Histogram with fixed `le` bucket boundaries
+#[derive(Debug)]
+pub struct Histogram {
+    /// Upper bounds, sorted ascending (+Inf is implicit)
+    bounds: Vec<f64>,
+    /// Per-bucket observation counts (non-cumulative, last is +Inf)
+    buckets: Vec<AtomicU64>,
+    /// Sum of observations, stored as f64 bits
+    sum: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &[f64]) -> Self {
+        let mut bounds: Vec<f64> = bounds.iter().copied().filter(|b| b.is_finite()).collect();
+        bounds.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        bounds.dedup();
+
+        let buckets = (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect();
+        Self {
+            bounds,
+            buckets,
+            sum: AtomicU64::new(0f64.to_bits()),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    /// Record an observation
+    pub fn observe(&self, v: f64) {
+        let idx = self
+            .bounds
+            .iter()
+            .position(|&b| v <= b)
+            .unwrap_or(self.bounds.len());
+        self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+
+        let mut current = self.sum.load(Ordering::Relaxed);
+        loop {
+            let new = (f64::from_bits(current) + v).to_bits();
+            match self
+                .sum
+                .compare_exchange_weak(current, new, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(c) => current = c,
+            }
+        }
+    }
+
+    /// Bucket upper bounds (excluding +Inf)
+    pub fn bounds(&self) -> &[f64] {
+        &self.bounds
+    }
+
+    /// Cumulative counts per bucket, ending with the +Inf bucket
+    pub fn cumulative_counts(&self) -> Vec<u64> {
+        let mut total = 0;
+        self.buckets
+            .iter()
+            .map(|b| {
+                total += b.load(Ordering::Relaxed);
+                total
+            })
+            .collect()
+    }
+
+    pub fn sum(&self) -> f64 {
+        f64::from_bits(self.sum.load(Ordering::Relaxed))
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
 /// A single metric
 #[derive(Debug)]
 pub struct Metric {
@@ -22,6 +108,8 @@ pub struct Metric {
     pub metric_type: MetricType,
     pub value: AtomicU64,
     pub labels: Vec<(String, String)>,
+    /// Bucket state for histogram metrics
+    pub histogram: Option<Arc<Histogram>>,
 }
 
 impl Metric {
@@ -32,6 +120,7 @@ impl Metric {
             metric_type: MetricType::Counter,
             value: AtomicU64::new(0),
             labels: Vec::new(),
+            histogram: None,
         }
     }
 
@@ -42,9 +131,38 @@ impl Metric {
             metric_type: MetricType::Gauge,
             value: AtomicU64::new(0),
             labels: Vec::new(),
+            histogram: None,
+        }
+    }
+
+    /// Create a histogram with the given bucket upper bounds
+    pub fn histogram(name: &str, help: &str, buckets: &[f64]) -> Self {
+        Self::histogram_from(name, help, Arc::new(Histogram::new(buckets)))
+    }
+
+    /// Create a histogram metric backed by an existing histogram
+    pub fn histogram_from(name: &str, help: &str, histogram: Arc<Histogram>) -> Self {
+        Self {
+            name: name.to_string(),
+            help: help.to_string(),
+            metric_type: MetricType::Histogram,
+            value: AtomicU64::new(0),
+            labels: Vec::new(),
+            histogram: Some(histogram),
         }
     }
 
+    /// Attach a label to this series
+    pub fn with_label(mut self, key: &str, value: &str) -> Self {
+        self.labels.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Series identifier (`name{k="v",...}`) used as registry key and in output
+    pub fn series(&self) -> String {
+        series_key(&self.name, &self.labels)
+    }
+
     pub fn inc(&self) {
         self.value.fetch_add(1, Ordering::Relaxed);
     }
@@ -60,6 +178,50 @@ impl Metric {
     pub fn get(&self) -> u64 {
         self.value.load(Ordering::Relaxed)
     }
+
+    /// Record an observation (no-op for non-histogram metrics)
+    pub fn observe(&self, v: f64) {
+        if let Some(histogram) = &self.histogram {
+            histogram.observe(v);
+        }
+    }
+
+    /// Write the sample lines for this series
+    fn write_samples(&self, output: &mut String) {
+        let histogram = match &self.histogram {
+            Some(h) => h,
+            None => {
+                output.push_str(&format!("{} {}\n", self.series(), self.get()));
+                return;
+            }
+        };
+
+        let bucket_name = format!("{}_bucket", self.name);
+        let counts = histogram.cumulative_counts();
+        let bounds = histogram.bounds().iter().map(|b| b.to_string());
+        for (le, count) in bounds.chain(std::iter::once("+Inf".to_string())).zip(counts) {
+            let mut labels = self.labels.clone();
+            labels.push(("le".to_string(), le));
+            output.push_str(&format!("{} {}\n", series_key(&bucket_name, &labels), count));
+        }
+
+        let sum_name = format!("{}_sum", self.name);
+        let count_name = format!("{}_count", self.name);
+        output.push_str(&format!("{} {}\n", series_key(&sum_name, &self.labels), histogram.sum()));
+        output.push_str(&format!("{} {}\n", series_key(&count_name, &self.labels), histogram.count()));
+    }
+}
+
+/// Format a series identifier from a metric name and its labels
+fn series_key(name: &str, labels: &[(String, String)]) -> String {
+    if labels.is_empty() {
+        return name.to_string();
+    }
+    let labels: Vec<_> = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, v))
+        .collect();
+    format!("{}{{{}}}", name, labels.join(","))
 }
 
 /// Metrics registry
@@ -84,6 +246,10 @@ impl MetricsRegistry {
             "celrix_commands_total",
             "Total commands processed",
         ));
+        registry.register(Metric::counter(
+            "celrix_commands_by_command_total",
+            "Commands processed, by command",
+        ));
         registry.register(Metric::counter(
             "celrix_commands_get_total",
             "Total GET commands",
@@ -112,10 +278,54 @@ impl MetricsRegistry {
             "celrix_connections_total",
             "Total connections accepted",
         ));
+        registry.register(Metric::counter(
+            "celrix_busy_rejections_total",
+            "Requests rejected because a worker queue was full",
+        ));
+        registry.register(Metric::counter(
+            "celrix_expired_keys_total",
+            "Keys removed by active expiry",
+        ));
         registry.register(Metric::gauge(
             "celrix_uptime_seconds",
             "Server uptime in seconds",
         ));
+        registry.register(Metric::gauge(
+            "celrix_latency_min_microseconds",
+            "Minimum command latency",
+        ));
+        registry.register(Metric::gauge(
+            "celrix_latency_avg_microseconds",
+            "Average command latency",
+        ));
+        registry.register(Metric::gauge(
+            "celrix_latency_max_microseconds",
+            "Maximum command latency",
+        ));
+        for (name, help) in [
+            ("celrix_latency_p50_microseconds", "Median command latency"),
+            ("celrix_latency_p99_microseconds", "99th percentile command latency"),
+            ("celrix_latency_p999_microseconds", "99.9th percentile command latency"),
+        ] {
+            registry.register(Metric::gauge(name, help));
+        }
+        registry.register(Metric::histogram(
+            "celrix_command_duration_seconds",
+            "Command execution latency",
+            DEFAULT_LATENCY_BUCKETS,
+        ));
+        registry.register(Metric::gauge(
+            "celrix_queue_depth",
+            "Work items waiting in a worker pool's queue",
+        ));
+        registry.register(Metric::gauge(
+            "celrix_queue_capacity",
+            "Capacity of a worker pool's queue",
+        ));
+        registry.register(Metric::counter(
+            "celrix_queue_rejections_total",
+            "Requests rejected because a worker pool's queue stayed full",
+        ));
 
         registry
     }
@@ -123,7 +333,7 @@ impl MetricsRegistry {
     /// Register a metric
     pub fn register(&self, metric: Metric) {
         let mut metrics = self.metrics.write().unwrap();
-        metrics.insert(metric.name.clone(), metric);
+        metrics.insert(metric.series(), metric);
     }
 
     /// Get a metric by name
@@ -146,36 +356,82 @@ impl MetricsRegistry {
         }
     }
 
+    /// Record an observation into a histogram
+    pub fn observe(&self, name: &str, value: f64) {
+        if let Some(metric) = self.metrics.read().unwrap().get(name) {
+            metric.observe(value);
+        }
+    }
+
+    /// Get a labeled series value
+    pub fn get_labeled(&self, name: &str, labels: &[(&str, &str)]) -> Option<u64> {
+        let key = series_key(name, &Self::owned_labels(labels));
+        self.metrics.read().unwrap().get(&key).map(|m| m.get())
+    }
+
+    /// Set a labeled series value, creating the series from the unlabeled
+    /// metric of the same name if it doesn't exist yet
+    pub fn set_labeled(&self, name: &str, labels: &[(&str, &str)], value: u64) {
+        let labels = Self::owned_labels(labels);
+        let key = series_key(name, &labels);
+
+        if let Some(metric) = self.metrics.read().unwrap().get(&key) {
+            metric.set(value);
+            return;
+        }
+
+        let mut metrics = self.metrics.write().unwrap();
+        let (help, metric_type) = metrics
+            .get(name)
+            .map(|m| (m.help.clone(), m.metric_type))
+            .unwrap_or_else(|| (String::new(), MetricType::Gauge));
+        let metric = metrics.entry(key).or_insert_with(|| Metric {
+            name: name.to_string(),
+            help,
+            metric_type,
+            value: AtomicU64::new(0),
+            labels,
+            histogram: None,
+        });
+        metric.set(value);
+    }
+
+    fn owned_labels(labels: &[(&str, &str)]) -> Vec<(String, String)> {
+        labels
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
     /// Export all metrics in Prometheus format
     pub fn export(&self) -> String {
         let metrics = self.metrics.read().unwrap();
         let mut output = String::new();
 
-        for metric in metrics.values() {
-            // Type line
-            let type_str = match metric.metric_type {
-                MetricType::Counter => "counter",
-                MetricType::Gauge => "gauge",
-                MetricType::Histogram => "histogram",
-            };
-            output.push_str(&format!("# HELP {} {}\n", metric.name, metric.help));
-            output.push_str(&format!("# TYPE {} {}\n", metric.name, type_str));
-
-            // Value line
-            if metric.labels.is_empty() {
-                output.push_str(&format!("{} {}\n", metric.name, metric.get()));
-            } else {
-                let labels: Vec<_> = metric
-                    .labels
-                    .iter()
-                    .map(|(k, v)| format!("{}=\"{}\"", k, v))
-                    .collect();
-                output.push_str(&format!(
-                    "{}{{{}}} {}\n",
-                    metric.name,
-                    labels.join(","),
-                    metric.get()
-                ));
+        // Group series by name so HELP/TYPE are emitted once per metric
+        let mut series: Vec<&Metric> = metrics.values().collect();
+        series.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.labels.cmp(&b.labels)));
+
+        let mut last_name: Option<&str> = None;
+        let mut series = series.into_iter().peekable();
+        while let Some(metric) = series.next() {
+            if last_name != Some(metric.name.as_str()) {
+                // Type line
+                let type_str = match metric.metric_type {
+                    MetricType::Counter => "counter",
+                    MetricType::Gauge => "gauge",
+                    MetricType::Histogram => "histogram",
+                };
+                output.push_str(&format!("# HELP {} {}\n", metric.name, metric.help));
+                output.push_str(&format!("# TYPE {} {}\n", metric.name, type_str));
+                last_name = Some(metric.name.as_str());
+            }
+
+            // The unlabeled series only names a labeled family; exporting it
+            // too would add a bogus sample to sums over the family
+            let family = metric.labels.is_empty() && series.peek().is_some_and(|next| next.name == metric.name);
+            if !family {
+                metric.write_samples(&mut output);
             }
         }
 
@@ -186,6 +442,12 @@ impl MetricsRegistry {
 /// Prometheus metrics exporter
 pub struct PrometheusExporter {
     registry: MetricsRegistry,
+    /// Server metrics copied into the registry on export
+    metrics: Option<Arc<Metrics>>,
+    /// Databases whose key count and memory are reported on export
+    databases: Option<Databases>,
+    /// Worker pool queues sampled on export, by pool name
+    queues: Vec<(String, CommandQueue)>,
 }
 
 impl Default for PrometheusExporter {
@@ -198,17 +460,96 @@ impl PrometheusExporter {
     pub fn new() -> Self {
         Self {
             registry: MetricsRegistry::new(),
+            metrics: None,
+            databases: None,
+            queues: Vec::new(),
         }
     }
 
+    /// Create an exporter backed by the server's internal metrics
+    pub fn with_metrics(metrics: Arc<Metrics>) -> Self {
+        let registry = MetricsRegistry::new();
+        registry.register(Metric::histogram_from(
+            "celrix_command_duration_seconds",
+            "Command execution latency",
+            metrics.command_duration().clone(),
+        ));
+        Self {
+            registry,
+            metrics: Some(metrics),
+            databases: None,
+            queues: Vec::new(),
+        }
+    }
+
+    /// Report `celrix_keys_total` and `celrix_memory_bytes` from these databases
+    pub fn with_databases(mut self, databases: Databases) -> Self {
+        self.databases = Some(databases);
+        self
+    }
+
+    /// Report depth, capacity and rejections of a worker pool's queue,
+    /// labeled `pool="<name>"`
+    pub fn with_queue(mut self, pool: &str, queue: CommandQueue) -> Self {
+        self.queues.push((pool.to_string(), queue));
+        self
+    }
+
     pub fn registry(&self) -> &MetricsRegistry {
         &self.registry
     }
 
     /// Export metrics in Prometheus text format
     pub fn export(&self) -> String {
+        self.sync_from_metrics();
         self.registry.export()
     }
+
+    /// Copy the current internal metrics into the registry
+    fn sync_from_metrics(&self) {
+        if let Some(databases) = &self.databases {
+            let keys: usize = databases.iter().map(|db| db.len()).sum();
+            let memory: usize = databases.iter().map(|db| db.memory_used()).sum();
+            self.registry.set("celrix_keys_total", keys as u64);
+            self.registry.set("celrix_memory_bytes", memory as u64);
+        }
+        for (pool, queue) in &self.queues {
+            let labels = [("pool", pool.as_str())];
+            self.registry.set_labeled("celrix_queue_depth", &labels, queue.len() as u64);
+            self.registry.set_labeled("celrix_queue_capacity", &labels, queue.capacity() as u64);
+            self.registry.set_labeled("celrix_queue_rejections_total", &labels, queue.rejected());
+        }
+        let metrics = match &self.metrics {
+            Some(m) => m,
+            None => return,
+        };
+        let registry = &self.registry;
+
+        registry.set("celrix_commands_total", metrics.total_ops());
+
+        let by_command = metrics.ops_by_command();
+        for (command, count) in &by_command {
+            registry.set_labeled("celrix_commands_by_command_total", &[("command", command)], *count);
+        }
+        for (command, name) in [
+            ("GET", "celrix_commands_get_total"),
+            ("SET", "celrix_commands_set_total"),
+            ("DEL", "celrix_commands_del_total"),
+        ] {
+            registry.set(name, by_command.get(command).copied().unwrap_or(0));
+        }
+
+        registry.set("celrix_connections_active", metrics.active_connections());
+        registry.set("celrix_connections_total", metrics.total_connections());
+        registry.set("celrix_busy_rejections_total", metrics.busy_rejections());
+        registry.set("celrix_expired_keys_total", metrics.expired_total());
+        registry.set("celrix_latency_min_microseconds", metrics.min_latency_us());
+        registry.set("celrix_latency_avg_microseconds", metrics.avg_latency_us().round() as u64);
+        registry.set("celrix_latency_max_microseconds", metrics.max_latency_us());
+        registry.set("celrix_latency_p50_microseconds", metrics.percentile(50.0));
+        registry.set("celrix_latency_p99_microseconds", metrics.percentile(99.0));
+        registry.set("celrix_latency_p999_microseconds", metrics.percentile(99.9));
+    }
 }
 
 #[cfg(test)]
@@ -237,4 +578,71 @@ mod tests {
         assert!(output.contains("# TYPE"));
         assert!(output.contains("counter"));
     }
+
+    #[test]
+    fn test_export_from_metrics() {
+        use std::time::Duration;
+
+        let metrics = Arc::new(Metrics::new());
+        metrics.record_operation("GET", Duration::from_micros(100));
+        metrics.record_operation("GET", Duration::from_micros(300));
+        metrics.record_operation("SET", Duration::from_micros(200));
+
+        let exporter = PrometheusExporter::with_metrics(metrics.clone());
+        let output = exporter.export();
+
+        assert!(output.contains("celrix_commands_total 3\n"));
+        assert!(output.contains("celrix_commands_by_command_total{command=\"GET\"} 2\n"));
+        assert!(output.contains("celrix_commands_by_command_total{command=\"SET\"} 1\n"));
+        assert!(output.contains("celrix_commands_get_total 2\n"));
+        assert!(output.contains("celrix_latency_min_microseconds 100\n"));
+        assert!(output.contains("celrix_latency_avg_microseconds 200\n"));
+        assert!(output.contains("celrix_latency_max_microseconds 300\n"));
+
+        // HELP/TYPE emitted once per metric name, not per series, and the
+        // labeled family sums to the total with no unlabeled sample
+        assert_eq!(output.matches("# TYPE celrix_commands_by_command_total ").count(), 1);
+        assert!(!output.contains("\ncelrix_commands_by_command_total "));
+        assert!(!output.contains("celrix_commands_total{"));
+
+        // Worker latencies feed the shared duration histogram
+        assert!(output.contains("celrix_command_duration_seconds_count 3\n"));
+
+        // Later operations show up on the next scrape
+        metrics.record_operation("GET", Duration::from_micros(100));
+        exporter.export();
+        let get_count = exporter
+            .registry()
+            .get_labeled("celrix_commands_by_command_total", &[("command", "GET")]);
+        assert_eq!(get_count, Some(3));
+    }
+
+    #[test]
+    fn test_histogram_buckets() {
+        let registry = MetricsRegistry::new();
+        registry.register(Metric::histogram("test_duration_seconds", "Test", &[0.1, 0.5, 1.0]));
+
+        let observations = [0.05, 0.2, 0.3, 0.7, 2.0, 0.1];
+        for v in observations {
+            registry.observe("test_duration_seconds", v);
+        }
+
+        let output = registry.export();
+        assert!(output.contains("# TYPE test_duration_seconds histogram\n"));
+
+        let buckets: Vec<u64> = output
+            .lines()
+            .filter(|l| l.starts_with("test_duration_seconds_bucket"))
+            .map(|l| l.rsplit(' ').next().unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(buckets, vec![2, 4, 5, 6]);
+        assert!(buckets.windows(2).all(|w| w[0] <= w[1]));
+
+        assert!(output.contains("test_duration_seconds_bucket{le=\"+Inf\"} 6\n"));
+        assert!(output.contains(&format!(
+            "test_duration_seconds_count {}\n",
+            observations.len()
+        )));
+        assert!(output.contains("test_duration_seconds_sum 3.35"));
+    }
 }
@@ -0,0 +1,125 @@
+//! Slowlog
+//!
+//! Bounded in-memory log of commands that took longer than a threshold.
+
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A command that exceeded the slowlog threshold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowlogEntry {
+    /// Increasing ID, unique for the server's lifetime
+    pub id: u64,
+    /// Unix time the command finished (seconds)
+    pub timestamp: u64,
+    pub duration_us: u64,
+    pub command: &'static str,
+    pub request_id: u64,
+    /// First key of the command, if any
+    pub key: Option<Bytes>,
+}
+
+impl SlowlogEntry {
+    /// SLOWLOG GET record: `id timestamp duration_us command request_id [key]`
+    pub fn record(&self) -> Bytes {
+        let mut record = format!(
+            "{} {} {} {} {}",
+            self.id,
+            self.timestamp,
+            self.duration_us,
+            self.command.to_lowercase(),
+            self.request_id
+        );
+        if let Some(key) = &self.key {
+            record.push(' ');
+            record.push_str(&String::from_utf8_lossy(key));
+        }
+        Bytes::from(record)
+    }
+}
+
+/// Most recent slow commands, oldest dropped first
+#[derive(Debug)]
+pub struct Slowlog {
+    threshold: Duration,
+    max_len: usize,
+    next_id: AtomicU64,
+    entries: Mutex<VecDeque<SlowlogEntry>>,
+}
+
+impl Slowlog {
+    pub fn new(threshold: Duration, max_len: usize) -> Self {
+        Self {
+            threshold,
+            max_len,
+            next_id: AtomicU64::new(0),
+            entries: Mutex::new(VecDeque::with_capacity(max_len.min(1024))),
+        }
+    }
+
+    /// Whether a command taking `elapsed` should be logged
+    #[inline]
+    pub fn is_slow(&self, elapsed: Duration) -> bool {
+        self.max_len > 0 && elapsed >= self.threshold
+    }
+
+    /// Log a command if it took at least the threshold
+    pub fn record(&self, command: &'static str, request_id: u64, key: Option<Bytes>, elapsed: Duration) {
+        if !self.is_slow(elapsed) {
+            return;
+        }
+        let entry = SlowlogEntry {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            duration_us: elapsed.as_micros() as u64,
+            command,
+            request_id,
+            key,
+        };
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == self.max_len {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Up to `count` entries, newest first
+    pub fn get(&self, count: usize) -> Vec<SlowlogEntry> {
+        self.entries.lock().unwrap().iter().rev().take(count).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop every entry
+    pub fn reset(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slowlog_bounded_and_filtered() {
+        let slowlog = Slowlog::new(Duration::from_millis(10), 2);
+        slowlog.record("GET", 1, None, Duration::from_millis(1));
+        for id in 2..5 {
+            slowlog.record("SET", id, Some(Bytes::from_static(b"k")), Duration::from_millis(20));
+        }
+
+        let entries = slowlog.get(10);
+        assert_eq!(entries.iter().map(|e| e.request_id).collect::<Vec<_>>(), vec![4, 3]);
+        assert_eq!(entries[0].duration_us, 20_000);
+        assert!(entries[0].record().ends_with(b" 20000 set 4 k"));
+    }
+}
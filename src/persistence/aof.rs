@@ -2,12 +2,59 @@
 //!
 //! Write-ahead logging for durability.
 
-use bytes::{BufMut, Bytes, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, BufWriter, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::storage::ConcurrentStore;
+
+/// File header: magic followed by the format version byte.
+///
+/// Files written before records were checksummed have no header and store
+/// bare `[len][body]` records; they are still readable and get upgraded
+/// when a writer opens them.
+const FILE_HEADER: [u8; 8] = *b"CELRIXA\x02";
+
+/// Record header: body length (u32) + CRC32 of the body (u32)
+const RECORD_HEADER_SIZE: usize = 8;
+
+/// Legacy record header: body length (u32)
+const LEGACY_RECORD_HEADER_SIZE: usize = 4;
+
+/// CRC-32 (IEEE) lookup table
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 (IEEE) checksum
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+/// Extend a CRC-32 computed over earlier bytes with `data`
+pub(crate) fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for byte in data {
+        crc = CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
 
 /// AOF configuration
 #[derive(Debug, Clone)]
@@ -126,6 +173,202 @@ impl AofEntry {
 
         buf.freeze()
     }
+
+    /// Decode an entry produced by `encode`
+    pub fn decode(mut buf: &[u8]) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        if buf.remaining() < 1 + 8 + 4 {
+            return Err(invalid("AOF entry too short"));
+        }
+        let op = match buf.get_u8() {
+            1 => AofOpType::Set,
+            2 => AofOpType::Del,
+            other => return Err(invalid(&format!("Unknown AOF op {}", other))),
+        };
+        let timestamp_ms = buf.get_u64_le();
+
+        let key_len = buf.get_u32_le() as usize;
+        if buf.remaining() < key_len + 4 {
+            return Err(invalid("AOF entry key truncated"));
+        }
+        let key = Bytes::copy_from_slice(&buf[..key_len]);
+        buf.advance(key_len);
+
+        let value_len = buf.get_u32_le() as usize;
+        if buf.remaining() < value_len + 8 {
+            return Err(invalid("AOF entry value truncated"));
+        }
+        let value = Bytes::copy_from_slice(&buf[..value_len]);
+        buf.advance(value_len);
+
+        let ttl_ms = buf.get_u64_le();
+
+        Ok(Self {
+            op,
+            key,
+            value: (op == AofOpType::Set).then_some(value),
+            ttl_ms: (ttl_ms > 0).then_some(ttl_ms),
+            timestamp_ms,
+        })
+    }
+
+    /// Apply this entry to a store, counting TTL from when it was logged
+    pub fn apply(&self, store: &ConcurrentStore) {
+        match self.op {
+            AofOpType::Set => {
+                let value = self.value.clone().unwrap_or_default();
+                let ttl = match self.ttl_ms {
+                    Some(ttl_ms) => {
+                        let elapsed = Self::now_ms().saturating_sub(self.timestamp_ms);
+                        match ttl_ms.checked_sub(elapsed).filter(|&ms| ms > 0) {
+                            Some(remaining) => Some(Duration::from_millis(remaining)),
+                            None => {
+                                // Already expired: the key must not survive replay
+                                store.del(&self.key);
+                                return;
+                            }
+                        }
+                    }
+                    None => None,
+                };
+                store.set_with_ttl(self.key.clone(), value, ttl);
+            }
+            AofOpType::Del => {
+                store.del(&self.key);
+            }
+        }
+    }
+}
+
+/// Sequential reader over an AOF file.
+///
+/// Yields entries in log order. A truncated or checksum-failing record
+/// (e.g. from a crash mid-append or a flipped bit) ends iteration rather
+/// than erroring; everything before it is trusted. Headerless legacy files
+/// are read without checksums.
+pub struct AofReader {
+    data: Bytes,
+    offset: usize,
+    corrupt: bool,
+    legacy: bool,
+}
+
+impl AofReader {
+    /// Read an AOF file (a missing file reads as empty)
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let mut reader = Self {
+            data: Bytes::from(data),
+            offset: 0,
+            corrupt: false,
+            legacy: false,
+        };
+
+        let magic = &FILE_HEADER[..FILE_HEADER.len() - 1];
+        if reader.data.starts_with(&FILE_HEADER) {
+            reader.offset = FILE_HEADER.len();
+        } else if reader.data.starts_with(magic) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported AOF format version {}", reader.data[magic.len()]),
+            ));
+        } else if !reader.data.is_empty() && FILE_HEADER.starts_with(&reader.data) {
+            // Crashed while writing the header of a fresh file
+            reader.corrupt = true;
+        } else if !reader.data.is_empty() {
+            reader.legacy = true;
+        }
+        Ok(reader)
+    }
+
+    /// Read an AOF file starting at the record at byte `offset`
+    pub fn open_at<P: AsRef<Path>>(path: P, offset: usize) -> io::Result<Self> {
+        let mut reader = Self::open(path)?;
+        if offset > reader.data.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("AOF offset {} is past the end of the file ({} bytes)", offset, reader.data.len()),
+            ));
+        }
+        // Offset 0 means "from the first record", which follows the header
+        reader.offset = offset.max(reader.offset);
+        Ok(reader)
+    }
+
+    /// Byte offset of the next record
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Whether reading stopped at a damaged record rather than a clean end
+    pub fn is_corrupt(&self) -> bool {
+        self.corrupt
+    }
+
+    /// Whether the file predates the header and record checksums
+    pub fn is_legacy(&self) -> bool {
+        self.legacy
+    }
+
+    /// Checksum of the first `len` bytes of the file, or None if it is
+    /// shorter. Appends never change it, so a different value later means
+    /// the file was rewritten or replaced.
+    pub fn prefix_checksum(&self, len: usize) -> Option<u32> {
+        self.data.get(..len).map(crc32)
+    }
+
+    /// Apply every entry to a store, returning how many were replayed
+    pub fn replay(self, store: &ConcurrentStore) -> io::Result<usize> {
+        let mut count = 0;
+        for entry in self {
+            entry?.apply(store);
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+impl Iterator for AofReader {
+    type Item = io::Result<AofEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.corrupt {
+            return None;
+        }
+        let rest = &self.data[self.offset..];
+        if rest.is_empty() {
+            return None;
+        }
+
+        let header_size = if self.legacy { LEGACY_RECORD_HEADER_SIZE } else { RECORD_HEADER_SIZE };
+        let body = rest.get(..header_size).and_then(|header| {
+            let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+            let body = rest.get(header_size..header_size + len)?;
+            if self.legacy {
+                return Some(body);
+            }
+            let crc = u32::from_le_bytes(header[4..].try_into().unwrap());
+            (crc32(body) == crc).then_some(body)
+        });
+        let Some(body) = body else {
+            warn!(
+                "AOF record at offset {} is truncated or corrupt; ignoring the remaining {} bytes",
+                self.offset,
+                rest.len()
+            );
+            self.corrupt = true;
+            return None;
+        };
+
+        let entry = AofEntry::decode(body);
+        self.offset += header_size + body.len();
+        Some(entry)
+    }
 }
 
 /// AOF writer (thread-safe)
@@ -142,10 +385,27 @@ impl AofWriter {
             fs::create_dir_all(parent)?;
         }
 
-        let file = OpenOptions::new()
+        let mut reader = AofReader::open(&config.path)?;
+        if reader.is_legacy() {
+            Self::upgrade_legacy(&config.path, reader)?;
+        } else {
+            // Drop any damaged tail so new appends follow the last good record
+            reader.by_ref().for_each(drop);
+            if reader.is_corrupt() {
+                let file = OpenOptions::new().write(true).open(&config.path)?;
+                file.set_len(reader.offset() as u64)?;
+                file.sync_all()?;
+            }
+        }
+
+        let mut file = OpenOptions::new()
             .create(true)
             .append(true)
             .open(&config.path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(&FILE_HEADER)?;
+            file.sync_all()?;
+        }
 
         Ok(Self {
             config,
@@ -154,14 +414,39 @@ impl AofWriter {
         })
     }
 
+    /// Convert a headerless legacy AOF to the current format.
+    ///
+    /// Every readable record is copied into a new file that is renamed over
+    /// the old one. The original is kept beside it with an `.aof.legacy`
+    /// extension, since without checksums a damaged tail can't be told
+    /// apart from real data.
+    fn upgrade_legacy(path: &Path, reader: AofReader) -> io::Result<()> {
+        let tmp_path = path.with_extension("aof.upgrade");
+        let mut tmp = BufWriter::new(File::create(&tmp_path)?);
+        tmp.write_all(&FILE_HEADER)?;
+        for entry in reader {
+            Self::write_record(&mut tmp, &entry?)?;
+        }
+        tmp.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+
+        fs::copy(path, path.with_extension("aof.legacy"))?;
+        fs::rename(&tmp_path, path)
+    }
+
+    /// Write a length-prefixed record
+    fn write_record<W: Write>(writer: &mut W, entry: &AofEntry) -> io::Result<()> {
+        let encoded = entry.encode();
+        writer.write_all(&(encoded.len() as u32).to_le_bytes())?;
+        writer.write_all(&crc32(&encoded).to_le_bytes())?;
+        writer.write_all(&encoded)
+    }
+
     /// Append an entry to the AOF
     pub fn append(&self, entry: &AofEntry) -> io::Result<()> {
-        let encoded = entry.encode();
         let mut writer = self.writer.lock().unwrap();
 
-        // Write length prefix + data
-        writer.write_all(&(encoded.len() as u32).to_le_bytes())?;
-        writer.write_all(&encoded)?;
+        // Write length prefix + checksum + data
+        Self::write_record(&mut *writer, entry)?;
 
         // Sync based on config
         if self.config.sync_mode == AofSyncMode::Always {
@@ -202,6 +487,48 @@ impl AofWriter {
     pub fn needs_rewrite(&self) -> bool {
         self.entry_count() >= self.config.rewrite_threshold
     }
+
+    /// Replace the AOF with one SET per live key in `store`.
+    ///
+    /// The new file is written beside the old one, fsynced, and renamed over
+    /// it. Appends block on the writer lock for the duration, so none are
+    /// lost in the swap; an append racing with the store scan is logged to
+    /// the new file and re-applies harmlessly on replay.
+    pub fn rewrite(&self, store: &ConcurrentStore) -> io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        writer.flush()?;
+
+        let tmp_path = self.config.path.with_extension("aof.rewrite");
+        let mut tmp = BufWriter::new(File::create(&tmp_path)?);
+        tmp.write_all(&FILE_HEADER)?;
+        let mut result = Ok(());
+        store.for_each_live(|key, value, ttl| {
+            if result.is_err() {
+                return;
+            }
+            // Round sub-millisecond TTLs up: 0 would mean "never expires"
+            let ttl_ms = ttl.map(|d| (d.as_millis() as u64).max(1));
+            let entry = AofEntry::set(key.clone(), value.clone(), ttl_ms);
+            result = Self::write_record(&mut tmp, &entry);
+        });
+        result?;
+        tmp.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+
+        fs::rename(&tmp_path, &self.config.path)?;
+        if let Some(parent) = self.config.path.parent() {
+            // Persist the rename itself; not all platforms can open directories
+            if let Ok(dir) = File::open(parent) {
+                let _ = dir.sync_all();
+            }
+        }
+
+        let file = OpenOptions::new().append(true).open(&self.config.path)?;
+        *writer = BufWriter::new(file);
+        self.entry_count
+            .store(0, std::sync::atomic::Ordering::Relaxed);
+
+        Ok(())
+    }
 }
 
 impl Clone for AofWriter {
@@ -248,5 +575,135 @@ mod tests {
         let encoded = entry.encode();
         assert!(!encoded.is_empty());
         assert_eq!(encoded[0], AofOpType::Set as u8);
+
+        let decoded = AofEntry::decode(&encoded).unwrap();
+        assert_eq!(decoded.key, entry.key);
+        assert_eq!(decoded.value, entry.value);
+        assert_eq!(decoded.ttl_ms, Some(60000));
+    }
+
+    #[test]
+    fn test_aof_rewrite_collapses_history() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("rewrite.aof");
+        let aof = AofWriter::open(AofConfig::default().with_path(&path)).unwrap();
+        let store = ConcurrentStore::new();
+
+        for i in 0..1000 {
+            let key = Bytes::from(format!("key{}", i % 10));
+            let value = Bytes::from(format!("value{}", i));
+            let ttl_ms = (i % 10 == 0).then_some(60_000);
+            store.set_with_ttl(key.clone(), value.clone(), ttl_ms.map(Duration::from_millis));
+            aof.log_set(key, value, ttl_ms).unwrap();
+        }
+        aof.flush().unwrap();
+        let before = fs::metadata(&path).unwrap().len();
+
+        aof.rewrite(&store).unwrap();
+        assert_eq!(aof.entry_count(), 0);
+        assert!(fs::metadata(&path).unwrap().len() < before / 50);
+
+        // Appends after the swap land in the new file
+        aof.log_del(Bytes::from_static(b"key9")).unwrap();
+        aof.flush().unwrap();
+        store.del(&Bytes::from_static(b"key9"));
+
+        let replayed = ConcurrentStore::new();
+        assert_eq!(AofReader::open(&path).unwrap().replay(&replayed).unwrap(), 11);
+        assert_eq!(replayed.len(), 9);
+        for key in store.keys() {
+            assert_eq!(replayed.get(&key), store.get(&key));
+        }
+        let mut with_ttl = 0;
+        replayed.for_each_live(|_, _, ttl| with_ttl += ttl.is_some() as usize);
+        assert_eq!(with_ttl, 1);
+    }
+
+    #[test]
+    fn test_replay_stops_at_corrupt_record() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("corrupt.aof");
+        let aof = AofWriter::open(AofConfig::default().with_path(&path)).unwrap();
+        for i in 0..10 {
+            aof.log_set(Bytes::from(format!("key{}", i)), Bytes::from_static(b"value"), None)
+                .unwrap();
+        }
+        aof.flush().unwrap();
+        drop(aof);
+
+        // Flip a byte inside the sixth record's body
+        let mut data = fs::read(&path).unwrap();
+        let start = FILE_HEADER.len();
+        let record_len = (data.len() - start) / 10;
+        data[start + 5 * record_len + RECORD_HEADER_SIZE + 12] ^= 0xFF;
+        fs::write(&path, &data).unwrap();
+
+        let store = ConcurrentStore::new();
+        let mut reader = AofReader::open(&path).unwrap();
+        let applied = reader.by_ref().map(|e| e.unwrap().apply(&store)).count();
+        assert_eq!(applied, 5);
+        assert!(reader.is_corrupt());
+        assert_eq!(reader.offset(), start + 5 * record_len);
+        assert_eq!(store.len(), 5);
+        assert!(store.exists(&Bytes::from_static(b"key4")));
+        assert!(!store.exists(&Bytes::from_static(b"key5")));
+
+        // Reopening cuts the damaged tail so new appends stay reachable
+        let aof = AofWriter::open(AofConfig::default().with_path(&path)).unwrap();
+        aof.log_set(Bytes::from_static(b"after"), Bytes::from_static(b"v"), None).unwrap();
+        aof.flush().unwrap();
+        let replayed = ConcurrentStore::new();
+        assert_eq!(AofReader::open(&path).unwrap().replay(&replayed).unwrap(), 6);
+        assert!(replayed.exists(&Bytes::from_static(b"after")));
+    }
+
+    #[test]
+    fn test_legacy_aof_is_upgraded_not_truncated() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("legacy.aof");
+
+        // Headerless `[len][body]` records, as written before checksums
+        let mut legacy = Vec::new();
+        for i in 0..10 {
+            let entry = AofEntry::set(Bytes::from(format!("key{}", i)), Bytes::from_static(b"value"), None);
+            let encoded = entry.encode();
+            legacy.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+            legacy.extend_from_slice(&encoded);
+        }
+        fs::write(&path, &legacy).unwrap();
+
+        let reader = AofReader::open(&path).unwrap();
+        assert!(reader.is_legacy());
+        assert_eq!(reader.replay(&ConcurrentStore::new()).unwrap(), 10);
+
+        let aof = AofWriter::open(AofConfig::default().with_path(&path)).unwrap();
+        aof.log_set(Bytes::from_static(b"after"), Bytes::from_static(b"v"), None).unwrap();
+        aof.flush().unwrap();
+
+        assert!(fs::read(&path).unwrap().starts_with(&FILE_HEADER));
+        assert_eq!(fs::read(path.with_extension("aof.legacy")).unwrap(), legacy);
+
+        let mut reader = AofReader::open(&path).unwrap();
+        assert!(!reader.is_legacy());
+        let replayed = ConcurrentStore::new();
+        assert_eq!(reader.by_ref().map(|e| e.unwrap().apply(&replayed)).count(), 11);
+        assert!(!reader.is_corrupt());
+        assert!(replayed.exists(&Bytes::from_static(b"key0")));
+        assert!(replayed.exists(&Bytes::from_static(b"key9")));
+        assert!(replayed.exists(&Bytes::from_static(b"after")));
+    }
+
+    #[test]
+    fn test_torn_header_is_rewritten() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("torn.aof");
+        fs::write(&path, &FILE_HEADER[..3]).unwrap();
+
+        let aof = AofWriter::open(AofConfig::default().with_path(&path)).unwrap();
+        aof.log_del(Bytes::from_static(b"key")).unwrap();
+        aof.flush().unwrap();
+
+        assert!(fs::read(&path).unwrap().starts_with(&FILE_HEADER));
+        assert_eq!(AofReader::open(&path).unwrap().count(), 1);
     }
 }
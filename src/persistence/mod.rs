@@ -5,5 +5,5 @@
 mod snapshot;
 mod aof;
 
-pub use snapshot::{Snapshot, SnapshotConfig};
-pub use aof::{AofWriter, AofConfig, AofEntry};
+pub use snapshot::{Snapshot, SnapshotConfig, SnapshotEntry};
+pub use aof::{AofWriter, AofConfig, AofEntry, AofReader, AofSyncMode};
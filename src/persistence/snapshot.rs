@@ -4,10 +4,13 @@
 
 use bytes::Bytes;
 use std::fs::{self, File};
-use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use super::aof::{crc32, crc32_update};
+use crate::storage::ConcurrentStore;
+
 /// Snapshot configuration
 #[derive(Debug, Clone)]
 pub struct SnapshotConfig {
@@ -45,14 +48,21 @@ impl SnapshotConfig {
 }
 
 /// Snapshot file format:
-/// - Magic: 4 bytes "CELX"
+/// - Magic: 4 bytes "CELS"
 /// - Version: 1 byte
 /// - Timestamp: 8 bytes (unix millis)
 /// - Entry count: 4 bytes
-/// - Entries: [key_len (4) + key + value_len (4) + value + ttl (8)]*
-
+/// - Payload length: 8 bytes (version 2+)
+/// - Payload CRC32: 4 bytes (version 2+)
+/// - Entries (the payload): [key_len (4) + key + value_len (4) + value + ttl (8)]*
 const SNAPSHOT_MAGIC: &[u8] = b"CELS";
-const SNAPSHOT_VERSION: u8 = 1;
+const SNAPSHOT_VERSION: u8 = 2;
+/// Version 1 files have no payload length or checksum
+const SNAPSHOT_VERSION_UNCHECKED: u8 = 1;
+/// Offset of the entry count, after magic, version and timestamp
+const COUNT_OFFSET: u64 = 4 + 1 + 8;
+/// Format version of DUMP payloads, which is independent of the file format
+const DUMP_VERSION: u8 = 1;
 
 /// Snapshot entry
 #[derive(Debug, Clone)]
@@ -62,6 +72,65 @@ pub struct SnapshotEntry {
     pub expires_at_ms: Option<u64>,
 }
 
+impl SnapshotEntry {
+    /// Write `[key_len][key][value_len][value][expires_at_ms, 0 = none]`
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&(self.key.len() as u32).to_le_bytes())?;
+        writer.write_all(&self.key)?;
+        writer.write_all(&(self.value.len() as u32).to_le_bytes())?;
+        writer.write_all(&self.value)?;
+        writer.write_all(&self.expires_at_ms.unwrap_or(0).to_le_bytes())
+    }
+
+    /// Read an entry written by `write_to`
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut len_buf = [0u8; 4];
+        reader.read_exact(&mut len_buf)?;
+        let mut key = vec![0u8; u32::from_le_bytes(len_buf) as usize];
+        reader.read_exact(&mut key)?;
+
+        reader.read_exact(&mut len_buf)?;
+        let mut value = vec![0u8; u32::from_le_bytes(len_buf) as usize];
+        reader.read_exact(&mut value)?;
+
+        let mut ttl_buf = [0u8; 8];
+        reader.read_exact(&mut ttl_buf)?;
+        let ttl = u64::from_le_bytes(ttl_buf);
+
+        Ok(Self {
+            key: Bytes::from(key),
+            value: Bytes::from(value),
+            expires_at_ms: (ttl > 0).then_some(ttl),
+        })
+    }
+
+    /// Serialize one value for DUMP: version, the entry (without its key)
+    /// and a CRC32 over both
+    pub fn dump(value: Bytes, expires_at_ms: Option<u64>) -> Bytes {
+        let entry = Self { key: Bytes::new(), value, expires_at_ms };
+        let mut buf = vec![DUMP_VERSION];
+        entry.write_to(&mut buf).expect("writing to a Vec can't fail");
+        let crc = crc32(&buf);
+        buf.extend_from_slice(&crc.to_le_bytes());
+        Bytes::from(buf)
+    }
+
+    /// Parse a DUMP payload, rejecting other versions and damaged blobs
+    pub fn parse_dump(blob: &[u8]) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "DUMP payload version or checksum are wrong");
+        let (body, crc) = blob.split_at_checked(blob.len().checked_sub(4).ok_or_else(invalid)?).ok_or_else(invalid)?;
+        if body.first() != Some(&DUMP_VERSION) || crc32(body).to_le_bytes() != crc {
+            return Err(invalid());
+        }
+        let mut rest = &body[1..];
+        let entry = Self::read_from(&mut rest).map_err(|_| invalid())?;
+        if !rest.is_empty() {
+            return Err(invalid());
+        }
+        Ok(entry)
+    }
+}
+
 /// Snapshot writer/reader
 pub struct Snapshot {
     config: SnapshotConfig,
@@ -85,41 +154,75 @@ impl Snapshot {
     /// Write a snapshot
     pub fn save(&self, entries: &[SnapshotEntry]) -> io::Result<PathBuf> {
         let path = self.snapshot_filename();
-        let file = File::create(&path)?;
-        let mut writer = BufWriter::new(file);
-
-        // Write header
-        writer.write_all(SNAPSHOT_MAGIC)?;
-        writer.write_all(&[SNAPSHOT_VERSION])?;
-
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
-        writer.write_all(&timestamp.to_le_bytes())?;
-        writer.write_all(&(entries.len() as u32).to_le_bytes())?;
+        let mut writer = Self::create(&path)?;
 
         // Write entries
+        let mut payload = ChecksumWriter::new(&mut writer);
         for entry in entries {
-            // Key
-            writer.write_all(&(entry.key.len() as u32).to_le_bytes())?;
-            writer.write_all(&entry.key)?;
+            entry.write_to(&mut payload)?;
+        }
+        let (len, crc) = payload.finish();
 
-            // Value
-            writer.write_all(&(entry.value.len() as u32).to_le_bytes())?;
-            writer.write_all(&entry.value)?;
+        Self::finish(writer, entries.len() as u32, len, crc)?;
+        self.cleanup_old_snapshots()?;
 
-            // TTL (0 = no expiry)
-            let ttl = entry.expires_at_ms.unwrap_or(0);
-            writer.write_all(&ttl.to_le_bytes())?;
-        }
+        Ok(path)
+    }
 
-        writer.flush()?;
+    /// Write a snapshot of `store`'s live keys without collecting them first.
+    ///
+    /// Entries go straight to the file as each shard is visited under its
+    /// read lock, so the result is consistent within a shard but not across
+    /// shards: a write racing the snapshot may or may not be included.
+    pub fn save_from_store(&self, store: &ConcurrentStore) -> io::Result<PathBuf> {
+        let path = self.snapshot_filename();
+        let mut writer = Self::create(&path)?;
+        let now_ms = unix_now_ms();
+
+        let mut payload = ChecksumWriter::new(&mut writer);
+        let mut count = 0u32;
+        let mut result = Ok(());
+        store.for_each_live(|key, value, ttl| {
+            if result.is_err() {
+                return;
+            }
+            let entry = SnapshotEntry {
+                key: key.clone(),
+                value: value.clone(),
+                expires_at_ms: ttl.map(|ttl| now_ms + (ttl.as_millis() as u64).max(1)),
+            };
+            result = entry.write_to(&mut payload);
+            count += 1;
+        });
+        result?;
+        let (len, crc) = payload.finish();
+
+        Self::finish(writer, count, len, crc)?;
         self.cleanup_old_snapshots()?;
 
         Ok(path)
     }
 
+    /// Create a snapshot file and write its header. The count, length and
+    /// checksum aren't known until the entries are written; `finish` fills
+    /// them in.
+    fn create(path: &Path) -> io::Result<BufWriter<File>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(SNAPSHOT_MAGIC)?;
+        writer.write_all(&[SNAPSHOT_VERSION])?;
+        writer.write_all(&unix_now_ms().to_le_bytes())?;
+        writer.write_all(&[0u8; 4 + 8 + 4])?;
+        Ok(writer)
+    }
+
+    fn finish(mut writer: BufWriter<File>, count: u32, len: u64, crc: u32) -> io::Result<()> {
+        writer.seek(SeekFrom::Start(COUNT_OFFSET))?;
+        writer.write_all(&count.to_le_bytes())?;
+        writer.write_all(&len.to_le_bytes())?;
+        writer.write_all(&crc.to_le_bytes())?;
+        writer.flush()
+    }
+
     /// Load the latest snapshot
     pub fn load_latest(&self) -> io::Result<Option<Vec<SnapshotEntry>>> {
         let latest = self.find_latest_snapshot()?;
@@ -131,6 +234,11 @@ impl Snapshot {
 
     /// Load a specific snapshot file
     pub fn load(&self, path: &Path) -> io::Result<Vec<SnapshotEntry>> {
+        Self::read(path)
+    }
+
+    /// Read a snapshot file without a configured snapshot directory
+    pub fn read(path: &Path) -> io::Result<Vec<SnapshotEntry>> {
         let file = File::open(path)?;
         let mut reader = BufReader::new(file);
 
@@ -146,7 +254,7 @@ impl Snapshot {
 
         let mut version = [0u8; 1];
         reader.read_exact(&mut version)?;
-        if version[0] != SNAPSHOT_VERSION {
+        if version[0] != SNAPSHOT_VERSION && version[0] != SNAPSHOT_VERSION_UNCHECKED {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 format!("Unsupported snapshot version: {}", version[0]),
@@ -160,38 +268,48 @@ impl Snapshot {
         reader.read_exact(&mut count_buf)?;
         let count = u32::from_le_bytes(count_buf) as usize;
 
+        if version[0] == SNAPSHOT_VERSION {
+            Self::verify_payload(&mut reader)?;
+        }
+
         // Read entries
         let mut entries = Vec::with_capacity(count);
         for _ in 0..count {
-            // Key
-            let mut key_len_buf = [0u8; 4];
-            reader.read_exact(&mut key_len_buf)?;
-            let key_len = u32::from_le_bytes(key_len_buf) as usize;
-            let mut key_buf = vec![0u8; key_len];
-            reader.read_exact(&mut key_buf)?;
-
-            // Value
-            let mut value_len_buf = [0u8; 4];
-            reader.read_exact(&mut value_len_buf)?;
-            let value_len = u32::from_le_bytes(value_len_buf) as usize;
-            let mut value_buf = vec![0u8; value_len];
-            reader.read_exact(&mut value_buf)?;
-
-            // TTL
-            let mut ttl_buf = [0u8; 8];
-            reader.read_exact(&mut ttl_buf)?;
-            let ttl = u64::from_le_bytes(ttl_buf);
-
-            entries.push(SnapshotEntry {
-                key: Bytes::from(key_buf),
-                value: Bytes::from(value_buf),
-                expires_at_ms: if ttl > 0 { Some(ttl) } else { None },
-            });
+            entries.push(SnapshotEntry::read_from(&mut reader)?);
         }
 
         Ok(entries)
     }
 
+    /// Check the payload against the header's length and checksum before
+    /// any entry is parsed, leaving `reader` at the first entry
+    fn verify_payload(reader: &mut BufReader<File>) -> io::Result<()> {
+        let mut len_buf = [0u8; 8];
+        let mut crc_buf = [0u8; 4];
+        reader.read_exact(&mut len_buf)?;
+        reader.read_exact(&mut crc_buf)?;
+        let (expected_len, expected_crc) = (u64::from_le_bytes(len_buf), u32::from_le_bytes(crc_buf));
+
+        let start = reader.stream_position()?;
+        let mut payload = ChecksumWriter::new(io::sink());
+        io::copy(reader, &mut payload)?;
+        let (len, crc) = payload.finish();
+        if len != expected_len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Snapshot payload is {} bytes, header says {}: file is truncated or padded", len, expected_len),
+            ));
+        }
+        if crc != expected_crc {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Snapshot checksum mismatch: header has {:08x}, payload hashes to {:08x}", expected_crc, crc),
+            ));
+        }
+        reader.seek(SeekFrom::Start(start))?;
+        Ok(())
+    }
+
     /// Find the latest snapshot file
     fn find_latest_snapshot(&self) -> io::Result<Option<PathBuf>> {
         let mut latest: Option<(PathBuf, SystemTime)> = None;
@@ -229,7 +347,7 @@ impl Snapshot {
             })
             .collect();
 
-        snapshots.sort_by(|a, b| b.1.cmp(&a.1)); // Newest first
+        snapshots.sort_by_key(|s| std::cmp::Reverse(s.1)); // Newest first
 
         for (path, _) in snapshots.into_iter().skip(self.config.max_snapshots) {
             fs::remove_file(path)?;
@@ -239,6 +357,41 @@ impl Snapshot {
     }
 }
 
+/// Passes writes through while tracking their length and CRC32
+struct ChecksumWriter<W> {
+    inner: W,
+    len: u64,
+    crc: u32,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, len: 0, crc: 0 }
+    }
+
+    /// Length and CRC32 of everything written
+    fn finish(self) -> (u64, u32) {
+        (self.len, self.crc)
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.len += written as u64;
+        self.crc = crc32_update(self.crc, &buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn unix_now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -270,4 +423,77 @@ mod tests {
         assert_eq!(loaded[0].key.as_ref(), b"key1");
         assert_eq!(loaded[1].expires_at_ms, Some(1234567890000));
     }
+
+    #[test]
+    fn test_save_from_store() {
+        let dir = tempdir().unwrap();
+        let snapshot = Snapshot::new(SnapshotConfig::default().with_dir(dir.path())).unwrap();
+        let store = ConcurrentStore::new();
+        for i in 0..100_000u32 {
+            let ttl = i.is_multiple_of(10).then_some(3600);
+            store.set(Bytes::from(format!("key:{}", i)), Bytes::from(i.to_le_bytes().to_vec()), ttl);
+        }
+        store.set_with_ttl(Bytes::from_static(b"gone"), Bytes::from_static(b"v"), Some(std::time::Duration::ZERO));
+
+        let path = snapshot.save_from_store(&store).unwrap();
+        let loaded = snapshot.load(&path).unwrap();
+
+        assert_eq!(loaded.len(), 100_000);
+        let now_ms = unix_now_ms();
+        for entry in &loaded {
+            let i: u32 = std::str::from_utf8(&entry.key).unwrap()[4..].parse().unwrap();
+            assert_eq!(entry.value.as_ref(), i.to_le_bytes());
+            match entry.expires_at_ms {
+                Some(at) => assert!(i.is_multiple_of(10) && at > now_ms && at <= now_ms + 3_600_000),
+                None => assert!(!i.is_multiple_of(10)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_corrupt_snapshot_fails_checksum() {
+        let dir = tempdir().unwrap();
+        let snapshot = Snapshot::new(SnapshotConfig::default().with_dir(dir.path())).unwrap();
+        let entry = SnapshotEntry {
+            key: Bytes::from_static(b"key"),
+            value: Bytes::from_static(b"value"),
+            expires_at_ms: None,
+        };
+        let path = snapshot.save(&[entry.clone(), entry]).unwrap();
+        let mut data = fs::read(&path).unwrap();
+
+        let last = data.len() - 9;
+        data[last] ^= 0xFF;
+        fs::write(&path, &data).unwrap();
+        let err = snapshot.load(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("checksum mismatch"), "{}", err);
+
+        data.truncate(data.len() - 10);
+        fs::write(&path, &data).unwrap();
+        let err = snapshot.load(&path).unwrap_err();
+        assert!(err.to_string().contains("truncated"), "{}", err);
+    }
+
+    #[test]
+    fn test_reads_unchecked_v1_snapshot() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("old.cel");
+        let mut data = SNAPSHOT_MAGIC.to_vec();
+        data.push(SNAPSHOT_VERSION_UNCHECKED);
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        let entry = SnapshotEntry {
+            key: Bytes::from_static(b"key"),
+            value: Bytes::from_static(b"value"),
+            expires_at_ms: Some(42),
+        };
+        entry.write_to(&mut data).unwrap();
+        fs::write(&path, &data).unwrap();
+
+        let loaded = Snapshot::read(&path).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].value.as_ref(), b"value");
+        assert_eq!(loaded[0].expires_at_ms, Some(42));
+    }
 }
@@ -2,17 +2,56 @@
 //!
 //! Implements Encoder and Decoder traits for framed I/O.
 
-use bytes::BytesMut;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::io;
 use tokio_util::codec::{Decoder, Encoder};
 
-use super::frame::{Frame, FrameHeader, HEADER_SIZE};
+use super::frame::{Frame, FrameHeader, FLAG_ACCEPTS_COMPRESSED, FLAG_COMPRESSED, HEADER_SIZE};
+use super::lz4;
+use crate::server::BufferPool;
+
+/// Largest payload a compressed frame may expand to
+pub const MAX_DECOMPRESSED_SIZE: usize = 256 * 1024 * 1024;
+
+/// Default limit on a frame's declared payload length
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
 
 /// Tokio codec for VCP frames
-#[derive(Debug, Default)]
 pub struct VcpCodec {
     /// Current decode state
     state: DecodeState,
+    /// Pool that written payload buffers are returned to
+    pool: Option<BufferPool>,
+    /// Compress payloads of at least this many bytes (None = never)
+    compression_threshold: Option<usize>,
+    /// Whether the peer has advertised `FLAG_ACCEPTS_COMPRESSED`
+    peer_accepts_compression: bool,
+    /// Largest payload accepted, before or after decompression
+    max_frame_size: usize,
+}
+
+impl Default for VcpCodec {
+    fn default() -> Self {
+        Self {
+            state: DecodeState::default(),
+            pool: None,
+            compression_threshold: None,
+            peer_accepts_compression: false,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+}
+
+impl std::fmt::Debug for VcpCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VcpCodec")
+            .field("state", &self.state)
+            .field("pooled", &self.pool.is_some())
+            .field("compression_threshold", &self.compression_threshold)
+            .field("peer_accepts_compression", &self.peer_accepts_compression)
+            .field("max_frame_size", &self.max_frame_size)
+            .finish()
+    }
 }
 
 #[derive(Debug, Default)]
@@ -26,6 +65,44 @@ impl VcpCodec {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Recycle payload buffers into `pool` once frames are written
+    pub fn with_pool(mut self, pool: BufferPool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Advertise compression support on every frame, and LZ4-compress
+    /// payloads of at least `threshold` bytes once the peer has advertised
+    /// it too. Peers that never do are sent plain frames.
+    pub fn with_compression(mut self, threshold: usize) -> Self {
+        self.compression_threshold = Some(threshold);
+        self
+    }
+
+    /// Reject frames declaring a payload larger than `max` bytes, before
+    /// buffering any of it, so a peer can't make us allocate gigabytes
+    pub fn with_max_frame_size(mut self, max: usize) -> Self {
+        self.max_frame_size = max;
+        self
+    }
+
+    /// Buffer pool for building response payloads, if configured
+    pub fn pool(&self) -> Option<&BufferPool> {
+        self.pool.as_ref()
+    }
+
+    /// Whether `src` already holds another complete frame, i.e. the peer is
+    /// pipelining and the next `decode` won't need to wait for the socket
+    pub fn has_buffered_frame(&self, src: &BytesMut) -> bool {
+        let payload_len = match &self.state {
+            DecodeState::Payload(header) => return src.len() >= header.payload_len as usize,
+            DecodeState::Header if src.len() < HEADER_SIZE => return false,
+            // Payload length sits after magic, version, opcode and flags
+            DecodeState::Header => u32::from_be_bytes(src[8..12].try_into().unwrap()),
+        };
+        src.len() >= HEADER_SIZE + payload_len as usize
+    }
 }
 
 impl Decoder for VcpCodec {
@@ -41,6 +118,15 @@ impl Decoder for VcpCodec {
                     }
 
                     let header = FrameHeader::decode(&mut src.split_to(HEADER_SIZE).freeze())?;
+                    if header.payload_len as usize > self.max_frame_size {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!(
+                                "Frame payload of {} bytes exceeds limit of {}",
+                                header.payload_len, self.max_frame_size
+                            ),
+                        ));
+                    }
                     self.state = DecodeState::Payload(header);
                 }
 
@@ -52,12 +138,20 @@ impl Decoder for VcpCodec {
                     }
 
                     let payload = src.split_to(payload_len).freeze();
-                    let frame = Frame {
+                    let mut frame = Frame {
                         header: header.clone(),
                         payload,
                     };
 
                     self.state = DecodeState::Header;
+                    if frame.header.flags & FLAG_ACCEPTS_COMPRESSED != 0 {
+                        self.peer_accepts_compression = true;
+                    }
+                    if frame.header.flags & FLAG_COMPRESSED != 0 {
+                        frame.payload = decompress_payload(frame.payload, self.max_frame_size)?;
+                        frame.header.payload_len = frame.payload.len() as u32;
+                    }
+                    frame.header.flags &= !(FLAG_COMPRESSED | FLAG_ACCEPTS_COMPRESSED);
                     return Ok(Some(frame));
                 }
             }
@@ -68,13 +162,57 @@ impl Decoder for VcpCodec {
 impl Encoder<Frame> for VcpCodec {
     type Error = io::Error;
 
-    fn encode(&mut self, item: Frame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+    fn encode(&mut self, mut item: Frame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if let Some(threshold) = self.compression_threshold {
+            item.header.flags |= FLAG_ACCEPTS_COMPRESSED;
+            if self.peer_accepts_compression && item.payload.len() >= threshold {
+                if let Some(compressed) = compress_payload(&item.payload) {
+                    let original = std::mem::replace(&mut item.payload, compressed);
+                    item.header.flags |= FLAG_COMPRESSED;
+                    item.header.payload_len = item.payload.len() as u32;
+                    if let Some(pool) = &self.pool {
+                        pool.recycle(original);
+                    }
+                }
+            }
+        }
+
         dst.reserve(HEADER_SIZE + item.payload.len());
         item.encode(dst);
+        if let Some(pool) = &self.pool {
+            pool.recycle(item.payload);
+        }
         Ok(())
     }
 }
 
+/// `[uncompressed len u32][LZ4 block]`, or None if that isn't smaller
+fn compress_payload(payload: &[u8]) -> Option<Bytes> {
+    let block = lz4::compress(payload);
+    if block.len() + 4 >= payload.len() {
+        return None;
+    }
+    let mut buf = BytesMut::with_capacity(block.len() + 4);
+    buf.put_u32(payload.len() as u32);
+    buf.put_slice(&block);
+    Some(buf.freeze())
+}
+
+fn decompress_payload(mut payload: Bytes, max_frame_size: usize) -> io::Result<Bytes> {
+    if payload.len() < 4 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Compressed payload too short"));
+    }
+    let len = payload.get_u32() as usize;
+    let limit = MAX_DECOMPRESSED_SIZE.min(max_frame_size);
+    if len > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Compressed payload expands to {} bytes, limit is {}", len, limit),
+        ));
+    }
+    lz4::decompress(&payload, len).map(Bytes::from)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -117,4 +255,106 @@ mod tests {
         let mut full_buf = full;
         assert!(codec.decode(&mut full_buf).unwrap().is_some());
     }
+
+    #[test]
+    fn test_has_buffered_frame() {
+        let mut codec = VcpCodec::new();
+        let mut buf = BytesMut::new();
+        for id in 0..2 {
+            let frame = Frame::new(OpCode::Set, id, Bytes::from_static(b"test data"));
+            codec.encode(frame, &mut buf).unwrap();
+        }
+        buf.truncate(buf.len() - 1);
+
+        assert!(codec.has_buffered_frame(&buf));
+        codec.decode(&mut buf).unwrap().unwrap();
+        // Second frame is one byte short
+        assert!(!codec.has_buffered_frame(&buf));
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        assert!(!codec.has_buffered_frame(&buf));
+    }
+
+    #[test]
+    fn test_pooled_encoding_reuses_buffers() {
+        use crate::protocol::Response;
+
+        let pool = BufferPool::new(4, 1024);
+        let mut codec = VcpCodec::new().with_pool(pool.clone());
+        let response = Response::Array(vec![Bytes::from(vec![b'x'; 512]); 4]);
+
+        let mut out = BytesMut::new();
+        for i in 0..1000 {
+            let frame = response.to_frame_pooled(i, &pool);
+            codec.encode(frame, &mut out).unwrap();
+            let decoded = codec.decode(&mut out).unwrap().unwrap();
+            assert_eq!(decoded.header.request_id, i);
+        }
+
+        // Every payload buffer went back to the pool after being written
+        assert_eq!(pool.len(), 4);
+        assert!(pool.hit_rate() > 0.99);
+    }
+
+    #[test]
+    fn test_rejects_oversized_frame_before_buffering() {
+        let mut codec = VcpCodec::new().with_max_frame_size(1024);
+        let mut buf = BytesMut::new();
+        codec.encode(Frame::new(OpCode::Set, 1, Bytes::from(vec![0u8; 1024])), &mut buf).unwrap();
+        assert!(codec.decode(&mut buf).unwrap().is_some());
+
+        // Only the header of a frame claiming a ~4GB payload
+        let mut header = BytesMut::new();
+        FrameHeader::new(OpCode::Set, 2).with_payload_len(u32::MAX).encode(&mut header);
+        let err = codec.decode(&mut header).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(header.capacity() < 1024);
+    }
+
+    /// Flags of the encoded frame at the start of `buf`
+    fn wire_flags(buf: &BytesMut) -> u16 {
+        u16::from_be_bytes([buf[6], buf[7]])
+    }
+
+    #[test]
+    fn test_compression_after_peer_advertises() {
+        let mut client = VcpCodec::new().with_compression(64);
+        let mut server = VcpCodec::new().with_compression(64);
+        let large = Bytes::from(b"celrix ".repeat(2048));
+
+        // The client can't know yet that the server decodes compressed frames
+        let mut buf = BytesMut::new();
+        client.encode(Frame::new(OpCode::Set, 1, large.clone()), &mut buf).unwrap();
+        assert_eq!(wire_flags(&buf), FLAG_ACCEPTS_COMPRESSED);
+        let request = server.decode(&mut buf).unwrap().unwrap();
+        assert_eq!((request.header.flags, &request.payload), (0, &large));
+
+        server.encode(Frame::value(1, large.clone()), &mut buf).unwrap();
+        assert_eq!(wire_flags(&buf), FLAG_ACCEPTS_COMPRESSED | FLAG_COMPRESSED);
+        assert!(buf.len() < HEADER_SIZE + large.len() / 10);
+        let response = client.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(response.header.flags, 0);
+        assert_eq!(response.header.payload_len as usize, large.len());
+        assert_eq!(response.payload, large);
+
+        // Below the threshold payloads go out as they are
+        let small = Bytes::from_static(b"small value");
+        server.encode(Frame::value(2, small.clone()), &mut buf).unwrap();
+        assert_eq!(wire_flags(&buf), FLAG_ACCEPTS_COMPRESSED);
+        assert_eq!(client.decode(&mut buf).unwrap().unwrap().payload, small);
+    }
+
+    #[test]
+    fn test_no_compression_for_old_peers() {
+        let mut old_client = VcpCodec::new();
+        let mut server = VcpCodec::new().with_compression(64);
+        let large = Bytes::from(b"celrix ".repeat(2048));
+
+        let mut buf = BytesMut::new();
+        old_client.encode(Frame::ping(1), &mut buf).unwrap();
+        server.decode(&mut buf).unwrap().unwrap();
+
+        server.encode(Frame::value(1, large.clone()), &mut buf).unwrap();
+        assert_eq!(wire_flags(&buf) & FLAG_COMPRESSED, 0);
+        assert_eq!(old_client.decode(&mut buf).unwrap().unwrap().payload, large);
+    }
 }
@@ -4,8 +4,70 @@
 
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::io;
+use std::time::Duration;
 
-use super::frame::{Frame, OpCode};
+use super::command_table::{self, CommandSpec};
+use super::extended_commands::ExtendedCommand;
+use super::frame::{Frame, OpCode, FLAG_COPY_REPLACE, FLAG_FLUSH_VECTORS, FLAG_GET_EXPIRED, FLAG_GET_TTL, FLAG_RESTORE_REPLACE, FLAG_SET_GET, FLAG_SET_NX, FLAG_SET_PX, FLAG_SET_XX};
+
+/// SET modifiers, carried in the frame header flags
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SetOptions {
+    /// Only set if the key does not exist
+    pub nx: bool,
+    /// Only set if the key already exists
+    pub xx: bool,
+    /// Return the previous value instead of OK
+    pub get: bool,
+    /// TTL is in milliseconds rather than seconds
+    pub px: bool,
+}
+
+impl SetOptions {
+    /// Decode options from header flags
+    pub fn from_flags(flags: u16) -> io::Result<Self> {
+        let options = Self {
+            nx: flags & FLAG_SET_NX != 0,
+            xx: flags & FLAG_SET_XX != 0,
+            get: flags & FLAG_SET_GET != 0,
+            px: flags & FLAG_SET_PX != 0,
+        };
+        if options.nx && options.xx {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "NX and XX options are mutually exclusive",
+            ));
+        }
+        Ok(options)
+    }
+
+    /// Encode options as header flags
+    pub fn to_flags(&self) -> u16 {
+        let mut flags = 0;
+        if self.nx {
+            flags |= FLAG_SET_NX;
+        }
+        if self.xx {
+            flags |= FLAG_SET_XX;
+        }
+        if self.get {
+            flags |= FLAG_SET_GET;
+        }
+        if self.px {
+            flags |= FLAG_SET_PX;
+        }
+        flags
+    }
+
+    /// Interpret a SET TTL value in the unit these options select
+    pub fn ttl_duration(&self, ttl: Option<u64>) -> Option<Duration> {
+        if self.px {
+            ttl.map(Duration::from_millis)
+        } else {
+            ttl.map(Duration::from_secs)
+        }
+    }
+}
 
 /// Parsed command from a VCP frame
 #[derive(Debug, Clone)]
@@ -16,30 +78,173 @@ pub enum Command {
     /// Get value by key
     Get { key: Bytes },
 
-    /// Set key-value with optional TTL (seconds)
+    /// Get value and remaining TTL (GET with `FLAG_GET_TTL`)
+    GetWithTtl { key: Bytes },
+
+    /// Get value, replying `Expired` instead of nil if the key has expired
+    /// but is still stored (GET with `FLAG_GET_EXPIRED`)
+    GetOrExpired { key: Bytes },
+
+    /// Set key-value with optional TTL (seconds, or milliseconds with PX)
     Set {
         key: Bytes,
         value: Bytes,
         ttl: Option<u64>,
+        options: SetOptions,
     },
 
     /// Delete key
     Del { key: Bytes },
 
+    /// Delete key, freeing its value in the background
+    Unlink { key: Bytes },
+
     /// Check if key exists
     Exists { key: Bytes },
 
+    /// Get and delete key atomically
+    GetDel { key: Bytes },
+
+    /// Set key and return the previous value atomically
+    GetSet { key: Bytes, value: Bytes },
+
+    /// Atomically exchange the values and TTLs of two keys
+    SwapKey { key1: Bytes, key2: Bytes },
+
+    /// Set a key's TTL in milliseconds
+    PExpire { key: Bytes, ms: u64 },
+
+    /// Remaining TTL in seconds (-1 = no expiry, -2 = missing)
+    Ttl { key: Bytes },
+
+    /// Remaining TTL in milliseconds (-1 = no expiry, -2 = missing)
+    PTtl { key: Bytes },
+
+    /// Kind of value stored at a key ("none" if missing)
+    Type { key: Bytes },
+
+    /// Append to a key's value, creating it if missing; returns the new length
+    Append { key: Bytes, value: Bytes },
+
+    /// Length of a key's value (0 if missing)
+    Strlen { key: Bytes },
+
+    /// Delete every key matching a glob pattern; returns the count removed
+    DelPattern { pattern: Bytes },
+
+    /// Serialize a key's value and TTL into an opaque blob (Nil if missing)
+    Dump { key: Bytes },
+
+    /// Recreate a key from a DUMP blob. `ttl_ms` > 0 overrides the dumped
+    /// TTL; fails if the key exists unless `replace` is set.
+    Restore { key: Bytes, blob: Bytes, ttl_ms: u64, replace: bool },
+
+    /// Move a key's value and TTL to `dst`, overwriting it; fails if `src`
+    /// is missing
+    Rename { src: Bytes, dst: Bytes },
+
+    /// Copy a key's value and TTL to `dst`; returns 1 if copied, 0 if `src`
+    /// is missing or `dst` exists and `replace` isn't set
+    Copy { src: Bytes, dst: Bytes, replace: bool },
+
+    /// A random live key (Nil if the database is empty)
+    RandomKey,
+
+    /// Number of keys in the selected database
+    DbSize,
+
     /// Add vector embedding
     VAdd {
         key: Bytes,
         vector: Vec<f32>,
     },
 
+    /// Add many vector embeddings in one request
+    VAddBatch {
+        entries: Vec<(Bytes, Vec<f32>)>,
+    },
+
     /// Search for similar vectors
     VSearch {
         vector: Vec<f32>,
         k: usize,
     },
+
+    /// Extended command served by the worker pool
+    Extended(ExtendedCommand),
+
+    /// Switch the connection to another logical database
+    Select { db: u32 },
+
+    /// Remove all keys from the current database
+    FlushDb,
+
+    /// Remove all keys from every database, and optionally all vectors
+    FlushAll { vectors: bool },
+
+    /// Authenticate the connection (empty username = "default")
+    Auth { username: Bytes, password: Bytes },
+
+    /// Pick a protocol version from those offered (empty = the server's
+    /// choice) and exchange `CAP_*` capability flags
+    Hello { versions: Vec<u8>, capabilities: u32 },
+
+    /// Number of supported commands (COMMAND COUNT)
+    CommandCount,
+
+    /// Metadata for the named commands (COMMAND INFO)
+    CommandInfo { names: Vec<Bytes> },
+
+    /// Name, opcode, arity and permission of every command (COMMAND LIST)
+    CommandList,
+
+    /// Slot ranges with their owning nodes (CLUSTER SLOTS)
+    ClusterSlots,
+
+    /// Block until `num_replicas` acknowledge this connection's writes, or
+    /// `timeout_ms` passes (0 = no timeout)
+    Wait { num_replicas: u32, timeout_ms: u64 },
+
+    /// Push key events of the selected database to this connection.
+    /// Empty `events` means every event; `pattern` filters by key glob.
+    Subscribe { events: Vec<Bytes>, pattern: Option<Bytes> },
+
+    /// Latest `count` slowlog entries, newest first (SLOWLOG GET)
+    SlowlogGet { count: u32 },
+
+    /// Log out, leave subscriptions and return to database 0
+    Reset,
+
+    /// Let the next command use a slot this node is importing, as a
+    /// client does when following an ASK redirect
+    Asking,
+
+    /// Every open connection with its address, user, age and last command
+    /// (CLIENT LIST)
+    ClientList,
+
+    /// Close the connection with this id (CLIENT KILL)
+    ClientKill { id: u64 },
+
+    /// Block the worker running it for `ms` milliseconds (DEBUG SLEEP)
+    DebugSleep { ms: u64 },
+
+    /// Encoding, size and TTL of a key (DEBUG OBJECT)
+    DebugObject { key: Bytes },
+
+    /// Start queuing this connection's commands for EXEC
+    Multi,
+
+    /// Run the queued commands atomically, unless a watched key changed.
+    /// EXEC has no payload on the wire; the connection fills in its queued
+    /// commands and the `(db, key, version)` of each watched key.
+    Exec { commands: Vec<Command>, watched: Vec<(usize, Bytes, u64)> },
+
+    /// Drop the queued commands and watched keys
+    Discard,
+
+    /// Abort the next EXEC if any of these keys is written first
+    Watch { keys: Vec<Bytes> },
 }
 
 impl Command {
@@ -47,10 +252,35 @@ impl Command {
     pub fn from_frame(frame: &Frame) -> io::Result<Self> {
         match frame.header.opcode {
             OpCode::Ping => Ok(Command::Ping),
+            OpCode::Reset => Ok(Command::Reset),
+            OpCode::Asking => Ok(Command::Asking),
+            OpCode::RandomKey => Ok(Command::RandomKey),
+            OpCode::DbSize => Ok(Command::DbSize),
+            OpCode::Multi => Ok(Command::Multi),
+            OpCode::Exec => Ok(Command::Exec { commands: Vec::new(), watched: Vec::new() }),
+            OpCode::Discard => Ok(Command::Discard),
+
+            OpCode::Watch => {
+                let mut payload = frame.payload.clone();
+                let mut keys = Vec::new();
+                while payload.has_remaining() {
+                    keys.push(Self::read_length_prefixed_buf(&mut payload)?);
+                }
+                if keys.is_empty() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "WATCH needs at least one key"));
+                }
+                Ok(Command::Watch { keys })
+            }
 
             OpCode::Get => {
                 let key = Self::read_length_prefixed(&frame.payload)?;
-                Ok(Command::Get { key })
+                if frame.header.flags & FLAG_GET_TTL != 0 {
+                    Ok(Command::GetWithTtl { key })
+                } else if frame.header.flags & FLAG_GET_EXPIRED != 0 {
+                    Ok(Command::GetOrExpired { key })
+                } else {
+                    Ok(Command::Get { key })
+                }
             }
 
             OpCode::Set => {
@@ -67,7 +297,8 @@ impl Command {
                 } else {
                     None
                 };
-                Ok(Command::Set { key, value, ttl })
+                let options = SetOptions::from_flags(frame.header.flags)?;
+                Ok(Command::Set { key, value, ttl, options })
             }
 
             OpCode::Del => {
@@ -75,27 +306,133 @@ impl Command {
                 Ok(Command::Del { key })
             }
 
+            OpCode::Unlink => {
+                let key = Self::read_length_prefixed(&frame.payload)?;
+                Ok(Command::Unlink { key })
+            }
+
             OpCode::Exists => {
                 let key = Self::read_length_prefixed(&frame.payload)?;
                 Ok(Command::Exists { key })
             }
 
+            OpCode::GetDel => {
+                let key = Self::read_length_prefixed(&frame.payload)?;
+                Ok(Command::GetDel { key })
+            }
+
+            OpCode::GetSet => {
+                let mut payload = frame.payload.clone();
+                let key = Self::read_length_prefixed_buf(&mut payload)?;
+                let value = Self::read_length_prefixed_buf(&mut payload)?;
+                Ok(Command::GetSet { key, value })
+            }
+
+            OpCode::SwapKey => {
+                let mut payload = frame.payload.clone();
+                let key1 = Self::read_length_prefixed_buf(&mut payload)?;
+                let key2 = Self::read_length_prefixed_buf(&mut payload)?;
+                Ok(Command::SwapKey { key1, key2 })
+            }
+
+            OpCode::PExpire => {
+                let mut payload = frame.payload.clone();
+                let key = Self::read_length_prefixed_buf(&mut payload)?;
+                if payload.remaining() < 8 {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Missing TTL"));
+                }
+                Ok(Command::PExpire { key, ms: payload.get_u64() })
+            }
+
+            OpCode::Ttl => {
+                let key = Self::read_length_prefixed(&frame.payload)?;
+                Ok(Command::Ttl { key })
+            }
+
+            OpCode::PTtl => {
+                let key = Self::read_length_prefixed(&frame.payload)?;
+                Ok(Command::PTtl { key })
+            }
+
+            OpCode::Type => {
+                let key = Self::read_length_prefixed(&frame.payload)?;
+                Ok(Command::Type { key })
+            }
+
+            OpCode::Append => {
+                let mut payload = frame.payload.clone();
+                let key = Self::read_length_prefixed_buf(&mut payload)?;
+                let value = Self::read_length_prefixed_buf(&mut payload)?;
+                Ok(Command::Append { key, value })
+            }
+
+            OpCode::Strlen => {
+                let key = Self::read_length_prefixed(&frame.payload)?;
+                Ok(Command::Strlen { key })
+            }
+
+            OpCode::DelPattern => {
+                let pattern = Self::read_length_prefixed(&frame.payload)?;
+                Ok(Command::DelPattern { pattern })
+            }
+
+            OpCode::Dump => {
+                let key = Self::read_length_prefixed(&frame.payload)?;
+                Ok(Command::Dump { key })
+            }
+
+            OpCode::Restore => {
+                let mut payload = frame.payload.clone();
+                let key = Self::read_length_prefixed_buf(&mut payload)?;
+                let blob = Self::read_length_prefixed_buf(&mut payload)?;
+                let ttl_ms = if payload.remaining() >= 8 { payload.get_u64() } else { 0 };
+                let replace = frame.header.flags & FLAG_RESTORE_REPLACE != 0;
+                Ok(Command::Restore { key, blob, ttl_ms, replace })
+            }
+
+            OpCode::Rename => {
+                let mut payload = frame.payload.clone();
+                let src = Self::read_length_prefixed_buf(&mut payload)?;
+                let dst = Self::read_length_prefixed_buf(&mut payload)?;
+                Ok(Command::Rename { src, dst })
+            }
+
+            OpCode::Copy => {
+                let mut payload = frame.payload.clone();
+                let src = Self::read_length_prefixed_buf(&mut payload)?;
+                let dst = Self::read_length_prefixed_buf(&mut payload)?;
+                let replace = frame.header.flags & FLAG_COPY_REPLACE != 0;
+                Ok(Command::Copy { src, dst, replace })
+            }
+
             OpCode::VAdd => {
                 let mut payload = frame.payload.clone();
                 let key = Self::read_length_prefixed_buf(&mut payload)?;
+                let vector = Self::read_vector_buf(&mut payload)?;
+                Ok(Command::VAdd { key, vector })
+            }
+
+            OpCode::VAddBatch => {
+                // [count: u32] then [key_len][key][dim: u32][f32...] per entry
+                let mut payload = frame.payload.clone();
+                if payload.remaining() < 4 {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Missing batch count"));
+                }
                 let count = payload.get_u32() as usize;
-                let mut vector = Vec::with_capacity(count);
+                let mut entries = Vec::with_capacity(count.min(payload.remaining() / 8));
                 for _ in 0..count {
-                    if payload.remaining() < 4 {
-                        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Insufficient vector data"));
-                    }
-                    vector.push(payload.get_f32());
+                    let key = Self::read_length_prefixed_buf(&mut payload)?;
+                    let vector = Self::read_vector_buf(&mut payload)?;
+                    entries.push((key, vector));
                 }
-                Ok(Command::VAdd { key, vector })
+                Ok(Command::VAddBatch { entries })
             }
 
             OpCode::VSearch => {
                 let mut payload = frame.payload.clone();
+                if payload.remaining() < 4 {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Missing vector length"));
+                }
                 let count = payload.get_u32() as usize;
                 let mut vector = Vec::with_capacity(count);
                 for _ in 0..count {
@@ -112,6 +449,148 @@ impl Command {
                 Ok(Command::VSearch { vector, k })
             }
 
+            // Extended commands with a server-side executor
+            OpCode::MGet
+            | OpCode::MSet
+            | OpCode::MDel
+            | OpCode::Incr
+            | OpCode::Decr
+            | OpCode::IncrBy
+            | OpCode::DecrBy
+            | OpCode::Scan => Ok(Command::Extended(ExtendedCommand::from_frame(frame)?)),
+
+            OpCode::Select => {
+                let mut payload = frame.payload.clone();
+                if payload.remaining() < 4 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Missing database index",
+                    ));
+                }
+                Ok(Command::Select { db: payload.get_u32() })
+            }
+
+            OpCode::FlushDb => Ok(Command::FlushDb),
+
+            OpCode::FlushAll => Ok(Command::FlushAll {
+                vectors: frame.header.flags & FLAG_FLUSH_VECTORS != 0,
+            }),
+
+            OpCode::Auth => {
+                let mut payload = frame.payload.clone();
+                let username = Self::read_length_prefixed_buf(&mut payload)?;
+                let password = Self::read_length_prefixed_buf(&mut payload)?;
+                Ok(Command::Auth { username, password })
+            }
+
+            OpCode::Hello => {
+                let mut payload = frame.payload.clone();
+                let count = if payload.has_remaining() { payload.get_u8() as usize } else { 0 };
+                if payload.remaining() < count + 4 {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Incomplete HELLO payload"));
+                }
+                let versions = payload.split_to(count).to_vec();
+                Ok(Command::Hello { versions, capabilities: payload.get_u32() })
+            }
+
+            OpCode::Command => {
+                let mut payload = frame.payload.clone();
+                let sub = Self::read_length_prefixed_buf(&mut payload)?;
+                let mut args = Vec::new();
+                while payload.has_remaining() {
+                    args.push(Self::read_length_prefixed_buf(&mut payload)?);
+                }
+                if sub.eq_ignore_ascii_case(b"COUNT") {
+                    Ok(Command::CommandCount)
+                } else if sub.eq_ignore_ascii_case(b"INFO") {
+                    Ok(Command::CommandInfo { names: args })
+                } else if sub.eq_ignore_ascii_case(b"LIST") {
+                    Ok(Command::CommandList)
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Unknown COMMAND subcommand: {}", String::from_utf8_lossy(&sub)),
+                    ))
+                }
+            }
+
+            OpCode::Subscribe => {
+                let mut payload = frame.payload.clone();
+                let pattern = Self::read_length_prefixed_buf(&mut payload)?;
+                let mut events = Vec::new();
+                while payload.has_remaining() {
+                    events.push(Self::read_length_prefixed_buf(&mut payload)?);
+                }
+                let pattern = (!pattern.is_empty()).then_some(pattern);
+                Ok(Command::Subscribe { events, pattern })
+            }
+
+            OpCode::Slowlog => {
+                let mut payload = frame.payload.clone();
+                let sub = Self::read_length_prefixed_buf(&mut payload)?;
+                if !sub.eq_ignore_ascii_case(b"GET") {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Unknown SLOWLOG subcommand: {}", String::from_utf8_lossy(&sub)),
+                    ));
+                }
+                let count = if payload.remaining() >= 4 { payload.get_u32() } else { 10 };
+                Ok(Command::SlowlogGet { count })
+            }
+
+            OpCode::Debug => {
+                let mut payload = frame.payload.clone();
+                let sub = Self::read_length_prefixed_buf(&mut payload)?;
+                if sub.eq_ignore_ascii_case(b"SLEEP") && payload.remaining() >= 8 {
+                    Ok(Command::DebugSleep { ms: payload.get_u64() })
+                } else if sub.eq_ignore_ascii_case(b"OBJECT") {
+                    Ok(Command::DebugObject { key: Self::read_length_prefixed_buf(&mut payload)? })
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Unknown DEBUG subcommand: {}", String::from_utf8_lossy(&sub)),
+                    ))
+                }
+            }
+
+            OpCode::Client => {
+                let mut payload = frame.payload.clone();
+                let sub = Self::read_length_prefixed_buf(&mut payload)?;
+                if sub.eq_ignore_ascii_case(b"LIST") {
+                    Ok(Command::ClientList)
+                } else if sub.eq_ignore_ascii_case(b"KILL") && payload.remaining() >= 8 {
+                    Ok(Command::ClientKill { id: payload.get_u64() })
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Unknown CLIENT subcommand: {}", String::from_utf8_lossy(&sub)),
+                    ))
+                }
+            }
+
+            OpCode::Wait => {
+                let mut payload = frame.payload.clone();
+                if payload.remaining() < 12 {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "WAIT payload truncated"));
+                }
+                Ok(Command::Wait {
+                    num_replicas: payload.get_u32(),
+                    timeout_ms: payload.get_u64(),
+                })
+            }
+
+            OpCode::Cluster => {
+                let sub = Self::read_length_prefixed(&frame.payload)?;
+                if sub.eq_ignore_ascii_case(b"SLOTS") {
+                    Ok(Command::ClusterSlots)
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Unknown CLUSTER subcommand: {}", String::from_utf8_lossy(&sub)),
+                    ))
+                }
+            }
+
             _ => Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 format!("Unexpected opcode for command: {:?}", frame.header.opcode),
@@ -119,17 +598,163 @@ impl Command {
         }
     }
 
+    /// Command name as reported in metrics
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Ping => "PING",
+            Command::Reset => "RESET",
+            Command::Asking => "ASKING",
+            Command::Get { .. } | Command::GetWithTtl { .. } | Command::GetOrExpired { .. } => {
+                "GET"
+            }
+            Command::Set { .. } => "SET",
+            Command::Del { .. } => "DEL",
+            Command::Unlink { .. } => "UNLINK",
+            Command::Exists { .. } => "EXISTS",
+            Command::GetDel { .. } => "GETDEL",
+            Command::GetSet { .. } => "GETSET",
+            Command::SwapKey { .. } => "SWAPKEY",
+            Command::PExpire { .. } => "PEXPIRE",
+            Command::Ttl { .. } => "TTL",
+            Command::PTtl { .. } => "PTTL",
+            Command::Type { .. } => "TYPE",
+            Command::Append { .. } => "APPEND",
+            Command::Strlen { .. } => "STRLEN",
+            Command::DelPattern { .. } => "DELPATTERN",
+            Command::Dump { .. } => "DUMP",
+            Command::Restore { .. } => "RESTORE",
+            Command::Rename { .. } => "RENAME",
+            Command::Copy { .. } => "COPY",
+            Command::RandomKey => "RANDOMKEY",
+            Command::DbSize => "DBSIZE",
+            Command::VAdd { .. } => "VADD",
+            Command::VAddBatch { .. } => "VADDBATCH",
+            Command::VSearch { .. } => "VSEARCH",
+            Command::Extended(ext) => ext.name(),
+            Command::Select { .. } => "SELECT",
+            Command::FlushDb => "FLUSHDB",
+            Command::FlushAll { .. } => "FLUSHALL",
+            Command::Auth { .. } => "AUTH",
+            Command::Hello { .. } => "HELLO",
+            Command::CommandCount | Command::CommandInfo { .. } | Command::CommandList => "COMMAND",
+            Command::ClusterSlots => "CLUSTER",
+            Command::Wait { .. } => "WAIT",
+            Command::Subscribe { .. } => "SUBSCRIBE",
+            Command::SlowlogGet { .. } => "SLOWLOG",
+            Command::ClientList | Command::ClientKill { .. } => "CLIENT",
+            Command::DebugSleep { .. } | Command::DebugObject { .. } => "DEBUG",
+            Command::Multi => "MULTI",
+            Command::Exec { .. } => "EXEC",
+            Command::Discard => "DISCARD",
+            Command::Watch { .. } => "WATCH",
+        }
+    }
+
+    /// Keys this command operates on (used for cluster routing)
+    pub fn keys(&self) -> Vec<&Bytes> {
+        match self {
+            Command::Get { key }
+            | Command::GetWithTtl { key }
+            | Command::GetOrExpired { key }
+            | Command::Set { key, .. }
+            | Command::Del { key }
+            | Command::Unlink { key }
+            | Command::Exists { key }
+            | Command::GetDel { key }
+            | Command::GetSet { key, .. }
+            | Command::PExpire { key, .. }
+            | Command::Ttl { key }
+            | Command::PTtl { key }
+            | Command::Type { key }
+            | Command::Append { key, .. }
+            | Command::Strlen { key }
+            | Command::Dump { key }
+            | Command::Restore { key, .. }
+            | Command::DebugObject { key }
+            | Command::VAdd { key, .. } => vec![key],
+            Command::SwapKey { key1, key2 } => vec![key1, key2],
+            Command::Rename { src, dst } | Command::Copy { src, dst, .. } => vec![src, dst],
+            Command::VAddBatch { entries } => entries.iter().map(|(key, _)| key).collect(),
+            Command::Extended(ext) => ext.keys(),
+            Command::Watch { keys } => keys.iter().collect(),
+            Command::Ping
+            | Command::Reset
+            | Command::Asking
+            | Command::VSearch { .. }
+            | Command::DelPattern { .. }
+            | Command::Select { .. }
+            | Command::FlushDb
+            | Command::FlushAll { .. }
+            | Command::Auth { .. }
+            | Command::Hello { .. }
+            | Command::CommandCount
+            | Command::CommandInfo { .. }
+            | Command::CommandList
+            | Command::ClusterSlots
+            | Command::Wait { .. }
+            | Command::Subscribe { .. }
+            | Command::SlowlogGet { .. }
+            | Command::ClientList
+            | Command::ClientKill { .. }
+            | Command::DebugSleep { .. }
+            | Command::RandomKey
+            | Command::DbSize
+            | Command::Multi
+            | Command::Exec { .. }
+            | Command::Discard => Vec::new(),
+        }
+    }
+
+    /// Static metadata for this command
+    pub fn spec(&self) -> &'static CommandSpec {
+        command_table::lookup(self.name()).expect("command missing from COMMAND_TABLE")
+    }
+
+    /// Header flags for this command
+    pub fn flags(&self) -> u16 {
+        match self {
+            Command::Set { options, .. } => options.to_flags(),
+            Command::FlushAll { vectors: true } => FLAG_FLUSH_VECTORS,
+            Command::GetWithTtl { .. } => FLAG_GET_TTL,
+            Command::GetOrExpired { .. } => FLAG_GET_EXPIRED,
+            Command::Restore { replace: true, .. } => FLAG_RESTORE_REPLACE,
+            Command::Copy { replace: true, .. } => FLAG_COPY_REPLACE,
+            _ => 0,
+        }
+    }
+
+    /// Encode command into a complete frame, including header flags
+    pub fn to_frame(&self, request_id: u64) -> Frame {
+        let (opcode, payload) = self.encode();
+        Frame::new(opcode, request_id, payload).with_flags(self.flags())
+    }
+
     /// Encode command to frame payload bytes
     pub fn encode(&self) -> (OpCode, Bytes) {
         match self {
             Command::Ping => (OpCode::Ping, Bytes::new()),
+            Command::Reset => (OpCode::Reset, Bytes::new()),
+            Command::Asking => (OpCode::Asking, Bytes::new()),
+            Command::RandomKey => (OpCode::RandomKey, Bytes::new()),
+            Command::DbSize => (OpCode::DbSize, Bytes::new()),
+            Command::Multi => (OpCode::Multi, Bytes::new()),
+            Command::Exec { .. } => (OpCode::Exec, Bytes::new()),
+            Command::Discard => (OpCode::Discard, Bytes::new()),
 
-            Command::Get { key } => {
+            Command::Watch { keys } => {
+                let mut buf = BytesMut::new();
+                for key in keys {
+                    Self::write_length_prefixed_buf(&mut buf, key);
+                }
+                (OpCode::Watch, buf.freeze())
+            }
+
+            Command::Get { key } | Command::GetWithTtl { key } | Command::GetOrExpired { key } => {
                 let payload = Self::write_length_prefixed(key);
                 (OpCode::Get, payload)
             }
 
-            Command::Set { key, value, ttl } => {
+            Command::Set { key, value, ttl, .. } => {
                 let mut buf = BytesMut::new();
                 Self::write_length_prefixed_buf(&mut buf, key);
                 Self::write_length_prefixed_buf(&mut buf, value);
@@ -142,11 +767,77 @@ impl Command {
                 (OpCode::Del, payload)
             }
 
+            Command::Unlink { key } => {
+                let payload = Self::write_length_prefixed(key);
+                (OpCode::Unlink, payload)
+            }
+
             Command::Exists { key } => {
                 let payload = Self::write_length_prefixed(key);
                 (OpCode::Exists, payload)
             }
 
+            Command::GetDel { key } => {
+                let payload = Self::write_length_prefixed(key);
+                (OpCode::GetDel, payload)
+            }
+
+            Command::GetSet { key, value } => {
+                let mut buf = BytesMut::new();
+                Self::write_length_prefixed_buf(&mut buf, key);
+                Self::write_length_prefixed_buf(&mut buf, value);
+                (OpCode::GetSet, buf.freeze())
+            }
+
+            Command::SwapKey { key1, key2 } => {
+                let mut buf = BytesMut::new();
+                Self::write_length_prefixed_buf(&mut buf, key1);
+                Self::write_length_prefixed_buf(&mut buf, key2);
+                (OpCode::SwapKey, buf.freeze())
+            }
+
+            Command::PExpire { key, ms } => {
+                let mut buf = BytesMut::new();
+                Self::write_length_prefixed_buf(&mut buf, key);
+                buf.put_u64(*ms);
+                (OpCode::PExpire, buf.freeze())
+            }
+
+            Command::Ttl { key } => (OpCode::Ttl, Self::write_length_prefixed(key)),
+
+            Command::PTtl { key } => (OpCode::PTtl, Self::write_length_prefixed(key)),
+
+            Command::Type { key } => (OpCode::Type, Self::write_length_prefixed(key)),
+
+            Command::Append { key, value } => {
+                let mut buf = BytesMut::with_capacity(8 + key.len() + value.len());
+                Self::write_length_prefixed_buf(&mut buf, key);
+                Self::write_length_prefixed_buf(&mut buf, value);
+                (OpCode::Append, buf.freeze())
+            }
+
+            Command::Strlen { key } => (OpCode::Strlen, Self::write_length_prefixed(key)),
+
+            Command::DelPattern { pattern } => (OpCode::DelPattern, Self::write_length_prefixed(pattern)),
+
+            Command::Dump { key } => (OpCode::Dump, Self::write_length_prefixed(key)),
+
+            Command::Restore { key, blob, ttl_ms, .. } => {
+                let mut buf = BytesMut::with_capacity(16 + key.len() + blob.len());
+                Self::write_length_prefixed_buf(&mut buf, key);
+                Self::write_length_prefixed_buf(&mut buf, blob);
+                buf.put_u64(*ttl_ms);
+                (OpCode::Restore, buf.freeze())
+            }
+
+            Command::Rename { src, dst } | Command::Copy { src, dst, .. } => {
+                let mut buf = BytesMut::with_capacity(8 + src.len() + dst.len());
+                Self::write_length_prefixed_buf(&mut buf, src);
+                Self::write_length_prefixed_buf(&mut buf, dst);
+                let opcode = if matches!(self, Command::Rename { .. }) { OpCode::Rename } else { OpCode::Copy };
+                (opcode, buf.freeze())
+            }
+
             Command::VAdd { key, vector } => {
                 let mut buf = BytesMut::new();
                 Self::write_length_prefixed_buf(&mut buf, key);
@@ -157,6 +848,19 @@ impl Command {
                 (OpCode::VAdd, buf.freeze())
             }
 
+            Command::VAddBatch { entries } => {
+                let mut buf = BytesMut::new();
+                buf.put_u32(entries.len() as u32);
+                for (key, vector) in entries {
+                    Self::write_length_prefixed_buf(&mut buf, key);
+                    buf.put_u32(vector.len() as u32);
+                    for &f in vector {
+                        buf.put_f32(f);
+                    }
+                }
+                (OpCode::VAddBatch, buf.freeze())
+            }
+
             Command::VSearch { vector, k } => {
                 let mut buf = BytesMut::new();
                 buf.put_u32(vector.len() as u32);
@@ -166,6 +870,106 @@ impl Command {
                 buf.put_u32(*k as u32);
                 (OpCode::VSearch, buf.freeze())
             }
+
+            Command::Extended(ext) => ext.encode(),
+
+            Command::Select { db } => {
+                let mut buf = BytesMut::with_capacity(4);
+                buf.put_u32(*db);
+                (OpCode::Select, buf.freeze())
+            }
+
+            Command::FlushDb => (OpCode::FlushDb, Bytes::new()),
+
+            Command::FlushAll { .. } => (OpCode::FlushAll, Bytes::new()),
+
+            Command::Auth { username, password } => {
+                let mut buf = BytesMut::with_capacity(8 + username.len() + password.len());
+                Self::write_length_prefixed_buf(&mut buf, username);
+                Self::write_length_prefixed_buf(&mut buf, password);
+                (OpCode::Auth, buf.freeze())
+            }
+
+            Command::Hello { versions, capabilities } => {
+                let mut buf = BytesMut::with_capacity(5 + versions.len());
+                buf.put_u8(versions.len() as u8);
+                buf.put_slice(versions);
+                buf.put_u32(*capabilities);
+                (OpCode::Hello, buf.freeze())
+            }
+
+            Command::CommandCount => {
+                let payload = Self::write_length_prefixed(&Bytes::from_static(b"COUNT"));
+                (OpCode::Command, payload)
+            }
+
+            Command::CommandInfo { names } => {
+                let mut buf = BytesMut::new();
+                Self::write_length_prefixed_buf(&mut buf, &Bytes::from_static(b"INFO"));
+                for name in names {
+                    Self::write_length_prefixed_buf(&mut buf, name);
+                }
+                (OpCode::Command, buf.freeze())
+            }
+
+            Command::CommandList => {
+                let payload = Self::write_length_prefixed(&Bytes::from_static(b"LIST"));
+                (OpCode::Command, payload)
+            }
+
+            Command::SlowlogGet { count } => {
+                let mut buf = BytesMut::new();
+                Self::write_length_prefixed_buf(&mut buf, &Bytes::from_static(b"GET"));
+                buf.put_u32(*count);
+                (OpCode::Slowlog, buf.freeze())
+            }
+
+            Command::ClientList => {
+                let payload = Self::write_length_prefixed(&Bytes::from_static(b"LIST"));
+                (OpCode::Client, payload)
+            }
+
+            Command::ClientKill { id } => {
+                let mut buf = BytesMut::new();
+                Self::write_length_prefixed_buf(&mut buf, &Bytes::from_static(b"KILL"));
+                buf.put_u64(*id);
+                (OpCode::Client, buf.freeze())
+            }
+
+            Command::DebugSleep { ms } => {
+                let mut buf = BytesMut::new();
+                Self::write_length_prefixed_buf(&mut buf, &Bytes::from_static(b"SLEEP"));
+                buf.put_u64(*ms);
+                (OpCode::Debug, buf.freeze())
+            }
+
+            Command::DebugObject { key } => {
+                let mut buf = BytesMut::new();
+                Self::write_length_prefixed_buf(&mut buf, &Bytes::from_static(b"OBJECT"));
+                Self::write_length_prefixed_buf(&mut buf, key);
+                (OpCode::Debug, buf.freeze())
+            }
+
+            Command::ClusterSlots => {
+                let payload = Self::write_length_prefixed(&Bytes::from_static(b"SLOTS"));
+                (OpCode::Cluster, payload)
+            }
+
+            Command::Subscribe { events, pattern } => {
+                let mut buf = BytesMut::new();
+                Self::write_length_prefixed_buf(&mut buf, pattern.as_ref().unwrap_or(&Bytes::new()));
+                for event in events {
+                    Self::write_length_prefixed_buf(&mut buf, event);
+                }
+                (OpCode::Subscribe, buf.freeze())
+            }
+
+            Command::Wait { num_replicas, timeout_ms } => {
+                let mut buf = BytesMut::with_capacity(12);
+                buf.put_u32(*num_replicas);
+                buf.put_u64(*timeout_ms);
+                (OpCode::Wait, buf.freeze())
+            }
         }
     }
 
@@ -174,6 +978,18 @@ impl Command {
         Self::read_length_prefixed_buf(&mut buf)
     }
 
+    /// Read a [dim: u32][f32...] vector
+    fn read_vector_buf(buf: &mut Bytes) -> io::Result<Vec<f32>> {
+        if buf.remaining() < 4 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Missing vector dimension"));
+        }
+        let count = buf.get_u32() as usize;
+        if buf.remaining() / 4 < count {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Insufficient vector data"));
+        }
+        Ok((0..count).map(|_| buf.get_f32()).collect())
+    }
+
     fn read_length_prefixed_buf(buf: &mut Bytes) -> io::Result<Bytes> {
         if buf.remaining() < 4 {
             return Err(io::Error::new(
@@ -236,12 +1052,13 @@ mod tests {
             key: Bytes::from_static(b"key"),
             value: Bytes::from_static(b"value"),
             ttl: Some(3600),
+            options: SetOptions::default(),
         };
         let (opcode, payload) = cmd.encode();
         let frame = Frame::new(opcode, 1, payload);
         let parsed = Command::from_frame(&frame).unwrap();
 
-        if let Command::Set { key, value, ttl } = parsed {
+        if let Command::Set { key, value, ttl, .. } = parsed {
             assert_eq!(key.as_ref(), b"key");
             assert_eq!(value.as_ref(), b"value");
             assert_eq!(ttl, Some(3600));
@@ -249,4 +1066,27 @@ mod tests {
             panic!("Expected Set command");
         }
     }
+
+    #[test]
+    fn test_set_options_flags() {
+        let cmd = Command::Set {
+            key: Bytes::from_static(b"key"),
+            value: Bytes::from_static(b"value"),
+            ttl: Some(250),
+            options: SetOptions { nx: true, xx: false, get: true, px: true },
+        };
+        let frame = cmd.to_frame(1);
+        assert_eq!(frame.header.flags, FLAG_SET_NX | FLAG_SET_GET | FLAG_SET_PX);
+
+        match Command::from_frame(&frame).unwrap() {
+            Command::Set { options, ttl, .. } => {
+                assert!(options.nx && options.get && !options.xx);
+                assert_eq!(options.ttl_duration(ttl), Some(Duration::from_millis(250)));
+            }
+            _ => panic!("Expected Set command"),
+        }
+
+        let conflicting = frame.with_flags(FLAG_SET_NX | FLAG_SET_XX);
+        assert!(Command::from_frame(&conflicting).is_err());
+    }
 }
@@ -0,0 +1,755 @@
+//! Command Table
+//!
+//! Static metadata for every supported command: arity, flags, key
+//! positions, categories, and which worker pool executes it.
+
+use bytes::Bytes;
+
+use super::frame::OpCode;
+use crate::security::Permission;
+
+/// Worker pool a command is routed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pool {
+    /// Key-value worker pool (low-latency, pinned)
+    Kv,
+    /// Vector worker pool (compute-heavy)
+    Vector,
+}
+
+/// Static command metadata
+#[derive(Debug, Clone, Copy)]
+pub struct CommandSpec {
+    /// Command name (uppercase)
+    pub name: &'static str,
+    /// Opcode requests for this command are sent with
+    pub opcode: OpCode,
+    /// Argument count including the command name (negative = at least N)
+    pub arity: i32,
+    /// Command flags (e.g. write, readonly, fast)
+    pub flags: &'static [&'static str],
+    /// Position of the first key argument (0 = no keys)
+    pub first_key: i32,
+    /// Position of the last key argument (-1 = last argument)
+    pub last_key: i32,
+    /// Step between key arguments
+    pub step: i32,
+    /// ACL categories
+    pub categories: &'static [&'static str],
+    /// Worker pool the command runs on
+    pub pool: Pool,
+}
+
+impl CommandSpec {
+    /// Check if the command modifies data
+    pub fn is_write(&self) -> bool {
+        self.flags.contains(&"write")
+    }
+
+    /// Check if the command only reads data
+    pub fn is_readonly(&self) -> bool {
+        self.flags.contains(&"readonly")
+    }
+
+    /// Check if the command may be queued inside MULTI
+    pub fn allowed_in_multi(&self) -> bool {
+        !self.flags.contains(&"no-multi")
+    }
+
+    /// Check if the command belongs to an ACL category (without `@`)
+    pub fn in_category(&self, category: &str) -> bool {
+        self.categories.iter().any(|c| c.eq_ignore_ascii_case(category))
+    }
+
+    /// Permission the ACL layer checks for this command; None for
+    /// connection-level commands any user may run
+    pub fn permission(&self) -> Option<Permission> {
+        if Permission::is_admin_command(self.name) {
+            Some(Permission::Admin)
+        } else if self.in_category("cluster") || self.name == "CLUSTER" {
+            Some(Permission::Cluster)
+        } else if self.is_write() {
+            Some(Permission::Write)
+        } else if self.is_readonly() {
+            Some(Permission::Read)
+        } else {
+            None
+        }
+    }
+
+    /// COMMAND LIST record: `name opcode arity permission`
+    pub fn listing(&self) -> Bytes {
+        let permission = match self.permission() {
+            Some(Permission::Read) => "read",
+            Some(Permission::Write) => "write",
+            Some(Permission::Admin) => "admin",
+            Some(Permission::Cluster) => "cluster",
+            Some(Permission::All) | None => "connection",
+        };
+        Bytes::from(format!(
+            "{} 0x{:02x} {} {}",
+            self.name.to_lowercase(),
+            self.opcode as u8,
+            self.arity,
+            permission
+        ))
+    }
+
+    /// COMMAND INFO record: `name arity flags first_key last_key step`
+    pub fn info(&self) -> Bytes {
+        Bytes::from(format!(
+            "{} {} {} {} {} {}",
+            self.name.to_lowercase(),
+            self.arity,
+            self.flags.join(","),
+            self.first_key,
+            self.last_key,
+            self.step
+        ))
+    }
+}
+
+/// All supported commands
+pub static COMMAND_TABLE: &[CommandSpec] = &[
+    CommandSpec {
+        name: "PING",
+        opcode: OpCode::Ping,
+        arity: -1,
+        flags: &["fast", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["fast", "connection"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "GET",
+        opcode: OpCode::Get,
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["read", "string", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "SET",
+        opcode: OpCode::Set,
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["write", "string", "slow"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "DEL",
+        opcode: OpCode::Del,
+        arity: 2,
+        flags: &["write"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["write", "keyspace", "slow"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "UNLINK",
+        opcode: OpCode::Unlink,
+        arity: 2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["write", "keyspace", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "EXISTS",
+        opcode: OpCode::Exists,
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["read", "keyspace", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "GETDEL",
+        opcode: OpCode::GetDel,
+        arity: 2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["write", "string", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "GETSET",
+        opcode: OpCode::GetSet,
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["write", "string", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "APPEND",
+        opcode: OpCode::Append,
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["write", "string", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "STRLEN",
+        opcode: OpCode::Strlen,
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["read", "string", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "DELPATTERN",
+        opcode: OpCode::DelPattern,
+        arity: 2,
+        flags: &["write", "no-multi"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["keyspace", "write", "slow", "dangerous"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "DUMP",
+        opcode: OpCode::Dump,
+        arity: 2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["keyspace", "read", "slow"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "RESTORE",
+        opcode: OpCode::Restore,
+        arity: -4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["keyspace", "write", "slow", "dangerous"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "RENAME",
+        opcode: OpCode::Rename,
+        arity: 3,
+        flags: &["write"],
+        first_key: 1,
+        last_key: 2,
+        step: 1,
+        categories: &["keyspace", "write", "slow"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "COPY",
+        opcode: OpCode::Copy,
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 2,
+        step: 1,
+        categories: &["keyspace", "write", "slow"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "RANDOMKEY",
+        opcode: OpCode::RandomKey,
+        arity: 1,
+        flags: &["readonly", "random"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["keyspace", "read", "slow"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "DBSIZE",
+        opcode: OpCode::DbSize,
+        arity: 1,
+        flags: &["readonly", "fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["keyspace", "read", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "SWAPKEY",
+        opcode: OpCode::SwapKey,
+        arity: 3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 2,
+        step: 1,
+        categories: &["write", "keyspace", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "PEXPIRE",
+        opcode: OpCode::PExpire,
+        arity: 3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["write", "keyspace", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "TTL",
+        opcode: OpCode::Ttl,
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["read", "keyspace", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "PTTL",
+        opcode: OpCode::PTtl,
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["read", "keyspace", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "TYPE",
+        opcode: OpCode::Type,
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["keyspace", "read", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "MGET",
+        opcode: OpCode::MGet,
+        arity: -2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        categories: &["read", "string", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "MSET",
+        opcode: OpCode::MSet,
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: -1,
+        step: 2,
+        categories: &["write", "string", "slow"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "MDEL",
+        opcode: OpCode::MDel,
+        arity: -2,
+        flags: &["write"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        categories: &["write", "keyspace", "slow"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "INCR",
+        opcode: OpCode::Incr,
+        arity: 2,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["write", "string", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "DECR",
+        opcode: OpCode::Decr,
+        arity: 2,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["write", "string", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "INCRBY",
+        opcode: OpCode::IncrBy,
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["write", "string", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "DECRBY",
+        opcode: OpCode::DecrBy,
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["write", "string", "fast"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "SCAN",
+        opcode: OpCode::Scan,
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["read", "keyspace", "slow"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "VADD",
+        opcode: OpCode::VAdd,
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        categories: &["write", "vector", "slow"],
+        pool: Pool::Vector,
+    },
+    CommandSpec {
+        name: "VADDBATCH",
+        opcode: OpCode::VAddBatch,
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: -1,
+        step: 2,
+        categories: &["write", "vector", "slow"],
+        pool: Pool::Vector,
+    },
+    CommandSpec {
+        name: "VSEARCH",
+        opcode: OpCode::VSearch,
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["read", "vector", "slow"],
+        pool: Pool::Vector,
+    },
+    CommandSpec {
+        name: "SELECT",
+        opcode: OpCode::Select,
+        arity: 2,
+        flags: &["loading", "stale", "fast", "no-multi"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["fast", "connection"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "FLUSHDB",
+        opcode: OpCode::FlushDb,
+        arity: 1,
+        flags: &["write"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["keyspace", "write", "slow", "dangerous"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "HELLO",
+        opcode: OpCode::Hello,
+        arity: -1,
+        flags: &["noscript", "loading", "stale", "fast", "no-auth"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["fast", "connection"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "AUTH",
+        opcode: OpCode::Auth,
+        arity: -2,
+        flags: &["noscript", "loading", "stale", "fast", "no-auth"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["fast", "connection"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "FLUSHALL",
+        opcode: OpCode::FlushAll,
+        arity: 1,
+        flags: &["write"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["keyspace", "write", "slow", "dangerous"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "COMMAND",
+        opcode: OpCode::Command,
+        arity: -1,
+        flags: &["loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["slow", "connection"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "CLUSTER",
+        opcode: OpCode::Cluster,
+        arity: -2,
+        flags: &["loading", "stale", "no-multi"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["slow"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "WAIT",
+        opcode: OpCode::Wait,
+        arity: 3,
+        flags: &["noscript", "no-multi"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["slow", "connection"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "SUBSCRIBE",
+        opcode: OpCode::Subscribe,
+        arity: -1,
+        flags: &["pubsub", "noscript", "loading", "stale", "no-multi"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["pubsub", "slow"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "SLOWLOG",
+        opcode: OpCode::Slowlog,
+        arity: -2,
+        flags: &["admin", "random", "loading", "stale", "no-multi"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["admin", "slow", "dangerous"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "CLIENT",
+        opcode: OpCode::Client,
+        arity: -2,
+        flags: &["admin", "noscript", "loading", "stale", "no-multi"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["admin", "slow", "dangerous", "connection"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "RESET",
+        opcode: OpCode::Reset,
+        arity: 1,
+        flags: &["noscript", "loading", "stale", "fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["fast", "connection"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "ASKING",
+        opcode: OpCode::Asking,
+        arity: 1,
+        flags: &["fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["fast", "connection"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "MULTI",
+        opcode: OpCode::Multi,
+        arity: 1,
+        flags: &["noscript", "loading", "stale", "fast", "no-multi"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["fast", "transaction"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "EXEC",
+        opcode: OpCode::Exec,
+        arity: 1,
+        flags: &["noscript", "loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["slow", "transaction"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "DISCARD",
+        opcode: OpCode::Discard,
+        arity: 1,
+        flags: &["noscript", "loading", "stale", "fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["fast", "transaction"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "WATCH",
+        opcode: OpCode::Watch,
+        arity: -2,
+        flags: &["noscript", "loading", "stale", "fast", "no-multi"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        categories: &["fast", "transaction"],
+        pool: Pool::Kv,
+    },
+    CommandSpec {
+        name: "DEBUG",
+        opcode: OpCode::Debug,
+        arity: -2,
+        flags: &["admin", "noscript", "loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        categories: &["admin", "slow", "dangerous"],
+        pool: Pool::Kv,
+    },
+];
+
+/// Look up a command by name (case-insensitive)
+pub fn lookup(name: &str) -> Option<&'static CommandSpec> {
+    COMMAND_TABLE
+        .iter()
+        .find(|spec| spec.name.eq_ignore_ascii_case(name))
+}
+
+/// Build COMMAND INFO records; unknown names yield an empty entry
+pub fn command_info(names: &[Bytes]) -> Vec<Bytes> {
+    names
+        .iter()
+        .map(|name| {
+            std::str::from_utf8(name)
+                .ok()
+                .and_then(lookup)
+                .map(|spec| spec.info())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// COMMAND LIST records for every supported command
+pub fn command_list() -> Vec<Bytes> {
+    COMMAND_TABLE.iter().map(|spec| spec.listing()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        let set = lookup("set").unwrap();
+        assert!(set.is_write());
+        assert!(!set.is_readonly());
+        assert_eq!((set.first_key, set.last_key, set.step), (1, 1, 1));
+        assert_eq!(lookup("VSEARCH").unwrap().pool, Pool::Vector);
+        assert!(lookup("NOSUCHCMD").is_none());
+    }
+
+    #[test]
+    fn test_command_info() {
+        let info = command_info(&[Bytes::from_static(b"SET"), Bytes::from_static(b"nope")]);
+        assert_eq!(info[0].as_ref(), b"set -3 write,denyoom 1 1 1");
+        assert!(info[1].is_empty());
+    }
+
+    #[test]
+    fn test_command_list_covers_request_opcodes() {
+        use crate::protocol::{Command, Frame};
+
+        let listing: Vec<String> = command_list()
+            .iter()
+            .map(|r| String::from_utf8(r.to_vec()).unwrap())
+            .collect();
+        for opcode in (0..=u8::MAX).filter_map(OpCode::from_u8) {
+            // Responses and internal streams aren't parsed as commands
+            let frame = Frame::new(opcode, 1, Bytes::new());
+            if let Err(e) = Command::from_frame(&frame) {
+                let msg = e.to_string();
+                if msg.starts_with("Unexpected opcode") || msg.starts_with("Unknown extended opcode") {
+                    continue;
+                }
+            }
+            let code = format!("0x{:02x}", opcode as u8);
+            let record = listing
+                .iter()
+                .find(|r| r.split(' ').nth(1) == Some(code.as_str()))
+                .unwrap_or_else(|| panic!("{:?} missing from COMMAND LIST", opcode));
+            let permission = record.rsplit(' ').next().unwrap();
+            assert!(["read", "write", "admin", "cluster", "connection"].contains(&permission), "{}", record);
+        }
+
+        assert!(listing.contains(&"get 0x03 2 read".to_string()), "{:?}", listing);
+        assert!(listing.contains(&"set 0x04 -3 write".to_string()));
+        assert!(listing.contains(&"flushall 0x43 1 admin".to_string()));
+        assert!(listing.contains(&"cluster 0x45 -2 cluster".to_string()));
+    }
+}
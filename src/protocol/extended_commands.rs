@@ -77,19 +77,25 @@ impl ExtendedCommand {
             OpCode::IncrBy => {
                 let mut payload = frame.payload.clone();
                 let key = Self::read_length_prefixed(&mut payload)?;
-                let delta = payload.get_i64();
+                let delta = Self::read_delta(&mut payload)?;
                 Ok(ExtendedCommand::IncrBy { key, delta })
             }
 
             OpCode::DecrBy => {
                 let mut payload = frame.payload.clone();
                 let key = Self::read_length_prefixed(&mut payload)?;
-                let delta = payload.get_i64();
+                let delta = Self::read_delta(&mut payload)?;
                 Ok(ExtendedCommand::DecrBy { key, delta })
             }
 
             OpCode::Scan => {
                 let mut payload = frame.payload.clone();
+                if payload.remaining() < 12 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Not enough data for cursor and count",
+                    ));
+                }
                 let cursor = payload.get_u64();
                 let count = payload.get_u32();
                 let pattern = if payload.remaining() > 0 {
@@ -120,6 +126,34 @@ impl ExtendedCommand {
         }
     }
 
+    /// Keys this command operates on
+    pub fn keys(&self) -> Vec<&Bytes> {
+        match self {
+            ExtendedCommand::MGet { keys } | ExtendedCommand::MDel { keys } => keys.iter().collect(),
+            ExtendedCommand::MSet { pairs } => pairs.iter().map(|(k, _)| k).collect(),
+            ExtendedCommand::Incr { key }
+            | ExtendedCommand::Decr { key }
+            | ExtendedCommand::IncrBy { key, .. }
+            | ExtendedCommand::DecrBy { key, .. } => vec![key],
+            ExtendedCommand::Scan { .. } | ExtendedCommand::Keys { .. } => Vec::new(),
+        }
+    }
+
+    /// Command name as reported in metrics
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExtendedCommand::MGet { .. } => "MGET",
+            ExtendedCommand::MSet { .. } => "MSET",
+            ExtendedCommand::MDel { .. } => "MDEL",
+            ExtendedCommand::Incr { .. } => "INCR",
+            ExtendedCommand::Decr { .. } => "DECR",
+            ExtendedCommand::IncrBy { .. } => "INCRBY",
+            ExtendedCommand::DecrBy { .. } => "DECRBY",
+            ExtendedCommand::Scan { .. } => "SCAN",
+            ExtendedCommand::Keys { .. } => "KEYS",
+        }
+    }
+
     /// Encode extended command to frame payload
     pub fn encode(&self) -> (OpCode, Bytes) {
         match self {
@@ -209,6 +243,16 @@ impl ExtendedCommand {
         Ok(buf.copy_to_bytes(len))
     }
 
+    fn read_delta(buf: &mut Bytes) -> io::Result<i64> {
+        if buf.remaining() < 8 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Not enough data for delta",
+            ));
+        }
+        Ok(buf.get_i64())
+    }
+
     fn read_key_list(data: &Bytes) -> io::Result<Vec<Bytes>> {
         let mut buf = data.clone();
         if buf.remaining() < 4 {
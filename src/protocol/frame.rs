@@ -11,9 +11,53 @@ pub const MAGIC: [u8; 4] = [0x43, 0x45, 0x4C, 0x58];
 /// Protocol version
 pub const VERSION: u8 = 1;
 
+/// Protocol versions this build speaks, offered and accepted by HELLO
+pub const SUPPORTED_VERSIONS: &[u8] = &[VERSION];
+
+/// HELLO capability: decodes `FLAG_COMPRESSED` payloads
+pub const CAP_COMPRESSION: u32 = 1 << 0;
+
 /// Fixed header size in bytes
 pub const HEADER_SIZE: usize = 22;
 
+/// SET flag: only set if the key does not exist
+pub const FLAG_SET_NX: u16 = 1 << 0;
+
+/// SET flag: only set if the key already exists
+pub const FLAG_SET_XX: u16 = 1 << 1;
+
+/// SET flag: return the previous value
+pub const FLAG_SET_GET: u16 = 1 << 2;
+
+/// SET flag: TTL is in milliseconds rather than seconds
+pub const FLAG_SET_PX: u16 = 1 << 3;
+
+/// GET flag: also return the remaining TTL
+pub const FLAG_GET_TTL: u16 = 1 << 0;
+
+/// GET flag: reply `Expired` rather than nil for a key that has expired
+/// but not been removed yet
+pub const FLAG_GET_EXPIRED: u16 = 1 << 1;
+
+/// FLUSHALL flag: also clear the vector store
+pub const FLAG_FLUSH_VECTORS: u16 = 1 << 0;
+
+/// RESTORE flag: overwrite the key if it already exists
+pub const FLAG_RESTORE_REPLACE: u16 = 1 << 0;
+
+/// COPY flag: overwrite the destination if it already exists
+pub const FLAG_COPY_REPLACE: u16 = 1 << 0;
+
+// The flags above are per command and use the low bits; the high bits are
+// frame-level and handled by `VcpCodec`, so commands never see them.
+
+/// Frame flag: the payload is an LZ4 block prefixed with its uncompressed
+/// length (u32)
+pub const FLAG_COMPRESSED: u16 = 1 << 15;
+
+/// Frame flag: the sender can decode `FLAG_COMPRESSED` payloads
+pub const FLAG_ACCEPTS_COMPRESSED: u16 = 1 << 14;
+
 /// Operation codes for VCP commands
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -48,10 +92,101 @@ pub enum OpCode {
     Nil = 0x13,
     Integer = 0x14,
     Array = 0x15,
+    Moved = 0x16,
+    Ask = 0x17,
+    Busy = 0x18,
+    Values = 0x19,
+    ValueTtl = 0x1A,
+    BatchAdded = 0x1B,
+    /// Pushed keyspace event (not a reply; request ID 0)
+    Event = 0x1C,
+    /// Command accepted into an open MULTI block
+    Queued = 0x1D,
+    /// One reply per command run by EXEC
+    Replies = 0x1E,
+    /// Key exists but has expired (GET with `FLAG_GET_EXPIRED`)
+    Expired = 0x1F,
 
     // Vector operations (Phase 4/9)
     VAdd = 0x20,
     VSearch = 0x21,
+    VAddBatch = 0x22,
+
+    // Atomic key operations
+    GetDel = 0x30,
+    GetSet = 0x31,
+    SwapKey = 0x32,
+
+    // Expiration
+    PExpire = 0x33,
+    Ttl = 0x34,
+    PTtl = 0x35,
+
+    // Key introspection
+    Type = 0x36,
+
+    // String operations
+    Append = 0x37,
+    Strlen = 0x38,
+    DelPattern = 0x39,
+    Dump = 0x3A,
+    Restore = 0x3B,
+    Rename = 0x3C,
+    Copy = 0x3D,
+    RandomKey = 0x3E,
+    DbSize = 0x3F,
+
+    // Server introspection
+    Command = 0x40,
+
+    // Database selection and administration
+    Select = 0x41,
+    FlushDb = 0x42,
+    FlushAll = 0x43,
+
+    // Connection
+    Auth = 0x44,
+
+    // Cluster introspection (subcommand in payload)
+    Cluster = 0x45,
+
+    // Block until writes reach replicas
+    Wait = 0x46,
+
+    // Push keyspace events to this connection
+    Subscribe = 0x47,
+
+    // Slow command log (subcommand in payload)
+    Slowlog = 0x48,
+
+    // Test hooks and internals (subcommand in payload)
+    Debug = 0x49,
+
+    // Return the connection to its initial state
+    Reset = 0x4A,
+
+    // Transactions
+    Multi = 0x4B,
+    Exec = 0x4C,
+    Discard = 0x4D,
+    Watch = 0x4E,
+
+    // Negotiate protocol version and capabilities
+    Hello = 0x4F,
+
+    // Replication stream between leader and followers
+    ReplSync = 0x50,
+    ReplEntries = 0x51,
+    ReplAck = 0x52,
+
+    // Connection administration (subcommand in payload)
+    Client = 0x53,
+
+    // Let the next command use a slot this node is importing
+    Asking = 0x54,
+
+    // More key operations
+    Unlink = 0x60,
 }
 
 impl OpCode {
@@ -78,8 +213,57 @@ impl OpCode {
             0x13 => Some(OpCode::Nil),
             0x14 => Some(OpCode::Integer),
             0x15 => Some(OpCode::Array),
+            0x16 => Some(OpCode::Moved),
+            0x17 => Some(OpCode::Ask),
+            0x18 => Some(OpCode::Busy),
+            0x19 => Some(OpCode::Values),
+            0x1A => Some(OpCode::ValueTtl),
+            0x1B => Some(OpCode::BatchAdded),
+            0x1C => Some(OpCode::Event),
+            0x1D => Some(OpCode::Queued),
+            0x1E => Some(OpCode::Replies),
+            0x1F => Some(OpCode::Expired),
             0x20 => Some(OpCode::VAdd),
             0x21 => Some(OpCode::VSearch),
+            0x22 => Some(OpCode::VAddBatch),
+            0x30 => Some(OpCode::GetDel),
+            0x31 => Some(OpCode::GetSet),
+            0x32 => Some(OpCode::SwapKey),
+            0x33 => Some(OpCode::PExpire),
+            0x34 => Some(OpCode::Ttl),
+            0x35 => Some(OpCode::PTtl),
+            0x36 => Some(OpCode::Type),
+            0x37 => Some(OpCode::Append),
+            0x38 => Some(OpCode::Strlen),
+            0x39 => Some(OpCode::DelPattern),
+            0x3A => Some(OpCode::Dump),
+            0x3B => Some(OpCode::Restore),
+            0x3C => Some(OpCode::Rename),
+            0x3D => Some(OpCode::Copy),
+            0x3E => Some(OpCode::RandomKey),
+            0x3F => Some(OpCode::DbSize),
+            0x40 => Some(OpCode::Command),
+            0x41 => Some(OpCode::Select),
+            0x42 => Some(OpCode::FlushDb),
+            0x43 => Some(OpCode::FlushAll),
+            0x44 => Some(OpCode::Auth),
+            0x45 => Some(OpCode::Cluster),
+            0x46 => Some(OpCode::Wait),
+            0x47 => Some(OpCode::Subscribe),
+            0x48 => Some(OpCode::Slowlog),
+            0x49 => Some(OpCode::Debug),
+            0x4A => Some(OpCode::Reset),
+            0x4B => Some(OpCode::Multi),
+            0x4C => Some(OpCode::Exec),
+            0x4D => Some(OpCode::Discard),
+            0x4E => Some(OpCode::Watch),
+            0x4F => Some(OpCode::Hello),
+            0x50 => Some(OpCode::ReplSync),
+            0x51 => Some(OpCode::ReplEntries),
+            0x52 => Some(OpCode::ReplAck),
+            0x53 => Some(OpCode::Client),
+            0x54 => Some(OpCode::Asking),
+            0x60 => Some(OpCode::Unlink),
             _ => None,
         }
     }
@@ -120,6 +304,11 @@ impl FrameHeader {
         self
     }
 
+    pub fn with_flags(mut self, flags: u16) -> Self {
+        self.flags = flags;
+        self
+    }
+
     pub fn encode(&self, buf: &mut BytesMut) {
         buf.put_slice(&MAGIC);
         buf.put_u8(self.version);
@@ -174,6 +363,11 @@ impl Frame {
         Self { header, payload }
     }
 
+    pub fn with_flags(mut self, flags: u16) -> Self {
+        self.header.flags = flags;
+        self
+    }
+
     pub fn ping(request_id: u64) -> Self {
         Self::new(OpCode::Ping, request_id, Bytes::new())
     }
@@ -0,0 +1,187 @@
+//! LZ4 Block Compression
+//!
+//! Minimal LZ4 block format encoder and decoder for compressed VCP
+//! payloads. The encoder is a single-pass greedy matcher; the decoder
+//! accepts any valid LZ4 block.
+
+use std::io;
+
+const MIN_MATCH: usize = 4;
+const HASH_LOG: u32 = 12;
+/// A match can't start within this many bytes of the end
+const MF_LIMIT: usize = 12;
+/// The last bytes of a block are always literals
+const LAST_LITERALS: usize = 5;
+const MAX_OFFSET: usize = u16::MAX as usize;
+
+#[inline]
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+}
+
+#[inline]
+fn hash(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2_654_435_761) >> (32 - HASH_LOG)) as usize
+}
+
+fn write_length(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        out.push(255);
+        len -= 255;
+    }
+    out.push(len as u8);
+}
+
+/// Emit `literals`, then a match of `match_len` bytes `offset` back
+/// (or nothing after the literals when `offset` is None)
+fn write_sequence(out: &mut Vec<u8>, literals: &[u8], matched: Option<(u16, usize)>) {
+    let match_code = matched.map_or(0, |(_, len)| len - MIN_MATCH);
+    out.push(((literals.len().min(15) as u8) << 4) | match_code.min(15) as u8);
+    if literals.len() >= 15 {
+        write_length(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+    if let Some((offset, _)) = matched {
+        out.extend_from_slice(&offset.to_le_bytes());
+        if match_code >= 15 {
+            write_length(out, match_code - 15);
+        }
+    }
+}
+
+/// Compress `input` as one LZ4 block
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() / 2 + 16);
+    let mut table = vec![0usize; 1 << HASH_LOG];
+    let mut anchor = 0;
+    let mut pos = 0;
+
+    if input.len() > MF_LIMIT {
+        let start_limit = input.len() - MF_LIMIT;
+        let match_limit = input.len() - LAST_LITERALS;
+        while pos < start_limit {
+            let sequence = read_u32(input, pos);
+            let slot = &mut table[hash(sequence)];
+            let candidate = std::mem::replace(slot, pos);
+            if candidate < pos && pos - candidate <= MAX_OFFSET && read_u32(input, candidate) == sequence {
+                let mut len = MIN_MATCH;
+                while pos + len < match_limit && input[candidate + len] == input[pos + len] {
+                    len += 1;
+                }
+                write_sequence(&mut out, &input[anchor..pos], Some(((pos - candidate) as u16, len)));
+                pos += len;
+                anchor = pos;
+            } else {
+                pos += 1;
+            }
+        }
+    }
+
+    write_sequence(&mut out, &input[anchor..], None);
+    out
+}
+
+fn corrupt() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "Corrupt LZ4 block")
+}
+
+fn read_length(input: &[u8], pos: &mut usize) -> io::Result<usize> {
+    let mut len = 0usize;
+    loop {
+        let byte = *input.get(*pos).ok_or_else(corrupt)?;
+        *pos += 1;
+        len += byte as usize;
+        if byte != 255 {
+            return Ok(len);
+        }
+    }
+}
+
+/// Decompress an LZ4 block that must expand to exactly `len` bytes
+pub fn decompress(input: &[u8], len: usize) -> io::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(len);
+    let mut pos = 0;
+
+    loop {
+        let token = *input.get(pos).ok_or_else(corrupt)?;
+        pos += 1;
+
+        let mut literal_len = (token >> 4) as usize;
+        if literal_len == 15 {
+            literal_len += read_length(input, &mut pos)?;
+        }
+        let literals = pos
+            .checked_add(literal_len)
+            .and_then(|end| input.get(pos..end))
+            .filter(|_| out.len() + literal_len <= len)
+            .ok_or_else(corrupt)?;
+        out.extend_from_slice(literals);
+        pos += literal_len;
+
+        // The final sequence has literals only
+        if pos == input.len() {
+            break;
+        }
+
+        let offset = input.get(pos..pos + 2).ok_or_else(corrupt)?;
+        let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+        pos += 2;
+        if offset == 0 || offset > out.len() {
+            return Err(corrupt());
+        }
+        let mut match_len = (token & 0x0F) as usize;
+        if match_len == 15 {
+            match_len += read_length(input, &mut pos)?;
+        }
+        match_len += MIN_MATCH;
+        if out.len() + match_len > len {
+            return Err(corrupt());
+        }
+
+        // Byte by byte: the match may overlap the bytes it produces
+        let start = out.len() - offset;
+        for i in start..start + match_len {
+            out.push(out[i]);
+        }
+    }
+
+    if out.len() != len {
+        return Err(corrupt());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let text = b"the quick brown fox jumps over the lazy dog. ".repeat(200);
+        let mut noise = Vec::new();
+        let mut x = 0x2545F491u32;
+        for _ in 0..5000 {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            noise.push(x as u8);
+        }
+
+        for input in [&b""[..], b"abc", b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", &text, &noise] {
+            let compressed = compress(input);
+            assert_eq!(decompress(&compressed, input.len()).unwrap(), input);
+        }
+        assert!(compress(&text).len() < text.len() / 10);
+    }
+
+    #[test]
+    fn test_rejects_corrupt_block() {
+        let input = b"abcdabcdabcdabcdabcdabcdabcdabcd".repeat(4);
+        let compressed = compress(&input);
+
+        assert!(decompress(&compressed, input.len() - 1).is_err());
+        assert!(decompress(&compressed[..compressed.len() - 3], input.len()).is_err());
+        // Offset reaching before the start of the output
+        assert!(decompress(&[0x00, 0x01, 0x00], 4).is_err());
+    }
+}
@@ -5,12 +5,18 @@
 
 mod codec;
 mod command;
+mod command_table;
 mod extended_commands;
 mod frame;
+mod lz4;
 mod response;
 
-pub use codec::VcpCodec;
-pub use command::Command;
+pub use codec::{VcpCodec, DEFAULT_MAX_FRAME_SIZE};
+pub use command::{Command, SetOptions};
+pub use command_table::{command_info, command_list, lookup, CommandSpec, Pool, COMMAND_TABLE};
 pub use extended_commands::ExtendedCommand;
-pub use frame::{Frame, FrameHeader, OpCode, HEADER_SIZE, MAGIC};
-pub use response::Response;
+pub use frame::{
+    Frame, FrameHeader, OpCode, CAP_COMPRESSION, FLAG_ACCEPTS_COMPRESSED, FLAG_COMPRESSED, FLAG_FLUSH_VECTORS, FLAG_GET_EXPIRED, FLAG_GET_TTL, FLAG_RESTORE_REPLACE, FLAG_SET_GET, FLAG_SET_NX, FLAG_SET_PX, FLAG_SET_XX, HEADER_SIZE, MAGIC,
+    SUPPORTED_VERSIONS, VERSION,
+};
+pub use response::{ErrorKind, Response};
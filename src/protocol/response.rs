@@ -2,9 +2,10 @@
 //!
 //! Response variants for command execution results.
 
-use bytes::Bytes;
+use bytes::{BufMut, Bytes, BytesMut};
 
 use super::frame::{Frame, OpCode};
+use crate::server::BufferPool;
 
 /// Response to a command
 #[derive(Debug, Clone)]
@@ -29,29 +30,217 @@ pub enum Response {
 
     /// Array response (list of byte arrays)
     Array(Vec<Bytes>),
+
+    /// Slot is owned by another node; client should update its slot map
+    Moved { slot: u16, addr: String },
+
+    /// Slot is migrating; retry this request only on the given node
+    Ask { slot: u16, addr: String },
+
+    /// Server is overloaded; retry after the hinted delay
+    Busy { retry_after_ms: u32 },
+
+    /// Per-key values where missing keys are nil (MGET)
+    Values(Vec<Option<Bytes>>),
+
+    /// Value with its remaining TTL in milliseconds (-1 = no expiry)
+    ValueWithTtl { value: Bytes, ttl_ms: i64 },
+
+    /// Outcome of a batch insert: how many entries were added and the
+    /// indices of those rejected
+    BatchAdded { added: u32, failed: Vec<u32> },
+
+    /// Keyspace event pushed to a subscribed connection
+    Event { event: Bytes, key: Bytes },
+
+    /// Command queued by an open MULTI, to run on EXEC
+    Queued,
+
+    /// Replies to the commands run by EXEC, in queue order
+    Replies(Vec<Response>),
+
+    /// Key is still stored but has expired, for GET with
+    /// `FLAG_GET_EXPIRED` (a plain GET replies nil)
+    Expired,
+}
+
+/// Item length marking a nil entry in a `Values` payload
+const NIL_ITEM_LEN: u32 = u32::MAX;
+
+/// What went wrong, read from the leading code of an `Error` message, so
+/// clients can tell a bad request from a failure worth retrying
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The frame couldn't be parsed (PROTOCOL, NOPROTO); resending it won't help
+    Protocol,
+    /// The command named something that doesn't exist (NOTFOUND)
+    NotFound,
+    /// The connection isn't allowed to run it (NOPERM, NOAUTH, WRONGPASS)
+    Permission,
+    /// The server can't serve it right now but may shortly (BUSY,
+    /// RATELIMITED, NOQUORUM, STALE)
+    Retryable,
+    /// The server failed in a way the request didn't cause (INTERNAL)
+    Internal,
+    /// The command ran and was rejected, e.g. bad arguments (ERR and the rest)
+    Command,
+}
+
+impl ErrorKind {
+    /// Classify an error message by its leading code
+    pub fn of(message: &str) -> Self {
+        match message.split(' ').next().unwrap_or_default() {
+            "PROTOCOL" | "NOPROTO" => ErrorKind::Protocol,
+            "NOTFOUND" => ErrorKind::NotFound,
+            "NOPERM" | "NOAUTH" | "WRONGPASS" => ErrorKind::Permission,
+            "BUSY" | "RATELIMITED" | "NOQUORUM" | "STALE" => ErrorKind::Retryable,
+            "INTERNAL" => ErrorKind::Internal,
+            _ => ErrorKind::Command,
+        }
+    }
 }
 
 impl Response {
+    /// Reply for a request frame that couldn't be parsed into a command
+    pub fn protocol_error(error: impl std::fmt::Display) -> Self {
+        Response::Error(format!("PROTOCOL {}", error))
+    }
+
+    /// Reply for a server-side failure the request didn't cause
+    pub fn internal_error(error: impl std::fmt::Display) -> Self {
+        Response::Error(format!("INTERNAL {}", error))
+    }
+
+    /// Category of an error reply; None for any other response
+    pub fn error_kind(&self) -> Option<ErrorKind> {
+        match self {
+            Response::Error(message) => Some(ErrorKind::of(message)),
+            Response::Busy { .. } => Some(ErrorKind::Retryable),
+            _ => None,
+        }
+    }
+
     /// Convert response to a VCP frame
     pub fn to_frame(&self, request_id: u64) -> Frame {
-        match self {
-            Response::Ok => Frame::ok(request_id),
-            Response::Nil => Frame::nil(request_id),
-            Response::Value(data) => Frame::value(request_id, data.clone()),
-            Response::Integer(n) => Frame::integer(request_id, *n),
-            Response::Error(msg) => Frame::error(request_id, msg),
-            Response::Pong => Frame::pong(request_id),
+        self.build_frame(request_id, BytesMut::new)
+    }
+
+    /// Convert response to a VCP frame, building the payload in a pooled buffer
+    pub fn to_frame_pooled(&self, request_id: u64, pool: &BufferPool) -> Frame {
+        self.build_frame(request_id, || pool.get())
+    }
+
+    fn build_frame(&self, request_id: u64, alloc: impl FnOnce() -> BytesMut) -> Frame {
+        let (opcode, buf) = match self {
+            Response::Ok => return Frame::ok(request_id),
+            Response::Nil => return Frame::nil(request_id),
+            Response::Value(data) => return Frame::value(request_id, data.clone()),
+            Response::Pong => return Frame::pong(request_id),
+            Response::Queued => return Frame::new(OpCode::Queued, request_id, Bytes::new()),
+            Response::Expired => return Frame::new(OpCode::Expired, request_id, Bytes::new()),
+            Response::Integer(n) => {
+                let mut buf = alloc();
+                buf.put_i64(*n);
+                (OpCode::Integer, buf)
+            }
+            Response::Error(msg) => {
+                let mut buf = alloc();
+                buf.put_slice(msg.as_bytes());
+                (OpCode::Error, buf)
+            }
             Response::Array(items) => {
-                use bytes::{BufMut, BytesMut};
-                let mut buf = BytesMut::new();
+                let mut buf = alloc();
                 buf.put_u32(items.len() as u32);
                 for item in items {
                     buf.put_u32(item.len() as u32);
                     buf.put_slice(item);
                 }
-                Frame::new(OpCode::Array, request_id, buf.freeze())
+                (OpCode::Array, buf)
+            }
+            Response::Moved { slot, addr } => {
+                let mut buf = alloc();
+                Self::encode_redirect(&mut buf, *slot, addr);
+                (OpCode::Moved, buf)
+            }
+            Response::Ask { slot, addr } => {
+                let mut buf = alloc();
+                Self::encode_redirect(&mut buf, *slot, addr);
+                (OpCode::Ask, buf)
+            }
+            Response::Busy { retry_after_ms } => {
+                let mut buf = alloc();
+                buf.put_u32(*retry_after_ms);
+                (OpCode::Busy, buf)
+            }
+            Response::Values(items) => {
+                let mut buf = alloc();
+                buf.put_u32(items.len() as u32);
+                for item in items {
+                    match item {
+                        Some(value) => {
+                            buf.put_u32(value.len() as u32);
+                            buf.put_slice(value);
+                        }
+                        None => buf.put_u32(NIL_ITEM_LEN),
+                    }
+                }
+                (OpCode::Values, buf)
+            }
+            Response::ValueWithTtl { value, ttl_ms } => {
+                let mut buf = alloc();
+                buf.put_u32(value.len() as u32);
+                buf.put_slice(value);
+                buf.put_i64(*ttl_ms);
+                (OpCode::ValueTtl, buf)
             }
+            Response::BatchAdded { added, failed } => {
+                let mut buf = alloc();
+                buf.put_u32(*added);
+                buf.put_u32(failed.len() as u32);
+                for &index in failed {
+                    buf.put_u32(index);
+                }
+                (OpCode::BatchAdded, buf)
+            }
+            Response::Event { event, key } => {
+                let mut buf = alloc();
+                buf.put_u32(event.len() as u32);
+                buf.put_slice(event);
+                buf.put_u32(key.len() as u32);
+                buf.put_slice(key);
+                (OpCode::Event, buf)
+            }
+            Response::Replies(replies) => {
+                // [count] then [opcode: u8][len: u32][payload] per reply
+                let mut buf = alloc();
+                buf.put_u32(replies.len() as u32);
+                for reply in replies {
+                    let frame = reply.to_frame(request_id);
+                    buf.put_u8(frame.header.opcode as u8);
+                    buf.put_u32(frame.payload.len() as u32);
+                    buf.put_slice(&frame.payload);
+                }
+                (OpCode::Replies, buf)
+            }
+        };
+        Frame::new(opcode, request_id, buf.freeze())
+    }
+
+    /// Redirect payload: slot (u16) followed by the UTF-8 address
+    fn encode_redirect(buf: &mut BytesMut, slot: u16, addr: &str) {
+        buf.put_u16(slot);
+        buf.put_slice(addr.as_bytes());
+    }
+
+    fn decode_redirect(payload: &Bytes) -> std::io::Result<(u16, String)> {
+        if payload.len() < 2 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Invalid redirect payload",
+            ));
         }
+        let slot = u16::from_be_bytes([payload[0], payload[1]]);
+        Ok((slot, String::from_utf8_lossy(&payload[2..]).into_owned()))
     }
 
     /// Parse response from a VCP frame
@@ -60,6 +249,8 @@ impl Response {
             OpCode::Ok => Ok(Response::Ok),
             OpCode::Nil => Ok(Response::Nil),
             OpCode::Pong => Ok(Response::Pong),
+            OpCode::Queued => Ok(Response::Queued),
+            OpCode::Expired => Ok(Response::Expired),
             OpCode::Value => Ok(Response::Value(frame.payload.clone())),
             OpCode::Integer => {
                 if frame.payload.len() >= 8 {
@@ -96,6 +287,107 @@ impl Response {
                 }
                 Ok(Response::Array(items))
             }
+            OpCode::Moved => {
+                let (slot, addr) = Self::decode_redirect(&frame.payload)?;
+                Ok(Response::Moved { slot, addr })
+            }
+            OpCode::Ask => {
+                let (slot, addr) = Self::decode_redirect(&frame.payload)?;
+                Ok(Response::Ask { slot, addr })
+            }
+            OpCode::Busy => {
+                let retry_after_ms = match frame.payload.get(..4) {
+                    Some(bytes) => u32::from_be_bytes(bytes.try_into().unwrap()),
+                    None => 0,
+                };
+                Ok(Response::Busy { retry_after_ms })
+            }
+            OpCode::Values => {
+                use bytes::Buf;
+                let mut buf = frame.payload.clone();
+                if buf.remaining() < 4 {
+                    return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid values payload"));
+                }
+                let count = buf.get_u32() as usize;
+                let mut items = Vec::with_capacity(count.min(buf.remaining() / 4));
+                for _ in 0..count {
+                    if buf.remaining() < 4 {
+                        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "Insufficient values data"));
+                    }
+                    let len = buf.get_u32();
+                    if len == NIL_ITEM_LEN {
+                        items.push(None);
+                        continue;
+                    }
+                    if buf.remaining() < len as usize {
+                        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "Insufficient item data"));
+                    }
+                    items.push(Some(buf.copy_to_bytes(len as usize)));
+                }
+                Ok(Response::Values(items))
+            }
+            OpCode::ValueTtl => {
+                use bytes::Buf;
+                let mut buf = frame.payload.clone();
+                let len = if buf.remaining() >= 4 { buf.get_u32() as usize } else { usize::MAX };
+                if buf.remaining() < len.saturating_add(8) {
+                    return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid value-with-TTL payload"));
+                }
+                let value = buf.copy_to_bytes(len);
+                Ok(Response::ValueWithTtl { value, ttl_ms: buf.get_i64() })
+            }
+            OpCode::BatchAdded => {
+                use bytes::Buf;
+                let mut buf = frame.payload.clone();
+                let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid batch payload");
+                if buf.remaining() < 8 {
+                    return Err(invalid());
+                }
+                let added = buf.get_u32();
+                let count = buf.get_u32() as usize;
+                if buf.remaining() / 4 < count {
+                    return Err(invalid());
+                }
+                let failed = (0..count).map(|_| buf.get_u32()).collect();
+                Ok(Response::BatchAdded { added, failed })
+            }
+            OpCode::Event => {
+                use bytes::Buf;
+                let mut buf = frame.payload.clone();
+                let mut field = || {
+                    let len = if buf.remaining() >= 4 { buf.get_u32() as usize } else { usize::MAX };
+                    if buf.remaining() < len {
+                        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid event payload"));
+                    }
+                    Ok(buf.copy_to_bytes(len))
+                };
+                let event = field()?;
+                let key = field()?;
+                Ok(Response::Event { event, key })
+            }
+            OpCode::Replies => {
+                use bytes::Buf;
+                let mut buf = frame.payload.clone();
+                let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid replies payload");
+                if buf.remaining() < 4 {
+                    return Err(invalid());
+                }
+                let count = buf.get_u32() as usize;
+                let mut replies = Vec::with_capacity(count.min(buf.remaining() / 5));
+                for _ in 0..count {
+                    if buf.remaining() < 5 {
+                        return Err(invalid());
+                    }
+                    let opcode = OpCode::from_u8(buf.get_u8()).ok_or_else(invalid)?;
+                    let len = buf.get_u32() as usize;
+                    if buf.remaining() < len {
+                        return Err(invalid());
+                    }
+                    let inner = Frame::new(opcode, frame.header.request_id, buf.copy_to_bytes(len));
+                    replies.push(Response::from_frame(&inner)?);
+                }
+                Ok(Response::Replies(replies))
+            }
             _ => Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
                 format!("Unexpected opcode for response: {:?}", frame.header.opcode),
@@ -109,6 +401,7 @@ impl std::fmt::Display for Response {
         match self {
             Response::Ok => write!(f, "OK"),
             Response::Nil => write!(f, "(nil)"),
+            Response::Expired => write!(f, "(expired)"),
             Response::Value(data) => {
                 let s = String::from_utf8_lossy(data);
                 write!(f, "\"{}\"", s)
@@ -125,6 +418,41 @@ impl std::fmt::Display for Response {
                 }
                 write!(f, "]")
             }
+            Response::Moved { slot, addr } => write!(f, "(error) MOVED {} {}", slot, addr),
+            Response::Ask { slot, addr } => write!(f, "(error) ASK {} {}", slot, addr),
+            Response::Busy { retry_after_ms } => {
+                write!(f, "(error) BUSY server overloaded, retry in {}ms", retry_after_ms)
+            }
+            Response::Values(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
+                    match item {
+                        Some(value) => write!(f, "\"{}\"", String::from_utf8_lossy(value))?,
+                        None => write!(f, "(nil)")?,
+                    }
+                }
+                write!(f, "]")
+            }
+            Response::ValueWithTtl { value, ttl_ms } => {
+                write!(f, "\"{}\" (ttl {}ms)", String::from_utf8_lossy(value), ttl_ms)
+            }
+            Response::BatchAdded { added, failed } if failed.is_empty() => write!(f, "(added) {}", added),
+            Response::BatchAdded { added, failed } => {
+                write!(f, "(added) {}, failed at {:?}", added, failed)
+            }
+            Response::Event { event, key } => {
+                write!(f, "(event) {} \"{}\"", String::from_utf8_lossy(event), String::from_utf8_lossy(key))
+            }
+            Response::Queued => write!(f, "QUEUED"),
+            Response::Replies(replies) => {
+                write!(f, "[")?;
+                for (i, reply) in replies.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
+                    write!(f, "{}", reply)?;
+                }
+                write!(f, "]")
+            }
         }
     }
 }
@@ -0,0 +1,112 @@
+//! Keyspace Notifications
+//!
+//! Publishes key events to `__keyspace@<db>__:<key>` (payload: event)
+//! and `__keyevent@<db>__:<event>` (payload: key), Redis-style.
+
+use bytes::Bytes;
+use tokio::sync::broadcast;
+
+use super::{Message, PubSub};
+use crate::security::acl::glob_match;
+
+/// Publishes key mutation events through a `PubSub` registry
+#[derive(Debug, Clone)]
+pub struct KeyspaceNotifier {
+    pubsub: PubSub,
+    db: u32,
+}
+
+impl KeyspaceNotifier {
+    pub fn new(pubsub: PubSub) -> Self {
+        Self { pubsub, db: 0 }
+    }
+
+    /// Publish under a different database index
+    pub fn with_db(mut self, db: u32) -> Self {
+        self.db = db;
+        self
+    }
+
+    /// Keyspace channel for a key
+    pub fn keyspace_channel(&self, key: &[u8]) -> String {
+        format!("__keyspace@{}__:{}", self.db, String::from_utf8_lossy(key))
+    }
+
+    /// Keyevent channel for an event
+    pub fn keyevent_channel(&self, event: &str) -> String {
+        format!("__keyevent@{}__:{}", self.db, event)
+    }
+
+    /// Publish an event (`set`, `del`, `expire`, `expired`, ...) for a key
+    pub fn notify(&self, event: &'static str, key: &Bytes) {
+        self.pubsub
+            .publish(&self.keyspace_channel(key), Bytes::from_static(event.as_bytes()));
+        self.pubsub.publish(&self.keyevent_channel(event), key.clone());
+    }
+}
+
+/// A connection's interest in key events of one database, filtered by
+/// event name and optionally by key pattern
+#[derive(Debug)]
+pub struct KeyEventSubscription {
+    rx: broadcast::Receiver<Message>,
+    prefix: String,
+    /// Event names to deliver (empty = all)
+    events: Vec<String>,
+    pattern: Option<String>,
+}
+
+impl KeyEventSubscription {
+    pub fn new(pubsub: &PubSub, db: u32, events: Vec<String>, pattern: Option<String>) -> Self {
+        let prefix = format!("__keyevent@{}__:", db);
+        Self {
+            rx: pubsub.psubscribe(&format!("{}*", prefix)),
+            prefix,
+            events,
+            pattern,
+        }
+    }
+
+    /// Next matching `(event, key)`; None once the registry is gone.
+    /// Events missed because this receiver lagged are skipped.
+    pub async fn recv(&mut self) -> Option<(String, Bytes)> {
+        loop {
+            let message = match self.rx.recv().await {
+                Ok(message) => message,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            };
+            let event = match message.channel.strip_prefix(&self.prefix) {
+                Some(event) => event,
+                None => continue,
+            };
+            if !self.events.is_empty() && !self.events.iter().any(|e| e.eq_ignore_ascii_case(event)) {
+                continue;
+            }
+            if let Some(pattern) = &self.pattern {
+                if !glob_match(pattern, &message.payload) {
+                    continue;
+                }
+            }
+            return Some((event.to_string(), message.payload));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notify_channels() {
+        let pubsub = PubSub::new();
+        let mut keyspace = pubsub.subscribe("__keyspace@0__:user:1");
+        let mut keyevent = pubsub.subscribe("__keyevent@0__:del");
+
+        let notifier = KeyspaceNotifier::new(pubsub);
+        notifier.notify("del", &Bytes::from_static(b"user:1"));
+
+        assert_eq!(keyspace.try_recv().unwrap().payload.as_ref(), b"del");
+        assert_eq!(keyevent.try_recv().unwrap().payload.as_ref(), b"user:1");
+    }
+}
@@ -0,0 +1,121 @@
+//! Pub/Sub Module
+//!
+//! In-process channel fan-out and keyspace notifications.
+
+mod keyspace;
+
+pub use keyspace::{KeyEventSubscription, KeyspaceNotifier};
+
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+
+use crate::security::acl::glob_match;
+
+/// Per-subscription buffer before slow receivers start lagging
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// A published message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub channel: String,
+    pub payload: Bytes,
+}
+
+#[derive(Debug, Default)]
+struct Subscriptions {
+    /// Exact channel subscriptions
+    channels: HashMap<String, broadcast::Sender<Message>>,
+    /// Glob pattern subscriptions
+    patterns: HashMap<String, broadcast::Sender<Message>>,
+}
+
+/// Channel registry shared across connections
+#[derive(Debug, Clone, Default)]
+pub struct PubSub {
+    inner: Arc<RwLock<Subscriptions>>,
+}
+
+impl PubSub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribe to an exact channel
+    pub fn subscribe(&self, channel: &str) -> broadcast::Receiver<Message> {
+        let mut subs = self.inner.write().unwrap();
+        subs.channels
+            .entry(channel.to_string())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    /// Subscribe to all channels matching a glob pattern
+    pub fn psubscribe(&self, pattern: &str) -> broadcast::Receiver<Message> {
+        let mut subs = self.inner.write().unwrap();
+        subs.patterns
+            .entry(pattern.to_string())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    /// Publish a message, returns the number of receivers it reached
+    pub fn publish(&self, channel: &str, payload: Bytes) -> usize {
+        let subs = self.inner.read().unwrap();
+        if subs.channels.is_empty() && subs.patterns.is_empty() {
+            return 0;
+        }
+
+        let message = Message {
+            channel: channel.to_string(),
+            payload,
+        };
+
+        let mut delivered = 0;
+        if let Some(tx) = subs.channels.get(channel) {
+            delivered += tx.send(message.clone()).unwrap_or(0);
+        }
+        for (pattern, tx) in &subs.patterns {
+            if glob_match(pattern, channel) {
+                delivered += tx.send(message.clone()).unwrap_or(0);
+            }
+        }
+        delivered
+    }
+
+    /// Drop channels and patterns that no longer have receivers
+    pub fn prune(&self) {
+        let mut subs = self.inner.write().unwrap();
+        subs.channels.retain(|_, tx| tx.receiver_count() > 0);
+        subs.patterns.retain(|_, tx| tx.receiver_count() > 0);
+    }
+
+    /// Number of channels with active subscriptions
+    pub fn channel_count(&self) -> usize {
+        self.inner.read().unwrap().channels.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_subscribe() {
+        let pubsub = PubSub::new();
+        let mut exact = pubsub.subscribe("news");
+        let mut pattern = pubsub.psubscribe("news*");
+
+        assert_eq!(pubsub.publish("news", Bytes::from_static(b"hello")), 2);
+        assert_eq!(exact.try_recv().unwrap().payload.as_ref(), b"hello");
+        assert_eq!(pattern.try_recv().unwrap().channel, "news");
+
+        assert_eq!(pubsub.publish("other", Bytes::from_static(b"x")), 0);
+
+        drop(exact);
+        drop(pattern);
+        pubsub.prune();
+        assert_eq!(pubsub.channel_count(), 0);
+    }
+}
@@ -20,6 +20,16 @@ pub enum Permission {
     All,
 }
 
+/// Commands that need `Permission::Admin` regardless of command allow-lists
+const ADMIN_COMMANDS: &[&str] = &["FLUSHDB", "FLUSHALL", "CONFIG", "SHUTDOWN", "DEBUG", "SLOWLOG", "CLIENT"];
+
+impl Permission {
+    /// Whether running `cmd` requires `Permission::Admin`
+    pub fn is_admin_command(cmd: &str) -> bool {
+        ADMIN_COMMANDS.iter().any(|c| c.eq_ignore_ascii_case(cmd))
+    }
+}
+
 /// ACL rule for key patterns
 #[derive(Debug, Clone)]
 pub struct AclRule {
@@ -109,6 +119,9 @@ impl Role {
         if self.denied_commands.contains(&cmd) {
             return false;
         }
+        if Permission::is_admin_command(&cmd) && !self.rules.iter().any(|r| r.allows(Permission::Admin)) {
+            return false;
+        }
         self.commands.is_empty() || self.commands.contains(&cmd) || self.commands.contains("*")
     }
 
@@ -225,18 +238,75 @@ impl Default for AclManager {
     }
 }
 
-/// Simple glob pattern matching
-fn glob_match(pattern: &str, text: &str) -> bool {
-    if pattern == "*" {
-        return true;
-    }
-    if pattern.ends_with('*') {
-        return text.starts_with(&pattern[..pattern.len() - 1]);
+/// Redis-style glob matching: `*` matches any run of bytes, `?` one byte,
+/// `[abc]`, `[a-z]` and `[^abc]` a byte class, and `\` escapes the next
+/// byte. Works on raw bytes, like Redis, so keys need no UTF-8 conversion.
+pub(crate) fn glob_match(pattern: impl AsRef<[u8]>, text: impl AsRef<[u8]>) -> bool {
+    let (pattern, text) = (pattern.as_ref(), text.as_ref());
+    let (mut p, mut t) = (0, 0);
+    // Where to resume after the last `*`: (pattern index past it, text index)
+    let mut backtrack = None;
+
+    while t < text.len() {
+        if p < pattern.len() {
+            if pattern[p] == b'*' {
+                while p < pattern.len() && pattern[p] == b'*' {
+                    p += 1;
+                }
+                backtrack = Some((p, t));
+                continue;
+            }
+            if let Some(len) = match_one(&pattern[p..], text[t]) {
+                p += len;
+                t += 1;
+                continue;
+            }
+        }
+        // Mismatch: let the last `*` swallow one more byte
+        match backtrack {
+            Some((star_p, star_t)) => {
+                p = star_p;
+                t = star_t + 1;
+                backtrack = Some((star_p, t));
+            }
+            None => return false,
+        }
     }
-    if pattern.starts_with('*') {
-        return text.ends_with(&pattern[1..]);
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Match the single-byte token at the start of `pattern` against `c`,
+/// returning how many pattern bytes it spans
+fn match_one(pattern: &[u8], c: u8) -> Option<usize> {
+    match pattern[0] {
+        b'?' => Some(1),
+        b'\\' if pattern.len() >= 2 => (pattern[1] == c).then_some(2),
+        b'[' => {
+            let mut i = 1;
+            let negate = pattern.get(i) == Some(&b'^');
+            if negate {
+                i += 1;
+            }
+            let mut matched = false;
+            // An unterminated class runs to the end of the pattern
+            while i < pattern.len() && pattern[i] != b']' {
+                if pattern[i] == b'\\' && i + 1 < pattern.len() {
+                    matched |= pattern[i + 1] == c;
+                    i += 2;
+                } else if i + 2 < pattern.len() && pattern[i + 1] == b'-' && pattern[i + 2] != b']' {
+                    let (lo, hi) = (pattern[i].min(pattern[i + 2]), pattern[i].max(pattern[i + 2]));
+                    matched |= (lo..=hi).contains(&c);
+                    i += 3;
+                } else {
+                    matched |= pattern[i] == c;
+                    i += 1;
+                }
+            }
+            let len = (i + 1).min(pattern.len());
+            (matched != negate).then_some(len)
+        }
+        literal => (literal == c).then_some(1),
     }
-    pattern == text
 }
 
 #[cfg(test)]
@@ -252,6 +322,62 @@ mod tests {
         assert!(!rule.allows(Permission::Admin));
     }
 
+    #[test]
+    fn test_glob_match() {
+        let cases = [
+            ("*", "", true),
+            ("*", "anything:at:all", true),
+            ("", "", true),
+            ("", "a", false),
+            ("user:*", "user:", true),
+            ("user:*:session", "user:42:session", true),
+            ("user:*:session", "user::session", true),
+            ("user:*:session", "user:42:profile", false),
+            ("user:*:session", "user:1:2:session", true),
+            ("*a*b*", "xxaxxbxx", true),
+            ("*a*b", "ba", false),
+            ("a**b", "ab", true),
+            ("*:*", "a:", true),
+            ("a?c", "abc", true),
+            ("a?c", "ac", false),
+            ("??", "a", false),
+            ("[abc]*", "bzz", true),
+            ("[abc]*", "dzz", false),
+            ("[^abc]x", "dx", true),
+            ("[^abc]x", "ax", false),
+            ("[a-c]1", "b1", true),
+            ("[c-a]1", "b1", true),
+            ("[a-c]1", "d1", false),
+            ("[a-]", "-", true),
+            ("[\\]]", "]", true),
+            ("h\\*llo", "h*llo", true),
+            ("h\\*llo", "hello", false),
+            ("h\\?", "h?", true),
+            ("h\\?", "hx", false),
+            ("[ab", "b", true),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(glob_match(pattern, text), expected, "{:?} vs {:?}", pattern, text);
+        }
+
+        // Keys are matched as raw bytes, valid UTF-8 or not
+        assert!(glob_match("bin:*", b"bin:\xff\x00"));
+        assert!(glob_match("bin:?", b"bin:\xff"));
+        assert!(!glob_match("bin:?", b"bin:\xc3\xa9"));
+    }
+
+    #[test]
+    fn test_flush_requires_admin() {
+        let reader = Role::new("reader").with_rule(AclRule::new("*").with_read()).allow_command("*");
+        assert!(reader.can_execute("GET"));
+        assert!(!reader.can_execute("flushall"));
+        assert!(!reader.can_execute("FLUSHDB"));
+
+        let ops = Role::new("ops").with_rule(AclRule::new("*").with_permission(Permission::Admin));
+        assert!(ops.can_execute("FLUSHALL"));
+        assert!(Role::admin().can_execute("FLUSHDB"));
+    }
+
     #[test]
     fn test_role() {
         let role = Role::admin();
@@ -3,8 +3,12 @@
 //! Security audit trail for compliance requirements.
 
 use std::collections::VecDeque;
-use std::sync::RwLock;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
 
 /// Audit event type
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -19,6 +23,8 @@ pub enum AuditEventType {
     Command,
     /// Permission denied
     PermissionDenied,
+    /// Requests rejected by the rate limiter
+    RateLimited,
     /// Configuration change
     ConfigChange,
     /// Admin action
@@ -125,6 +131,128 @@ impl AuditEvent {
     }
 }
 
+/// When the file sink flushes buffered lines to disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditFlush {
+    /// Flush after every event
+    PerEvent,
+    /// Flush on the first event after the interval has passed (and on drop)
+    Periodic(Duration),
+}
+
+/// File sink settings
+#[derive(Debug, Clone)]
+pub struct AuditFileConfig {
+    /// Active log file; rotated files get `.1`, `.2`, ... appended
+    pub path: PathBuf,
+    /// Rotate once the active file would exceed this many bytes
+    pub max_bytes: u64,
+    /// Rotated files to keep (0 = discard on rotation)
+    pub retain: usize,
+    pub flush: AuditFlush,
+}
+
+impl AuditFileConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_bytes: 64 * 1024 * 1024,
+            retain: 5,
+            flush: AuditFlush::PerEvent,
+        }
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn with_retain(mut self, retain: usize) -> Self {
+        self.retain = retain;
+        self
+    }
+
+    pub fn with_flush(mut self, flush: AuditFlush) -> Self {
+        self.flush = flush;
+        self
+    }
+
+    /// Path of the `n`th rotated file
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        path.into()
+    }
+}
+
+/// Appends events as JSON lines, rotating by size
+struct AuditFileSink {
+    config: AuditFileConfig,
+    writer: BufWriter<File>,
+    size: u64,
+    last_flush: Instant,
+}
+
+impl AuditFileSink {
+    fn open(config: AuditFileConfig) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&config.path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            config,
+            writer: BufWriter::new(file),
+            size,
+            last_flush: Instant::now(),
+        })
+    }
+
+    fn write(&mut self, event: &AuditEvent) -> io::Result<()> {
+        let line = event.to_json() + "\n";
+        if self.size > 0 && self.size + line.len() as u64 > self.config.max_bytes {
+            self.rotate()?;
+        }
+        self.writer.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+
+        let due = match self.config.flush {
+            AuditFlush::PerEvent => true,
+            AuditFlush::Periodic(interval) => self.last_flush.elapsed() >= interval,
+        };
+        if due {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    /// Shift `path.N` to `path.N+1` (dropping the oldest) and start a new file
+    fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        let retain = self.config.retain;
+        if retain == 0 {
+            fs::remove_file(&self.config.path)?;
+        } else {
+            let _ = fs::remove_file(self.config.rotated_path(retain));
+            for n in (1..retain).rev() {
+                let from = self.config.rotated_path(n);
+                if from.exists() {
+                    fs::rename(&from, self.config.rotated_path(n + 1))?;
+                }
+            }
+            fs::rename(&self.config.path, self.config.rotated_path(1))?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&self.config.path)?;
+        self.writer = BufWriter::new(file);
+        self.size = 0;
+        Ok(())
+    }
+}
+
 /// Audit logger
 pub struct AuditLogger {
     /// In-memory buffer
@@ -133,6 +261,8 @@ pub struct AuditLogger {
     max_size: usize,
     /// Logging enabled
     enabled: bool,
+    /// Durable copy of every event (None = memory only)
+    file: Option<Mutex<AuditFileSink>>,
 }
 
 impl AuditLogger {
@@ -141,6 +271,21 @@ impl AuditLogger {
             buffer: RwLock::new(VecDeque::with_capacity(max_size)),
             max_size,
             enabled: true,
+            file: None,
+        }
+    }
+
+    /// Also append every event to a rotating file
+    pub fn with_file_sink(mut self, config: AuditFileConfig) -> io::Result<Self> {
+        self.file = Some(Mutex::new(AuditFileSink::open(config)?));
+        Ok(self)
+    }
+
+    /// Write any buffered events to the file sink
+    pub fn flush(&self) -> io::Result<()> {
+        match &self.file {
+            Some(file) => file.lock().unwrap().flush(),
+            None => Ok(()),
         }
     }
 
@@ -150,6 +295,12 @@ impl AuditLogger {
             return;
         }
 
+        if let Some(file) = &self.file {
+            if let Err(e) = file.lock().unwrap().write(&event) {
+                warn!("Failed to write audit event to file: {}", e);
+            }
+        }
+
         let mut buffer = self.buffer.write().unwrap();
         if buffer.len() >= self.max_size {
             buffer.pop_front();
@@ -201,6 +352,15 @@ impl AuditLogger {
         self.log(event);
     }
 
+    /// Log a closed connection and why it was closed
+    pub fn log_disconnect(&self, client_ip: &str, reason: &str) {
+        self.log(
+            AuditEvent::new(AuditEventType::Disconnect)
+                .with_client(client_ip)
+                .with_message(reason),
+        );
+    }
+
     /// Get recent events
     pub fn recent(&self, count: usize) -> Vec<AuditEvent> {
         let buffer = self.buffer.read().unwrap();
@@ -260,4 +420,41 @@ mod tests {
         assert!(json.contains("Login"));
         assert!(json.contains("admin"));
     }
+
+    #[test]
+    fn test_file_sink_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AuditFileConfig::new(dir.path().join("audit.log"))
+            .with_max_bytes(512)
+            .with_retain(2);
+        let logger = AuditLogger::new(10).with_file_sink(config.clone()).unwrap();
+
+        for i in 0..40 {
+            logger.log_command("user1", "GET", Some(&format!("key:{}", i)));
+        }
+        assert_eq!(logger.recent(100).len(), 10);
+
+        // Oldest rotations beyond `retain` are dropped
+        assert!(config.rotated_path(1).exists());
+        assert!(config.rotated_path(2).exists());
+        assert!(!config.rotated_path(3).exists());
+
+        let read = |path: PathBuf| fs::read_to_string(path).unwrap();
+        let (older, newer, active) =
+            (read(config.rotated_path(2)), read(config.rotated_path(1)), read(config.path.clone()));
+        for file in [&older, &newer, &active] {
+            assert!(file.len() <= 512);
+            assert!(file.lines().all(|l| l.starts_with('{') && l.contains(r#""command":"GET""#)));
+        }
+        assert!(active.ends_with("\"key\":\"key:39\"}\n"));
+
+        // Files hold a contiguous run of events, oldest first
+        let keys: Vec<usize> = [older, newer, active]
+            .concat()
+            .lines()
+            .map(|l| l.rsplit("key:").next().unwrap().trim_end_matches("\"}").parse().unwrap())
+            .collect();
+        assert!(keys.windows(2).all(|w| w[1] == w[0] + 1));
+        assert_eq!(*keys.last().unwrap(), 39);
+    }
 }
@@ -6,6 +6,9 @@ use std::collections::HashMap;
 use std::sync::RwLock;
 use std::time::Instant;
 
+/// Random bytes in a session token
+const SESSION_TOKEN_BYTES: usize = 32;
+
 /// Authentication result
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AuthResult {
@@ -79,6 +82,11 @@ impl AuthManager {
         }
     }
 
+    /// Whether connections must authenticate before running commands
+    pub fn requires_auth(&self) -> bool {
+        self.config.require_auth
+    }
+
     /// Add a user
     pub fn add_user(&self, username: &str, password_hash: &str) {
         let mut users = self.users.write().unwrap();
@@ -113,12 +121,25 @@ impl AuthManager {
         AuthResult::Failed
     }
 
-    /// Create a session
+    /// Create a session, returning an unguessable token for it
     pub fn create_session(&self, username: &str) -> String {
-        let token = format!("{}_{}", username, Instant::now().elapsed().as_nanos());
         let mut sessions = self.sessions.write().unwrap();
-        sessions.insert(token.clone(), (username.to_string(), Instant::now()));
-        token
+        loop {
+            let token = Self::random_token();
+            // 256 random bits never collide in practice, but a reused token
+            // would hand one user's session to another
+            if !sessions.contains_key(&token) {
+                sessions.insert(token.clone(), (username.to_string(), Instant::now()));
+                return token;
+            }
+        }
+    }
+
+    /// 32 bytes from the OS CSPRNG, hex encoded
+    fn random_token() -> String {
+        let mut bytes = [0u8; SESSION_TOKEN_BYTES];
+        getrandom::fill(&mut bytes).expect("OS random number generator unavailable");
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
     }
 
     /// Validate a session
@@ -194,4 +215,32 @@ mod tests {
         auth.end_session(&token);
         assert_eq!(auth.validate_session(&token), None);
     }
+
+    #[test]
+    fn test_session_tokens_are_random_and_unique() {
+        use std::collections::HashSet;
+        use std::sync::Arc;
+
+        let auth = Arc::new(AuthManager::default());
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let auth = auth.clone();
+                std::thread::spawn(move || {
+                    let user = format!("user{}", t);
+                    (0..500).map(|_| (user.clone(), auth.create_session(&user))).collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        let mut seen = HashSet::new();
+        for handle in handles {
+            for (user, token) in handle.join().unwrap() {
+                assert_eq!(token.len(), SESSION_TOKEN_BYTES * 2);
+                assert!(!token.contains(&user));
+                assert_eq!(auth.validate_session(&token), Some(user));
+                assert!(seen.insert(token), "duplicate session token");
+            }
+        }
+        assert_eq!(seen.len(), 4000);
+    }
 }
@@ -10,4 +10,4 @@ pub mod audit;
 pub use auth::{AuthManager, AuthConfig, Credentials, AuthResult};
 pub use acl::{AclManager, Permission, Role, AclRule};
 pub use tls::{TlsConfig, TlsAcceptor};
-pub use audit::{AuditLogger, AuditEvent, AuditEventType};
+pub use audit::{AuditLogger, AuditEvent, AuditEventType, AuditFileConfig, AuditFlush};
@@ -22,18 +22,13 @@ pub struct TlsConfig {
 }
 
 /// TLS version
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum TlsVersion {
     Tls12,
+    #[default]
     Tls13,
 }
 
-impl Default for TlsVersion {
-    fn default() -> Self {
-        Self::Tls13
-    }
-}
-
 impl Default for TlsConfig {
     fn default() -> Self {
         Self {
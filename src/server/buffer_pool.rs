@@ -2,18 +2,29 @@
 //!
 //! Pre-allocated buffer pool for zero-allocation hot path.
 
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use crossbeam::queue::ArrayQueue;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 /// Default buffer size
 const DEFAULT_BUFFER_SIZE: usize = 4096;
 
+/// Recycled buffers larger than this many `buffer_size`s are dropped
+const MAX_RECYCLE_FACTOR: usize = 16;
+
 /// Pool of pre-allocated buffers
 #[derive(Clone)]
 pub struct BufferPool {
     pool: Arc<ArrayQueue<BytesMut>>,
     buffer_size: usize,
+    stats: Arc<PoolStats>,
+}
+
+#[derive(Default)]
+struct PoolStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
 }
 
 impl BufferPool {
@@ -30,6 +41,7 @@ impl BufferPool {
         Self {
             pool: Arc::new(pool),
             buffer_size,
+            stats: Arc::new(PoolStats::default()),
         }
     }
 
@@ -41,9 +53,16 @@ impl BufferPool {
     /// Get a buffer from the pool, or allocate a new one
     #[inline]
     pub fn get(&self) -> BytesMut {
-        self.pool
-            .pop()
-            .unwrap_or_else(|| BytesMut::with_capacity(self.buffer_size))
+        match self.pool.pop() {
+            Some(buf) => {
+                self.stats.hits.fetch_add(1, Ordering::Relaxed);
+                buf
+            }
+            None => {
+                self.stats.misses.fetch_add(1, Ordering::Relaxed);
+                BytesMut::with_capacity(self.buffer_size)
+            }
+        }
     }
 
     /// Return a buffer to the pool
@@ -54,6 +73,28 @@ impl BufferPool {
         let _ = self.pool.push(buf);
     }
 
+    /// Return the storage behind a frozen buffer to the pool, if this was
+    /// the last reference and its size is reasonable for reuse
+    #[inline]
+    pub fn recycle(&self, bytes: Bytes) {
+        if let Ok(buf) = bytes.try_into_mut() {
+            let capacity = buf.capacity();
+            if capacity >= self.buffer_size && capacity <= self.buffer_size * MAX_RECYCLE_FACTOR {
+                self.put(buf);
+            }
+        }
+    }
+
+    /// Fraction of `get` calls served from the pool
+    pub fn hit_rate(&self) -> f64 {
+        let hits = self.stats.hits.load(Ordering::Relaxed);
+        let misses = self.stats.misses.load(Ordering::Relaxed);
+        if hits + misses == 0 {
+            return 0.0;
+        }
+        hits as f64 / (hits + misses) as f64
+    }
+
     /// Get current pool size
     pub fn len(&self) -> usize {
         self.pool.len()
@@ -0,0 +1,129 @@
+//! Client Registry
+//!
+//! Tracks active connections for CLIENT LIST and lets CLIENT KILL close them.
+
+use bytes::Bytes;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// Error for CLIENT KILL of an id that isn't connected
+pub const NO_SUCH_CLIENT_ERROR: &str = "NOTFOUND no such client";
+
+/// Connections currently being served, shared by every handler
+#[derive(Clone, Default)]
+pub struct ClientRegistry {
+    next_id: Arc<AtomicU64>,
+    clients: Arc<Mutex<BTreeMap<u64, Arc<ClientInfo>>>>,
+}
+
+/// What CLIENT LIST reports about one connection
+struct ClientInfo {
+    id: u64,
+    /// Peer address (None for Unix socket clients)
+    addr: Option<SocketAddr>,
+    connected_at: Instant,
+    user: RwLock<Option<String>>,
+    last_command: Mutex<&'static str>,
+    /// Cancelled by CLIENT KILL
+    kill: CancellationToken,
+}
+
+impl ClientInfo {
+    /// CLIENT LIST record: `id=<id> addr=<ip:port|unix> user=<name> age=<secs> cmd=<last command>`
+    fn record(&self) -> Bytes {
+        let addr = self.addr.map_or_else(|| "unix".to_string(), |addr| addr.to_string());
+        let user = self.user.read().unwrap().clone().unwrap_or_else(|| "default".to_string());
+        Bytes::from(format!(
+            "id={} addr={} user={} age={} cmd={}",
+            self.id,
+            addr,
+            user,
+            self.connected_at.elapsed().as_secs(),
+            self.last_command.lock().unwrap().to_lowercase()
+        ))
+    }
+}
+
+/// One connection's registration; removed from the registry on drop
+pub struct ClientHandle {
+    registry: ClientRegistry,
+    info: Arc<ClientInfo>,
+}
+
+impl ClientRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a new connection under the next id (starting at 1)
+    pub fn register(&self, addr: Option<SocketAddr>) -> ClientHandle {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let info = Arc::new(ClientInfo {
+            id,
+            addr,
+            connected_at: Instant::now(),
+            user: RwLock::new(None),
+            last_command: Mutex::new("NULL"),
+            kill: CancellationToken::new(),
+        });
+        self.clients.lock().unwrap().insert(id, info.clone());
+        ClientHandle { registry: self.clone(), info }
+    }
+
+    /// CLIENT LIST records of every connection, oldest first
+    pub fn list(&self) -> Vec<Bytes> {
+        self.clients.lock().unwrap().values().map(|info| info.record()).collect()
+    }
+
+    /// Signal connection `id` to close; false if no such connection
+    pub fn kill(&self, id: u64) -> bool {
+        match self.clients.lock().unwrap().get(&id) {
+            Some(info) => {
+                info.kill.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Number of registered connections
+    pub fn len(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ClientHandle {
+    /// Id CLIENT KILL addresses this connection by
+    pub fn id(&self) -> u64 {
+        self.info.id
+    }
+
+    /// Record the user the connection authenticated as (None after RESET)
+    pub fn set_user(&self, user: Option<String>) {
+        *self.info.user.write().unwrap() = user;
+    }
+
+    /// Record the command the connection ran most recently
+    pub fn set_last_command(&self, name: &'static str) {
+        *self.info.last_command.lock().unwrap() = name;
+    }
+
+    /// Resolves once CLIENT KILL targets this connection
+    pub async fn killed(&self) {
+        self.info.kill.cancelled().await
+    }
+}
+
+impl Drop for ClientHandle {
+    fn drop(&mut self) {
+        self.registry.clients.lock().unwrap().remove(&self.info.id);
+    }
+}
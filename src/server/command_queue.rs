@@ -3,6 +3,8 @@
 //! MPMC bounded queue for routing commands from network tasks to workers.
 
 use crossbeam::channel::{self, Receiver, Sender, TrySendError};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
 use crate::protocol::Command;
@@ -15,6 +17,8 @@ pub struct WorkItem {
     pub command: Command,
     /// Request ID for response matching
     pub request_id: u64,
+    /// Logical database selected by the connection
+    pub db: usize,
     /// Response channel to send result back
     pub response_tx: tokio::sync::oneshot::Sender<WorkResult>,
 }
@@ -30,12 +34,20 @@ pub enum WorkResult {
     Integer(i64),
     /// Nil response
     Nil,
+    /// Key stored but expired (GET with `FLAG_GET_EXPIRED`)
+    Expired,
     /// Error response
     Error(String),
     /// Pong response
     Pong,
     /// Array response
     Array(Vec<WorkResult>),
+    /// Per-key values, None for missing keys
+    Values(Vec<Option<Bytes>>),
+    /// Value with remaining TTL in milliseconds (-1 = no expiry)
+    ValueWithTtl { value: Bytes, ttl_ms: i64 },
+    /// Batch insert outcome: entries added and indices rejected
+    BatchAdded { added: u32, failed: Vec<u32> },
 }
 
 /// Bounded MPMC command queue
@@ -48,6 +60,8 @@ pub struct CommandQueue {
     sender: Sender<WorkItem>,
     receiver: Receiver<WorkItem>,
     capacity: usize,
+    /// Enqueues given up on because the queue stayed full
+    rejected: Arc<AtomicU64>,
 }
 
 impl CommandQueue {
@@ -58,6 +72,7 @@ impl CommandQueue {
             sender,
             receiver,
             capacity,
+            rejected: Arc::new(AtomicU64::new(0)),
         }
     }
 
@@ -119,6 +134,16 @@ impl CommandQueue {
     pub fn capacity(&self) -> usize {
         self.capacity
     }
+
+    /// Count a request turned away because the queue stayed full
+    pub fn record_rejection(&self) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Requests turned away because the queue stayed full
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
 }
 
 #[cfg(test)]
@@ -135,6 +160,7 @@ mod tests {
         let item = WorkItem {
             command: Command::Ping,
             request_id: 1,
+            db: 0,
             response_tx: tx,
         };
 
@@ -159,6 +185,7 @@ mod tests {
                                 key: Bytes::from(format!("key-{}-{}", i, j)),
                             },
                             request_id: (i * 25 + j) as u64,
+                            db: 0,
                             response_tx: tx,
                         };
                         q.send(item).unwrap();
@@ -1,5 +1,121 @@
 //! Server Configuration
 
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::config_file::{self, Value};
+use crate::persistence::{AofConfig, AofSyncMode, SnapshotConfig};
+use crate::protocol::DEFAULT_MAX_FRAME_SIZE;
+use crate::security::tls::TlsVersion;
+use crate::security::TlsConfig;
+use crate::storage::{EvictionConfig, EvictionPolicy, OomAction, DEFAULT_DATABASES};
+
+/// Most worker threads either pool may be configured with
+pub const MAX_WORKERS: usize = 1024;
+
+/// Prefix of the environment variables that override config settings,
+/// e.g. `CELRIX_PORT` or `CELRIX_TLS_CERT_FILE` for `tls.cert_file`
+pub const ENV_PREFIX: &str = "CELRIX_";
+
+/// Settings a config file or `CELRIX_*` variable may set
+const SETTINGS: &[&str] = &[
+    "bind",
+    "port",
+    "unix_socket",
+    "proxy_protocol",
+    "kv_workers",
+    "vector_workers",
+    "queue_capacity",
+    "ttl_cleaner_interval",
+    "ttl_adaptive",
+    "queue_degraded_ratio",
+    "memory_degraded_ratio",
+    "disabled_commands",
+    "notify_keyspace_events",
+    "lazy_free",
+    "route_by_key",
+    "shard_count",
+    "databases",
+    "max_connections",
+    "connection_limit_policy",
+    "idle_timeout",
+    "queue_send_timeout",
+    "compression_threshold",
+    "max_frame_size",
+    "max_value_size",
+    "rate_limit",
+    "rate_limit_burst",
+    "slowlog_log_slower_than",
+    "slowlog_max_len",
+    "replica_max_lag",
+    "tls.enabled",
+    "tls.cert_file",
+    "tls.key_file",
+    "tls.ca_file",
+    "tls.require_client_cert",
+    "tls.min_version",
+    "eviction.policy",
+    "eviction.max_memory",
+    "eviction.max_keys",
+    "eviction.sample_size",
+    "eviction.lfu_decay_interval",
+    "eviction.oom_action",
+    "snapshot.dir",
+    "snapshot.interval",
+    "snapshot.max_snapshots",
+    "snapshot.compress",
+    "aof.path",
+    "aof.sync",
+    "aof.rewrite_threshold",
+];
+
+/// Why a configuration couldn't be loaded
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The config file couldn't be read
+    Io(std::io::Error),
+    /// The config file isn't valid TOML (1-based line)
+    Parse { line: usize, message: String },
+    /// A setting has a bad value or conflicts with another
+    Invalid { key: String, message: String },
+}
+
+impl ConfigError {
+    pub(crate) fn invalid(key: &str, message: &str) -> Self {
+        ConfigError::Invalid { key: key.to_string(), message: message.to_string() }
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "cannot read config file: {}", e),
+            ConfigError::Parse { line, message } => write!(f, "config line {}: {}", line, message),
+            ConfigError::Invalid { key, message } => write!(f, "invalid `{}`: {}", key, message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// What the accept loop does once `max_connections` are open
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConnectionLimitPolicy {
+    /// Accept, send an error frame, and close
+    #[default]
+    Reject,
+    /// Stop accepting until a connection closes
+    Wait,
+}
+
 /// Server configuration
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -9,14 +125,108 @@ pub struct Config {
     /// Port number
     pub port: u16,
 
+    /// Also accept clients on a Unix domain socket at this path
+    pub unix_socket: Option<PathBuf>,
+
+    /// Expect a PROXY protocol v1 header on every TCP connection and use
+    /// the client address it carries. Only enable behind a load balancer
+    /// that sends one: raw clients can't connect while it's on.
+    pub proxy_protocol: bool,
+
     /// Number of KV worker threads (0 = auto-detect)
     pub kv_workers: usize,
 
     /// Number of Vector worker threads (0 = auto-detect)
     pub vector_workers: usize,
 
+    /// Capacity of each worker pool's command queue
+    pub queue_capacity: usize,
+
     /// TTL cleaner interval in seconds
     pub ttl_cleaner_interval: u64,
+
+    /// Sample keys with TTLs between TTL cleaner sweeps, more often while
+    /// many are found expired (concurrent server only)
+    pub ttl_adaptive: bool,
+
+    /// Queue depth (fraction of capacity) above which health reports degraded
+    pub queue_degraded_ratio: f64,
+
+    /// Memory use (fraction of `eviction.max_memory`) above which health
+    /// reports degraded
+    pub memory_degraded_ratio: f64,
+
+    /// Commands rejected for every connection, regardless of ACLs (uppercase)
+    pub disabled_commands: HashSet<String>,
+
+    /// Publish keyspace notifications on writes
+    pub notify_keyspace_events: bool,
+
+    /// Free large values removed by DEL in the background, like UNLINK
+    pub lazy_free: bool,
+
+    /// Send keyed KV commands to a worker picked by the key's slot rather
+    /// than the shared queue, so each worker mostly touches its own shards
+    pub route_by_key: bool,
+
+    /// DashMap shards per database, a power of two; more shards mean less
+    /// lock contention for very large keyspaces (0 = auto, four per KV
+    /// worker rounded up to a power of two)
+    pub shard_count: usize,
+
+    /// Number of logical databases selectable with SELECT
+    pub databases: usize,
+
+    /// Maximum simultaneous client connections (0 = unlimited)
+    pub max_connections: usize,
+
+    /// Behavior when `max_connections` is reached
+    pub connection_limit_policy: ConnectionLimitPolicy,
+
+    /// Close connections idle for this many seconds (0 = disabled)
+    pub idle_timeout: u64,
+
+    /// How long to wait for room in a full worker queue before replying BUSY (ms)
+    pub queue_send_timeout: u64,
+
+    /// LZ4-compress payloads of at least this many bytes for clients that
+    /// support it (0 = never compress)
+    pub compression_threshold: usize,
+
+    /// Close connections that send a frame with a larger payload (bytes)
+    pub max_frame_size: usize,
+
+    /// Refuse SET, APPEND, MSET and RESTORE writes that would store a
+    /// larger value (bytes, 0 = unlimited)
+    pub max_value_size: usize,
+
+    /// Commands per second allowed per user or client IP (0 = unlimited)
+    pub rate_limit: f64,
+
+    /// Commands a client may send in a burst before the rate applies
+    pub rate_limit_burst: u32,
+
+    /// Log commands taking at least this many microseconds in the slowlog
+    pub slowlog_log_slower_than: u64,
+
+    /// Slowlog entries kept (0 = slowlog disabled)
+    pub slowlog_max_len: usize,
+
+    /// On a follower, refuse key reads once it's more than this many
+    /// replication entries behind the leader
+    pub replica_max_lag: u64,
+
+    /// Client connection encryption
+    pub tls: TlsConfig,
+
+    /// Memory limits and eviction policy
+    pub eviction: EvictionConfig,
+
+    /// Periodic snapshots (None = disabled)
+    pub snapshot: Option<SnapshotConfig>,
+
+    /// Append-only file (None = disabled)
+    pub aof: Option<AofConfig>,
 }
 
 impl Default for Config {
@@ -24,20 +234,251 @@ impl Default for Config {
         Self {
             bind: "0.0.0.0".to_string(),
             port: 6380,
+            unix_socket: None,
+            proxy_protocol: false,
             kv_workers: 0,     // Auto-detect (typically num_cores)
             vector_workers: 4, // Conservative default for heavy vector ops
+            queue_capacity: 10000,
             ttl_cleaner_interval: 10,
+            ttl_adaptive: true,
+            queue_degraded_ratio: 0.8,
+            memory_degraded_ratio: 0.9,
+            disabled_commands: HashSet::new(),
+            notify_keyspace_events: false,
+            lazy_free: false,
+            route_by_key: false,
+            shard_count: 0,
+            databases: DEFAULT_DATABASES,
+            max_connections: 10000,
+            connection_limit_policy: ConnectionLimitPolicy::Reject,
+            idle_timeout: 0,
+            queue_send_timeout: 5,
+            compression_threshold: 1024,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            max_value_size: DEFAULT_MAX_FRAME_SIZE,
+            rate_limit: 0.0,
+            rate_limit_burst: 100,
+            slowlog_log_slower_than: 10_000,
+            slowlog_max_len: 128,
+            replica_max_lag: 1000,
+            tls: TlsConfig::default(),
+            eviction: EvictionConfig::default(),
+            snapshot: None,
+            aof: None,
         }
     }
 }
 
 impl Config {
+    /// Load a TOML config file, then apply `CELRIX_*` environment overrides
+    /// and validate the result
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+        Self::from_sources(Some(&contents), std::env::vars())
+    }
+
+    /// Defaults with `CELRIX_*` environment overrides, validated
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_sources(None, std::env::vars())
+    }
+
+    /// Defaults, overlaid by `toml` and then by the `CELRIX_*` entries of `vars`
+    pub(crate) fn from_sources(
+        toml: Option<&str>,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let mut config = Config::default();
+        for (key, value) in toml.map(config_file::parse).transpose()?.unwrap_or_default() {
+            config.set(&key, &value)?;
+        }
+        for (name, raw) in vars {
+            let Some(setting) = name.strip_prefix(ENV_PREFIX) else { continue };
+            let key = SETTINGS
+                .iter()
+                .find(|key| key.replace('.', "_").eq_ignore_ascii_case(setting))
+                .ok_or_else(|| ConfigError::invalid(&name, "unknown setting"))?;
+            config.set(key, &Value::from_env(&raw))?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Apply one `section.key` setting
+    fn set(&mut self, key: &str, value: &Value) -> Result<(), ConfigError> {
+        match key {
+            "bind" => self.bind = value.as_str(key)?,
+            "port" => {
+                self.port = value.as_u64(key)?.try_into().map_err(|_| ConfigError::invalid(key, "must be at most 65535"))?
+            }
+            "unix_socket" => self.unix_socket = Some(PathBuf::from(value.as_str(key)?)),
+            "proxy_protocol" => self.proxy_protocol = value.as_bool(key)?,
+            "kv_workers" => self.kv_workers = value.as_usize(key)?,
+            "vector_workers" => self.vector_workers = value.as_usize(key)?,
+            "queue_capacity" => self.queue_capacity = value.as_usize(key)?,
+            "ttl_cleaner_interval" => self.ttl_cleaner_interval = value.as_u64(key)?,
+            "ttl_adaptive" => self.ttl_adaptive = value.as_bool(key)?,
+            "queue_degraded_ratio" => self.queue_degraded_ratio = value.as_f64(key)?,
+            "memory_degraded_ratio" => self.memory_degraded_ratio = value.as_f64(key)?,
+            "disabled_commands" => {
+                self.disabled_commands = value.as_str_list(key)?.iter().map(|c| c.to_uppercase()).collect()
+            }
+            "notify_keyspace_events" => self.notify_keyspace_events = value.as_bool(key)?,
+            "lazy_free" => self.lazy_free = value.as_bool(key)?,
+            "route_by_key" => self.route_by_key = value.as_bool(key)?,
+            "shard_count" => self.shard_count = value.as_usize(key)?,
+            "databases" => self.databases = value.as_usize(key)?,
+            "max_connections" => self.max_connections = value.as_usize(key)?,
+            "connection_limit_policy" => {
+                self.connection_limit_policy = match value.as_str(key)?.to_ascii_lowercase().as_str() {
+                    "reject" => ConnectionLimitPolicy::Reject,
+                    "wait" => ConnectionLimitPolicy::Wait,
+                    _ => return Err(ConfigError::invalid(key, "expected `reject` or `wait`")),
+                }
+            }
+            "idle_timeout" => self.idle_timeout = value.as_u64(key)?,
+            "queue_send_timeout" => self.queue_send_timeout = value.as_u64(key)?,
+            "compression_threshold" => self.compression_threshold = value.as_usize(key)?,
+            "max_frame_size" => self.max_frame_size = value.as_usize(key)?,
+            "max_value_size" => self.max_value_size = value.as_usize(key)?,
+            "rate_limit" => self.rate_limit = value.as_f64(key)?,
+            "rate_limit_burst" => {
+                self.rate_limit_burst =
+                    value.as_u64(key)?.try_into().map_err(|_| ConfigError::invalid(key, "out of range"))?
+            }
+            "slowlog_log_slower_than" => self.slowlog_log_slower_than = value.as_u64(key)?,
+            "slowlog_max_len" => self.slowlog_max_len = value.as_usize(key)?,
+            "replica_max_lag" => self.replica_max_lag = value.as_u64(key)?,
+            "tls.enabled" => self.tls.enabled = value.as_bool(key)?,
+            "tls.cert_file" => self.tls.cert_file = PathBuf::from(value.as_str(key)?),
+            "tls.key_file" => self.tls.key_file = PathBuf::from(value.as_str(key)?),
+            "tls.ca_file" => self.tls.ca_file = Some(PathBuf::from(value.as_str(key)?)),
+            "tls.require_client_cert" => self.tls.require_client_cert = value.as_bool(key)?,
+            "tls.min_version" => {
+                self.tls.min_version = match value.as_str(key)?.as_str() {
+                    "1.2" => TlsVersion::Tls12,
+                    "1.3" => TlsVersion::Tls13,
+                    _ => return Err(ConfigError::invalid(key, "expected \"1.2\" or \"1.3\"")),
+                }
+            }
+            "eviction.policy" => {
+                self.eviction.policy = match value.as_str(key)?.to_ascii_lowercase().replace('_', "-").as_str() {
+                    "none" | "noeviction" => EvictionPolicy::None,
+                    "lru" | "allkeys-lru" => EvictionPolicy::Lru,
+                    "lfu" | "allkeys-lfu" => EvictionPolicy::Lfu,
+                    "random" | "allkeys-random" => EvictionPolicy::Random,
+                    "volatile-lru" => EvictionPolicy::VolatileLru,
+                    "volatile-lfu" => EvictionPolicy::VolatileLfu,
+                    "volatile-random" => EvictionPolicy::VolatileRandom,
+                    "volatile-ttl" => EvictionPolicy::VolatileTtl,
+                    _ => return Err(ConfigError::invalid(key, "unknown eviction policy")),
+                }
+            }
+            "eviction.max_memory" => self.eviction.max_memory = value.as_usize(key)?,
+            "eviction.max_keys" => self.eviction.max_keys = value.as_usize(key)?,
+            "eviction.sample_size" => self.eviction.sample_size = value.as_usize(key)?,
+            "eviction.lfu_decay_interval" => {
+                self.eviction.lfu_decay_interval = Duration::from_secs(value.as_u64(key)?)
+            }
+            "eviction.oom_action" => {
+                self.eviction.oom_action = match value.as_str(key)?.to_ascii_lowercase().as_str() {
+                    "reject" => OomAction::Reject,
+                    "evict" => OomAction::Evict,
+                    _ => return Err(ConfigError::invalid(key, "expected \"reject\" or \"evict\"")),
+                }
+            }
+            "snapshot.dir" => self.snapshot.get_or_insert_with(Default::default).dir = PathBuf::from(value.as_str(key)?),
+            "snapshot.interval" => self.snapshot.get_or_insert_with(Default::default).interval_secs = value.as_u64(key)?,
+            "snapshot.max_snapshots" => {
+                self.snapshot.get_or_insert_with(Default::default).max_snapshots = value.as_usize(key)?
+            }
+            "snapshot.compress" => self.snapshot.get_or_insert_with(Default::default).compress = value.as_bool(key)?,
+            "aof.path" => self.aof.get_or_insert_with(Default::default).path = PathBuf::from(value.as_str(key)?),
+            "aof.sync" => {
+                self.aof.get_or_insert_with(Default::default).sync_mode =
+                    match value.as_str(key)?.to_ascii_lowercase().as_str() {
+                        "no" => AofSyncMode::No,
+                        "everysec" => AofSyncMode::EverySecond,
+                        "always" => AofSyncMode::Always,
+                        _ => return Err(ConfigError::invalid(key, "expected `no`, `everysec` or `always`")),
+                    }
+            }
+            "aof.rewrite_threshold" => {
+                self.aof.get_or_insert_with(Default::default).rewrite_threshold = value.as_usize(key)?
+            }
+            _ => return Err(ConfigError::invalid(key, "unknown setting")),
+        }
+        Ok(())
+    }
+
+    /// Reject settings the server can't run with
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let check = |ok: bool, key: &str, message: &str| if ok { Ok(()) } else { Err(ConfigError::invalid(key, message)) };
+        check(!self.bind.is_empty(), "bind", "must not be empty")?;
+        check(self.port != 0, "port", "must not be 0")?;
+        let too_many = format!("at most {} workers", MAX_WORKERS);
+        check(self.kv_workers <= MAX_WORKERS, "kv_workers", &too_many)?;
+        check(self.vector_workers <= MAX_WORKERS, "vector_workers", &too_many)?;
+        check(self.queue_capacity > 0, "queue_capacity", "must be at least 1")?;
+        check(self.databases > 0, "databases", "must be at least 1")?;
+        check(
+            self.shard_count == 0 || (self.shard_count > 1 && self.shard_count.is_power_of_two()),
+            "shard_count",
+            "must be 0 (auto) or a power of two above 1",
+        )?;
+        check(
+            self.queue_degraded_ratio > 0.0 && self.queue_degraded_ratio <= 1.0,
+            "queue_degraded_ratio",
+            "must be in (0, 1]",
+        )?;
+        check(
+            self.memory_degraded_ratio > 0.0 && self.memory_degraded_ratio <= 1.0,
+            "memory_degraded_ratio",
+            "must be in (0, 1]",
+        )?;
+        check(self.rate_limit.is_finite() && self.rate_limit >= 0.0, "rate_limit", "must not be negative")?;
+        check(
+            self.rate_limit == 0.0 || self.rate_limit_burst > 0,
+            "rate_limit_burst",
+            "must be at least 1 when rate_limit is set",
+        )?;
+        if self.tls.enabled {
+            check(!self.tls.cert_file.as_os_str().is_empty(), "tls.cert_file", "required when TLS is enabled")?;
+            check(!self.tls.key_file.as_os_str().is_empty(), "tls.key_file", "required when TLS is enabled")?;
+        }
+        check(
+            !self.tls.require_client_cert || self.tls.ca_file.is_some(),
+            "tls.ca_file",
+            "required when client certificates are required",
+        )?;
+        check(
+            self.eviction.policy == EvictionPolicy::None || self.eviction.max_memory > 0 || self.eviction.max_keys > 0,
+            "eviction.policy",
+            "needs eviction.max_memory or eviction.max_keys",
+        )?;
+        if let Some(snapshot) = &self.snapshot {
+            check(snapshot.max_snapshots > 0, "snapshot.max_snapshots", "must be at least 1")?;
+        }
+        Ok(())
+    }
+
     /// Create a new config with custom port
     pub fn with_port(mut self, port: u16) -> Self {
         self.port = port;
         self
     }
 
+    /// Read client addresses from PROXY protocol headers (concurrent server only)
+    pub fn with_proxy_protocol(mut self, enabled: bool) -> Self {
+        self.proxy_protocol = enabled;
+        self
+    }
+
+    /// Also listen on a Unix domain socket (concurrent server only)
+    pub fn with_unix_socket(mut self, path: impl Into<PathBuf>) -> Self {
+        self.unix_socket = Some(path.into());
+        self
+    }
+
     /// Create a new config with custom bind address
     pub fn with_bind(mut self, bind: impl Into<String>) -> Self {
         self.bind = bind.into();
@@ -49,4 +490,190 @@ impl Config {
         self.ttl_cleaner_interval = interval;
         self
     }
+
+    /// Use `count` DashMap shards per database (0 = auto)
+    pub fn with_shard_count(mut self, count: usize) -> Self {
+        self.shard_count = count;
+        self
+    }
+
+    /// DashMap shards per database, resolving auto from the KV worker count
+    pub fn shards(&self) -> usize {
+        if self.shard_count > 0 {
+            return self.shard_count;
+        }
+        let kv_workers = if self.kv_workers == 0 { num_cpus::get() } else { self.kv_workers };
+        (kv_workers * 4).next_power_of_two()
+    }
+
+    /// Set the number of logical databases (at least 1)
+    pub fn with_databases(mut self, databases: usize) -> Self {
+        self.databases = databases.max(1);
+        self
+    }
+
+    /// Set the maximum number of simultaneous connections (0 = unlimited)
+    pub fn with_max_connections(mut self, max: usize) -> Self {
+        self.max_connections = max;
+        self
+    }
+
+    /// Set what happens when the connection limit is reached
+    pub fn with_connection_limit_policy(mut self, policy: ConnectionLimitPolicy) -> Self {
+        self.connection_limit_policy = policy;
+        self
+    }
+
+    /// Set the idle connection timeout in seconds (0 = disabled)
+    pub fn with_idle_timeout(mut self, secs: u64) -> Self {
+        self.idle_timeout = secs;
+        self
+    }
+
+    /// Idle connection timeout, if enabled
+    pub fn idle_timeout_duration(&self) -> Option<Duration> {
+        (self.idle_timeout > 0).then(|| Duration::from_secs(self.idle_timeout))
+    }
+
+    /// Set how long to wait on a full worker queue before replying BUSY (ms)
+    pub fn with_queue_send_timeout(mut self, ms: u64) -> Self {
+        self.queue_send_timeout = ms;
+        self
+    }
+
+    /// Wait on a full worker queue before replying BUSY
+    pub fn queue_send_timeout_duration(&self) -> Duration {
+        Duration::from_millis(self.queue_send_timeout)
+    }
+
+    /// Limit each user (or unauthenticated client IP) to `per_sec` commands
+    /// per second, allowing bursts of up to `burst` (0 = unlimited)
+    pub fn with_rate_limit(mut self, per_sec: f64, burst: u32) -> Self {
+        self.rate_limit = per_sec;
+        self.rate_limit_burst = burst;
+        self
+    }
+
+    /// Log commands slower than `threshold_us` microseconds, keeping the
+    /// latest `max_len` (0 = disabled)
+    pub fn with_slowlog(mut self, threshold_us: u64, max_len: usize) -> Self {
+        self.slowlog_log_slower_than = threshold_us;
+        self.slowlog_max_len = max_len;
+        self
+    }
+
+    /// Enable or disable keyspace notifications
+    pub fn with_keyspace_notifications(mut self, enabled: bool) -> Self {
+        self.notify_keyspace_events = enabled;
+        self
+    }
+
+    /// Free large deleted values in the background (concurrent server only)
+    pub fn with_lazy_free(mut self, enabled: bool) -> Self {
+        self.lazy_free = enabled;
+        self
+    }
+
+    /// Route keyed KV commands to a worker by slot (concurrent server only)
+    pub fn with_route_by_key(mut self, enabled: bool) -> Self {
+        self.route_by_key = enabled;
+        self
+    }
+
+    /// Disable commands by name (case-insensitive)
+    pub fn with_disabled_commands<I, S>(mut self, commands: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.disabled_commands
+            .extend(commands.into_iter().map(|c| c.as_ref().to_uppercase()));
+        self
+    }
+
+    /// Check if a command has been disabled
+    pub fn is_command_disabled(&self, name: &str) -> bool {
+        !self.disabled_commands.is_empty()
+            && self.disabled_commands.contains(&name.to_uppercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+# Sample deployment
+bind = "127.0.0.1"
+port = 7000
+kv_workers = 8
+queue_capacity = 50_000
+disabled_commands = ["keys", "flushall"]  # trailing comment
+connection_limit_policy = "wait"
+
+[tls]
+enabled = true
+cert_file = "/etc/celrix/server.crt"
+key_file = "/etc/celrix/server.key"
+min_version = "1.2"
+
+[eviction]
+policy = "volatile-lru"
+max_memory = 1073741824
+oom_action = "evict"
+
+[snapshot]
+dir = "/var/lib/celrix"
+interval = 60
+
+[aof]
+sync = "always"
+"#;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_load_file_with_env_overrides() {
+        let config = Config::from_sources(Some(SAMPLE), env(&[])).unwrap();
+        assert_eq!((config.bind.as_str(), config.port, config.kv_workers), ("127.0.0.1", 7000, 8));
+        assert_eq!(config.queue_capacity, 50_000);
+        assert!(config.is_command_disabled("KEYS") && config.is_command_disabled("flushall"));
+        assert_eq!(config.connection_limit_policy, ConnectionLimitPolicy::Wait);
+        assert!(config.tls.enabled);
+        assert_eq!(config.tls.min_version, TlsVersion::Tls12);
+        assert_eq!(config.eviction.policy, EvictionPolicy::VolatileLru);
+        assert_eq!(config.eviction.oom_action, OomAction::Evict);
+        assert_eq!(config.snapshot.as_ref().unwrap().interval_secs, 60);
+        assert_eq!(config.aof.as_ref().unwrap().sync_mode, AofSyncMode::Always);
+
+        let vars = env(&[("CELRIX_PORT", "7001"), ("CELRIX_TLS_ENABLED", "false"), ("HOME", "/root")]);
+        let config = Config::from_sources(Some(SAMPLE), vars).unwrap();
+        assert_eq!(config.port, 7001);
+        assert!(!config.tls.enabled);
+        assert_eq!(config.kv_workers, 8);
+    }
+
+    #[test]
+    fn test_invalid_config_rejected() {
+        let invalid_key = |toml: &str, vars: &[(&str, &str)]| match Config::from_sources(Some(toml), env(vars)) {
+            Err(ConfigError::Invalid { key, .. }) => key,
+            other => panic!("Expected invalid setting, got {:?}", other),
+        };
+        assert_eq!(invalid_key("port = 0", &[]), "port");
+        assert_eq!(invalid_key("", &[("CELRIX_PORT", "70000")]), "port");
+        assert_eq!(invalid_key("kv_workers = -1", &[]), "kv_workers");
+        assert_eq!(invalid_key("vector_workers = 100000", &[]), "vector_workers");
+        assert_eq!(invalid_key("queue_capacity = 0", &[]), "queue_capacity");
+        assert_eq!(invalid_key("shard_count = 48", &[]), "shard_count");
+        assert_eq!(invalid_key("", &[("CELRIX_SHARD_COUNT", "1")]), "shard_count");
+        assert_eq!(invalid_key("[eviction]\npolicy = \"lru\"", &[]), "eviction.policy");
+        assert_eq!(invalid_key("", &[("CELRIX_NO_SUCH_SETTING", "1")]), "CELRIX_NO_SUCH_SETTING");
+
+        assert!(matches!(
+            Config::from_sources(Some("port = \"7000"), env(&[])),
+            Err(ConfigError::Parse { line: 1, .. })
+        ));
+    }
 }
@@ -0,0 +1,210 @@
+//! Config File Parsing
+//!
+//! The subset of TOML the server config needs: `[section]` headers and
+//! `key = value` lines holding strings, integers, floats, booleans, or
+//! single-line arrays of those.
+
+use super::ConfigError;
+
+/// A setting read from a config file or environment variable
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    /// Environment variables are untyped; fields parse them on demand
+    pub(crate) fn from_env(raw: &str) -> Self {
+        Value::Str(raw.trim().to_string())
+    }
+
+    pub(crate) fn as_str(&self, key: &str) -> Result<String, ConfigError> {
+        match self {
+            Value::Str(s) => Ok(s.clone()),
+            _ => Err(ConfigError::invalid(key, "expected a string")),
+        }
+    }
+
+    pub(crate) fn as_u64(&self, key: &str) -> Result<u64, ConfigError> {
+        match self {
+            Value::Int(i) => u64::try_from(*i).map_err(|_| ConfigError::invalid(key, "must not be negative")),
+            Value::Str(s) => s.replace('_', "").parse().map_err(|_| ConfigError::invalid(key, "expected an unsigned integer")),
+            _ => Err(ConfigError::invalid(key, "expected an unsigned integer")),
+        }
+    }
+
+    pub(crate) fn as_usize(&self, key: &str) -> Result<usize, ConfigError> {
+        self.as_u64(key)?.try_into().map_err(|_| ConfigError::invalid(key, "out of range"))
+    }
+
+    pub(crate) fn as_f64(&self, key: &str) -> Result<f64, ConfigError> {
+        match self {
+            Value::Float(f) => Ok(*f),
+            Value::Int(i) => Ok(*i as f64),
+            Value::Str(s) => s.parse().map_err(|_| ConfigError::invalid(key, "expected a number")),
+            _ => Err(ConfigError::invalid(key, "expected a number")),
+        }
+    }
+
+    pub(crate) fn as_bool(&self, key: &str) -> Result<bool, ConfigError> {
+        match self {
+            Value::Bool(b) => Ok(*b),
+            Value::Str(s) => match s.to_ascii_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => Ok(true),
+                "false" | "no" | "off" | "0" => Ok(false),
+                _ => Err(ConfigError::invalid(key, "expected true or false")),
+            },
+            _ => Err(ConfigError::invalid(key, "expected true or false")),
+        }
+    }
+
+    /// Array of strings; a plain string is split on commas
+    pub(crate) fn as_str_list(&self, key: &str) -> Result<Vec<String>, ConfigError> {
+        match self {
+            Value::Array(items) => items.iter().map(|item| item.as_str(key)).collect(),
+            Value::Str(s) => Ok(s.split(',').map(str::trim).filter(|s| !s.is_empty()).map(String::from).collect()),
+            _ => Err(ConfigError::invalid(key, "expected a list of strings")),
+        }
+    }
+}
+
+/// Parse a config file into `(section.key, value)` pairs, in file order
+pub(crate) fn parse(input: &str) -> Result<Vec<(String, Value)>, ConfigError> {
+    let mut section = String::new();
+    let mut settings = Vec::new();
+    for (index, raw) in input.lines().enumerate() {
+        let line_no = index + 1;
+        let err = |message: &str| ConfigError::Parse { line: line_no, message: message.to_string() };
+        let line = strip_comment(raw).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            let name = header.strip_suffix(']').ok_or_else(|| err("unterminated section header"))?.trim();
+            if !is_bare_key(name) {
+                return Err(err("invalid section name"));
+            }
+            section = name.to_string();
+            continue;
+        }
+        let (key, value) = line.split_once('=').ok_or_else(|| err("expected `key = value`"))?;
+        let key = key.trim();
+        if !is_bare_key(key) {
+            return Err(err("invalid key"));
+        }
+        let value = parse_value(value.trim()).map_err(|message| err(&message))?;
+        let key = if section.is_empty() { key.to_string() } else { format!("{}.{}", section, key) };
+        settings.push((key, value));
+    }
+    Ok(settings)
+}
+
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Drop a trailing `# comment`, leaving `#` inside strings alone
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_value(raw: &str) -> Result<Value, String> {
+    if let Some(inner) = raw.strip_prefix('[') {
+        let inner = inner.strip_suffix(']').ok_or("unterminated array")?;
+        return split_array(inner)?
+            .into_iter()
+            .map(|item| parse_value(item.trim()))
+            .collect::<Result<_, _>>()
+            .map(Value::Array);
+    }
+    if let Some(inner) = raw.strip_prefix('"') {
+        return unescape(inner.strip_suffix('"').ok_or("unterminated string")?).map(Value::Str);
+    }
+    if let Some(inner) = raw.strip_prefix('\'') {
+        return Ok(Value::Str(inner.strip_suffix('\'').ok_or("unterminated string")?.to_string()));
+    }
+    match raw {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        "" => return Err("missing value".to_string()),
+        _ => {}
+    }
+    let number = raw.replace('_', "");
+    if let Ok(i) = number.parse::<i64>() {
+        return Ok(Value::Int(i));
+    }
+    number.parse::<f64>().map(Value::Float).map_err(|_| format!("unrecognized value `{}`", raw))
+}
+
+/// Split array items on commas outside strings, allowing a trailing comma
+fn split_array(inner: &str) -> Result<Vec<&str>, String> {
+    let mut items = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[') => return Err("nested arrays are not supported".to_string()),
+            (None, ',') => {
+                items.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        escaped = false;
+    }
+    if quote.is_some() {
+        return Err("unterminated string".to_string());
+    }
+    let last = &inner[start..];
+    if !last.trim().is_empty() {
+        items.push(last);
+    } else if items.last().is_some_and(|item| item.trim().is_empty()) {
+        return Err("empty array item".to_string());
+    }
+    Ok(items)
+}
+
+fn unescape(s: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            other => return Err(format!("unsupported escape `\\{}`", other.map_or(String::new(), String::from))),
+        }
+    }
+    Ok(out)
+}
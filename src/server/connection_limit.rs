@@ -0,0 +1,82 @@
+//! Connection Limits
+//!
+//! Caps simultaneous client connections and tracks them in metrics.
+
+use std::sync::Arc;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::metrics::Metrics;
+use crate::protocol::Frame;
+
+use super::config::{Config, ConnectionLimitPolicy};
+
+/// Error sent to clients rejected at the connection limit
+pub const MAX_CLIENTS_ERROR: &str = "ERR max number of clients reached";
+
+/// Admission control for the accept loop
+pub struct ConnectionLimiter {
+    /// None = unlimited
+    semaphore: Option<Arc<Semaphore>>,
+    policy: ConnectionLimitPolicy,
+    metrics: Arc<Metrics>,
+}
+
+/// Held for the lifetime of a connection; frees its slot on drop
+pub struct ConnectionGuard {
+    _permit: Option<OwnedSemaphorePermit>,
+    metrics: Arc<Metrics>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.metrics.connection_closed();
+    }
+}
+
+impl ConnectionLimiter {
+    pub fn new(config: &Config, metrics: Arc<Metrics>) -> Self {
+        let semaphore = (config.max_connections > 0)
+            .then(|| Arc::new(Semaphore::new(config.max_connections)));
+        Self {
+            semaphore,
+            policy: config.connection_limit_policy,
+            metrics,
+        }
+    }
+
+    /// Wait for a free slot before accepting (Wait policy only)
+    pub async fn reserve(&self) -> Option<OwnedSemaphorePermit> {
+        match (&self.semaphore, self.policy) {
+            (Some(semaphore), ConnectionLimitPolicy::Wait) => {
+                semaphore.clone().acquire_owned().await.ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Admit an accepted connection, using a slot from `reserve` if any.
+    /// Returns None if the connection must be rejected.
+    pub fn admit(&self, reserved: Option<OwnedSemaphorePermit>) -> Option<ConnectionGuard> {
+        let permit = match (&self.semaphore, reserved) {
+            (None, _) => None,
+            (Some(_), Some(permit)) => Some(permit),
+            (Some(semaphore), None) => Some(semaphore.clone().try_acquire_owned().ok()?),
+        };
+        self.metrics.connection_opened();
+        Some(ConnectionGuard {
+            _permit: permit,
+            metrics: self.metrics.clone(),
+        })
+    }
+
+    /// Tell a rejected client why and close the socket
+    pub fn reject<S: AsyncWrite + Unpin + Send + 'static>(mut socket: S) {
+        tokio::spawn(async move {
+            let mut buf = bytes::BytesMut::new();
+            Frame::error(0, MAX_CLIENTS_ERROR).encode(&mut buf);
+            let _ = socket.write_all(&buf).await;
+            let _ = socket.shutdown().await;
+        });
+    }
+}
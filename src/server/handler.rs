@@ -3,12 +3,14 @@
 //! Processes VCP frames and dispatches commands.
 
 use crate::metrics::Metrics;
-use crate::protocol::{Command, Response, VcpCodec};
-use crate::storage::Store;
+use crate::protocol::{command_info, command_list, Command, Response, VcpCodec, COMMAND_TABLE};
+use crate::security::AuditLogger;
+use crate::server::Config;
+use crate::storage::{Lookup, SetCondition, Store};
 use crate::vector::SemanticCache;
 use futures::{SinkExt, StreamExt};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tokio::net::TcpStream;
 use tokio_util::codec::Framed;
 use tracing::debug;
@@ -18,52 +20,115 @@ pub struct Handler {
     store: Store,
     vector_store: SemanticCache,
     metrics: Arc<Metrics>,
+    config: Arc<Config>,
+    audit: Option<Arc<AuditLogger>>,
 }
 
 impl Handler {
     /// Create a new handler
-    pub fn new(store: Store, vector_store: SemanticCache, metrics: Arc<Metrics>) -> Self {
-        Self { store, vector_store, metrics }
+    pub fn new(
+        store: Store,
+        vector_store: SemanticCache,
+        metrics: Arc<Metrics>,
+        config: Arc<Config>,
+    ) -> Self {
+        Self { store, vector_store, metrics, config, audit: None }
+    }
+
+    /// Record disconnects in an audit log
+    pub fn with_audit(mut self, audit: Option<Arc<AuditLogger>>) -> Self {
+        self.audit = audit;
+        self
     }
 
     /// Run the handler for a connection
     pub async fn run(self, mut framed: Framed<TcpStream, VcpCodec>) -> std::io::Result<()> {
-        while let Some(result) = framed.next().await {
-            let frame = result?;
+        let idle_timeout = self.config.idle_timeout_duration();
+        let pool = framed.codec().pool().cloned();
+        let reason = loop {
+            let next = match idle_timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, framed.next()).await {
+                    Ok(next) => next,
+                    Err(_) => break "idle timeout",
+                },
+                None => framed.next().await,
+            };
+            let frame = match next {
+                Some(result) => result?,
+                None => break "client closed",
+            };
             let start = Instant::now();
 
             let request_id = frame.header.request_id;
-            let cmd_name = format!("{:?}", frame.header.opcode);
 
-            let response = match Command::from_frame(&frame) {
-                Ok(cmd) => self.execute(cmd),
-                Err(e) => Response::Error(e.to_string()),
+            let (cmd_name, response) = match Command::from_frame(&frame) {
+                Ok(cmd) => (cmd.name(), self.execute(cmd)),
+                Err(e) => ("INVALID", Response::protocol_error(e)),
             };
 
-            let response_frame = response.to_frame(request_id);
+            let response_frame = match &pool {
+                Some(pool) => response.to_frame_pooled(request_id, pool),
+                None => response.to_frame(request_id),
+            };
             framed.send(response_frame).await?;
 
             let elapsed = start.elapsed();
-            self.metrics.record_operation(&cmd_name, elapsed);
+            self.metrics.record_operation(cmd_name, elapsed);
             debug!(cmd = %cmd_name, latency = ?elapsed, "Command executed");
-        }
+        };
 
+        if let Some(audit) = &self.audit {
+            let peer = framed.get_ref().peer_addr().map(|a| a.ip().to_string());
+            audit.log_disconnect(peer.as_deref().unwrap_or("unknown"), reason);
+        }
         Ok(())
     }
 
     /// Execute a command and return response
     fn execute(&self, cmd: Command) -> Response {
+        if self.config.is_command_disabled(cmd.name()) {
+            return Response::Error("ERR command disabled".to_string());
+        }
+
         match cmd {
             Command::Ping => Response::Pong,
 
+            Command::CommandCount => Response::Integer(COMMAND_TABLE.len() as i64),
+
+            Command::CommandInfo { names } => Response::Array(command_info(&names)),
+
+            Command::CommandList => Response::Array(command_list()),
+
             Command::Get { key } => match self.store.get(&key) {
                 Some(value) => Response::Value(value),
                 None => Response::Nil,
             },
 
-            Command::Set { key, value, ttl } => {
-                self.store.set(key, value, ttl);
-                Response::Ok
+            Command::GetOrExpired { key } => match self.store.lookup(&key) {
+                Lookup::Live(value) => Response::Value(value),
+                Lookup::Expired => Response::Expired,
+                Lookup::Missing => Response::Nil,
+            },
+
+            Command::GetWithTtl { key } => match self.store.get_with_ttl(&key) {
+                Some((value, expires_at)) => Response::ValueWithTtl {
+                    value,
+                    ttl_ms: super::remaining_ms(expires_at),
+                },
+                None => Response::Nil,
+            },
+
+            Command::Set { key, value, ttl, options } => {
+                let condition = SetCondition::new(options.nx, options.xx);
+                let ttl = options.ttl_duration(ttl);
+                let (applied, old) = self.store.set_with(key, value, ttl, condition);
+                if options.get {
+                    old.map_or(Response::Nil, Response::Value)
+                } else if applied {
+                    Response::Ok
+                } else {
+                    Response::Nil
+                }
             }
 
             Command::Del { key } => {
@@ -71,11 +136,103 @@ impl Handler {
                 Response::Integer(if existed { 1 } else { 0 })
             }
 
+            // No background reclaimer in the single-threaded store
+            Command::Unlink { key } => Response::Integer(self.store.del(&key) as i64),
+
             Command::Exists { key } => {
                 let exists = self.store.exists(&key);
                 Response::Integer(if exists { 1 } else { 0 })
             }
 
+            Command::GetDel { key } => match self.store.get_del(&key) {
+                Some(value) => Response::Value(value),
+                None => Response::Nil,
+            },
+
+            Command::GetSet { key, value } => match self.store.get_set(key, value) {
+                Some(old) => Response::Value(old),
+                None => Response::Nil,
+            },
+
+            Command::SwapKey { key1, key2 } => {
+                self.store.swap(&key1, &key2);
+                Response::Ok
+            }
+
+            Command::Rename { src, dst } => {
+                if self.store.rename(&src, &dst) {
+                    Response::Ok
+                } else {
+                    Response::Error(super::NO_SUCH_KEY_ERROR.to_string())
+                }
+            }
+
+            Command::Copy { src, dst, replace } => Response::Integer(self.store.copy(&src, &dst, replace) as i64),
+
+            Command::RandomKey => self.store.random_key().map_or(Response::Nil, Response::Value),
+
+            Command::DbSize => Response::Integer(self.store.live_len() as i64),
+
+            Command::PExpire { key, ms } => {
+                let updated = self.store.pexpire(&key, Duration::from_millis(ms));
+                Response::Integer(updated as i64)
+            }
+
+            Command::Ttl { key } => Response::Integer(match self.store.pttl(&key) {
+                None => -2,
+                Some(None) => -1,
+                // Round to the nearest second, as Redis does
+                Some(Some(ttl)) => ((ttl.as_millis() + 500) / 1000) as i64,
+            }),
+
+            Command::PTtl { key } => Response::Integer(match self.store.pttl(&key) {
+                None => -2,
+                Some(None) => -1,
+                Some(Some(ttl)) => ttl.as_millis() as i64,
+            }),
+
+            Command::Append { key, value } => Response::Integer(self.store.append(key, &value) as i64),
+
+            Command::Strlen { key } => Response::Integer(self.store.strlen(&key) as i64),
+
+            Command::DelPattern { pattern } => {
+                Response::Integer(self.store.del_matching(&String::from_utf8_lossy(&pattern)) as i64)
+            }
+
+            // DEBUG needs Admin, and no users are configured in
+            // single-threaded mode
+            Command::DebugSleep { .. } | Command::DebugObject { .. } => {
+                Response::Error("ERR DEBUG is not supported in single-threaded mode".to_string())
+            }
+
+            Command::Dump { key } => match self.store.get_with_ttl(&key) {
+                Some((value, expires_at)) => Response::Value(super::dump_payload(value, expires_at)),
+                None => Response::Nil,
+            },
+
+            Command::Restore { key, blob, ttl_ms, replace } => match super::restore_payload(&blob, ttl_ms) {
+                Err(e) => Response::Error(e),
+                Ok(None) => {
+                    if replace {
+                        self.store.del(&key);
+                    }
+                    Response::Ok
+                }
+                Ok(Some((value, ttl))) => {
+                    let condition = if replace { SetCondition::Always } else { SetCondition::IfAbsent };
+                    match self.store.set_with(key, value, ttl, condition) {
+                        (true, _) => Response::Ok,
+                        (false, _) => Response::Error(super::BUSYKEY_ERROR.to_string()),
+                    }
+                }
+            },
+
+            // The single-threaded store only holds strings
+            Command::Type { key } => {
+                let kind = if self.store.exists(&key) { "string" } else { "none" };
+                Response::Value(bytes::Bytes::from_static(kind.as_bytes()))
+            }
+
             Command::VAdd { key, vector } => {
                 // Use key as value for now
                 let value = key.clone();
@@ -85,11 +242,89 @@ impl Handler {
                 }
             }
 
-            Command::VSearch { vector, k } => {
+            Command::VAddBatch { entries } => {
+                let total = entries.len();
+                let failed = self
+                    .vector_store
+                    .set_many(entries.into_iter().map(|(key, vector)| (key.clone(), vector, key)));
+                Response::BatchAdded {
+                    added: (total - failed.len()) as u32,
+                    failed: failed.into_iter().map(|i| i as u32).collect(),
+                }
+            }
+
+            Command::VSearch { vector, k: _ } => {
+                let dimension = self.vector_store.config().dimension;
+                if vector.len() != dimension {
+                    return Response::Error(format!(
+                        "Dimension mismatch: expected {}, got {}",
+                        dimension,
+                        vector.len()
+                    ));
+                }
                 let results = self.vector_store.semantic_get(&vector);
                 let keys: Vec<bytes::Bytes> = results.into_iter().map(|r| r.key).collect();
                 Response::Array(keys)
             }
+
+            // Single-threaded mode has one keyspace
+            Command::Select { db: 0 } => Response::Ok,
+            Command::Select { .. } => Response::Error("ERR DB index is out of range".to_string()),
+
+            Command::ClusterSlots => {
+                Response::Error("ERR This instance has cluster support disabled".to_string())
+            }
+
+            // Single-threaded mode has no replicas to wait for
+            Command::Wait { .. } => Response::Integer(0),
+
+            Command::Subscribe { .. } => {
+                Response::Error("ERR SUBSCRIBE is not supported in single-threaded mode".to_string())
+            }
+
+            // Single-threaded mode keeps no slowlog
+            Command::SlowlogGet { .. } => Response::Array(Vec::new()),
+
+            Command::ClientList | Command::ClientKill { .. } => {
+                Response::Error("ERR CLIENT is not supported in single-threaded mode".to_string())
+            }
+
+            // No users are configured in single-threaded mode
+            Command::Auth { .. } => {
+                Response::Error("ERR AUTH called without any users configured".to_string())
+            }
+
+            // Nothing is kept per connection in single-threaded mode
+            Command::Reset => Response::Ok,
+
+            // Single-threaded mode serves every slot
+            Command::Asking => Response::Ok,
+
+            // Negotiated, but frames aren't checked against it and nothing is compressed
+            Command::Hello { versions, .. } => match super::negotiate_version(&versions) {
+                Ok(version) => super::hello_reply(version, 0),
+                Err(e) => Response::Error(e),
+            },
+
+            Command::Multi | Command::Exec { .. } | Command::Discard | Command::Watch { .. } => {
+                Response::Error("ERR transactions are not supported in single-threaded mode".to_string())
+            }
+
+            Command::FlushDb | Command::FlushAll { vectors: false } => {
+                self.store.clear();
+                Response::Ok
+            }
+
+            Command::FlushAll { vectors: true } => {
+                self.store.clear();
+                self.vector_store.clear();
+                Response::Ok
+            }
+
+            // Extended commands are only served in concurrent mode
+            Command::Extended(ext) => {
+                Response::Error(format!("ERR unsupported command '{}'", ext.name()))
+            }
         }
     }
 }
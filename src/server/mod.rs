@@ -4,33 +4,256 @@
 //! Supports both single-threaded and multi-threaded modes.
 
 mod buffer_pool;
+mod client_registry;
 mod command_queue;
 mod config;
+mod config_file;
+mod connection_limit;
 mod handler;
+mod proxy_protocol;
+mod rate_limit;
+mod unix_socket;
 mod worker_pool;
 
 pub use buffer_pool::BufferPool;
+pub use client_registry::{ClientHandle, ClientRegistry, NO_SUCH_CLIENT_ERROR};
 pub use command_queue::{CommandQueue, WorkItem, WorkResult};
-pub use config::Config;
+pub use config::{Config, ConfigError, ConnectionLimitPolicy};
+pub use connection_limit::{ConnectionGuard, ConnectionLimiter, MAX_CLIENTS_ERROR};
 pub use handler::Handler;
-pub use worker_pool::{WorkerPool, WorkerPoolConfig};
+pub use rate_limit::{RateLimiter, Throttled};
+pub use unix_socket::UnixSocketListener;
+pub use worker_pool::{route_key, WorkerPool, WorkerPoolConfig};
 
+use crate::cluster::{ClusterRouter, FollowerState, QuorumFence, ReplicationManager, Route};
 use crate::metrics::Metrics;
-use crate::protocol::VcpCodec;
+use crate::observability::{HealthCheck, Slowlog};
+use crate::persistence::SnapshotEntry;
+use crate::protocol::{Command, Frame, OpCode, Pool, Response, VcpCodec, CAP_COMPRESSION, SUPPORTED_VERSIONS};
+use crate::pubsub::{KeyEventSubscription, KeyspaceNotifier, PubSub};
+use crate::security::{AclManager, AuditEvent, AuditEventType, AuditLogger, AuthManager, AuthResult, Permission};
 use bytes::Bytes;
-use crate::storage::{ConcurrentStore, ConcurrentTtlCleaner, Store, TtlCleaner};
+use crate::storage::{AdaptiveExpiry, ConcurrentStore, ConcurrentTtlCleaner, Databases, Store, TtlCleaner};
 use crate::vector::SemanticCache;
-use std::sync::Arc;
+use crossbeam::channel::TrySendError;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpListener;
+use tokio::sync::OwnedSemaphorePermit;
 use tokio_util::codec::Framed;
 use tracing::{error, info};
 
+/// Milliseconds until `expires_at` as reported to clients (-1 = no expiry)
+pub(crate) fn remaining_ms(expires_at: Option<Instant>) -> i64 {
+    expires_at.map_or(-1, |at| at.saturating_duration_since(Instant::now()).as_millis() as i64)
+}
+
+fn unix_now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+/// Highest of the client's `versions` this server supports (the newest
+/// supported when none are offered), or a NOPROTO error
+pub(crate) fn negotiate_version(versions: &[u8]) -> Result<u8, String> {
+    if versions.is_empty() {
+        return Ok(*SUPPORTED_VERSIONS.iter().max().unwrap());
+    }
+    versions
+        .iter()
+        .copied()
+        .filter(|v| SUPPORTED_VERSIONS.contains(v))
+        .max()
+        .ok_or_else(|| {
+            let list = |vs: &[u8]| vs.iter().map(u8::to_string).collect::<Vec<_>>().join(",");
+            format!(
+                "NOPROTO unsupported protocol version {}, server supports {}",
+                list(versions),
+                list(SUPPORTED_VERSIONS)
+            )
+        })
+}
+
+/// HELLO reply: `["proto", <version>, "capabilities", <CAP_* flags>]`
+pub(crate) fn hello_reply(version: u8, capabilities: u32) -> Response {
+    Response::Array(vec![
+        Bytes::from_static(b"proto"),
+        Bytes::from(version.to_string()),
+        Bytes::from_static(b"capabilities"),
+        Bytes::from(capabilities.to_string()),
+    ])
+}
+
+/// DEBUG OBJECT record: `encoding:<int|embstr|raw> serializedlength:<bytes> ttl_ms:<ms|-1>`
+/// Appends `idle_ms` and `freq` when the store tracks access (`access`
+/// is the idle time and access count)
+pub(crate) fn debug_object(value: &Bytes, expires_at: Option<Instant>, access: Option<(Duration, u64)>) -> Bytes {
+    let is_int = value.len() <= 20 && std::str::from_utf8(value).is_ok_and(|s| s.parse::<i64>().is_ok());
+    let encoding = match value.len() {
+        _ if is_int => "int",
+        0..=44 => "embstr",
+        _ => "raw",
+    };
+    let mut info = format!(
+        "encoding:{} serializedlength:{} ttl_ms:{}",
+        encoding,
+        value.len(),
+        remaining_ms(expires_at)
+    );
+    if let Some((idle, freq)) = access {
+        info.push_str(&format!(" idle_ms:{} freq:{}", idle.as_millis(), freq));
+    }
+    Bytes::from(info)
+}
+
+/// DUMP payload for a live value, with its expiry as a unix timestamp
+pub(crate) fn dump_payload(value: Bytes, expires_at: Option<Instant>) -> Bytes {
+    let expires_at_ms = expires_at.map(|at| unix_now_ms() + (remaining_ms(Some(at)) as u64).max(1));
+    SnapshotEntry::dump(value, expires_at_ms)
+}
+
+/// Value and TTL to RESTORE from a DUMP payload; `ttl_ms` > 0 replaces the
+/// dumped TTL. `Ok(None)` if the dumped value has already expired.
+pub(crate) fn restore_payload(blob: &[u8], ttl_ms: u64) -> Result<Option<(Bytes, Option<Duration>)>, String> {
+    let entry = SnapshotEntry::parse_dump(blob).map_err(|e| format!("ERR {}", e))?;
+    let ttl = match (ttl_ms, entry.expires_at_ms) {
+        (0, None) => None,
+        (0, Some(at)) => match at.checked_sub(unix_now_ms()).filter(|&ms| ms > 0) {
+            Some(ms) => Some(Duration::from_millis(ms)),
+            None => return Ok(None),
+        },
+        (ms, _) => Some(Duration::from_millis(ms)),
+    };
+    Ok(Some((entry.value, ttl)))
+}
+
+/// Codec for a client connection, compressing large payloads if enabled
+fn connection_codec(config: &Config, pool: BufferPool) -> VcpCodec {
+    let codec = VcpCodec::new().with_pool(pool).with_max_frame_size(config.max_frame_size);
+    match config.compression_threshold {
+        0 => codec,
+        threshold => codec.with_compression(threshold),
+    }
+}
+
+/// How long a proxied connection may take to send its PROXY header
+const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What the concurrent server's accept loop builds each connection from
+struct Connections {
+    limiter: ConnectionLimiter,
+    buffer_pool: BufferPool,
+    kv_queue: CommandQueue,
+    /// Per-worker KV queues for keyed commands (empty = shared queue only)
+    kv_routes: Arc<[CommandQueue]>,
+    vector_queue: CommandQueue,
+    config: Arc<Config>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    cluster: Option<Arc<ClusterRouter>>,
+    audit: Option<Arc<AuditLogger>>,
+    auth: Option<Arc<AuthManager>>,
+    acl: Option<Arc<AclManager>>,
+    replication: Option<Arc<ReplicationManager>>,
+    follower: Option<Arc<FollowerState>>,
+    metrics: Arc<Metrics>,
+    pubsub: PubSub,
+    slowlog: Option<Arc<Slowlog>>,
+    clients: ClientRegistry,
+}
+
+impl Connections {
+    /// Admit `socket` and serve it on its own task, or reject it at the
+    /// connection limit. `peer_addr` is None for Unix socket clients.
+    fn accept<S>(&self, socket: S, peer_addr: Option<SocketAddr>, reserved: Option<OwnedSemaphorePermit>)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let peer = peer_addr.map_or_else(|| "unix socket".to_string(), |addr| addr.to_string());
+        let guard = match self.limiter.admit(reserved) {
+            Some(guard) => guard,
+            None => {
+                info!("Rejecting connection from {}: too many clients", peer);
+                ConnectionLimiter::reject(socket);
+                return;
+            }
+        };
+        info!("New connection from {}", peer);
+
+        let mut handler = ConcurrentHandler::new(self.kv_queue.clone(), self.vector_queue.clone(), self.config.clone())
+            .with_audit(self.audit.clone())
+            .with_auth(self.auth.clone())
+            .with_acl(self.acl.clone())
+            .with_replication(self.replication.clone())
+            .with_pubsub(self.pubsub.clone())
+            .with_slowlog(self.slowlog.clone())
+            .with_rate_limiter(self.rate_limiter.clone())
+            .with_metrics(self.metrics.clone())
+            .with_key_routes(self.kv_routes.clone());
+        if let Some(addr) = peer_addr {
+            handler = handler.with_peer_addr(addr);
+        }
+        if let Some(router) = self.cluster.clone() {
+            handler = handler.with_cluster(router);
+        }
+        if let Some(follower) = self.follower.clone() {
+            handler = handler.with_follower(follower);
+        }
+        let codec = connection_codec(&self.config, self.buffer_pool.clone());
+        let proxied = self.config.proxy_protocol && peer_addr.is_some();
+        let clients = self.clients.clone();
+
+        tokio::spawn(async move {
+            let mut socket = socket;
+            if proxied {
+                let header = tokio::time::timeout(PROXY_HEADER_TIMEOUT, proxy_protocol::read_v1(&mut socket)).await;
+                match header {
+                    Ok(Ok(Some(client))) => {
+                        info!("Connection from {} is proxied for {}", peer, client);
+                        handler = handler.with_peer_addr(client);
+                    }
+                    Ok(Ok(None)) => {}
+                    Ok(Err(e)) => {
+                        error!("Closing connection from {}: {}", peer, e);
+                        return;
+                    }
+                    Err(_) => {
+                        error!("Closing connection from {}: no PROXY header", peer);
+                        return;
+                    }
+                }
+            }
+
+            // Registered once the real client address is known
+            let handler = handler.with_clients(clients);
+            let framed = Framed::new(socket, codec);
+            if let Err(e) = handler.run(framed).await {
+                error!("Connection error from {}: {}", peer, e);
+            }
+
+            drop(guard);
+            info!("Connection closed: {}", peer);
+        });
+    }
+}
+
+/// Error for RESTORE onto an existing key without REPLACE
+pub(crate) const BUSYKEY_ERROR: &str = "BUSYKEY Target key name already exists.";
+
+/// Reply for commands that need an existing key (RENAME, DEBUG OBJECT)
+pub(crate) const NO_SUCH_KEY_ERROR: &str = "NOTFOUND no such key";
+
 /// CELRIX Server (Single-threaded mode - Phase 1 compatibility)
 pub struct Server {
     config: Config,
     store: Store,
     vector_store: SemanticCache,
     metrics: Arc<Metrics>,
+    audit: Option<Arc<AuditLogger>>,
 }
 
 impl Server {
@@ -41,36 +264,65 @@ impl Server {
             store: Store::new(),
             vector_store: SemanticCache::with_defaults(),
             metrics: Arc::new(Metrics::new()),
+            audit: None,
         }
     }
 
+    /// Record connection events in an audit log
+    pub fn with_audit_logger(mut self, audit: Arc<AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
     /// Run the server
     pub async fn run(self) -> std::io::Result<()> {
         let addr = format!("{}:{}", self.config.bind, self.config.port);
         let listener = TcpListener::bind(&addr).await?;
 
         info!("CELRIX server listening on {}", addr);
+        self.serve(listener).await
+    }
 
+    /// Serve connections from an already-bound listener
+    pub async fn serve(self, listener: TcpListener) -> std::io::Result<()> {
         // Start TTL cleaner
         TtlCleaner::spawn(self.store.clone(), self.config.ttl_cleaner_interval);
 
+        let config = Arc::new(self.config.clone());
+        let buffer_pool = BufferPool::with_defaults();
+        let limiter = ConnectionLimiter::new(&config, self.metrics.clone());
+
         loop {
+            let reserved = limiter.reserve().await;
             match listener.accept().await {
                 Ok((socket, peer_addr)) => {
+                    let guard = match limiter.admit(reserved) {
+                        Some(guard) => guard,
+                        None => {
+                            info!("Rejecting connection from {}: too many clients", peer_addr);
+                            ConnectionLimiter::reject(socket);
+                            continue;
+                        }
+                    };
                     info!("New connection from {}", peer_addr);
 
                     let store = self.store.clone();
                     let vector_store = self.vector_store.clone();
                     let metrics = self.metrics.clone();
+                    let config = config.clone();
+                    let pool = buffer_pool.clone();
+                    let audit = self.audit.clone();
 
                     tokio::spawn(async move {
-                        let framed = Framed::new(socket, VcpCodec::new());
-                        let handler = Handler::new(store, vector_store, metrics);
+                        let framed = Framed::new(socket, connection_codec(&config, pool));
+                        let handler = Handler::new(store, vector_store, metrics, config)
+                            .with_audit(audit);
 
                         if let Err(e) = handler.run(framed).await {
                             error!("Connection error from {}: {}", peer_addr, e);
                         }
 
+                        drop(guard);
                         info!("Connection closed: {}", peer_addr);
                     });
                 }
@@ -95,9 +347,28 @@ impl Server {
 /// CELRIX Concurrent Server (Multi-threaded mode - Phase 2)
 pub struct ConcurrentServer {
     config: Config,
-    store: ConcurrentStore,
+    databases: Databases,
     vector_store: SemanticCache,
     metrics: Arc<Metrics>,
+    health: Arc<RwLock<HealthCheck>>,
+    pubsub: PubSub,
+    /// Slot routing (None = clustering disabled)
+    cluster: Option<Arc<ClusterRouter>>,
+    audit: Option<Arc<AuditLogger>>,
+    /// User accounts for AUTH (None = authentication disabled)
+    auth: Option<Arc<AuthManager>>,
+    /// Per-user permissions (None = everyone may do everything)
+    acl: Option<Arc<AclManager>>,
+    /// Replication log writes are recorded in (None = no replicas)
+    replication: Option<Arc<ReplicationManager>>,
+    /// Refuses writes without cluster quorum (None = never fenced)
+    fence: Option<Arc<QuorumFence>>,
+    /// Replication progress when this node is a follower (None = leader)
+    follower: Option<Arc<FollowerState>>,
+    /// Commands slower than the configured threshold (None = disabled)
+    slowlog: Option<Arc<Slowlog>>,
+    /// Open connections, for CLIENT LIST and CLIENT KILL
+    clients: ClientRegistry,
     // worker_config removed, superseded by Config fields
 }
 
@@ -109,25 +380,113 @@ impl ConcurrentServer {
 
     /// Create with custom worker pool configuration
     pub fn with_worker_config(config: Config, _worker_config: WorkerPoolConfig) -> Self {
-        // Create store with shard count matching KV worker count or num_cpus
-        let num_started_workers = if config.kv_workers == 0 { num_cpus::get() } else { config.kv_workers };
-        let target_shards = num_started_workers * 4;
-        
-        // DashMap requires power of two
-        let num_shards = target_shards.next_power_of_two();
+        let num_shards = config.shards();
+
+        let pubsub = PubSub::new();
+        let databases = Databases::from_fn(config.databases, |db| {
+            let store = ConcurrentStore::with_shard_amount(num_shards).with_lazy_free(config.lazy_free);
+            if config.notify_keyspace_events {
+                store.with_notifier(KeyspaceNotifier::new(pubsub.clone()).with_db(db as u32))
+            } else {
+                store
+            }
+        });
+
+        let slowlog = (config.slowlog_max_len > 0).then(|| {
+            Arc::new(Slowlog::new(
+                Duration::from_micros(config.slowlog_log_slower_than),
+                config.slowlog_max_len,
+            ))
+        });
 
         Self {
             config,
-            store: ConcurrentStore::with_shard_amount(num_shards),
+            databases,
             vector_store: SemanticCache::with_defaults(),
             metrics: Arc::new(Metrics::new()),
+            health: Arc::new(RwLock::new(HealthCheck::new())),
+            pubsub,
+            cluster: None,
+            audit: None,
+            auth: None,
+            acl: None,
+            replication: None,
+            fence: None,
+            follower: None,
+            slowlog,
+            clients: ClientRegistry::new(),
         }
     }
 
+    /// Record connection events in an audit log
+    pub fn with_audit_logger(mut self, audit: Arc<AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Accept AUTH against these users; if the manager requires auth,
+    /// connections may only PING or AUTH until they log in
+    pub fn with_auth(mut self, auth: Arc<AuthManager>) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Record every write in `manager`'s log so followers can stream it
+    pub fn with_replication(mut self, manager: Arc<ReplicationManager>) -> Self {
+        let databases = self.databases.clone();
+        self.databases = Databases::from_fn(databases.len(), |db| {
+            let store = databases.get(db).unwrap().clone();
+            store.with_replication(manager.clone(), db as u32)
+        });
+        self.replication = Some(manager);
+        self
+    }
+
+    /// Check users' permissions against these roles
+    pub fn with_acl(mut self, acl: Arc<AclManager>) -> Self {
+        self.acl = Some(acl);
+        self
+    }
+
+    /// Refuse writes while this node can't confirm it has cluster quorum
+    pub fn with_fence(mut self, fence: Arc<QuorumFence>) -> Self {
+        self.fence = Some(fence);
+        self
+    }
+
+    /// Enable cluster mode: redirect keys whose slot isn't served locally
+    pub fn with_cluster(mut self, router: Arc<ClusterRouter>) -> Self {
+        self.cluster = Some(router);
+        self
+    }
+
+    /// Serve as a read replica of the leader `state` tracks: writes are
+    /// refused, and key reads too once lag exceeds `replica_max_lag`
+    pub fn with_follower(mut self, state: Arc<FollowerState>) -> Self {
+        self.follower = Some(state);
+        self
+    }
+
     /// Run the concurrent server
     pub async fn run(self) -> std::io::Result<()> {
         let addr = format!("{}:{}", self.config.bind, self.config.port);
         let listener = TcpListener::bind(&addr).await?;
+        self.serve(listener).await
+    }
+
+    /// Serve connections from an already-bound listener, and from
+    /// `config.unix_socket` if set
+    pub async fn serve(self, listener: TcpListener) -> std::io::Result<()> {
+        let addr = listener.local_addr()?;
+        // Removes the socket file when serving stops
+        let unix_listener = match &self.config.unix_socket {
+            Some(path) => {
+                let unix_listener = UnixSocketListener::bind(path)?;
+                info!("CELRIX concurrent server listening on unix socket {}", path.display());
+                Some(unix_listener)
+            }
+            None => None,
+        };
 
         // Determine worker counts
         let num_kv_workers = if self.config.kv_workers == 0 {
@@ -147,163 +506,2856 @@ impl ConcurrentServer {
             addr, num_kv_workers, num_vector_workers
         );
 
-        // Start TTL cleaner for concurrent store
-        ConcurrentTtlCleaner::spawn(self.store.clone(), self.config.ttl_cleaner_interval);
+        // Start a TTL cleaner per logical database
+        for store in self.databases.iter() {
+            let mut cleaner = ConcurrentTtlCleaner::new(store.clone(), self.config.ttl_cleaner_interval)
+                .with_metrics(self.metrics.clone());
+            if self.config.ttl_adaptive {
+                cleaner = cleaner.with_adaptive(AdaptiveExpiry::default());
+            }
+            tokio::spawn(cleaner.run());
+        }
 
         // --- KV POOL ---
-        let mut kv_pool_config = WorkerPoolConfig::default();
-        kv_pool_config.num_workers = num_kv_workers;
-        kv_pool_config.pin_to_cores = true; // Pin KV workers for low latency
+        let kv_pool_config = WorkerPoolConfig {
+            num_workers: num_kv_workers,
+            pin_to_cores: true, // Pin KV workers for low latency
+            queue_capacity: self.config.queue_capacity,
+        };
 
         let mut kv_pool = WorkerPool::new(
             kv_pool_config,
-            self.store.clone(),
+            self.databases.clone(),
             self.vector_store.clone(),
             self.metrics.clone(),
-        );
+        )
+        .with_fence(self.fence.clone())
+        .with_slowlog(self.slowlog.clone())
+        .with_eviction(self.config.eviction.clone())
+        .with_max_value_size(self.config.max_value_size)
+        .with_key_routing(self.config.route_by_key);
         kv_pool.start();
         let kv_queue = kv_pool.queue().clone();
+        let kv_routes: Arc<[CommandQueue]> = kv_pool.worker_queues().into();
 
         // --- VECTOR POOL ---
-        let mut vector_pool_config = WorkerPoolConfig::default();
-        vector_pool_config.num_workers = num_vector_workers;
-        vector_pool_config.pin_to_cores = false; // Don't pin vector workers to allow OS scheduling freedom for heavy compute
+        let vector_pool_config = WorkerPoolConfig {
+            num_workers: num_vector_workers,
+            pin_to_cores: false, // Don't pin vector workers to allow OS scheduling freedom for heavy compute
+            queue_capacity: self.config.queue_capacity,
+        };
 
         let mut vector_pool = WorkerPool::new(
             vector_pool_config,
-            self.store.clone(),
+            self.databases.clone(),
             self.vector_store.clone(),
             self.metrics.clone(),
-        );
+        )
+        .with_fence(self.fence.clone())
+        .with_slowlog(self.slowlog.clone())
+        .with_eviction(self.config.eviction.clone())
+        .with_max_value_size(self.config.max_value_size);
         vector_pool.start();
         let vector_queue = vector_pool.queue().clone();
 
-        loop {
-            match listener.accept().await {
-                Ok((socket, peer_addr)) => {
-                    info!("New connection from {}", peer_addr);
-
-                    let kv_q = kv_queue.clone();
-                    let vec_q = vector_queue.clone();
-                    // ... metrics
-
-                    tokio::spawn(async move {
-                        let framed = Framed::new(socket, VcpCodec::new());
-                        let handler = ConcurrentHandler::new(kv_q, vec_q);
-
-                        if let Err(e) = handler.run(framed).await {
-                            error!("Connection error from {}: {}", peer_addr, e);
-                        }
+        // Surface store, memory and queue health to probes
+        {
+            let mut health = self.health.write().unwrap();
+            health.register_store("store", self.databases.clone());
+            health.register_memory(
+                "memory",
+                self.databases.clone(),
+                self.config.eviction.max_memory,
+                self.config.memory_degraded_ratio,
+            );
+            let ratio = self.config.queue_degraded_ratio;
+            health.register_queue("kv_queue", kv_queue.clone(), ratio);
+            health.register_queue("vector_queue", vector_queue.clone(), ratio);
+            for (i, queue) in kv_routes.iter().enumerate() {
+                health.register_queue(&format!("kv_worker_queue_{}", i), queue.clone(), ratio);
+            }
+        }
 
-                        info!("Connection closed: {}", peer_addr);
-                    });
-                }
-                Err(e) => {
-                    error!("Accept error: {}", e);
+        let config = Arc::new(self.config.clone());
+        let rate_limiter = RateLimiter::from_config(&config).map(Arc::new);
+        if let Some(rate_limiter) = rate_limiter.clone() {
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(60));
+                loop {
+                    interval.tick().await;
+                    rate_limiter.prune();
                 }
+            });
+        }
+        let connections = Connections {
+            limiter: ConnectionLimiter::new(&config, self.metrics.clone()),
+            buffer_pool: BufferPool::with_defaults(),
+            kv_queue,
+            kv_routes,
+            vector_queue,
+            config,
+            rate_limiter,
+            cluster: self.cluster.clone(),
+            audit: self.audit.clone(),
+            auth: self.auth.clone(),
+            acl: self.acl.clone(),
+            replication: self.replication.clone(),
+            follower: self.follower.clone(),
+            metrics: self.metrics.clone(),
+            pubsub: self.pubsub.clone(),
+            slowlog: self.slowlog.clone(),
+            clients: self.clients.clone(),
+        };
+
+        loop {
+            let reserved = connections.limiter.reserve().await;
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((socket, peer_addr)) => connections.accept(socket, Some(peer_addr), reserved),
+                    Err(e) => error!("Accept error: {}", e),
+                },
+                accepted = unix_socket::accept_from(unix_listener.as_ref()) => match accepted {
+                    Ok(socket) => connections.accept(socket, None, reserved),
+                    Err(e) => error!("Unix socket accept error: {}", e),
+                },
             }
         }
     }
 
-    /// Get a reference to the store
+    /// Get a reference to the default database (DB 0)
     pub fn store(&self) -> &ConcurrentStore {
-        &self.store
+        self.databases.get(0).expect("at least one database")
+    }
+
+    /// Get all logical databases
+    pub fn databases(&self) -> &Databases {
+        &self.databases
+    }
+
+    /// DashMap shards in each database
+    pub fn shards(&self) -> usize {
+        self.store().shards()
+    }
+
+    /// Get the vector store shared by the workers
+    pub fn vector_store(&self) -> &SemanticCache {
+        &self.vector_store
     }
 
     /// Get metrics reference
     pub fn metrics(&self) -> &Arc<Metrics> {
         &self.metrics
     }
+
+    /// Get the pub/sub registry
+    pub fn pubsub(&self) -> &PubSub {
+        &self.pubsub
+    }
+
+    /// Get the slowlog, if enabled
+    pub fn slowlog(&self) -> Option<&Arc<Slowlog>> {
+        self.slowlog.as_ref()
+    }
+
+    /// Get the registry of open connections
+    pub fn clients(&self) -> &ClientRegistry {
+        &self.clients
+    }
+
+    /// Get health check registry (built-in checks are registered on `run`)
+    pub fn health(&self) -> &Arc<RwLock<HealthCheck>> {
+        &self.health
+    }
 }
 
 /// Handler for concurrent server that routes to worker pool
 pub struct ConcurrentHandler {
     kv_queue: CommandQueue,
+    /// Per-worker KV queues keyed commands are routed to (empty = shared queue)
+    kv_routes: Arc<[CommandQueue]>,
     vector_queue: CommandQueue,
+    config: Arc<Config>,
+    /// Logical database selected by this connection
+    db: AtomicUsize,
+    /// Slot routing (None = clustering disabled)
+    cluster: Option<Arc<ClusterRouter>>,
+    /// ASKING was sent, so the next command may use a slot being imported
+    asking: AtomicBool,
+    audit: Option<Arc<AuditLogger>>,
+    metrics: Option<Arc<Metrics>>,
+    auth: Option<Arc<AuthManager>>,
+    acl: Option<Arc<AclManager>>,
+    replication: Option<Arc<ReplicationManager>>,
+    /// Replication progress when serving as a follower
+    follower: Option<Arc<FollowerState>>,
+    /// Replication offset after this connection's latest write, for WAIT
+    last_write_offset: AtomicU64,
+    /// User this connection authenticated as, if any
+    user: RwLock<Option<String>>,
+    peer_addr: Option<SocketAddr>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    /// Source of keyspace events for SUBSCRIBE
+    pubsub: Option<PubSub>,
+    /// Subscription made by the latest SUBSCRIBE, picked up by `run`
+    subscription: Mutex<Option<KeyEventSubscription>>,
+    /// Served by SLOWLOG GET
+    slowlog: Option<Arc<Slowlog>>,
+    /// Commands queued since MULTI (None = no transaction open)
+    transaction: Mutex<Option<QueuedCommands>>,
+    /// Keys watched for the next EXEC as `(db, key, version)`
+    watched: Mutex<Vec<(usize, Bytes, u64)>>,
+    /// Version agreed by HELLO, which every later frame must carry
+    /// (0 = not negotiated, any supported version is accepted)
+    protocol_version: AtomicU8,
+    /// `CAP_*` flags the client announced in HELLO
+    client_capabilities: AtomicU32,
+    /// Connections CLIENT LIST and CLIENT KILL see (None = not tracked)
+    clients: Option<ClientRegistry>,
+    /// This connection's own entry in `clients`
+    client: Option<ClientHandle>,
+}
+
+/// Commands an open MULTI has queued
+#[derive(Default)]
+struct QueuedCommands {
+    commands: Vec<Command>,
+    /// A command was refused while queuing, so EXEC must abort
+    failed: bool,
+}
+
+/// What a connection's read loop woke up for
+enum Incoming {
+    Frame(Option<std::io::Result<Frame>>),
+    IdleTimeout,
+    /// A request finished: its sequence number, id and response
+    Done(u64, u64, Response),
+    Event(Option<(String, Bytes)>),
+    /// CLIENT KILL targeted this connection
+    Killed,
+}
+
+/// Requests one connection may have in flight at once
+const MAX_IN_FLIGHT: usize = 128;
+
+/// The keys a pipelined request touches and whether it writes them. Two
+/// requests conflict, and so keep their arrival order, when either writes
+/// and they share a key; a keyless command (FLUSHDB, DBSIZE, VSEARCH, ...)
+/// shares every key.
+#[derive(Debug, Default)]
+struct Footprint {
+    keys: Vec<Bytes>,
+    write: bool,
+}
+
+impl Footprint {
+    fn of(cmd: &Command) -> Self {
+        Self { keys: cmd.keys().into_iter().cloned().collect(), write: cmd.spec().is_write() }
+    }
+
+    fn conflicts(&self, other: &Footprint) -> bool {
+        (self.write || other.write)
+            && (self.keys.is_empty() || other.keys.is_empty() || self.keys.iter().any(|key| other.keys.contains(key)))
+    }
+}
+
+/// Commands that read or change per-connection state, so they can't overlap
+/// the connection's other requests
+fn is_connection_state(opcode: OpCode) -> bool {
+    matches!(
+        opcode,
+        OpCode::Auth
+            | OpCode::Hello
+            | OpCode::Select
+            | OpCode::Subscribe
+            | OpCode::Wait
+            | OpCode::Reset
+            | OpCode::Asking
+            | OpCode::Multi
+            | OpCode::Exec
+            | OpCode::Discard
+            | OpCode::Watch
+    )
+}
+
+/// Queue a response, holding the flush while pipelined requests are still
+/// buffered so a burst is answered with one write rather than one per response
+async fn reply<S: AsyncRead + AsyncWrite + Unpin>(
+    framed: &mut Framed<S, VcpCodec>,
+    pool: Option<&BufferPool>,
+    request_id: u64,
+    response: Response,
+) -> std::io::Result<()> {
+    use futures::SinkExt;
+
+    let frame = match pool {
+        Some(pool) => response.to_frame_pooled(request_id, pool),
+        None => response.to_frame(request_id),
+    };
+    framed.feed(frame).await?;
+    if !framed.codec().has_buffered_frame(framed.read_buffer()) {
+        framed.flush().await?;
+    }
+    Ok(())
 }
 
 impl ConcurrentHandler {
-    pub fn new(kv_queue: CommandQueue, vector_queue: CommandQueue) -> Self {
-        Self { kv_queue, vector_queue }
+    pub fn new(kv_queue: CommandQueue, vector_queue: CommandQueue, config: Arc<Config>) -> Self {
+        Self {
+            kv_queue,
+            kv_routes: Arc::new([]),
+            vector_queue,
+            config,
+            db: AtomicUsize::new(0),
+            cluster: None,
+            asking: AtomicBool::new(false),
+            audit: None,
+            metrics: None,
+            auth: None,
+            acl: None,
+            replication: None,
+            follower: None,
+            last_write_offset: AtomicU64::new(0),
+            user: RwLock::new(None),
+            peer_addr: None,
+            rate_limiter: None,
+            pubsub: None,
+            subscription: Mutex::new(None),
+            slowlog: None,
+            transaction: Mutex::new(None),
+            watched: Mutex::new(Vec::new()),
+            protocol_version: AtomicU8::new(0),
+            client_capabilities: AtomicU32::new(0),
+            clients: None,
+            client: None,
+        }
     }
 
-    pub async fn run(
-        self,
-        mut framed: Framed<tokio::net::TcpStream, VcpCodec>,
-    ) -> std::io::Result<()> {
-        use crate::protocol::{Command, Response};
-        use futures::{SinkExt, StreamExt};
+    /// Serve SLOWLOG GET from this log
+    pub fn with_slowlog(mut self, slowlog: Option<Arc<Slowlog>>) -> Self {
+        self.slowlog = slowlog;
+        self
+    }
 
-        while let Some(result) = framed.next().await {
-            let frame = result?;
-            let request_id = frame.header.request_id;
+    /// Serve SUBSCRIBE from this registry's keyspace events
+    pub fn with_pubsub(mut self, pubsub: PubSub) -> Self {
+        self.pubsub = Some(pubsub);
+        self
+    }
 
-            match Command::from_frame(&frame) {
-                Ok(cmd) => {
-                    // Create oneshot channel for response
-                    let (tx, rx) = tokio::sync::oneshot::channel();
+    /// Count BUSY rejections in server metrics
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
 
-                    // Decide target queue before moving cmd
-                    let target_queue = match cmd {
-                        Command::VAdd { .. } | Command::VSearch { .. } => &self.vector_queue,
-                        _ => &self.kv_queue,
-                    };
+    /// Record disconnects in an audit log
+    pub fn with_audit(mut self, audit: Option<Arc<AuditLogger>>) -> Self {
+        self.audit = audit;
+        self
+    }
 
-                    let work_item = WorkItem {
-                        command: cmd,
-                        request_id,
-                        response_tx: tx,
-                    };
+    /// Check AUTH against these users
+    pub fn with_auth(mut self, auth: Option<Arc<AuthManager>>) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Check this connection's user against these permissions
+    pub fn with_acl(mut self, acl: Option<Arc<AclManager>>) -> Self {
+        self.acl = acl;
+        self
+    }
+
+    /// Track writes against this replication log so WAIT can block on them
+    pub fn with_replication(mut self, replication: Option<Arc<ReplicationManager>>) -> Self {
+        self.replication = replication;
+        self
+    }
+
+    /// Throttle this connection's commands with a limiter shared across connections
+    pub fn with_rate_limiter(mut self, rate_limiter: Option<Arc<RateLimiter>>) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Remote address, recorded in audit events
+    pub fn with_peer_addr(mut self, addr: SocketAddr) -> Self {
+        self.peer_addr = Some(addr);
+        self
+    }
+
+    /// User this connection is authenticated as
+    pub fn user(&self) -> Option<String> {
+        self.user.read().unwrap().clone()
+    }
+
+    /// Redirect keys whose slot isn't served by this node
+    pub fn with_cluster(mut self, router: Arc<ClusterRouter>) -> Self {
+        self.cluster = Some(router);
+        self
+    }
+
+    /// Send keyed KV commands to `routes[route_key(key, routes.len())]`
+    /// instead of the shared KV queue (empty = always the shared queue)
+    pub fn with_key_routes(mut self, routes: Arc<[CommandQueue]>) -> Self {
+        self.kv_routes = routes;
+        self
+    }
 
-                    if target_queue.send(work_item).is_err() {
-                        let response = Response::Error("Queue full".to_string());
-                        let response_frame = response.to_frame(request_id);
-                        framed.send(response_frame).await?;
-                        continue;
+    /// Register this connection in `clients`, which CLIENT LIST and
+    /// CLIENT KILL also operate on; it's removed when the handler is dropped
+    pub fn with_clients(mut self, clients: ClientRegistry) -> Self {
+        self.client = Some(clients.register(self.peer_addr));
+        self.clients = Some(clients);
+        self
+    }
+
+    /// Id of this connection in its client registry, if registered
+    pub fn client_id(&self) -> Option<u64> {
+        self.client.as_ref().map(ClientHandle::id)
+    }
+
+    /// Serve as a read replica, see `ConcurrentServer::with_follower`
+    pub fn with_follower(mut self, follower: Arc<FollowerState>) -> Self {
+        self.follower = Some(follower);
+        self
+    }
+
+    /// Logical database currently selected by this connection
+    pub fn db(&self) -> usize {
+        self.db.load(Ordering::Relaxed)
+    }
+
+    /// Serve a connection until it closes. Requests run concurrently and
+    /// each response goes out as soon as it's ready, tagged with its
+    /// request's id, so replies may arrive out of order. A request that
+    /// conflicts with one still running (see `Footprint`) waits for it, so
+    /// e.g. a pipelined SET then GET of one key still reads the SET.
+    /// Connection-state commands, and everything inside MULTI, wait for
+    /// earlier requests and run alone.
+    pub async fn run<S: AsyncRead + AsyncWrite + Unpin>(self, mut framed: Framed<S, VcpCodec>) -> std::io::Result<()> {
+        use futures::stream::FuturesUnordered;
+        use futures::{SinkExt, StreamExt};
+
+        let idle_timeout = self.config.idle_timeout_duration();
+        let pool = framed.codec().pool().cloned();
+        let mut subscription: Option<KeyEventSubscription> = None;
+        let mut in_flight = FuturesUnordered::new();
+        // Footprints of the requests in `in_flight`, by sequence number
+        let mut running: Vec<(u64, Footprint)> = Vec::new();
+        let mut next_seq = 0u64;
+        let this = &self;
+        let reason = loop {
+            // A slow request isn't the client idling
+            let idle = idle_timeout.filter(|_| in_flight.is_empty());
+            let can_read = in_flight.len() < MAX_IN_FLIGHT;
+            let read = async {
+                match idle {
+                    Some(timeout) => tokio::time::timeout(timeout, framed.next())
+                        .await
+                        .map_or(Incoming::IdleTimeout, Incoming::Frame),
+                    None => Incoming::Frame(framed.next().await),
+                }
+            };
+            let incoming = tokio::select! {
+                incoming = read, if can_read => incoming,
+                Some((seq, request_id, response)) = in_flight.next(), if !in_flight.is_empty() => {
+                    Incoming::Done(seq, request_id, response)
+                }
+                event = async { subscription.as_mut()?.recv().await }, if subscription.is_some() => {
+                    Incoming::Event(event)
+                }
+                _ = async { self.client.as_ref().unwrap().killed().await }, if self.client.is_some() => {
+                    Incoming::Killed
+                }
+            };
+            let frame = match incoming {
+                Incoming::Frame(Some(result)) => result?,
+                Incoming::Frame(None) => {
+                    while let Some((_, request_id, response)) = in_flight.next().await {
+                        reply(&mut framed, pool.as_ref(), request_id, response).await?;
                     }
+                    break "client closed";
+                }
+                Incoming::IdleTimeout => break "idle timeout",
+                Incoming::Done(seq, request_id, response) => {
+                    running.retain(|(running_seq, _)| *running_seq != seq);
+                    reply(&mut framed, pool.as_ref(), request_id, response).await?;
+                    continue;
+                }
+                Incoming::Event(Some((event, key))) => {
+                    framed.send(Response::Event { event: Bytes::from(event), key }.to_frame(0)).await?;
+                    continue;
+                }
+                Incoming::Event(None) => {
+                    subscription = None;
+                    continue;
+                }
+                Incoming::Killed => break "killed by CLIENT KILL",
+            };
 
-                    // Wait for response
-                    match rx.await {
-                        Ok(result) => {
-                            let response = match result {
-                                WorkResult::Ok => Response::Ok,
-                                WorkResult::Value(v) => Response::Value(v),
-                                WorkResult::Integer(i) => Response::Integer(i),
-                                WorkResult::Nil => Response::Nil,
-                                WorkResult::Error(e) => Response::Error(e),
-                                WorkResult::Pong => Response::Pong,
-                                WorkResult::Array(items) => {
-                                    // Map WorkResult values to Bytes for Response::Array
-                                    let mut resp_items = Vec::with_capacity(items.len());
-                                    for item in items {
-                                        if let WorkResult::Value(val) = item {
-                                            resp_items.push(val);
-                                        } else {
-                                            // Fallback for non-value items in array if any
-                                            resp_items.push(Bytes::from(format!("{:?}", item)));
-                                        }
-                                    }
-                                    Response::Array(resp_items)
-                                }
-                            };
-                            let response_frame = response.to_frame(request_id);
-                            framed.send(response_frame).await?;
-                        }
-                        Err(_) => {
-                            let response = Response::Error("Worker error".to_string());
-                            let response_frame = response.to_frame(request_id);
-                            framed.send(response_frame).await?;
-                        }
+            if !is_connection_state(frame.header.opcode) && !self.in_transaction() {
+                let parsed = Command::from_frame(&frame);
+                let footprint = parsed.as_ref().map_or_else(|_| Footprint::default(), Footprint::of);
+                // Let earlier requests this one depends on (or that depend
+                // on it) finish first
+                while running.iter().any(|(_, other)| other.conflicts(&footprint)) {
+                    let Some((seq, request_id, response)) = in_flight.next().await else { break };
+                    running.retain(|(running_seq, _)| *running_seq != seq);
+                    reply(&mut framed, pool.as_ref(), request_id, response).await?;
+                }
+                let seq = next_seq;
+                next_seq += 1;
+                running.push((seq, footprint));
+                // Taken in arrival order, so ASKING applies to the next request
+                let asking = self.asking.swap(false, Ordering::Relaxed);
+                in_flight.push(async move {
+                    let response = this.process_parsed(&frame, parsed, asking).await;
+                    (seq, frame.header.request_id, response)
+                });
+                continue;
+            }
+            while let Some((_, request_id, response)) = in_flight.next().await {
+                reply(&mut framed, pool.as_ref(), request_id, response).await?;
+            }
+            running.clear();
+            let response = self.process(&frame).await;
+            if frame.header.opcode == OpCode::Reset && matches!(response, Response::Ok) {
+                subscription = None;
+            }
+            if let Some(subscribed) = self.subscription.lock().unwrap().take() {
+                subscription = Some(subscribed);
+            }
+            reply(&mut framed, pool.as_ref(), frame.header.request_id, response).await?;
+        };
+
+        if let Some(audit) = &self.audit {
+            let peer = self.peer_addr.map(|a| a.ip().to_string());
+            audit.log_disconnect(peer.as_deref().unwrap_or("unknown"), reason);
+        }
+        Ok(())
+    }
+
+    /// Process a single request frame and produce its response
+    pub async fn process(&self, frame: &Frame) -> Response {
+        let asking = self.asking.swap(false, Ordering::Relaxed);
+        self.process_parsed(frame, Command::from_frame(frame), asking).await
+    }
+
+    /// `process` for a frame already parsed into `parsed`, sent right after
+    /// ASKING if `asking`
+    async fn process_parsed(&self, frame: &Frame, parsed: std::io::Result<Command>, asking: bool) -> Response {
+        if let Some(error) = self.check_version(frame.header.version) {
+            return error;
+        }
+        match parsed {
+            Ok(cmd) => {
+                if let Some(client) = &self.client {
+                    client.set_last_command(cmd.name());
+                }
+                // Ahead of AUTH so password guessing is throttled too
+                if let Some(limited) = self.rate_limit(&cmd) {
+                    return limited;
+                }
+                if let Command::Auth { username, password } = &cmd {
+                    return self.authenticate(username, password);
+                }
+                if let Command::Hello { versions, capabilities } = &cmd {
+                    return self.hello(versions, *capabilities);
+                }
+                if !matches!(cmd, Command::Ping | Command::Reset) && self.needs_auth() {
+                    return Response::Error("NOAUTH Authentication required".to_string());
+                }
+                if self.config.is_command_disabled(cmd.name()) {
+                    return Response::Error("ERR command disabled".to_string());
+                }
+                if let Some(denied) = self.check_acl(&cmd) {
+                    return denied;
+                }
+                match cmd {
+                    Command::Multi => return self.multi(),
+                    Command::Exec { .. } => return self.exec(frame.header.request_id).await,
+                    Command::Discard => return self.discard(),
+                    _ => {}
+                }
+                if !cmd.spec().allowed_in_multi() {
+                    if let Some(queued) = self.transaction.lock().unwrap().as_mut() {
+                        queued.failed = true;
+                        return Response::Error(format!("ERR {} inside MULTI is not allowed", cmd.name()));
                     }
                 }
-                Err(e) => {
-                    let response = Response::Error(e.to_string());
-                    let response_frame = response.to_frame(request_id);
-                    framed.send(response_frame).await?;
+                if let Command::Watch { keys } = cmd {
+                    return self.watch(keys, frame.header.request_id).await;
+                }
+                // SELECT is connection state; no worker round-trip needed
+                if let Command::Select { db } = cmd {
+                    if db as usize >= self.config.databases {
+                        return Response::Error("ERR DB index is out of range".to_string());
+                    }
+                    self.db.store(db as usize, Ordering::Relaxed);
+                    return Response::Ok;
+                }
+                if let Command::Reset = cmd {
+                    return self.reset();
+                }
+                if let Command::ClusterSlots = cmd {
+                    return self.cluster_slots();
+                }
+                if let Command::Asking = cmd {
+                    self.asking.store(true, Ordering::Relaxed);
+                    return Response::Ok;
+                }
+                if let Command::Wait { num_replicas, timeout_ms } = cmd {
+                    return self.wait(num_replicas as usize, timeout_ms).await;
+                }
+                if let Command::Subscribe { events, pattern } = cmd {
+                    return self.subscribe(events, pattern);
+                }
+                if let Command::SlowlogGet { count } = cmd {
+                    let entries = self.slowlog.as_ref().map_or_else(Vec::new, |log| log.get(count as usize));
+                    return Response::Array(entries.iter().map(|e| e.record()).collect());
+                }
+                if let Command::ClientList = cmd {
+                    let clients = self.clients.as_ref().map_or_else(Vec::new, ClientRegistry::list);
+                    return Response::Array(clients);
+                }
+                if let Command::ClientKill { id } = cmd {
+                    return match &self.clients {
+                        Some(clients) if clients.kill(id) => Response::Ok,
+                        _ => Response::Error(NO_SUCH_CLIENT_ERROR.to_string()),
+                    };
+                }
+                if matches!(cmd, Command::DebugSleep { .. } | Command::DebugObject { .. })
+                    && !self.has_permission(Permission::Admin)
+                {
+                    return Response::Error("NOPERM this user has no permissions to run 'debug'".to_string());
+                }
+                if let Some(refused) = self.check_replica(&cmd) {
+                    return refused;
+                }
+                if let Some(redirect) = self.redirect(&cmd, asking) {
+                    return redirect;
+                }
+                if let Some(queued) = self.transaction.lock().unwrap().as_mut() {
+                    queued.commands.push(cmd);
+                    return Response::Queued;
+                }
+                if let Command::DelPattern { pattern } = &cmd {
+                    let pattern = String::from_utf8_lossy(pattern).into_owned();
+                    return self.del_pattern(cmd, &pattern, frame.header.request_id).await;
+                }
+                let ask = self.ask_if_missing(&cmd);
+                let response = self.dispatch(cmd, frame.header.request_id).await;
+                match (response, ask) {
+                    // The key may already have moved to the migration target
+                    (Response::Nil, Some(ask)) => ask,
+                    (response, _) => response,
                 }
             }
+            Err(e) => {
+                if let Some(queued) = self.transaction.lock().unwrap().as_mut() {
+                    queued.failed = true;
+                }
+                Response::protocol_error(e)
+            }
         }
+    }
 
-        Ok(())
+    /// Whether MULTI has opened a transaction that EXEC hasn't closed
+    fn in_transaction(&self) -> bool {
+        self.transaction.lock().unwrap().is_some()
+    }
+
+    /// MULTI: queue the following commands until EXEC or DISCARD
+    fn multi(&self) -> Response {
+        let mut transaction = self.transaction.lock().unwrap();
+        if transaction.is_some() {
+            return Response::Error("ERR MULTI calls can not be nested".to_string());
+        }
+        *transaction = Some(QueuedCommands::default());
+        Response::Ok
+    }
+
+    /// DISCARD: drop the queued commands and stop watching keys
+    fn discard(&self) -> Response {
+        if self.transaction.lock().unwrap().take().is_none() {
+            return Response::Error("ERR DISCARD without MULTI".to_string());
+        }
+        self.watched.lock().unwrap().clear();
+        Response::Ok
+    }
+
+    /// WATCH: remember the keys' current versions for the next EXEC
+    async fn watch(&self, keys: Vec<Bytes>, request_id: u64) -> Response {
+        let db = self.db();
+        let versions = match self.submit(Command::Watch { keys: keys.clone() }, request_id).await {
+            Ok(WorkResult::Array(versions)) => versions,
+            Ok(result) => return work_response(result),
+            Err(response) => return response,
+        };
+        let mut watched = self.watched.lock().unwrap();
+        for (key, version) in keys.into_iter().zip(versions) {
+            if let WorkResult::Integer(version) = version {
+                watched.push((db, key, version as u64));
+            }
+        }
+        Response::Ok
+    }
+
+    /// EXEC: run the queued commands on one worker with no other command
+    /// interleaved, replying nil instead if a watched key was written
+    async fn exec(&self, request_id: u64) -> Response {
+        let Some(queued) = self.transaction.lock().unwrap().take() else {
+            return Response::Error("ERR EXEC without MULTI".to_string());
+        };
+        let watched = std::mem::take(&mut *self.watched.lock().unwrap());
+        if queued.failed {
+            return Response::Error("EXECABORT Transaction discarded because of previous errors".to_string());
+        }
+        let exec = Command::Exec { commands: queued.commands, watched };
+        match self.submit(exec, request_id).await {
+            Ok(WorkResult::Array(results)) => Response::Replies(results.into_iter().map(work_response).collect()),
+            Ok(result) => work_response(result),
+            Err(response) => response,
+        }
+    }
+
+    /// Start pushing the selected database's key events to this connection,
+    /// replacing any earlier subscription
+    fn subscribe(&self, events: Vec<Bytes>, pattern: Option<Bytes>) -> Response {
+        let pubsub = match &self.pubsub {
+            Some(pubsub) if self.config.notify_keyspace_events => pubsub,
+            _ => return Response::Error("ERR keyspace notifications are disabled".to_string()),
+        };
+        let events = events.iter().map(|e| String::from_utf8_lossy(e).into_owned()).collect();
+        let pattern = pattern.map(|p| String::from_utf8_lossy(&p).into_owned());
+        let subscription = KeyEventSubscription::new(pubsub, self.db() as u32, events, pattern);
+        *self.subscription.lock().unwrap() = Some(subscription);
+        Response::Ok
+    }
+
+    /// Whether this connection must AUTH before running commands
+    fn needs_auth(&self) -> bool {
+        self.auth.as_ref().is_some_and(|auth| auth.requires_auth())
+            && self.user.read().unwrap().is_none()
+    }
+
+    /// Error reply if this connection's user or IP is over its rate limit.
+    /// Rejections are audited in batches, as the limiter reports them.
+    fn rate_limit(&self, cmd: &Command) -> Option<Response> {
+        let limiter = self.rate_limiter.as_ref()?;
+        let user = self.user();
+        let ip = self.peer_addr.map(|a| a.ip().to_string());
+        let key = match (&user, &ip) {
+            (Some(user), _) => format!("user:{}", user),
+            (None, Some(ip)) => format!("ip:{}", ip),
+            (None, None) => "anonymous".to_string(),
+        };
+        let throttled = limiter.check(&key).err()?;
+
+        if let (Some(audit), Some(rejected)) = (&self.audit, throttled.report) {
+            let mut event = AuditEvent::new(AuditEventType::RateLimited)
+                .with_command(cmd.name())
+                .with_message(&format!("rate limited ({} rejected)", rejected))
+                .failed();
+            if let Some(user) = &user {
+                event = event.with_user(user);
+            }
+            if let Some(ip) = &ip {
+                event = event.with_client(ip);
+            }
+            audit.log(event);
+        }
+        Some(Response::Error(format!(
+            "RATELIMITED too many requests, retry in {}ms",
+            throttled.retry_after.as_millis().max(1)
+        )))
+    }
+
+    /// NOPERM reply if ACLs don't let this connection's user run `cmd`, or
+    /// don't grant the command's permission on one of its keys; audited.
+    /// PING and RESET are open to everyone, as they are before AUTH.
+    fn check_acl(&self, cmd: &Command) -> Option<Response> {
+        let acl = self.acl.as_ref()?;
+        if matches!(cmd, Command::Ping | Command::Reset) {
+            return None;
+        }
+        let user = self.user();
+        let username = user.as_deref().unwrap_or("default");
+        let (key, error) = if !acl.can_execute(username, cmd.name()) {
+            let error = format!(
+                "NOPERM this user has no permissions to run the '{}' command",
+                cmd.name().to_lowercase()
+            );
+            (None, error)
+        } else {
+            // Commands without a permission class touch no key data
+            let permission = cmd.spec().permission()?;
+            let key = cmd
+                .keys()
+                .into_iter()
+                .map(|key| String::from_utf8_lossy(key).into_owned())
+                .find(|key| !acl.can_access(username, key, permission))?;
+            let error = "NOPERM this user has no permissions to access one of the keys used as arguments";
+            (Some(key), error.to_string())
+        };
+
+        if let Some(audit) = &self.audit {
+            audit.log_denied(username, cmd.name(), key.as_deref());
+        }
+        Some(Response::Error(error))
+    }
+
+    /// Run DELPATTERN, which needs Write access to keys matching the pattern
+    /// and is always audited
+    async fn del_pattern(&self, cmd: Command, pattern: &str, request_id: u64) -> Response {
+        let user = self.user();
+        let username = user.as_deref().unwrap_or("default");
+        let ip = self.peer_addr.map(|a| a.ip().to_string());
+        let allowed = self
+            .acl
+            .as_ref()
+            .is_none_or(|acl| acl.can_access(username, pattern, Permission::Write));
+
+        let response = if allowed {
+            self.dispatch(cmd, request_id).await
+        } else {
+            Response::Error(format!(
+                "NOPERM this user has no permissions to delete keys matching '{}'",
+                pattern
+            ))
+        };
+
+        if let Some(audit) = &self.audit {
+            let mut event = AuditEvent::new(if allowed {
+                AuditEventType::Command
+            } else {
+                AuditEventType::PermissionDenied
+            })
+            .with_user(username)
+            .with_command("DELPATTERN")
+            .with_key(pattern);
+            match &response {
+                Response::Integer(n) => event = event.with_message(&format!("deleted {} keys", n)),
+                _ => event = event.failed(),
+            }
+            if let Some(ip) = &ip {
+                event = event.with_client(ip);
+            }
+            audit.log(event);
+        }
+        response
+    }
+
+    /// Check credentials and, on success, bind the user to this connection
+    fn authenticate(&self, username: &Bytes, password: &Bytes) -> Response {
+        let Some(auth) = &self.auth else {
+            return Response::Error("ERR AUTH called without any users configured".to_string());
+        };
+        let username = match username.as_ref() {
+            b"" => "default".into(),
+            name => String::from_utf8_lossy(name),
+        };
+        let result = auth.authenticate(&username, &String::from_utf8_lossy(password));
+        let success = result == AuthResult::Success;
+
+        if let Some(audit) = &self.audit {
+            let peer = self.peer_addr.map(|a| a.ip().to_string());
+            audit.log_login(&username, peer.as_deref().unwrap_or("unknown"), success);
+        }
+        if !success {
+            return Response::Error(
+                "WRONGPASS invalid username-password pair or user is disabled".to_string(),
+            );
+        }
+        if let Some(client) = &self.client {
+            client.set_user(Some(username.to_string()));
+        }
+        *self.user.write().unwrap() = Some(username.into_owned());
+        Response::Ok
+    }
+
+    /// RESET: forget the authenticated user, any subscription not yet
+    /// picked up by `run` (which drops its own), the open transaction and
+    /// watched keys, the selected database and the write offset WAIT tracks
+    fn reset(&self) -> Response {
+        *self.user.write().unwrap() = None;
+        if let Some(client) = &self.client {
+            client.set_user(None);
+        }
+        self.subscription.lock().unwrap().take();
+        self.transaction.lock().unwrap().take();
+        self.watched.lock().unwrap().clear();
+        self.db.store(0, Ordering::Relaxed);
+        self.asking.store(false, Ordering::Relaxed);
+        self.last_write_offset.store(0, Ordering::Relaxed);
+        self.protocol_version.store(0, Ordering::Relaxed);
+        self.client_capabilities.store(0, Ordering::Relaxed);
+        Response::Ok
+    }
+
+    /// On a follower, refuse writes, and key reads while the follower is
+    /// too far behind the leader (or not streaming from it) to serve them
+    fn check_replica(&self, cmd: &Command) -> Option<Response> {
+        let follower = self.follower.as_ref()?;
+        let leader = follower.leader_addr().unwrap_or_else(|| "unknown".to_string());
+        if cmd.spec().is_write() {
+            return Some(Response::Error(format!("READONLY writes must go to the leader at {}", leader)));
+        }
+        if cmd.keys().is_empty() {
+            return None;
+        }
+        let max_lag = self.config.replica_max_lag;
+        match follower.lag() {
+            Some(lag) if lag <= max_lag => None,
+            Some(lag) => Some(Response::Error(format!(
+                "STALE replica is {} entries behind the leader at {} (limit {}); read from the leader",
+                lag, leader, max_lag
+            ))),
+            None => Some(Response::Error(format!(
+                "STALE replica is not streaming from the leader at {}; read from the leader",
+                leader
+            ))),
+        }
+    }
+
+    /// Settle the protocol version for the rest of the connection
+    fn hello(&self, versions: &[u8], capabilities: u32) -> Response {
+        match negotiate_version(versions) {
+            Ok(version) => {
+                self.protocol_version.store(version, Ordering::Relaxed);
+                self.client_capabilities.store(capabilities, Ordering::Relaxed);
+                let ours = if self.config.compression_threshold > 0 { CAP_COMPRESSION } else { 0 };
+                hello_reply(version, ours)
+            }
+            Err(e) => Response::Error(e),
+        }
+    }
+
+    /// Error for a frame whose header version we can't interpret: one
+    /// other than the negotiated version, or any unsupported one before HELLO
+    fn check_version(&self, version: u8) -> Option<Response> {
+        match self.protocol_version.load(Ordering::Relaxed) {
+            0 if SUPPORTED_VERSIONS.contains(&version) => None,
+            0 => Some(Response::Error(format!("NOPROTO unsupported frame version {}", version))),
+            negotiated if version == negotiated => None,
+            negotiated => Some(Response::Error(format!(
+                "NOPROTO frame version {} doesn't match negotiated version {}",
+                version, negotiated
+            ))),
+        }
+    }
+
+    /// Whether this connection's user holds `permission` on every key
+    /// (always, without ACLs)
+    fn has_permission(&self, permission: Permission) -> bool {
+        let user = self.user();
+        self.acl
+            .as_ref()
+            .is_none_or(|acl| acl.can_access(user.as_deref().unwrap_or("default"), "*", permission))
+    }
+
+    /// Serve CLUSTER SLOTS from the router; needs the Cluster permission
+    fn cluster_slots(&self) -> Response {
+        let Some(router) = &self.cluster else {
+            return Response::Error("ERR This instance has cluster support disabled".to_string());
+        };
+        if !self.has_permission(Permission::Cluster) {
+            return Response::Error("NOPERM this user has no permissions to run 'cluster'".to_string());
+        }
+        Response::Array(router.slot_records().into_iter().map(Bytes::from).collect())
+    }
+
+    /// WAIT: block until `num_replicas` have acked this connection's last
+    /// write or the timeout passes, replying with the number that have
+    async fn wait(&self, num_replicas: usize, timeout_ms: u64) -> Response {
+        let Some(manager) = &self.replication else {
+            return Response::Integer(0);
+        };
+        let target = self.last_write_offset.load(Ordering::Relaxed);
+        let deadline = (timeout_ms > 0).then(|| tokio::time::Instant::now() + Duration::from_millis(timeout_ms));
+        loop {
+            let acked = manager.acked();
+            tokio::pin!(acked);
+            acked.as_mut().enable();
+
+            let count = manager.acked_count(target);
+            if count >= num_replicas {
+                return Response::Integer(count as i64);
+            }
+            match deadline {
+                Some(deadline) => {
+                    if tokio::time::timeout_at(deadline, acked).await.is_err() {
+                        return Response::Integer(manager.acked_count(target) as i64);
+                    }
+                }
+                None => acked.await,
+            }
+        }
+    }
+
+    /// Response for a command whose keys aren't served here, if any.
+    /// `asking` lets the command use a slot this node is importing.
+    fn redirect(&self, cmd: &Command, asking: bool) -> Option<Response> {
+        let router = self.cluster.as_ref()?;
+        match router.route_keys(cmd.keys().into_iter().map(|k| k.as_ref())) {
+            Route::Local => None,
+            Route::Importing { .. } if asking => None,
+            Route::Moved { slot, addr } | Route::Importing { slot, addr } => {
+                Some(Response::Moved { slot, addr: addr.to_string() })
+            }
+            Route::Migrating { slot, addr } if cmd.spec().is_write() => {
+                Some(Response::Ask { slot, addr: addr.to_string() })
+            }
+            Route::Migrating { .. } => None,
+            Route::CrossSlot => Some(Response::Error(
+                "CROSSSLOT Keys in request don't hash to the same slot".to_string(),
+            )),
+            Route::Unavailable { slot } => {
+                Some(Response::Error(format!("CLUSTERDOWN Hash slot {} not served", slot)))
+            }
+        }
+    }
+
+    /// ASK redirect to send instead of a miss, for reads of a slot that is
+    /// migrating away from this node
+    fn ask_if_missing(&self, cmd: &Command) -> Option<Response> {
+        let router = self.cluster.as_ref()?;
+        match router.route_keys(cmd.keys().into_iter().map(|k| k.as_ref())) {
+            Route::Migrating { slot, addr } => Some(Response::Ask { slot, addr: addr.to_string() }),
+            _ => None,
+        }
+    }
+
+    /// Queue a work item, waiting briefly for room before giving up with BUSY.
+    /// Polls with `try_send` so a full queue never blocks the runtime thread.
+    async fn enqueue(&self, queue: &CommandQueue, mut item: WorkItem) -> Result<(), Response> {
+        let timeout = self.config.queue_send_timeout_duration();
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            match queue.try_send(item) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Disconnected(_)) => {
+                    return Err(Response::internal_error("worker pool unavailable"));
+                }
+                Err(TrySendError::Full(returned)) => item = returned,
+            }
+            if tokio::time::Instant::now() >= deadline {
+                queue.record_rejection();
+                if let Some(metrics) = &self.metrics {
+                    metrics.record_busy_rejection();
+                }
+                let retry_after_ms = timeout.as_millis().clamp(1, u32::MAX as u128) as u32;
+                return Err(Response::Busy { retry_after_ms });
+            }
+            tokio::time::sleep(Duration::from_micros(200)).await;
+        }
+    }
+
+    /// Queue `cmd` runs from: the vector pool's, or for KV commands the
+    /// shared queue unless key routing picks a worker's own
+    fn target_queue(&self, cmd: &Command) -> &CommandQueue {
+        match cmd.spec().pool {
+            Pool::Vector => &self.vector_queue,
+            Pool::Kv => match cmd.keys().first() {
+                Some(key) if !self.kv_routes.is_empty() => {
+                    &self.kv_routes[route_key(key, self.kv_routes.len())]
+                }
+                _ => &self.kv_queue,
+            },
+        }
+    }
+
+    /// Send a command to the appropriate worker pool and await the result
+    async fn dispatch(&self, cmd: Command, request_id: u64) -> Response {
+        match self.submit(cmd, request_id).await {
+            Ok(result) => work_response(result),
+            Err(response) => response,
+        }
+    }
+
+    /// Run a command on a worker and return its raw result; Err carries the
+    /// reply if it never ran
+    async fn submit(&self, cmd: Command, request_id: u64) -> Result<WorkResult, Response> {
+        // Create oneshot channel for response
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        // Decide target queue before moving cmd
+        let is_write = match &cmd {
+            Command::Exec { commands, .. } => commands.iter().any(|c| c.spec().is_write()),
+            cmd => cmd.spec().is_write(),
+        };
+        let target_queue = self.target_queue(&cmd);
+
+        let work_item = WorkItem {
+            command: cmd,
+            request_id,
+            db: self.db(),
+            response_tx: tx,
+        };
+
+        self.enqueue(target_queue, work_item).await?;
+
+        // Wait for response
+        let result = rx.await;
+        if let (true, Some(manager)) = (is_write, &self.replication) {
+            self.last_write_offset.store(manager.offset(), Ordering::Relaxed);
+        }
+        result.map_err(|_| Response::internal_error("worker dropped the request"))
+    }
+}
+
+/// Reply for a worker's result
+fn work_response(result: WorkResult) -> Response {
+    match result {
+        WorkResult::Ok => Response::Ok,
+        WorkResult::Value(v) => Response::Value(v),
+        WorkResult::Integer(i) => Response::Integer(i),
+        WorkResult::Nil => Response::Nil,
+        WorkResult::Expired => Response::Expired,
+        WorkResult::Error(e) => Response::Error(e),
+        WorkResult::Pong => Response::Pong,
+        WorkResult::Values(items) => Response::Values(items),
+        WorkResult::ValueWithTtl { value, ttl_ms } => Response::ValueWithTtl { value, ttl_ms },
+        WorkResult::BatchAdded { added, failed } => Response::BatchAdded { added, failed },
+        WorkResult::Array(items) => {
+            // Map WorkResult values to Bytes for Response::Array
+            let mut resp_items = Vec::with_capacity(items.len());
+            for item in items {
+                if let WorkResult::Value(val) = item {
+                    resp_items.push(val);
+                } else {
+                    // Fallback for non-value items in array if any
+                    resp_items.push(Bytes::from(format!("{:?}", item)));
+                }
+            }
+            Response::Array(resp_items)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::ExtendedCommand;
+    use crate::storage::{EvictionConfig, EvictionPolicy, OomAction, ENTRY_OVERHEAD, OOM_ERROR};
+
+    /// Start a small worker pool and return a handler wired to it
+    fn test_handler(config: Config) -> (ConcurrentHandler, WorkerPool) {
+        let pool_config = WorkerPoolConfig {
+            num_workers: 2,
+            pin_to_cores: false,
+            queue_capacity: 64,
+        };
+        let mut pool = WorkerPool::new(
+            pool_config,
+            Databases::new(config.databases, 4),
+            SemanticCache::with_defaults(),
+            Arc::new(Metrics::new()),
+        )
+        .with_eviction(config.eviction.clone())
+        .with_max_value_size(config.max_value_size)
+        .with_key_routing(config.route_by_key);
+        pool.start();
+        let queue = pool.queue().clone();
+        let handler = ConcurrentHandler::new(queue.clone(), queue, Arc::new(config))
+            .with_key_routes(pool.worker_queues().into());
+        (handler, pool)
+    }
+
+    fn frame(cmd: Command) -> Frame {
+        cmd.to_frame(1)
+    }
+
+    fn set_frame(key: &[u8], value: &[u8]) -> Frame {
+        frame(Command::Set {
+            key: Bytes::copy_from_slice(key),
+            value: Bytes::copy_from_slice(value),
+            ttl: None,
+            options: Default::default(),
+        })
+    }
+
+    fn exec() -> Frame {
+        frame(Command::Exec { commands: Vec::new(), watched: Vec::new() })
+    }
+
+    #[tokio::test]
+    async fn test_disabled_command_rejected() {
+        let config = Config::default().with_disabled_commands(["set", "FLUSHALL"]);
+        let (handler, _pool) = test_handler(config);
+
+        let set = set_frame(b"k", b"v");
+        match handler.process(&set).await {
+            Response::Error(e) => assert_eq!(e, "ERR command disabled"),
+            other => panic!("Expected error, got {:?}", other),
+        }
+
+        let get = frame(Command::Get { key: Bytes::from_static(b"k") });
+        assert!(matches!(handler.process(&get).await, Response::Nil));
+        assert!(matches!(handler.process(&frame(Command::Ping)).await, Response::Pong));
+    }
+
+    #[tokio::test]
+    async fn test_auth_gates_commands() {
+        use crate::security::AuthConfig;
+
+        let auth = Arc::new(AuthManager::new(AuthConfig::default()));
+        auth.add_user("alice", "s3cret");
+        let (handler, _pool) = test_handler(Config::default());
+        let handler = handler.with_auth(Some(auth));
+        let get = frame(Command::Get { key: Bytes::from_static(b"k") });
+        let login = |password: &'static [u8]| {
+            frame(Command::Auth {
+                username: Bytes::from_static(b"alice"),
+                password: Bytes::from_static(password),
+            })
+        };
+
+        // Only PING and AUTH before logging in
+        match handler.process(&get).await {
+            Response::Error(e) => assert!(e.starts_with("NOAUTH"), "{}", e),
+            other => panic!("Expected NOAUTH, got {:?}", other),
+        }
+        assert!(matches!(handler.process(&frame(Command::Ping)).await, Response::Pong));
+
+        match handler.process(&login(b"wrong")).await {
+            Response::Error(e) => assert!(e.starts_with("WRONGPASS"), "{}", e),
+            other => panic!("Expected WRONGPASS, got {:?}", other),
+        }
+        assert_eq!(handler.user(), None);
+
+        assert!(matches!(handler.process(&login(b"s3cret")).await, Response::Ok));
+        assert_eq!(handler.user().as_deref(), Some("alice"));
+        assert!(matches!(handler.process(&get).await, Response::Nil));
+    }
+
+    #[tokio::test]
+    async fn test_reset_logs_out() {
+        use crate::security::AuthConfig;
+
+        let auth = Arc::new(AuthManager::new(AuthConfig::default()));
+        auth.add_user("alice", "s3cret");
+        let (handler, _pool) = test_handler(Config::default());
+        let handler = handler.with_auth(Some(auth));
+        let login = frame(Command::Auth {
+            username: Bytes::from_static(b"alice"),
+            password: Bytes::from_static(b"s3cret"),
+        });
+        let get = frame(Command::Get { key: Bytes::from_static(b"k") });
+
+        assert!(matches!(handler.process(&login).await, Response::Ok));
+        assert!(matches!(handler.process(&frame(Command::Select { db: 3 })).await, Response::Ok));
+        assert!(matches!(handler.process(&get).await, Response::Nil));
+
+        assert!(matches!(handler.process(&frame(Command::Reset)).await, Response::Ok));
+        assert_eq!((handler.user(), handler.db()), (None, 0));
+        match handler.process(&get).await {
+            Response::Error(e) => assert!(e.starts_with("NOAUTH"), "{}", e),
+            other => panic!("Expected NOAUTH, got {:?}", other),
+        }
+        // RESET itself never needs auth
+        assert!(matches!(handler.process(&frame(Command::Reset)).await, Response::Ok));
+    }
+
+    #[tokio::test]
+    async fn test_multi_exec() {
+        let (handler, _pool) = test_handler(Config::default());
+        let key = || Bytes::from_static(b"counter");
+        let set = set_frame(&key(), b"1");
+        let incr = frame(Command::Extended(ExtendedCommand::Incr { key: key() }));
+
+        assert!(matches!(handler.process(&frame(Command::Multi)).await, Response::Ok));
+        assert!(matches!(handler.process(&set).await, Response::Queued));
+        assert!(matches!(handler.process(&incr).await, Response::Queued));
+        assert!(matches!(handler.process(&frame(Command::Get { key: key() })).await, Response::Queued));
+        // Nothing runs before EXEC
+        assert!(matches!(handler.process(&frame(Command::Multi)).await, Response::Error(_)));
+
+        match handler.process(&exec()).await {
+            Response::Replies(replies) => {
+                assert!(matches!(replies[0], Response::Ok));
+                assert!(matches!(replies[1], Response::Integer(2)));
+                assert!(matches!(&replies[2], Response::Value(v) if &v[..] == b"2"));
+            }
+            other => panic!("Expected replies, got {:?}", other),
+        }
+        assert!(matches!(handler.process(&exec()).await, Response::Error(_)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_exec_excludes_other_workers() {
+        let (handler, pool) = test_handler(Config::default());
+        let queue = pool.queue().clone();
+        let writer = ConcurrentHandler::new(queue.clone(), queue, Arc::new(Config::default()));
+
+        // Another connection overwrites the key on the other worker throughout
+        let stop = Arc::new(AtomicBool::new(false));
+        let writing = tokio::spawn({
+            let stop = stop.clone();
+            async move {
+                while !stop.load(Ordering::Relaxed) {
+                    writer.process(&set_frame(b"k", b"other")).await;
+                }
+            }
+        });
+
+        for _ in 0..20 {
+            assert!(matches!(handler.process(&frame(Command::Multi)).await, Response::Ok));
+            handler.process(&set_frame(b"k", b"tx")).await;
+            for _ in 0..1000 {
+                handler.process(&frame(Command::Get { key: Bytes::from_static(b"k") })).await;
+            }
+            match handler.process(&exec()).await {
+                Response::Replies(replies) => {
+                    assert!(replies[1..].iter().all(|r| matches!(r, Response::Value(v) if &v[..] == b"tx")));
+                }
+                other => panic!("Expected replies, got {:?}", other),
+            }
+        }
+        stop.store(true, Ordering::Relaxed);
+        writing.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_discard_drops_queued_commands() {
+        let (handler, _pool) = test_handler(Config::default());
+        let set = set_frame(b"k", b"v");
+        let get = frame(Command::Get { key: Bytes::from_static(b"k") });
+
+        assert!(matches!(handler.process(&frame(Command::Multi)).await, Response::Ok));
+        assert!(matches!(handler.process(&set).await, Response::Queued));
+        assert!(matches!(handler.process(&frame(Command::Discard)).await, Response::Ok));
+        assert!(matches!(handler.process(&get).await, Response::Nil));
+        assert!(matches!(handler.process(&frame(Command::Discard)).await, Response::Error(_)));
+
+        // A command refused while queuing aborts the whole transaction
+        assert!(matches!(handler.process(&frame(Command::Multi)).await, Response::Ok));
+        assert!(matches!(handler.process(&set).await, Response::Queued));
+        assert!(matches!(handler.process(&frame(Command::Select { db: 1 })).await, Response::Error(_)));
+        match handler.process(&exec()).await {
+            Response::Error(e) => assert!(e.starts_with("EXECABORT"), "{}", e),
+            other => panic!("Expected EXECABORT, got {:?}", other),
+        }
+        assert!(matches!(handler.process(&get).await, Response::Nil));
+    }
+
+    #[tokio::test]
+    async fn test_watch_aborts_on_concurrent_write() {
+        let (handler, pool) = test_handler(Config::default());
+        let other = ConcurrentHandler::new(pool.queue().clone(), pool.queue().clone(), Arc::new(Config::default()));
+        let watch = frame(Command::Watch { keys: vec![Bytes::from_static(b"balance")] });
+        let get = frame(Command::Get { key: Bytes::from_static(b"balance") });
+
+        assert!(matches!(handler.process(&set_frame(b"balance", b"10")).await, Response::Ok));
+        assert!(matches!(handler.process(&watch).await, Response::Ok));
+        assert!(matches!(handler.process(&frame(Command::Multi)).await, Response::Ok));
+        assert!(matches!(handler.process(&set_frame(b"balance", b"20")).await, Response::Queued));
+        // Another connection writes the watched key before EXEC
+        assert!(matches!(other.process(&set_frame(b"balance", b"99")).await, Response::Ok));
+        assert!(matches!(handler.process(&exec()).await, Response::Nil));
+        assert!(matches!(handler.process(&get).await, Response::Value(v) if &v[..] == b"99"));
+
+        // EXEC clears the watch, so the next transaction goes through
+        assert!(matches!(handler.process(&watch).await, Response::Ok));
+        assert!(matches!(handler.process(&frame(Command::Multi)).await, Response::Ok));
+        assert!(matches!(handler.process(&set_frame(b"balance", b"20")).await, Response::Queued));
+        assert!(matches!(handler.process(&exec()).await, Response::Replies(r) if r.len() == 1));
+        assert!(matches!(handler.process(&get).await, Response::Value(v) if &v[..] == b"20"));
+    }
+
+    #[tokio::test]
+    async fn test_del_pattern() {
+        use crate::security::{AclRule, Role};
+
+        let acl = Arc::new(AclManager::new());
+        let audit = Arc::new(AuditLogger::new(100));
+        let (handler, _pool) = test_handler(Config::default());
+
+        for key in ["session:1", "session:2", "sessions", "user:1"] {
+            let set = set_frame(key.as_bytes(), b"v");
+            handler.process(&set).await;
+        }
+        let handler = handler.with_acl(Some(acl.clone())).with_audit(Some(audit.clone()));
+        let purge = frame(Command::DelPattern { pattern: Bytes::from_static(b"session:*") });
+
+        // Read-only users can't purge
+        acl.assign_role("default", "readonly");
+        match handler.process(&purge).await {
+            Response::Error(e) => assert!(e.starts_with("NOPERM"), "{}", e),
+            other => panic!("Expected NOPERM, got {:?}", other),
+        }
+        assert_eq!(audit.recent(1)[0].event_type, AuditEventType::PermissionDenied);
+
+        acl.add_role(Role::new("purger").with_rule(AclRule::new("*").with_write()).allow_command("DELPATTERN"));
+        acl.assign_role("default", "purger");
+        assert!(matches!(handler.process(&purge).await, Response::Integer(2)));
+        let event = &audit.recent(1)[0];
+        assert_eq!(event.key.as_deref(), Some("session:*"));
+        assert_eq!(event.message.as_deref(), Some("deleted 2 keys"));
+
+        for (key, exists) in [("session:1", false), ("sessions", true), ("user:1", true)] {
+            let get = frame(Command::Exists { key: Bytes::from(key) });
+            let expected = if exists { 1 } else { 0 };
+            assert!(matches!(handler.process(&get).await, Response::Integer(n) if n == expected), "{}", key);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_acl_enforced_per_command() {
+        use crate::security::AuthConfig;
+
+        let auth = Arc::new(AuthManager::new(AuthConfig::default()));
+        auth.add_user("reader", "s3cret");
+        let acl = Arc::new(AclManager::new());
+        acl.assign_role("reader", "readonly");
+        let audit = Arc::new(AuditLogger::new(100));
+        let (handler, _pool) = test_handler(Config::default());
+        let handler = handler.with_auth(Some(auth)).with_acl(Some(acl)).with_audit(Some(audit.clone()));
+        let login = frame(Command::Auth {
+            username: Bytes::from_static(b"reader"),
+            password: Bytes::from_static(b"s3cret"),
+        });
+        let set = set_frame(b"k", b"v");
+        let get = frame(Command::Get { key: Bytes::from_static(b"k") });
+
+        assert!(matches!(handler.process(&login).await, Response::Ok));
+        match handler.process(&set).await {
+            Response::Error(e) => assert!(e.starts_with("NOPERM") && e.contains("'set'"), "{}", e),
+            other => panic!("Expected NOPERM, got {:?}", other),
+        }
+        let denied = &audit.recent(1)[0];
+        assert_eq!(denied.event_type, AuditEventType::PermissionDenied);
+        assert_eq!((denied.username.as_deref(), denied.command.as_deref()), (Some("reader"), Some("SET")));
+
+        assert!(matches!(handler.process(&get).await, Response::Nil));
+        assert!(matches!(handler.process(&frame(Command::Ping)).await, Response::Pong));
+    }
+
+    #[tokio::test]
+    async fn test_acl_denies_commands_without_key_permission() {
+        use crate::security::{AclRule, AuthConfig, Role};
+
+        let auth = Arc::new(AuthManager::new(AuthConfig::default()));
+        auth.add_user("reader", "s3cret");
+        let acl = Arc::new(AclManager::new());
+        acl.add_role(
+            Role::new("viewer")
+                .with_rule(AclRule::new("*").with_read())
+                .allow_command("*")
+                .deny_command("SUBSCRIBE")
+                .deny_command("WATCH"),
+        );
+        acl.assign_role("reader", "viewer");
+        let audit = Arc::new(AuditLogger::new(100));
+        let (handler, _pool) = test_handler(Config::default());
+        let handler = handler.with_auth(Some(auth)).with_acl(Some(acl)).with_audit(Some(audit.clone()));
+        let login = frame(Command::Auth {
+            username: Bytes::from_static(b"reader"),
+            password: Bytes::from_static(b"s3cret"),
+        });
+        assert!(matches!(handler.process(&login).await, Response::Ok));
+
+        let subscribe = frame(Command::Subscribe { events: Vec::new(), pattern: None });
+        match handler.process(&subscribe).await {
+            Response::Error(e) => assert!(e.starts_with("NOPERM") && e.contains("'subscribe'"), "{}", e),
+            other => panic!("Expected NOPERM, got {:?}", other),
+        }
+        assert!(handler.subscription.lock().unwrap().is_none());
+        assert_eq!(audit.recent(1)[0].command.as_deref(), Some("SUBSCRIBE"));
+
+        let watch = frame(Command::Watch { keys: vec![Bytes::from_static(b"k")] });
+        match handler.process(&watch).await {
+            Response::Error(e) => assert!(e.starts_with("NOPERM") && e.contains("'watch'"), "{}", e),
+            other => panic!("Expected NOPERM, got {:?}", other),
+        }
+        let get = frame(Command::Get { key: Bytes::from_static(b"k") });
+        assert!(matches!(handler.process(&get).await, Response::Nil));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_throttles_bursts_only() {
+        let limiter = Arc::new(RateLimiter::new(50.0, 5));
+        let audit = Arc::new(AuditLogger::new(100));
+        let (bursty, pool) = test_handler(Config::default());
+        let bursty = bursty
+            .with_rate_limiter(Some(limiter.clone()))
+            .with_audit(Some(audit.clone()))
+            .with_peer_addr("10.0.0.1:5000".parse().unwrap());
+        let slow = ConcurrentHandler::new(
+            pool.queue().clone(),
+            pool.queue().clone(),
+            Arc::new(Config::default()),
+        )
+        .with_rate_limiter(Some(limiter))
+        .with_peer_addr("10.0.0.2:5000".parse().unwrap());
+        let get = frame(Command::Get { key: Bytes::from_static(b"k") });
+
+        let mut rejected = 0;
+        for _ in 0..20 {
+            if let Response::Error(e) = bursty.process(&get).await {
+                assert!(e.starts_with("RATELIMITED"), "{}", e);
+                rejected += 1;
+            }
+        }
+        assert!(rejected >= 10, "only {} of 20 rejected", rejected);
+        // One audit event for the whole burst
+        let events = audit.recent(100);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, AuditEventType::RateLimited);
+        assert_eq!(events[0].message.as_deref(), Some("rate limited (1 rejected)"));
+
+        // 25/s against a 50/s limit never runs dry
+        for _ in 0..10 {
+            assert!(matches!(slow.process(&get).await, Response::Nil));
+            tokio::time::sleep(Duration::from_millis(40)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_covers_auth() {
+        use crate::security::AuthConfig;
+
+        let auth = Arc::new(AuthManager::new(AuthConfig::default()));
+        auth.add_user("alice", "s3cret");
+        let (handler, _pool) = test_handler(Config::default());
+        let handler = handler
+            .with_auth(Some(auth))
+            .with_rate_limiter(Some(Arc::new(RateLimiter::new(1.0, 3))))
+            .with_peer_addr("10.0.0.1:5000".parse().unwrap());
+        let login = |password: &'static [u8]| {
+            frame(Command::Auth {
+                username: Bytes::from_static(b"alice"),
+                password: Bytes::from_static(password),
+            })
+        };
+
+        for _ in 0..3 {
+            match handler.process(&login(b"guess")).await {
+                Response::Error(e) => assert!(e.starts_with("WRONGPASS"), "{}", e),
+                other => panic!("Expected WRONGPASS, got {:?}", other),
+            }
+        }
+        // Out of tokens: even the right password waits
+        match handler.process(&login(b"s3cret")).await {
+            Response::Error(e) => assert!(e.starts_with("RATELIMITED"), "{}", e),
+            other => panic!("Expected RATELIMITED, got {:?}", other),
+        }
+        assert_eq!(handler.user(), None);
+    }
+
+    #[tokio::test]
+    async fn test_flushall_clears_keys_and_vectors() {
+        let databases = Databases::new(2, 4);
+        let vectors = SemanticCache::with_defaults();
+        let mut pool = WorkerPool::new(
+            WorkerPoolConfig { num_workers: 1, pin_to_cores: false, queue_capacity: 16 },
+            databases.clone(),
+            vectors.clone(),
+            Arc::new(Metrics::new()),
+        );
+        pool.start();
+        let handler = ConcurrentHandler::new(
+            pool.queue().clone(),
+            pool.queue().clone(),
+            Arc::new(Config::default().with_databases(2)),
+        );
+
+        for db in databases.iter() {
+            db.set(Bytes::from_static(b"k"), Bytes::from_static(b"v"), None);
+        }
+        let dim = vectors.config().dimension;
+        vectors.set(Bytes::from_static(b"q"), vec![0.5; dim], Bytes::new(), None).unwrap();
+
+        let flush = |vectors| frame(Command::FlushAll { vectors });
+        assert!(matches!(handler.process(&flush(false)).await, Response::Ok));
+        assert!(databases.iter().all(|db| db.is_empty()));
+        assert_eq!(vectors.len(), 1);
+
+        assert!(matches!(handler.process(&flush(true)).await, Response::Ok));
+        assert_eq!(vectors.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_command_count_and_info() {
+        let (handler, _pool) = test_handler(Config::default());
+
+        match handler.process(&frame(Command::CommandCount)).await {
+            Response::Integer(n) => assert_eq!(n as usize, crate::protocol::COMMAND_TABLE.len()),
+            other => panic!("Expected integer, got {:?}", other),
+        }
+
+        let info = frame(Command::CommandInfo { names: vec![Bytes::from_static(b"set")] });
+        match handler.process(&info).await {
+            Response::Array(items) => {
+                let fields: Vec<_> = std::str::from_utf8(&items[0]).unwrap().split(' ').collect();
+                assert_eq!(fields[0], "set");
+                assert!(fields[2].split(',').any(|f| f == "write"));
+                assert_eq!(fields[3], "1");
+            }
+            other => panic!("Expected array, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_get_flag_returns_old_value() {
+        let (handler, _pool) = test_handler(Config::default());
+        let set = |value: &'static [u8], options| {
+            frame(Command::Set {
+                key: Bytes::from_static(b"k"),
+                value: Bytes::from_static(value),
+                ttl: None,
+                options,
+            })
+        };
+        let get = crate::protocol::SetOptions { get: true, ..Default::default() };
+
+        assert!(matches!(handler.process(&set(b"v1", get)).await, Response::Nil));
+        match handler.process(&set(b"v2", get)).await {
+            Response::Value(v) => assert_eq!(v.as_ref(), b"v1"),
+            other => panic!("Expected value, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_keyspace_notifications() {
+        let key = Bytes::from_static(b"user:1");
+
+        let server = ConcurrentServer::new(Config::default().with_keyspace_notifications(true));
+        let mut events = server.pubsub().subscribe("__keyevent@0__:set");
+        server.store().set(key.clone(), Bytes::from_static(b"v"), None);
+        let msg = events.try_recv().unwrap();
+        assert_eq!(msg.payload, key);
+
+        let server = ConcurrentServer::new(Config::default());
+        let mut events = server.pubsub().subscribe("__keyevent@0__:set");
+        server.store().set(key, Bytes::from_static(b"v"), None);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_select_isolates_databases_per_connection() {
+        let (first, pool) = test_handler(Config::default().with_databases(2));
+        let second = ConcurrentHandler::new(
+            pool.queue().clone(),
+            pool.queue().clone(),
+            Arc::new(Config::default().with_databases(2)),
+        );
+        let key = Bytes::from_static(b"k");
+        let get = frame(Command::Get { key: key.clone() });
+
+        first.process(&set_frame(&key, b"zero")).await;
+        assert!(matches!(first.process(&frame(Command::Select { db: 1 })).await, Response::Ok));
+        assert!(matches!(first.process(&get).await, Response::Nil));
+        first.process(&set_frame(&key, b"one")).await;
+
+        // The other connection is still on DB 0
+        match second.process(&get).await {
+            Response::Value(v) => assert_eq!(v.as_ref(), b"zero"),
+            other => panic!("Expected value, got {:?}", other),
+        }
+        assert!(matches!(
+            second.process(&frame(Command::Select { db: 2 })).await,
+            Response::Error(_)
+        ));
+
+        // FLUSHDB only clears the selected database; FLUSHALL clears both
+        first.process(&frame(Command::FlushDb)).await;
+        assert!(matches!(first.process(&get).await, Response::Nil));
+        assert!(matches!(second.process(&get).await, Response::Value(_)));
+        second.process(&frame(Command::FlushAll { vectors: false })).await;
+        assert!(matches!(second.process(&get).await, Response::Nil));
+    }
+
+    #[tokio::test]
+    async fn test_cluster_redirects() {
+        use crate::cluster::{ShardManager, Slot, SlotRange};
+
+        let owned = Bytes::from_static(b"owned");
+        let foreign = Bytes::from_static(b"foreign");
+        let migrating = Bytes::from_static(b"migrating");
+        let importing = Bytes::from_static(b"importing");
+        let slot = |key: &Bytes| Slot::from_key(key).0;
+        let slots = [slot(&owned), slot(&foreign), slot(&migrating), slot(&importing)];
+        assert!((1..slots.len()).all(|i| !slots[..i].contains(&slots[i])));
+
+        // Node 1 owns everything except the foreign key's slot (node 2);
+        // the migrating key's slot is owned by node 2 and moving to node 3,
+        // the importing key's slot is owned by node 2 and moving here
+        let shards = Arc::new(ShardManager::new());
+        shards.assign_slots(1, SlotRange::new(0, 16383));
+        for key in [&foreign, &migrating, &importing] {
+            shards.assign_slots(2, SlotRange::new(slot(key), slot(key)));
+        }
+        shards.start_migration(slot(&migrating), 3);
+        shards.start_migration(slot(&importing), 1);
+
+        let router = ClusterRouter::new(1, shards)
+            .with_node_addr(2, "10.0.0.2:6380".parse().unwrap())
+            .with_node_addr(3, "10.0.0.3:6380".parse().unwrap());
+        let (handler, _pool) = test_handler(Config::default());
+        let handler = handler.with_cluster(Arc::new(router));
+
+        let get = |key: &Bytes| frame(Command::Get { key: key.clone() });
+        assert!(matches!(handler.process(&get(&owned)).await, Response::Nil));
+
+        match handler.process(&get(&foreign)).await {
+            Response::Moved { slot: s, addr } => {
+                assert_eq!(s, slot(&foreign));
+                assert_eq!(addr, "10.0.0.2:6380");
+            }
+            other => panic!("Expected MOVED, got {:?}", other),
+        }
+
+        // Only the owner sends ASK; a bystander sends clients to the owner
+        match handler.process(&get(&migrating)).await {
+            Response::Moved { slot: s, addr } => {
+                assert_eq!(s, slot(&migrating));
+                assert_eq!(addr, "10.0.0.2:6380");
+            }
+            other => panic!("Expected MOVED, got {:?}", other),
+        }
+
+        // An importing slot is served only to the request right after ASKING
+        let moved_to_owner = |response| matches!(response, Response::Moved { addr, .. } if addr == "10.0.0.2:6380");
+        assert!(moved_to_owner(handler.process(&get(&importing)).await));
+        assert!(matches!(handler.process(&frame(Command::Asking)).await, Response::Ok));
+        assert!(matches!(handler.process(&get(&importing)).await, Response::Nil));
+        assert!(moved_to_owner(handler.process(&get(&importing)).await));
+
+        // Redirects survive the wire encoding
+        let moved = Response::Moved { slot: 7, addr: "10.0.0.2:6380".to_string() };
+        match Response::from_frame(&moved.to_frame(1)).unwrap() {
+            Response::Moved { slot, addr } => assert_eq!((slot, addr.as_str()), (7, "10.0.0.2:6380")),
+            other => panic!("Expected MOVED, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_migrating_slot_serves_held_keys() {
+        use crate::cluster::{ShardManager, Slot};
+
+        let shards = Arc::new(ShardManager::new());
+        shards.distribute_slots(&[1]);
+        let router = ClusterRouter::new(1, shards.clone()).with_node_addr(3, "10.0.0.3:6380".parse().unwrap());
+        let (handler, _pool) = test_handler(Config::default());
+        let handler = handler.with_cluster(Arc::new(router));
+        let get = |key: &'static [u8]| frame(Command::Get { key: Bytes::from_static(key) });
+        assert!(matches!(handler.process(&set_frame(b"{m}:held", b"v")).await, Response::Ok));
+        shards.start_migration(Slot::from_key(b"{m}").0, 3);
+
+        // Keys still here are read locally; writes and misses go to the target
+        assert!(matches!(handler.process(&get(b"{m}:held")).await, Response::Value(_)));
+        for request in [get(b"{m}:moved"), set_frame(b"{m}:held", b"v")] {
+            match handler.process(&request).await {
+                Response::Ask { addr, .. } => assert_eq!(addr, "10.0.0.3:6380"),
+                other => panic!("Expected ASK, got {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_writes_fenced_without_quorum() {
+        use crate::cluster::{ClusterTopology, Node, NO_QUORUM_ERROR};
+
+        let addr: SocketAddr = "127.0.0.1:6380".parse().unwrap();
+        let mut topology = ClusterTopology::new();
+        for id in 1..=3 {
+            topology.add_node(Node::new(id, addr, addr));
+        }
+        let topology = Arc::new(RwLock::new(topology));
+        let fence = Arc::new(QuorumFence::new(topology.clone()));
+        let mut pool = WorkerPool::new(
+            WorkerPoolConfig { num_workers: 1, pin_to_cores: false, queue_capacity: 16 },
+            Databases::new(1, 4),
+            SemanticCache::with_defaults(),
+            Arc::new(Metrics::new()),
+        )
+        .with_fence(Some(fence.clone()));
+        pool.start();
+        let handler = ConcurrentHandler::new(pool.queue().clone(), pool.queue().clone(), Arc::new(Config::default()));
+
+        let key = Bytes::from_static(b"k");
+        let set = set_frame(&key, b"v");
+        let get = frame(Command::Get { key: key.clone() });
+        let del = frame(Command::Del { key });
+        assert!(matches!(handler.process(&set).await, Response::Ok));
+
+        // Partitioned away from the other two nodes: writes stop, reads go on
+        for id in [2, 3] {
+            topology.write().unwrap().get_node_mut(id).unwrap().mark_down();
+        }
+        for write in [&set, &del] {
+            match handler.process(write).await {
+                Response::Error(e) => assert_eq!(e, NO_QUORUM_ERROR),
+                other => panic!("Expected NOQUORUM, got {:?}", other),
+            }
+        }
+        assert!(matches!(handler.process(&get).await, Response::Value(_)));
+
+        // A split brain fences writes even with quorum
+        topology.write().unwrap().get_node_mut(2).unwrap().state = crate::cluster::NodeState::Healthy;
+        assert!(matches!(handler.process(&set).await, Response::Ok));
+        fence.set_split_brain(true);
+        assert!(matches!(handler.process(&set).await, Response::Error(_)));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_replica_acks() {
+        use crate::cluster::ReplicationConfig;
+
+        let manager = Arc::new(ReplicationManager::new(ReplicationConfig::default()));
+        manager.add_replica(1);
+        manager.add_replica(2);
+        let databases = Databases::from_fn(1, |db| ConcurrentStore::new().with_replication(manager.clone(), db as u32));
+        let mut pool = WorkerPool::new(
+            WorkerPoolConfig { num_workers: 1, pin_to_cores: false, queue_capacity: 16 },
+            databases,
+            SemanticCache::with_defaults(),
+            Arc::new(Metrics::new()),
+        );
+        pool.start();
+        let handler = ConcurrentHandler::new(pool.queue().clone(), pool.queue().clone(), Arc::new(Config::default()))
+            .with_replication(Some(manager.clone()));
+
+        let set = set_frame(b"k", b"v");
+        assert!(matches!(handler.process(&set).await, Response::Ok));
+        let offset = manager.offset();
+        let wait = |num_replicas, timeout_ms| frame(Command::Wait { num_replicas, timeout_ms });
+
+        // Returns as soon as enough replicas ack, well before the timeout
+        let start = Instant::now();
+        let ack = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            manager.ack(1, offset);
+        };
+        let wait_one = wait(1, 5000);
+        let (reply, _) = tokio::join!(handler.process(&wait_one), ack);
+        assert!(matches!(reply, Response::Integer(1)), "{:?}", reply);
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        // Otherwise the partial count once the timeout passes
+        let start = Instant::now();
+        assert!(matches!(handler.process(&wait(2, 100)).await, Response::Integer(1)));
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_cluster_slots() {
+        use crate::cluster::sharding::TOTAL_SLOTS;
+        use crate::cluster::{ShardManager, Slot};
+        use crate::security::{AclRule, Role};
+
+        let shards = Arc::new(ShardManager::new());
+        shards.distribute_slots(&[1, 2, 3]);
+        shards.start_migration(0, 2);
+        let router = ClusterRouter::new(1, shards.clone())
+            .with_node_addr(1, "10.0.0.1:6380".parse().unwrap())
+            .with_node_addr(2, "10.0.0.2:6380".parse().unwrap())
+            .with_node_addr(3, "10.0.0.3:6380".parse().unwrap());
+        let acl = Arc::new(AclManager::new());
+        let (handler, _pool) = test_handler(Config::default());
+        let handler = handler.with_cluster(Arc::new(router)).with_acl(Some(acl.clone()));
+        let slots = frame(Command::ClusterSlots);
+
+        acl.assign_role("default", "readonly");
+        match handler.process(&slots).await {
+            Response::Error(e) => assert!(e.starts_with("NOPERM"), "{}", e),
+            other => panic!("Expected NOPERM, got {:?}", other),
+        }
+
+        acl.add_role(Role::new("topology").with_rule(AclRule::new("*").with_permission(Permission::Cluster)));
+        acl.assign_role("default", "topology");
+        let records = match handler.process(&slots).await {
+            Response::Array(items) => items,
+            other => panic!("Expected array, got {:?}", other),
+        };
+
+        let mut next = 0;
+        for record in &records {
+            let fields: Vec<_> = std::str::from_utf8(record).unwrap().split(' ').collect();
+            let (start, end): (u16, u16) = (fields[0].parse().unwrap(), fields[1].parse().unwrap());
+            let owner: u64 = fields[2].parse().unwrap();
+            assert_eq!(start, next, "ranges must be contiguous");
+            assert!((start..=end).all(|s| shards.get_node_for_slot(Slot(s)) == Some(owner)));
+            assert_eq!(fields[3], format!("10.0.0.{}:6380", owner));
+            if start == 0 {
+                assert_eq!(&fields[4..], ["migrating", "2", "10.0.0.2:6380"]);
+            }
+            next = end + 1;
+        }
+        assert_eq!(next, TOTAL_SLOTS);
+        assert_eq!(records.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_max_connections_rejects_extra_clients() {
+        use futures::{SinkExt, StreamExt};
+        use tokio::net::TcpStream;
+
+        let mut config = Config::default().with_max_connections(2);
+        config.kv_workers = 1;
+        config.vector_workers = 1;
+        let server = ConcurrentServer::new(config);
+        let metrics = server.metrics().clone();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(server.serve(listener));
+
+        async fn ping(client: &mut Framed<TcpStream, VcpCodec>) -> Response {
+            client.send(Frame::ping(1)).await.unwrap();
+            Response::from_frame(&client.next().await.unwrap().unwrap()).unwrap()
+        }
+
+        let mut clients = Vec::new();
+        for _ in 0..2 {
+            let mut client = Framed::new(TcpStream::connect(addr).await.unwrap(), VcpCodec::new());
+            assert!(matches!(ping(&mut client).await, Response::Pong));
+            clients.push(client);
+        }
+
+        let mut extra = Framed::new(TcpStream::connect(addr).await.unwrap(), VcpCodec::new());
+        match Response::from_frame(&extra.next().await.unwrap().unwrap()).unwrap() {
+            Response::Error(e) => assert_eq!(e, MAX_CLIENTS_ERROR),
+            other => panic!("Expected error, got {:?}", other),
+        }
+        assert!(extra.next().await.is_none());
+
+        for client in &mut clients {
+            assert!(matches!(ping(client).await, Response::Pong));
+        }
+        assert_eq!(metrics.active_connections(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_set_then_get_keeps_order() {
+        use futures::{SinkExt, StreamExt};
+        use tokio::net::TcpStream;
+
+        let server = ConcurrentServer::new(Config { kv_workers: 4, vector_workers: 1, ..Default::default() });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(server.serve(listener));
+
+        let mut client = Framed::new(TcpStream::connect(addr).await.unwrap(), VcpCodec::new());
+        let key = Bytes::from_static(b"k");
+
+        // A slow write to the key, then a read an idle worker would
+        // otherwise pick up while the write is still running
+        let mut pairs: Vec<_> = (0..50_000).map(|i| (Bytes::from(format!("f{}", i)), Bytes::from_static(b"x"))).collect();
+        pairs.push((key.clone(), Bytes::from_static(b"mset")));
+        client.feed(Command::Extended(ExtendedCommand::MSet { pairs }).to_frame(1000)).await.unwrap();
+        client.feed(Command::Get { key: key.clone() }.to_frame(1001)).await.unwrap();
+        client.flush().await.unwrap();
+        for _ in 0..2 {
+            let frame = client.next().await.unwrap().unwrap();
+            if frame.header.request_id == 1001 {
+                assert!(matches!(Response::from_frame(&frame).unwrap(), Response::Value(v) if &v[..] == b"mset"));
+            }
+        }
+
+        for i in 0..200u64 {
+            let set = Command::Set { key: key.clone(), value: Bytes::from(format!("v{}", i)), ttl: None, options: Default::default() };
+            client.feed(set.to_frame(2 * i)).await.unwrap();
+            client.feed(Command::Get { key: key.clone() }.to_frame(2 * i + 1)).await.unwrap();
+        }
+        client.flush().await.unwrap();
+
+        let mut gets = std::collections::HashMap::new();
+        for _ in 0..400 {
+            let frame = client.next().await.unwrap().unwrap();
+            if frame.header.request_id % 2 == 1 {
+                gets.insert(frame.header.request_id / 2, Response::from_frame(&frame).unwrap());
+            }
+        }
+        for i in 0..200u64 {
+            match &gets[&i] {
+                Response::Value(v) => assert_eq!(v, &Bytes::from(format!("v{}", i))),
+                other => panic!("GET {} saw {:?}", i, other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_list_and_kill() {
+        use futures::{SinkExt, StreamExt};
+        use tokio::net::TcpStream;
+
+        let server = ConcurrentServer::new(Config { kv_workers: 1, vector_workers: 1, ..Default::default() });
+        let clients = server.clients().clone();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(server.serve(listener));
+
+        async fn request(client: &mut Framed<TcpStream, VcpCodec>, cmd: Command) -> Response {
+            client.send(cmd.to_frame(1)).await.unwrap();
+            Response::from_frame(&client.next().await.unwrap().unwrap()).unwrap()
+        }
+
+        let mut admin = Framed::new(TcpStream::connect(addr).await.unwrap(), VcpCodec::new());
+        let mut victim = Framed::new(TcpStream::connect(addr).await.unwrap(), VcpCodec::new());
+        assert!(matches!(request(&mut victim, Command::Ping).await, Response::Pong));
+
+        let records = match request(&mut admin, Command::ClientList).await {
+            Response::Array(records) => records,
+            other => panic!("Expected client records, got {:?}", other),
+        };
+        assert_eq!(records.len(), 2);
+        let records: Vec<String> = records.iter().map(|r| String::from_utf8_lossy(r).into_owned()).collect();
+        let victim_addr = victim.get_ref().local_addr().unwrap();
+        let victim_record = records.iter().find(|r| r.contains(&format!("addr={} ", victim_addr))).unwrap();
+        assert!(victim_record.ends_with("user=default age=0 cmd=ping"), "{}", victim_record);
+        let id: u64 = victim_record.split(' ').next().unwrap().strip_prefix("id=").unwrap().parse().unwrap();
+
+        assert!(matches!(request(&mut admin, Command::ClientKill { id }).await, Response::Ok));
+        assert!(victim.next().await.is_none());
+        match request(&mut admin, Command::ClientKill { id }).await {
+            Response::Error(e) => assert_eq!(e, NO_SUCH_CLIENT_ERROR),
+            other => panic!("Expected error, got {:?}", other),
+        }
+        assert!(matches!(request(&mut admin, Command::Ping).await, Response::Pong));
+        // The killed handler deregisters as it finishes dropping
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while clients.len() > 1 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn test_shard_count() {
+        let server = ConcurrentServer::new(Config { kv_workers: 2, shard_count: 256, ..Default::default() });
+        assert_eq!(server.shards(), 256);
+        assert!(server.databases().iter().all(|db| db.shards() == 256));
+
+        // Auto: four per KV worker, rounded up to a power of two
+        let server = ConcurrentServer::new(Config { kv_workers: 3, ..Default::default() });
+        assert_eq!(server.shards(), 16);
+    }
+
+    #[tokio::test]
+    async fn test_client_commands_need_admin() {
+        let acl = Arc::new(AclManager::new());
+        let (handler, _pool) = test_handler(Config::default());
+        let handler = handler.with_acl(Some(acl.clone())).with_clients(ClientRegistry::new());
+        let id = handler.client_id().unwrap();
+
+        acl.assign_role("default", "readonly");
+        for cmd in [Command::ClientList, Command::ClientKill { id }] {
+            match handler.process(&frame(cmd)).await {
+                Response::Error(e) => assert!(e.starts_with("NOPERM"), "{}", e),
+                other => panic!("Expected NOPERM, got {:?}", other),
+            }
+        }
+
+        acl.assign_role("default", "admin");
+        assert!(matches!(handler.process(&frame(Command::ClientList)).await, Response::Array(r) if r.len() == 1));
+    }
+
+    #[tokio::test]
+    async fn test_responses_interleave_by_request_id() {
+        use futures::{SinkExt, StreamExt};
+        use tokio::net::TcpStream;
+
+        let server = ConcurrentServer::new(Config { kv_workers: 2, vector_workers: 1, ..Default::default() });
+        let store = server.store().clone();
+        store.set(Bytes::from_static(b"slow"), Bytes::from_static(b"v"), None);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(server.serve(listener));
+
+        // Hold the key's shard read-locked so the SET has to wait for it
+        let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+        let holder = std::thread::spawn(move || {
+            store.for_each_live(|_, _, _| {
+                locked_tx.send(()).unwrap();
+                std::thread::sleep(Duration::from_millis(200));
+            });
+        });
+        locked_rx.recv().unwrap();
+
+        let mut client = Framed::new(TcpStream::connect(addr).await.unwrap(), VcpCodec::new());
+        client.send(set_frame(b"slow", b"v2")).await.unwrap();
+        client.send(Command::Get { key: Bytes::from_static(b"other") }.to_frame(2)).await.unwrap();
+
+        // The GET of an unrelated key overtakes the blocked SET on the other worker
+        let first = client.next().await.unwrap().unwrap();
+        assert_eq!(first.header.request_id, 2);
+        assert!(matches!(Response::from_frame(&first).unwrap(), Response::Nil));
+        let second = client.next().await.unwrap().unwrap();
+        assert_eq!(second.header.request_id, 1);
+        assert!(matches!(Response::from_frame(&second).unwrap(), Response::Ok));
+        holder.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unix_socket_serves_commands() {
+        use futures::{SinkExt, StreamExt};
+        use tokio::net::UnixStream;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("celrix.sock");
+        let config = Config { kv_workers: 1, vector_workers: 1, ..Default::default() }.with_unix_socket(&path);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = tokio::spawn(ConcurrentServer::new(config).serve(listener));
+        while !path.exists() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        let mut client = Framed::new(UnixStream::connect(&path).await.unwrap(), VcpCodec::new());
+        client.send(set_frame(b"k", b"v")).await.unwrap();
+        let reply = client.next().await.unwrap().unwrap();
+        assert!(matches!(Response::from_frame(&reply).unwrap(), Response::Ok));
+        client.send(Command::Get { key: Bytes::from_static(b"k") }.to_frame(2)).await.unwrap();
+        let reply = client.next().await.unwrap().unwrap();
+        assert!(matches!(Response::from_frame(&reply).unwrap(), Response::Value(v) if &v[..] == b"v"));
+
+        server.abort();
+        assert!(server.await.unwrap_err().is_cancelled());
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_pushes_expired_events() {
+        use futures::{SinkExt, StreamExt};
+        use tokio::net::TcpStream;
+
+        let config = Config { kv_workers: 1, vector_workers: 1, ..Default::default() }
+            .with_keyspace_notifications(true)
+            .with_ttl_interval(1);
+        let server = ConcurrentServer::new(config);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(server.serve(listener));
+
+        let mut client = Framed::new(TcpStream::connect(addr).await.unwrap(), VcpCodec::new());
+        let subscribe = Command::Subscribe {
+            events: vec![Bytes::from_static(b"expired")],
+            pattern: Some(Bytes::from_static(b"session:*")),
+        };
+        client.send(subscribe.to_frame(1)).await.unwrap();
+        let reply = client.next().await.unwrap().unwrap();
+        assert!(matches!(Response::from_frame(&reply).unwrap(), Response::Ok));
+
+        for (id, key) in [(2, &b"other"[..]), (3, &b"session:1"[..])] {
+            let set = Command::Set {
+                key: Bytes::copy_from_slice(key),
+                value: Bytes::from_static(b"v"),
+                ttl: Some(50),
+                options: crate::protocol::SetOptions { px: true, ..Default::default() },
+            };
+            client.send(set.to_frame(id)).await.unwrap();
+            let reply = client.next().await.unwrap().unwrap();
+            assert!(matches!(Response::from_frame(&reply).unwrap(), Response::Ok));
+        }
+
+        // Only the matching key's expiry is pushed, once the cleaner runs
+        let pushed = tokio::time::timeout(Duration::from_secs(3), client.next()).await.unwrap().unwrap().unwrap();
+        assert_eq!(pushed.header.request_id, 0);
+        match Response::from_frame(&pushed).unwrap() {
+            Response::Event { event, key } => {
+                assert_eq!(event.as_ref(), b"expired");
+                assert_eq!(key.as_ref(), b"session:1");
+            }
+            other => panic!("Expected event, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slowlog_records_slow_commands() {
+        let databases = Databases::new(1, 4);
+        let store = databases.get(0).unwrap().clone();
+        store.set(Bytes::from_static(b"slow"), Bytes::from_static(b"v"), None);
+        let slowlog = Arc::new(Slowlog::new(Duration::from_millis(50), 16));
+        let pool_config = WorkerPoolConfig { num_workers: 1, pin_to_cores: false, queue_capacity: 8 };
+        let mut pool = WorkerPool::new(pool_config, databases, SemanticCache::with_defaults(), Arc::new(Metrics::new()))
+            .with_slowlog(Some(slowlog.clone()));
+        pool.start();
+        let handler = ConcurrentHandler::new(pool.queue().clone(), pool.queue().clone(), Arc::new(Config::default()))
+            .with_slowlog(Some(slowlog.clone()));
+
+        // Hold the key's shard read-locked so the SET has to wait for it
+        let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+        let holder = std::thread::spawn(move || {
+            store.for_each_live(|_, _, _| {
+                locked_tx.send(()).unwrap();
+                std::thread::sleep(Duration::from_millis(150));
+            });
+        });
+        locked_rx.recv().unwrap();
+        let set = set_frame(b"slow", b"v2");
+        assert!(matches!(handler.process(&set).await, Response::Ok));
+        holder.join().unwrap();
+        handler.process(&frame(Command::Get { key: Bytes::from_static(b"fast") })).await;
+
+        match handler.process(&frame(Command::SlowlogGet { count: 10 })).await {
+            Response::Array(records) => {
+                assert_eq!(records.len(), 1, "{:?}", records);
+                let record = String::from_utf8(records[0].to_vec()).unwrap();
+                let fields: Vec<&str> = record.split(' ').collect();
+                assert_eq!(fields[3..], ["set", "1", "slow"]);
+                assert!(fields[2].parse::<u64>().unwrap() >= 50_000, "{}", record);
+            }
+            other => panic!("Expected slowlog entries, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_debug_sleep_and_object() {
+        let acl = Arc::new(AclManager::new());
+        let (handler, _pool) = test_handler(Config::default());
+        let handler = handler.with_acl(Some(acl.clone()));
+        let sleep = frame(Command::DebugSleep { ms: 100 });
+
+        acl.assign_role("default", "readonly");
+        match handler.process(&sleep).await {
+            Response::Error(e) => assert!(e.starts_with("NOPERM"), "{}", e),
+            other => panic!("Expected NOPERM, got {:?}", other),
+        }
+
+        acl.assign_role("default", "admin");
+        let start = Instant::now();
+        assert!(matches!(handler.process(&sleep).await, Response::Ok));
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(100) && elapsed < Duration::from_millis(500), "{:?}", elapsed);
+
+        let set = set_frame(b"n", b"12345");
+        handler.process(&set).await;
+        match handler.process(&frame(Command::DebugObject { key: Bytes::from_static(b"n") })).await {
+            Response::Value(v) => {
+                let info = String::from_utf8(v.to_vec()).unwrap();
+                assert!(info.starts_with("encoding:int serializedlength:5 ttl_ms:-1 idle_ms:"), "{}", info);
+                assert!(info.ends_with(" freq:1"), "{}", info);
+            }
+            other => panic!("Expected object info, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_idle_connections_are_closed() {
+        use crate::security::AuditEventType;
+        use futures::{SinkExt, StreamExt};
+        use std::time::Duration;
+        use tokio::net::TcpStream;
+
+        let mut config = Config::default().with_idle_timeout(1);
+        config.kv_workers = 1;
+        config.vector_workers = 1;
+        let audit = Arc::new(AuditLogger::new(100));
+        let server = ConcurrentServer::new(config).with_audit_logger(audit.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(server.serve(listener));
+
+        let mut idle = Framed::new(TcpStream::connect(addr).await.unwrap(), VcpCodec::new());
+        let mut active = Framed::new(TcpStream::connect(addr).await.unwrap(), VcpCodec::new());
+
+        // Ping well within the window for longer than the timeout
+        for _ in 0..6 {
+            active.send(Frame::ping(1)).await.unwrap();
+            let frame = active.next().await.unwrap().unwrap();
+            assert!(matches!(Response::from_frame(&frame).unwrap(), Response::Pong));
+            tokio::time::sleep(Duration::from_millis(300)).await;
+        }
+
+        let closed = tokio::time::timeout(Duration::from_secs(1), idle.next()).await;
+        assert!(matches!(closed, Ok(None)));
+
+        let events = audit.recent(10);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, AuditEventType::Disconnect);
+        assert_eq!(events[0].message.as_deref(), Some("idle timeout"));
+    }
+
+    #[tokio::test]
+    async fn test_proxy_header_sets_client_ip() {
+        use crate::security::AuditEventType;
+        use futures::{SinkExt, StreamExt};
+        use tokio::io::AsyncWriteExt;
+        use tokio::net::TcpStream;
+
+        let config = Config { kv_workers: 1, vector_workers: 1, ..Default::default() }.with_proxy_protocol(true);
+        let audit = Arc::new(AuditLogger::new(100));
+        let server = ConcurrentServer::new(config).with_audit_logger(audit.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(server.serve(listener));
+
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        socket.write_all(b"PROXY TCP4 203.0.113.7 10.0.0.1 51000 6380\r\n").await.unwrap();
+        let mut client = Framed::new(socket, VcpCodec::new());
+        client.send(Frame::ping(1)).await.unwrap();
+        let frame = client.next().await.unwrap().unwrap();
+        assert!(matches!(Response::from_frame(&frame).unwrap(), Response::Pong));
+        drop(client);
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while audit.recent(1).is_empty() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let event = &audit.recent(1)[0];
+        assert_eq!(event.event_type, AuditEventType::Disconnect);
+        assert_eq!(event.client_ip.as_deref(), Some("203.0.113.7"));
+    }
+
+    #[tokio::test]
+    async fn test_follower_serves_reads_within_lag_bound() {
+        let config = Config { replica_max_lag: 10, ..Config::default() };
+        let (handler, _pool) = test_handler(config);
+        let state = Arc::new(FollowerState::new());
+        let handler = handler.with_follower(state.clone());
+        let get = frame(Command::Get { key: Bytes::from_static(b"k") });
+        let error = |response| match response {
+            Response::Error(e) => e,
+            other => panic!("Expected error, got {:?}", other),
+        };
+
+        // Not streaming yet, so the lag is unknown
+        assert!(error(handler.process(&get).await).starts_with("STALE replica is not streaming"));
+
+        state.mark_connected("10.0.0.1:6380", 100);
+        state.record(95, 100);
+        assert!(matches!(handler.process(&get).await, Response::Nil));
+        assert!(matches!(handler.process(&exists(b"k")).await, Response::Integer(0)));
+        assert_eq!(
+            error(handler.process(&set_frame(b"k", &[b'x'; 100])).await),
+            "READONLY writes must go to the leader at 10.0.0.1:6380"
+        );
+
+        state.record(95, 120);
+        assert_eq!(
+            error(handler.process(&get).await),
+            "STALE replica is 25 entries behind the leader at 10.0.0.1:6380 (limit 10); read from the leader"
+        );
+        // Keyless commands don't depend on replicated data
+        assert!(matches!(handler.process(&frame(Command::Ping)).await, Response::Pong));
+
+        state.record(120, 120);
+        assert!(matches!(handler.process(&get).await, Response::Nil));
+        state.mark_disconnected();
+        assert!(error(handler.process(&get).await).starts_with("STALE"));
+    }
+
+    #[tokio::test]
+    async fn test_hello_negotiates_version() {
+        let (handler, _pool) = test_handler(Config::default());
+        let hello = |versions: &[u8]| frame(Command::Hello { versions: versions.to_vec(), capabilities: CAP_COMPRESSION });
+
+        match handler.process(&hello(&[1, 9])).await {
+            Response::Array(reply) => assert_eq!(reply, [&b"proto"[..], b"1", b"capabilities", b"1"]),
+            other => panic!("Expected HELLO reply, got {:?}", other),
+        }
+        assert!(matches!(handler.process(&frame(Command::Ping)).await, Response::Pong));
+
+        // Frames in any other version are refused once one is agreed
+        let mut v2 = frame(Command::Ping);
+        v2.header.version = 2;
+        match handler.process(&v2).await {
+            Response::Error(e) => assert_eq!(e, "NOPROTO frame version 2 doesn't match negotiated version 1"),
+            other => panic!("Expected version error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hello_rejects_unsupported_version() {
+        let (handler, _pool) = test_handler(Config::default());
+        let hello = frame(Command::Hello { versions: vec![2, 3], capabilities: 0 });
+        match handler.process(&hello).await {
+            Response::Error(e) => assert_eq!(e, "NOPROTO unsupported protocol version 2,3, server supports 1"),
+            other => panic!("Expected NOPROTO, got {:?}", other),
+        }
+
+        let mut v9 = frame(Command::Ping);
+        v9.header.version = 9;
+        assert!(matches!(handler.process(&v9).await, Response::Error(e) if e.starts_with("NOPROTO")));
+        // The failed HELLO left the connection usable at version 1
+        assert!(matches!(handler.process(&frame(Command::Ping)).await, Response::Pong));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_frame_closes_connection() {
+        use crate::protocol::FrameHeader;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpStream;
+
+        let config = Config { kv_workers: 1, vector_workers: 1, max_frame_size: 1024, ..Default::default() };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(ConcurrentServer::new(config).serve(listener));
+
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        let mut header = bytes::BytesMut::new();
+        FrameHeader::new(OpCode::Set, 1).with_payload_len(u32::MAX).encode(&mut header);
+        socket.write_all(&header).await.unwrap();
+
+        // Closed on the header alone, without waiting for the payload
+        let mut buf = [0u8; 64];
+        let read = tokio::time::timeout(Duration::from_secs(2), socket.read(&mut buf)).await.unwrap();
+        assert!(matches!(read, Ok(0) | Err(_)), "{:?}", read);
+    }
+
+    /// Room for one 100-byte value, under a policy with no TTL keys to evict
+    fn oom_config(action: OomAction) -> Config {
+        Config {
+            eviction: EvictionConfig::default()
+                .with_max_memory(ENTRY_OVERHEAD + 150)
+                .with_policy(EvictionPolicy::VolatileLru)
+                .with_oom_action(action),
+            ..Config::default()
+        }
+    }
+
+    fn exists(key: &'static [u8]) -> Frame {
+        frame(Command::Exists { key: Bytes::from_static(key) })
+    }
+
+    #[tokio::test]
+    async fn test_oom_reject_refuses_write() {
+        let (handler, _pool) = test_handler(oom_config(OomAction::Reject));
+        assert!(matches!(handler.process(&set_frame(b"k1", &[b'x'; 100])).await, Response::Ok));
+
+        match handler.process(&set_frame(b"k2", &[b'x'; 100])).await {
+            Response::Error(e) => assert_eq!(e, OOM_ERROR),
+            other => panic!("Expected OOM error, got {:?}", other),
+        }
+        assert!(matches!(handler.process(&exists(b"k1")).await, Response::Integer(1)));
+        assert!(matches!(handler.process(&exists(b"k2")).await, Response::Integer(0)));
+    }
+
+    #[tokio::test]
+    async fn test_oom_reject_covers_mset_and_copy() {
+        let (handler, _pool) = test_handler(oom_config(OomAction::Reject));
+        let mset = frame(Command::Extended(ExtendedCommand::MSet {
+            pairs: vec![
+                (Bytes::from_static(b"m1"), Bytes::from(vec![b'x'; 100])),
+                (Bytes::from_static(b"m2"), Bytes::from(vec![b'x'; 100])),
+            ],
+        }));
+        assert!(matches!(handler.process(&mset).await, Response::Error(e) if e == OOM_ERROR));
+        assert!(matches!(handler.process(&exists(b"m1")).await, Response::Integer(0)));
+
+        assert!(matches!(handler.process(&set_frame(b"k1", &[b'x'; 100])).await, Response::Ok));
+        let copy = frame(Command::Copy { src: Bytes::from_static(b"k1"), dst: Bytes::from_static(b"k2"), replace: false });
+        assert!(matches!(handler.process(&copy).await, Response::Error(e) if e == OOM_ERROR));
+        assert!(matches!(handler.process(&exists(b"k2")).await, Response::Integer(0)));
+    }
+
+    #[tokio::test]
+    async fn test_oom_evict_makes_room() {
+        let (handler, _pool) = test_handler(oom_config(OomAction::Evict));
+        assert!(matches!(handler.process(&set_frame(b"k1", &[b'x'; 100])).await, Response::Ok));
+        assert!(matches!(handler.process(&set_frame(b"k2", &[b'x'; 100])).await, Response::Ok));
+
+        assert!(matches!(handler.process(&exists(b"k1")).await, Response::Integer(0)));
+        assert!(matches!(handler.process(&exists(b"k2")).await, Response::Integer(1)));
+    }
+
+    fn assert_too_large(response: Response) {
+        match response {
+            Response::Error(e) => assert!(e.contains("exceeds max_value_size (100 bytes)"), "{}", e),
+            other => panic!("Expected value size error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_max_value_size_rejects_large_writes() {
+        let config = Config { kv_workers: 1, vector_workers: 1, max_value_size: 100, ..Default::default() };
+        let (handler, _pool) = test_handler(config);
+
+        assert!(matches!(handler.process(&set_frame(b"small", &[b'x'; 100])).await, Response::Ok));
+        assert_too_large(handler.process(&set_frame(b"big", &[b'x'; 101])).await);
+        assert!(matches!(handler.process(&exists(b"big")).await, Response::Integer(0)));
+
+        let mset = frame(Command::Extended(ExtendedCommand::MSet {
+            pairs: vec![
+                (Bytes::from_static(b"m1"), Bytes::from_static(b"v")),
+                (Bytes::from_static(b"m2"), Bytes::from(vec![b'x'; 101])),
+            ],
+        }));
+        assert_too_large(handler.process(&mset).await);
+        assert!(matches!(handler.process(&exists(b"m1")).await, Response::Integer(0)));
+    }
+
+    #[tokio::test]
+    async fn test_max_value_size_counts_appended_value() {
+        let config = Config { kv_workers: 1, vector_workers: 1, max_value_size: 100, ..Default::default() };
+        let (handler, _pool) = test_handler(config);
+        assert!(matches!(handler.process(&set_frame(b"k", &[b'x'; 90])).await, Response::Ok));
+
+        let append = |len: usize| {
+            frame(Command::Append { key: Bytes::from_static(b"k"), value: Bytes::from(vec![b'y'; len]) })
+        };
+        assert!(matches!(handler.process(&append(10)).await, Response::Integer(100)));
+        assert_too_large(handler.process(&append(1)).await);
+        let strlen = frame(Command::Strlen { key: Bytes::from_static(b"k") });
+        assert!(matches!(handler.process(&strlen).await, Response::Integer(100)));
+    }
+
+    /// Index of the queue in `queues` that `cmd` was sent to, answering it
+    /// with nil; None if it went to `shared`
+    async fn routed_to(
+        handler: &Arc<ConcurrentHandler>,
+        shared: &CommandQueue,
+        queues: &[CommandQueue],
+        cmd: Command,
+    ) -> Option<usize> {
+        let task = tokio::spawn({
+            let handler = handler.clone();
+            async move { handler.process(&frame(cmd)).await }
+        });
+        let target = loop {
+            if let Ok(item) = shared.try_recv() {
+                break (None, item);
+            }
+            if let Some((i, item)) = queues.iter().enumerate().find_map(|(i, q)| q.try_recv().ok().map(|item| (i, item))) {
+                break (Some(i), item);
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        };
+        target.1.response_tx.send(WorkResult::Nil).unwrap();
+        task.await.unwrap();
+        target.0
+    }
+
+    #[tokio::test]
+    async fn test_route_by_key_sends_same_key_to_same_worker() {
+        let shared = CommandQueue::new(8);
+        let queues: Vec<CommandQueue> = (0..4).map(|_| CommandQueue::new(8)).collect();
+        let handler = Arc::new(
+            ConcurrentHandler::new(shared.clone(), shared.clone(), Arc::new(Config::default()))
+                .with_key_routes(queues.clone().into()),
+        );
+
+        for key in [&b"user:1"[..], b"user:2", b"session:abc", b"{user:1}.cart"] {
+            let get = || Command::Get { key: Bytes::copy_from_slice(key) };
+            let first = routed_to(&handler, &shared, &queues, get()).await;
+            assert_eq!(first, Some(route_key(key, 4)));
+            for _ in 0..3 {
+                assert_eq!(routed_to(&handler, &shared, &queues, get()).await, first);
+            }
+            let del = Command::Del { key: Bytes::copy_from_slice(key) };
+            assert_eq!(routed_to(&handler, &shared, &queues, del).await, first);
+        }
+        assert_eq!(route_key(b"{user:1}.cart", 4), route_key(b"user:1", 4));
+        assert_eq!(routed_to(&handler, &shared, &queues, Command::Ping).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_route_by_key_serves_commands() {
+        let config = Config { kv_workers: 2, route_by_key: true, ..Default::default() };
+        let (handler, pool) = test_handler(config);
+        assert_eq!(pool.worker_queues().len(), 2);
+
+        for key in [&b"a"[..], b"b", b"c", b"d"] {
+            let set = set_frame(key, b"v");
+            assert!(matches!(handler.process(&set).await, Response::Ok));
+        }
+        let get = frame(Command::Get { key: Bytes::from_static(b"c") });
+        assert!(matches!(handler.process(&get).await, Response::Value(v) if v == "v"));
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_replies_busy() {
+        // No workers drain this queue, so it fills after one item
+        let queue = CommandQueue::new(1);
+        let metrics = Arc::new(Metrics::new());
+        let config = Config::default().with_queue_send_timeout(2);
+        let handler = ConcurrentHandler::new(queue.clone(), queue.clone(), Arc::new(config))
+            .with_metrics(metrics.clone());
+
+        let (tx, _rx) = tokio::sync::oneshot::channel();
+        queue
+            .try_send(WorkItem { command: Command::Ping, request_id: 0, db: 0, response_tx: tx })
+            .unwrap();
+
+        for _ in 0..3 {
+            match handler.process(&frame(Command::Ping)).await {
+                Response::Busy { retry_after_ms } => assert_eq!(retry_after_ms, 2),
+                other => panic!("Expected BUSY, got {:?}", other),
+            }
+        }
+        assert_eq!(metrics.busy_rejections(), 3);
+
+        let busy = Response::Busy { retry_after_ms: 2 }.to_frame(7);
+        assert!(matches!(Response::from_frame(&busy).unwrap(), Response::Busy { retry_after_ms: 2 }));
+    }
+
+    #[tokio::test]
+    async fn test_queue_saturation_is_exported() {
+        use crate::observability::PrometheusExporter;
+
+        // No workers: the queue holds whatever is sent until drained here
+        let queue = CommandQueue::new(4);
+        let config = Config::default().with_queue_send_timeout(1);
+        let handler = ConcurrentHandler::new(queue.clone(), queue.clone(), Arc::new(config));
+        let exporter = PrometheusExporter::new()
+            .with_queue("kv", queue.clone())
+            .with_queue("vector", CommandQueue::new(8));
+
+        let mut receivers = Vec::new();
+        for request_id in 0..4 {
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            queue.try_send(WorkItem { command: Command::Ping, request_id, db: 0, response_tx: tx }).unwrap();
+            receivers.push(rx);
+        }
+        for _ in 0..5 {
+            assert!(matches!(handler.process(&frame(Command::Ping)).await, Response::Busy { .. }));
+        }
+        assert_eq!(queue.rejected(), 5);
+
+        let output = exporter.export();
+        assert!(output.contains("celrix_queue_depth{pool=\"kv\"} 4\n"), "{}", output);
+        assert!(output.contains("celrix_queue_capacity{pool=\"kv\"} 4\n"));
+        assert!(output.contains("celrix_queue_rejections_total{pool=\"kv\"} 5\n"));
+        assert!(output.contains("celrix_queue_depth{pool=\"vector\"} 0\n"));
+        assert!(output.contains("celrix_queue_rejections_total{pool=\"vector\"} 0\n"));
+
+        // Depth follows the queue down; rejections only ever count up
+        queue.try_recv().unwrap();
+        queue.try_recv().unwrap();
+        let output = exporter.export();
+        assert!(output.contains("celrix_queue_depth{pool=\"kv\"} 2\n"), "{}", output);
+        assert!(output.contains("celrix_queue_rejections_total{pool=\"kv\"} 5\n"));
+    }
+
+    #[tokio::test]
+    async fn test_type_reports_string_or_none() {
+        let (handler, _pool) = test_handler(Config::default());
+        let key = Bytes::from_static(b"k");
+        let kind = |response| match response {
+            Response::Value(v) => String::from_utf8(v.to_vec()).unwrap(),
+            other => panic!("Expected value, got {:?}", other),
+        };
+
+        let type_cmd = frame(Command::Type { key: key.clone() });
+        assert_eq!(kind(handler.process(&type_cmd).await), "none");
+
+        let set = set_frame(&key, b"v");
+        handler.process(&set).await;
+        assert_eq!(kind(handler.process(&type_cmd).await), "string");
+
+        handler.process(&frame(Command::Del { key })).await;
+        assert_eq!(kind(handler.process(&type_cmd).await), "none");
+    }
+
+    #[tokio::test]
+    async fn test_vsearch_rejects_wrong_dimension() {
+        let (handler, _pool) = test_handler(Config::default());
+        // The default cache holds 1536-dimension vectors
+        let vadd = frame(Command::VAdd { key: Bytes::from_static(b"v"), vector: vec![0.5; 1536] });
+        assert!(matches!(handler.process(&vadd).await, Response::Ok));
+
+        for len in [1, 1535, 1537, 4096] {
+            match handler.process(&frame(Command::VSearch { vector: vec![0.5; len], k: 1 })).await {
+                Response::Error(e) => assert_eq!(e, format!("Dimension mismatch: expected 1536, got {}", len)),
+                other => panic!("Expected dimension error, got {:?}", other),
+            }
+        }
+        let search = frame(Command::VSearch { vector: vec![0.5; 1536], k: 1 });
+        assert!(matches!(handler.process(&search).await, Response::Array(keys) if keys.len() == 1));
+    }
+
+    #[tokio::test]
+    async fn test_get_with_ttl() {
+        let (handler, _pool) = test_handler(Config::default());
+        let set = |key: &'static [u8], ttl| {
+            frame(Command::Set {
+                key: Bytes::from_static(key),
+                value: Bytes::from_static(b"v"),
+                ttl,
+                options: Default::default(),
+            })
+        };
+        let get = |key: &'static [u8]| frame(Command::GetWithTtl { key: Bytes::from_static(key) });
+        handler.process(&set(b"ttl", Some(10))).await;
+        handler.process(&set(b"forever", None)).await;
+
+        // Round-trip through the wire format as a client would see it
+        let response = handler.process(&get(b"ttl")).await;
+        match Response::from_frame(&response.to_frame(1)).unwrap() {
+            Response::ValueWithTtl { value, ttl_ms } => {
+                assert_eq!(value.as_ref(), b"v");
+                assert!((9_000..=10_000).contains(&ttl_ms), "ttl {}ms", ttl_ms);
+            }
+            other => panic!("Expected value with TTL, got {:?}", other),
+        }
+        assert!(matches!(
+            handler.process(&get(b"forever")).await,
+            Response::ValueWithTtl { ttl_ms: -1, .. }
+        ));
+        assert!(matches!(handler.process(&get(b"missing")).await, Response::Nil));
+
+        // Plain GET is unchanged
+        let plain = frame(Command::Get { key: Bytes::from_static(b"ttl") });
+        assert!(matches!(handler.process(&plain).await, Response::Value(_)));
+    }
+
+    #[tokio::test]
+    async fn test_error_kinds() {
+        use crate::protocol::{ErrorKind, OpCode};
+
+        let (handler, _pool) = test_handler(Config::default());
+        let kind = |response: Response| Response::from_frame(&response.to_frame(1)).unwrap().error_kind();
+
+        // GET whose key length runs past the payload
+        let malformed = Frame::new(OpCode::Get, 1, Bytes::from_static(&[0, 0, 0, 9, b'k']));
+        assert_eq!(kind(handler.process(&malformed).await), Some(ErrorKind::Protocol));
+
+        let rename = frame(Command::Rename { src: Bytes::from_static(b"missing"), dst: Bytes::from_static(b"dst") });
+        assert_eq!(kind(handler.process(&rename).await), Some(ErrorKind::NotFound));
+
+        let set = set_frame(b"text", b"abc");
+        handler.process(&set).await;
+        let incr = frame(Command::Extended(ExtendedCommand::Incr { key: Bytes::from_static(b"text") }));
+        assert_eq!(kind(handler.process(&incr).await), Some(ErrorKind::Command));
+        assert_eq!(kind(handler.process(&frame(Command::Ping)).await), None);
+
+        assert_eq!(kind(Response::Busy { retry_after_ms: 5 }), Some(ErrorKind::Retryable));
+        assert_eq!(ErrorKind::of("NOPERM this user has no permissions"), ErrorKind::Permission);
+        assert_eq!(ErrorKind::of("INTERNAL worker pool unavailable"), ErrorKind::Internal);
+    }
+
+    #[tokio::test]
+    async fn test_get_reports_expired_keys() {
+        let (handler, _pool) = test_handler(Config::default());
+        let set = |key: &'static [u8], ttl_ms| {
+            frame(Command::Set {
+                key: Bytes::from_static(key),
+                value: Bytes::from_static(b"v"),
+                ttl: ttl_ms,
+                options: crate::protocol::SetOptions { px: true, ..Default::default() },
+            })
+        };
+        // Round-trip through the wire format as a client would see it
+        let handler = &handler;
+        let get = |key: &'static [u8]| async move {
+            let response = handler.process(&frame(Command::GetOrExpired { key: Bytes::from_static(key) })).await;
+            Response::from_frame(&response.to_frame(1)).unwrap()
+        };
+        handler.process(&set(b"live", None)).await;
+        handler.process(&set(b"lapsed", Some(10))).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert!(matches!(get(b"live").await, Response::Value(v) if &v[..] == b"v"));
+        assert!(matches!(get(b"lapsed").await, Response::Expired));
+        assert!(matches!(get(b"never").await, Response::Nil));
+
+        // Plain GET still can't tell the two apart
+        for key in [&b"lapsed"[..], b"never"] {
+            let plain = frame(Command::Get { key: Bytes::from_static(key) });
+            assert!(matches!(handler.process(&plain).await, Response::Nil));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dump_restore_round_trip() {
+        let (source, _source_pool) = test_handler(Config::default());
+        let (target, _target_pool) = test_handler(Config::default());
+        let key = Bytes::from_static(b"k");
+        source
+            .process(&frame(Command::Set {
+                key: key.clone(),
+                value: Bytes::from_static(b"v"),
+                ttl: Some(60_000),
+                options: crate::protocol::SetOptions { px: true, ..Default::default() },
+            }))
+            .await;
+
+        let blob = match source.process(&frame(Command::Dump { key: key.clone() })).await {
+            Response::Value(blob) => blob,
+            other => panic!("Expected DUMP blob, got {:?}", other),
+        };
+        let restore = |blob: Bytes, replace| {
+            frame(Command::Restore { key: key.clone(), blob, ttl_ms: 0, replace })
+        };
+        assert!(matches!(target.process(&restore(blob.clone(), false)).await, Response::Ok));
+
+        let get = frame(Command::Get { key: key.clone() });
+        assert!(matches!(target.process(&get).await, Response::Value(v) if v.as_ref() == b"v"));
+        match target.process(&frame(Command::PTtl { key: key.clone() })).await {
+            Response::Integer(ms) => assert!((50_000..=60_000).contains(&ms), "pttl {}ms", ms),
+            other => panic!("Expected PTTL, got {:?}", other),
+        }
+
+        match target.process(&restore(blob.clone(), false)).await {
+            Response::Error(e) => assert!(e.starts_with("BUSYKEY"), "{}", e),
+            other => panic!("Expected BUSYKEY, got {:?}", other),
+        }
+        assert!(matches!(target.process(&restore(blob.clone(), true)).await, Response::Ok));
+
+        let mut corrupt = blob.to_vec();
+        corrupt[2] ^= 0xff;
+        assert!(matches!(
+            target.process(&restore(Bytes::from(corrupt), true)).await,
+            Response::Error(_)
+        ));
+        let missing = frame(Command::Dump { key: Bytes::from_static(b"missing") });
+        assert!(matches!(source.process(&missing).await, Response::Nil));
+    }
+
+    #[tokio::test]
+    async fn test_millisecond_ttl() {
+        let (handler, _pool) = test_handler(Config::default());
+        let key = Bytes::from_static(b"bucket");
+        let int = |r: Response| match r {
+            Response::Integer(n) => n,
+            other => panic!("Expected integer, got {:?}", other),
+        };
+
+        let set = Command::Set {
+            key: key.clone(),
+            value: Bytes::from_static(b"1"),
+            ttl: Some(250),
+            options: crate::protocol::SetOptions { px: true, ..Default::default() },
+        };
+        assert!(matches!(handler.process(&frame(set)).await, Response::Ok));
+        let pttl = int(handler.process(&frame(Command::PTtl { key: key.clone() })).await);
+        assert!(pttl > 0 && pttl <= 250, "pttl = {}", pttl);
+        assert_eq!(int(handler.process(&frame(Command::Ttl { key: key.clone() })).await), 0);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let get = frame(Command::Get { key: key.clone() });
+        assert!(matches!(handler.process(&get).await, Response::Value(_)));
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(matches!(handler.process(&get).await, Response::Nil));
+        assert_eq!(int(handler.process(&frame(Command::PTtl { key: key.clone() })).await), -2);
+
+        // PEXPIRE on a persistent key
+        handler.process(&set_frame(&key, b"2")).await;
+        assert_eq!(int(handler.process(&frame(Command::Ttl { key: key.clone() })).await), -1);
+        let expire = frame(Command::PExpire { key: key.clone(), ms: 2000 });
+        assert_eq!(int(handler.process(&expire).await), 1);
+        assert_eq!(int(handler.process(&frame(Command::Ttl { key })).await), 2);
+    }
+
+    #[tokio::test]
+    async fn test_multi_key_and_counter_commands() {
+        let (handler, _pool) = test_handler(Config::default());
+        let ext = |cmd| frame(Command::Extended(cmd));
+        let b = |s: &'static str| Bytes::from_static(s.as_bytes());
+
+        let mset = ext(ExtendedCommand::MSet { pairs: vec![(b("a"), b("1")), (b("b"), b("x"))] });
+        assert!(matches!(handler.process(&mset).await, Response::Ok));
+
+        let mget = ext(ExtendedCommand::MGet { keys: vec![b("a"), b("missing"), b("b")] });
+        match handler.process(&mget).await {
+            Response::Values(items) => assert_eq!(items, vec![Some(b("1")), None, Some(b("x"))]),
+            other => panic!("Expected values, got {:?}", other),
+        }
+
+        let incr = ext(ExtendedCommand::IncrBy { key: b("a"), delta: 41 });
+        assert!(matches!(handler.process(&incr).await, Response::Integer(42)));
+        let decr = ext(ExtendedCommand::Decr { key: b("fresh") });
+        assert!(matches!(handler.process(&decr).await, Response::Integer(-1)));
+        let bad = ext(ExtendedCommand::Incr { key: b("b") });
+        assert!(matches!(handler.process(&bad).await, Response::Error(_)));
+
+        let mdel = ext(ExtendedCommand::MDel { keys: vec![b("a"), b("b"), b("missing")] });
+        assert!(matches!(handler.process(&mdel).await, Response::Integer(2)));
     }
 }
@@ -0,0 +1,81 @@
+//! PROXY Protocol
+//!
+//! Reads the HAProxy PROXY protocol v1 header a load balancer sends ahead
+//! of the client's traffic, recovering the client's real address.
+
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Longest v1 header, including the trailing CRLF
+pub const MAX_HEADER_LEN: usize = 107;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid PROXY header: {}", msg))
+}
+
+/// Parse a v1 header line without its CRLF, e.g.
+/// `PROXY TCP4 203.0.113.7 10.0.0.1 51000 6380`. Returns the source
+/// address, or None for `PROXY UNKNOWN` (e.g. a load balancer health check).
+pub fn parse_v1(line: &[u8]) -> io::Result<Option<SocketAddr>> {
+    let line = std::str::from_utf8(line).map_err(|_| invalid("not ASCII"))?;
+    let mut fields = line.split(' ');
+    if fields.next() != Some("PROXY") {
+        return Err(invalid("missing PROXY signature"));
+    }
+    let ipv4 = match fields.next() {
+        Some("TCP4") => true,
+        Some("TCP6") => false,
+        Some("UNKNOWN") => return Ok(None),
+        _ => return Err(invalid("unknown protocol")),
+    };
+
+    let fields: Vec<&str> = fields.collect();
+    let [src_ip, dst_ip, src_port, dst_port] = fields[..] else {
+        return Err(invalid("expected source and destination addresses and ports"));
+    };
+    let src_ip: IpAddr = src_ip.parse().map_err(|_| invalid("bad source address"))?;
+    let dst_ip: IpAddr = dst_ip.parse().map_err(|_| invalid("bad destination address"))?;
+    if src_ip.is_ipv4() != ipv4 || dst_ip.is_ipv4() != ipv4 {
+        return Err(invalid("address family doesn't match protocol"));
+    }
+    let src_port: u16 = src_port.parse().map_err(|_| invalid("bad source port"))?;
+    dst_port.parse::<u16>().map_err(|_| invalid("bad destination port"))?;
+    Ok(Some(SocketAddr::new(src_ip, src_port)))
+}
+
+/// Read and parse the v1 header at the start of `stream`. Reads a byte at
+/// a time so nothing after the header is consumed.
+pub async fn read_v1<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Option<SocketAddr>> {
+    let mut line = Vec::with_capacity(MAX_HEADER_LEN);
+    while !line.ends_with(b"\r\n") {
+        if line.len() == MAX_HEADER_LEN {
+            return Err(invalid("no CRLF within 107 bytes"));
+        }
+        line.push(stream.read_u8().await?);
+    }
+    parse_v1(&line[..line.len() - 2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_v1() {
+        let addr = parse_v1(b"PROXY TCP4 203.0.113.7 10.0.0.1 51000 6380").unwrap();
+        assert_eq!(addr, Some("203.0.113.7:51000".parse().unwrap()));
+        let addr = parse_v1(b"PROXY TCP6 2001:db8::1 2001:db8::2 4000 6380").unwrap();
+        assert_eq!(addr, Some("[2001:db8::1]:4000".parse().unwrap()));
+        assert_eq!(parse_v1(b"PROXY UNKNOWN").unwrap(), None);
+
+        for bad in [
+            &b"GET / HTTP/1.1"[..],
+            b"PROXY TCP4 203.0.113.7 10.0.0.1 51000",
+            b"PROXY TCP4 2001:db8::1 10.0.0.1 51000 6380",
+            b"PROXY TCP4 203.0.113.7 10.0.0.1 70000 6380",
+        ] {
+            assert!(parse_v1(bad).is_err());
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Per-client Rate Limiting
+//!
+//! Token buckets keyed by authenticated user, or by client IP for
+//! connections that haven't logged in. Rejections are counted per bucket
+//! so a flooding client produces a handful of audit events, not one each.
+
+use dashmap::DashMap;
+use std::time::{Duration, Instant};
+
+use super::config::Config;
+
+/// Minimum gap between audit reports for one throttled client
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    /// Rejections not yet reported
+    unreported: u64,
+    reported_at: Option<Instant>,
+}
+
+/// Why `RateLimiter::check` turned a request away
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Throttled {
+    /// How long until a token is available
+    pub retry_after: Duration,
+    /// Rejections to audit now, if a report is due: the first rejection for
+    /// a key, then the count since the last report at most once a second
+    pub report: Option<u64>,
+}
+
+/// Shared token-bucket limiter, one bucket per client key
+pub struct RateLimiter {
+    /// Tokens added per second
+    rate: f64,
+    /// Bucket capacity
+    burst: f64,
+    buckets: DashMap<String, Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: u32) -> Self {
+        Self {
+            rate,
+            burst: burst.max(1) as f64,
+            buckets: DashMap::new(),
+        }
+    }
+
+    /// Limiter for the configured rate, if rate limiting is enabled
+    pub fn from_config(config: &Config) -> Option<Self> {
+        (config.rate_limit > 0.0).then(|| Self::new(config.rate_limit, config.rate_limit_burst))
+    }
+
+    /// Take a token for `key`, or say how long until one is available
+    pub fn check(&self, key: &str) -> Result<(), Throttled> {
+        let now = Instant::now();
+        let mut bucket = self.buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            refilled_at: now,
+            unreported: 0,
+            reported_at: None,
+        });
+
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            bucket.unreported += 1;
+            let due = bucket.reported_at.is_none_or(|at| now.duration_since(at) >= REPORT_INTERVAL);
+            let report = due.then(|| {
+                bucket.reported_at = Some(now);
+                std::mem::take(&mut bucket.unreported)
+            });
+            Err(Throttled {
+                retry_after: Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate),
+                report,
+            })
+        }
+    }
+
+    /// Drop buckets that have refilled completely; they'd be recreated full
+    pub fn prune(&self) {
+        let now = Instant::now();
+        self.buckets.retain(|_, bucket| {
+            let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+            bucket.tokens + elapsed * self.rate < self.burst
+        });
+    }
+
+    /// Number of clients currently tracked
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets_are_per_key() {
+        let limiter = RateLimiter::new(1.0, 3);
+        for _ in 0..3 {
+            assert!(limiter.check("alice").is_ok());
+        }
+        let throttled = limiter.check("alice").unwrap_err();
+        assert!(throttled.retry_after > Duration::ZERO && throttled.retry_after <= Duration::from_secs(1));
+        assert!(limiter.check("bob").is_ok());
+
+        // The first rejection is reported at once, later ones batched
+        assert_eq!(throttled.report, Some(1));
+        assert_eq!(limiter.check("alice").unwrap_err().report, None);
+        assert_eq!(limiter.check("alice").unwrap_err().report, None);
+
+        // Full buckets are dropped, partially drained ones kept
+        limiter.prune();
+        assert_eq!(limiter.len(), 2);
+    }
+}
@@ -0,0 +1,84 @@
+//! Unix Socket Listener
+//!
+//! Local client connections over a Unix domain socket, access-controlled
+//! by the socket file's permissions.
+
+use std::io;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use tokio::net::{UnixListener, UnixStream};
+
+/// Listener that removes its socket file when dropped
+pub struct UnixSocketListener {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl UnixSocketListener {
+    /// Bind `path`, replacing a socket left behind by an earlier run.
+    /// Any other kind of file at `path` is an error rather than removed.
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        match std::fs::symlink_metadata(path) {
+            Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path)?,
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(Self {
+            listener: UnixListener::bind(path)?,
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub async fn accept(&self) -> io::Result<UnixStream> {
+        self.listener.accept().await.map(|(stream, _)| stream)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for UnixSocketListener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Accept from `listener`, or wait forever if there is none
+pub(crate) async fn accept_from(listener: Option<&UnixSocketListener>) -> io::Result<UnixStream> {
+    match listener {
+        Some(listener) => listener.accept().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_bind_replaces_stale_socket_and_cleans_up() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("celrix.sock");
+
+        // Dropping a std listener leaves its socket file behind, like a crash
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        let listener = UnixSocketListener::bind(&path).unwrap();
+        assert!(path.exists());
+        drop(listener);
+        assert!(!path.exists());
+
+        let file = dir.path().join("data.txt");
+        std::fs::write(&file, b"keep me").unwrap();
+        assert!(UnixSocketListener::bind(&file).is_err());
+        assert_eq!(std::fs::read(&file).unwrap(), b"keep me");
+    }
+}
@@ -2,14 +2,24 @@
 //!
 //! Multi-threaded worker pool with CPU core affinity.
 
+use bytes::Bytes;
+use crossbeam::channel::{select, Receiver};
+use crossbeam::utils::CachePadded;
+use parking_lot::RwLock;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
+use std::time::Duration;
 use tracing::{debug, info};
 
+use crate::cluster::{QuorumFence, Slot, NO_QUORUM_ERROR};
 use crate::metrics::Metrics;
-use crate::protocol::Command;
-use crate::storage::ConcurrentStore;
-use crate::vector::{SemanticCache, SemanticResult};
+use crate::observability::Slowlog;
+use crate::protocol::{command_info, command_list, Command, ExtendedCommand, SetOptions, COMMAND_TABLE};
+use crate::storage::{
+    ConcurrentStore, Databases, EvictionConfig, Lookup, SetCondition, ENTRY_OVERHEAD, OOM_ERROR, SCAN_TIME_BUDGET,
+};
+use crate::vector::SemanticCache;
 
 use super::command_queue::{CommandQueue, WorkItem, WorkResult};
 
@@ -34,21 +44,145 @@ impl Default for WorkerPoolConfig {
     }
 }
 
+/// Shared state each worker thread runs against
+struct WorkerState {
+    databases: Databases,
+    vector_store: SemanticCache,
+    metrics: Arc<Metrics>,
+    fence: Option<Arc<QuorumFence>>,
+    slowlog: Option<Arc<Slowlog>>,
+    limits: Arc<WriteLimits>,
+    exec_gate: Arc<ExecGate>,
+}
+
+/// Keeps other workers' commands from landing between an EXEC's watch
+/// check and its last command, without a shared lock on every command
+/// while no transaction is running.
+///
+/// A worker marks itself active (on its own cache line) and runs its
+/// command lock-free unless an EXEC is pending, in which case it takes
+/// `lock` shared. EXEC announces itself in `pending`, takes `lock`
+/// exclusively, then waits out any lock-free commands already started.
+///
+/// Only this pool's workers are held off. Writes that don't go through
+/// them can still interleave with a transaction: commands on the vector
+/// pool, keys removed by the background TTL cleaner, and entries a
+/// follower's replication applier writes.
+struct ExecGate {
+    /// EXECs waiting for or holding `lock`
+    pending: AtomicUsize,
+    lock: RwLock<()>,
+    /// Per worker: running a command without holding `lock`
+    active: Box<[CachePadded<AtomicBool>]>,
+}
+
+/// Clears a worker's active flag when its lock-free command ends
+struct ActiveGuard<'a>(&'a AtomicBool);
+
+impl Drop for ActiveGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+impl ExecGate {
+    fn new(workers: usize) -> Self {
+        Self {
+            pending: AtomicUsize::new(0),
+            lock: RwLock::new(()),
+            active: (0..workers).map(|_| CachePadded::new(AtomicBool::new(false))).collect(),
+        }
+    }
+
+    /// Run an ordinary command on `worker`
+    fn run<T>(&self, worker: usize, f: impl FnOnce() -> T) -> T {
+        let active = &self.active[worker];
+        // SeqCst pairs with `run_exclusive`: either EXEC sees this flag or
+        // this sees EXEC's `pending`
+        active.store(true, Ordering::SeqCst);
+        if self.pending.load(Ordering::SeqCst) == 0 {
+            let _active = ActiveGuard(active);
+            return f();
+        }
+        active.store(false, Ordering::SeqCst);
+        let _shared = self.lock.read();
+        f()
+    }
+
+    /// Run EXEC with no other worker's command in progress
+    fn run_exclusive<T>(&self, f: impl FnOnce() -> T) -> T {
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        let result = {
+            let _exclusive = self.lock.write();
+            for active in self.active.iter() {
+                while active.load(Ordering::SeqCst) {
+                    thread::yield_now();
+                }
+            }
+            f()
+        };
+        self.pending.fetch_sub(1, Ordering::SeqCst);
+        result
+    }
+}
+
+/// Checks a write must pass before it touches the store
+#[derive(Debug, Clone, Default)]
+struct WriteLimits {
+    /// Memory limit, made room under before writes that add bytes
+    eviction: EvictionConfig,
+    /// Largest value a write may store (0 = unlimited)
+    max_value_size: usize,
+}
+
+impl WriteLimits {
+    /// Refuse `cmd` if it would store a value over `max_value_size`, or if
+    /// there's no room for it under the memory limit
+    fn check(&self, store: &ConcurrentStore, cmd: &Command) -> Result<(), String> {
+        if self.max_value_size > 0 {
+            if let Some(len) = stored_value_len(store, cmd) {
+                if len > self.max_value_size {
+                    return Err(format!(
+                        "ERR value of {} bytes exceeds max_value_size ({} bytes)",
+                        len, self.max_value_size
+                    ));
+                }
+            }
+        }
+        if let Some(incoming) = write_size(store, cmd) {
+            if !self.eviction.make_room(store, incoming) {
+                return Err(OOM_ERROR.to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Multi-threaded worker pool
 pub struct WorkerPool {
     config: WorkerPoolConfig,
     queue: CommandQueue,
-    store: ConcurrentStore,
+    databases: Databases,
     vector_store: SemanticCache,
     metrics: Arc<Metrics>,
+    /// Refuses writes without cluster quorum (None = never fenced)
+    fence: Option<Arc<QuorumFence>>,
+    /// Records commands slower than its threshold (None = disabled)
+    slowlog: Option<Arc<Slowlog>>,
+    /// Memory and value size limits checked before writes
+    limits: WriteLimits,
+    /// Give each worker its own queue for keyed commands
+    route_by_key: bool,
+    /// One queue per worker when routing by key, created on `start`
+    worker_queues: Vec<CommandQueue>,
     handles: Vec<JoinHandle<()>>,
 }
 
 impl WorkerPool {
-    /// Create a new worker pool
+    /// Create a new worker pool over one store or a set of logical databases
     pub fn new(
         config: WorkerPoolConfig,
-        store: ConcurrentStore,
+        databases: impl Into<Databases>,
         vector_store: SemanticCache,
         metrics: Arc<Metrics>,
     ) -> Self {
@@ -56,13 +190,49 @@ impl WorkerPool {
         Self {
             config,
             queue,
-            store,
+            databases: databases.into(),
             vector_store,
             metrics,
+            fence: None,
+            slowlog: None,
+            limits: WriteLimits::default(),
+            route_by_key: false,
+            worker_queues: Vec::new(),
             handles: Vec::new(),
         }
     }
 
+    /// Refuse writes (and, if configured, reads) while `fence` is up
+    pub fn with_fence(mut self, fence: Option<Arc<QuorumFence>>) -> Self {
+        self.fence = fence;
+        self
+    }
+
+    /// Record commands slower than the slowlog's threshold
+    pub fn with_slowlog(mut self, slowlog: Option<Arc<Slowlog>>) -> Self {
+        self.slowlog = slowlog;
+        self
+    }
+
+    /// Make room under `eviction.max_memory` before writes, or refuse them
+    pub fn with_eviction(mut self, eviction: EvictionConfig) -> Self {
+        self.limits.eviction = eviction;
+        self
+    }
+
+    /// Refuse writes that would store a value over `max` bytes (0 = unlimited)
+    pub fn with_max_value_size(mut self, max: usize) -> Self {
+        self.limits.max_value_size = max;
+        self
+    }
+
+    /// Give each worker a queue of its own alongside the shared one, so
+    /// keyed commands can be sent to the worker `route_key` picks
+    pub fn with_key_routing(mut self, enabled: bool) -> Self {
+        self.route_by_key = enabled;
+        self
+    }
+
     /// Start the worker threads
     pub fn start(&mut self) {
         let num_workers = if self.config.num_workers == 0 {
@@ -79,13 +249,26 @@ impl WorkerPool {
             Vec::new()
         };
 
+        let exec_gate = Arc::new(ExecGate::new(num_workers));
+        let limits = Arc::new(self.limits.clone());
+        if self.route_by_key {
+            let capacity = (self.config.queue_capacity / num_workers).max(1);
+            self.worker_queues = (0..num_workers).map(|_| CommandQueue::new(capacity)).collect();
+        }
+
         for i in 0..num_workers {
             let receiver = self.queue.receiver();
-                    let store = self.store.clone();
-                    let vector_store = self.vector_store.clone();
-                    let metrics = self.metrics.clone();
-                    // ... (pinning logc)
-                    let core_id = if self.config.pin_to_cores && i < core_ids.len() {
+            let own = self.worker_queues.get(i).map(CommandQueue::receiver);
+            let state = WorkerState {
+                databases: self.databases.clone(),
+                vector_store: self.vector_store.clone(),
+                metrics: self.metrics.clone(),
+                fence: self.fence.clone(),
+                slowlog: self.slowlog.clone(),
+                limits: limits.clone(),
+                exec_gate: exec_gate.clone(),
+            };
+            let core_id = if self.config.pin_to_cores && i < core_ids.len() {
                 Some(core_ids[i])
             } else {
                 None
@@ -102,7 +285,7 @@ impl WorkerPool {
                     }
 
                     info!("Worker {} started", i);
-                    Self::worker_loop(i, receiver, store, vector_store, metrics);
+                    Self::worker_loop(i, receiver, own, state);
                     info!("Worker {} stopped", i);
                 })
                 .expect("Failed to spawn worker thread");
@@ -116,19 +299,58 @@ impl WorkerPool {
         &self.queue
     }
 
+    /// Per-worker queues for keyed commands, indexed by `route_key` (empty
+    /// unless key routing is enabled and the pool has started)
+    pub fn worker_queues(&self) -> &[CommandQueue] {
+        &self.worker_queues
+    }
+
+    /// Next item from the worker's own queue or the shared one, whichever
+    /// has work first (None once the queues are closed)
+    fn next_item(shared: &Receiver<WorkItem>, own: Option<&Receiver<WorkItem>>) -> Option<WorkItem> {
+        match own {
+            None => shared.recv().ok(),
+            Some(own) => select! {
+                recv(own) -> item => item.ok(),
+                recv(shared) -> item => item.ok(),
+            },
+        }
+    }
+
     /// Worker main loop
     fn worker_loop(
         worker_id: usize,
-        receiver: crossbeam::channel::Receiver<WorkItem>,
-        store: ConcurrentStore,
-        vector_store: SemanticCache,
-        metrics: Arc<Metrics>,
+        receiver: Receiver<WorkItem>,
+        own: Option<Receiver<WorkItem>>,
+        state: WorkerState,
     ) {
-        while let Ok(work_item) = receiver.recv() {
+        let WorkerState { databases, vector_store, metrics, fence, slowlog, limits, exec_gate } = state;
+        while let Some(work_item) = Self::next_item(&receiver, own.as_ref()) {
             let start = std::time::Instant::now();
-            let cmd_name = format!("{:?}", work_item.command);
+            let cmd_name = work_item.command.name();
+            let request_id = work_item.request_id;
+            let key = match &slowlog {
+                Some(_) => work_item.command.keys().first().map(|k| (*k).clone()),
+                None => None,
+            };
 
-            let result = Self::execute_command(&store, &vector_store, work_item.command);
+            let result = match (databases.get(work_item.db), work_item.command) {
+                (Some(store), Command::Exec { commands, watched }) => exec_gate.run_exclusive(|| {
+                    Self::execute_transaction(
+                        &databases,
+                        store,
+                        &vector_store,
+                        fence.as_deref(),
+                        &limits,
+                        commands,
+                        &watched,
+                    )
+                }),
+                (Some(store), command) => exec_gate.run(worker_id, || {
+                    Self::execute_command(&databases, store, &vector_store, fence.as_deref(), &limits, command)
+                }),
+                (None, _) => WorkResult::Error("ERR DB index is out of range".to_string()),
+            };
 
             // Send response back
             if work_item.response_tx.send(result).is_err() {
@@ -136,23 +358,105 @@ impl WorkerPool {
             }
 
             let elapsed = start.elapsed();
-            metrics.record_operation(&cmd_name, elapsed);
+            metrics.record_operation(cmd_name, elapsed);
+            if let Some(slowlog) = &slowlog {
+                slowlog.record(cmd_name, request_id, key, elapsed);
+            }
         }
     }
 
+    /// Run EXEC's queued commands back to back, or reply nil without running
+    /// any if a watched key's version moved since WATCH
+    fn execute_transaction(
+        databases: &Databases,
+        store: &ConcurrentStore,
+        vector_store: &SemanticCache,
+        fence: Option<&QuorumFence>,
+        limits: &WriteLimits,
+        commands: Vec<Command>,
+        watched: &[(usize, Bytes, u64)],
+    ) -> WorkResult {
+        let changed = watched.iter().any(|(db, key, version)| {
+            databases.get(*db).map_or(0, |watched| watched.version(key)) != *version
+        });
+        if changed {
+            return WorkResult::Nil;
+        }
+        WorkResult::Array(
+            commands
+                .into_iter()
+                .map(|cmd| Self::execute_command(databases, store, vector_store, fence, limits, cmd))
+                .collect(),
+        )
+    }
+
     /// Execute a command against the store
-    fn execute_command(store: &ConcurrentStore, vector_store: &SemanticCache, cmd: Command) -> WorkResult {
+    fn execute_command(
+        databases: &Databases,
+        store: &ConcurrentStore,
+        vector_store: &SemanticCache,
+        fence: Option<&QuorumFence>,
+        limits: &WriteLimits,
+        cmd: Command,
+    ) -> WorkResult {
+        if let Some(fence) = fence {
+            let spec = cmd.spec();
+            let allowed = if spec.is_write() { fence.can_write() } else { fence.can_read() };
+            if !allowed {
+                return WorkResult::Error(NO_QUORUM_ERROR.to_string());
+            }
+        }
+        if let Err(e) = limits.check(store, &cmd) {
+            return WorkResult::Error(e);
+        }
+
         match cmd {
             Command::Ping => WorkResult::Pong,
 
+            Command::CommandCount => WorkResult::Integer(COMMAND_TABLE.len() as i64),
+
+            Command::CommandInfo { names } => WorkResult::Array(
+                command_info(&names).into_iter().map(WorkResult::Value).collect(),
+            ),
+
+            Command::CommandList => {
+                WorkResult::Array(command_list().into_iter().map(WorkResult::Value).collect())
+            }
+
             Command::Get { key } => match store.get(&key) {
                 Some(value) => WorkResult::Value(value),
                 None => WorkResult::Nil,
             },
 
-            Command::Set { key, value, ttl } => {
-                store.set(key, value, ttl);
-                WorkResult::Ok
+            Command::GetOrExpired { key } => match store.lookup(&key) {
+                Lookup::Live(value) => WorkResult::Value(value),
+                Lookup::Expired => WorkResult::Expired,
+                Lookup::Missing => WorkResult::Nil,
+            },
+
+            Command::GetWithTtl { key } => match store.get_with_ttl(&key) {
+                Some((value, expires_at)) => WorkResult::ValueWithTtl {
+                    value,
+                    ttl_ms: super::remaining_ms(expires_at),
+                },
+                None => WorkResult::Nil,
+            },
+
+            Command::Set { key, value, ttl, options } => {
+                if options == SetOptions::default() {
+                    store.set(key, value, ttl);
+                    return WorkResult::Ok;
+                }
+                let condition = SetCondition::new(options.nx, options.xx);
+                let ttl = options.ttl_duration(ttl);
+                let (applied, old) = store.set_with(key, value, ttl, condition);
+                if options.get {
+                    old.map_or(WorkResult::Nil, WorkResult::Value)
+                } else if applied {
+                    WorkResult::Ok
+                } else {
+                    WorkResult::Nil
+                }
             }
 
             Command::Del { key } => {
@@ -160,11 +464,180 @@ impl WorkerPool {
                 WorkResult::Integer(if existed { 1 } else { 0 })
             }
 
+            Command::Unlink { key } => WorkResult::Integer(store.unlink(&key) as i64),
+
             Command::Exists { key } => {
                 let exists = store.exists(&key);
                 WorkResult::Integer(if exists { 1 } else { 0 })
             }
 
+            Command::Extended(ext) => Self::execute_extended(store, ext),
+
+            // The connection validates and applies SELECT; reaching here means DB is valid
+            Command::Select { .. } => WorkResult::Ok,
+
+            // The slot map lives with the connection's cluster router
+            Command::ClusterSlots => {
+                WorkResult::Error("INTERNAL CLUSTER must be handled by the connection".to_string())
+            }
+
+            // WAIT tracks the connection's own writes
+            Command::Wait { .. } => {
+                WorkResult::Error("INTERNAL WAIT must be handled by the connection".to_string())
+            }
+
+            // Subscriptions stream to the connection that made them
+            Command::Subscribe { .. } => {
+                WorkResult::Error("INTERNAL SUBSCRIBE must be handled by the connection".to_string())
+            }
+
+            // The slowlog is shared by all workers and read by the connection
+            Command::SlowlogGet { .. } => {
+                WorkResult::Error("INTERNAL SLOWLOG must be handled by the connection".to_string())
+            }
+
+            // The client registry belongs to the connections
+            Command::ClientList | Command::ClientKill { .. } => {
+                WorkResult::Error("INTERNAL CLIENT must be handled by the connection".to_string())
+            }
+
+            // Authentication is connection state and never reaches a worker
+            Command::Auth { .. } => {
+                WorkResult::Error("INTERNAL AUTH must be handled by the connection".to_string())
+            }
+
+            Command::Reset => {
+                WorkResult::Error("INTERNAL RESET must be handled by the connection".to_string())
+            }
+
+            Command::Asking => {
+                WorkResult::Error("INTERNAL ASKING must be handled by the connection".to_string())
+            }
+
+            Command::Hello { .. } => {
+                WorkResult::Error("INTERNAL HELLO must be handled by the connection".to_string())
+            }
+
+            // Versions the connection compares at EXEC
+            Command::Watch { keys } => {
+                WorkResult::Array(keys.iter().map(|key| WorkResult::Integer(store.version(key) as i64)).collect())
+            }
+
+            // The queue lives with the connection; EXEC only runs from the worker loop
+            Command::Multi | Command::Exec { .. } | Command::Discard => {
+                WorkResult::Error("INTERNAL MULTI, EXEC and DISCARD must be handled by the connection".to_string())
+            }
+
+            Command::FlushDb => {
+                store.clear();
+                WorkResult::Ok
+            }
+
+            Command::FlushAll { vectors } => {
+                databases.flush_all();
+                if vectors {
+                    vector_store.clear();
+                }
+                WorkResult::Ok
+            }
+
+            Command::GetDel { key } => match store.get_del(&key) {
+                Some(value) => WorkResult::Value(value),
+                None => WorkResult::Nil,
+            },
+
+            Command::GetSet { key, value } => match store.get_set(key, value) {
+                Some(old) => WorkResult::Value(old),
+                None => WorkResult::Nil,
+            },
+
+            Command::SwapKey { key1, key2 } => {
+                store.swap(&key1, &key2);
+                WorkResult::Ok
+            }
+
+            Command::Rename { src, dst } => {
+                if store.rename(&src, &dst) {
+                    WorkResult::Ok
+                } else {
+                    WorkResult::Error(super::NO_SUCH_KEY_ERROR.to_string())
+                }
+            }
+
+            Command::Copy { src, dst, replace } => WorkResult::Integer(store.copy(&src, &dst, replace) as i64),
+
+            Command::RandomKey => store.random_key().map_or(WorkResult::Nil, WorkResult::Value),
+
+            Command::DbSize => WorkResult::Integer(store.live_len() as i64),
+
+            Command::PExpire { key, ms } => {
+                let updated = store.pexpire(&key, Duration::from_millis(ms));
+                WorkResult::Integer(updated as i64)
+            }
+
+            Command::Ttl { key } => WorkResult::Integer(match store.pttl(&key) {
+                None => -2,
+                Some(None) => -1,
+                // Round to the nearest second, as Redis does
+                Some(Some(ttl)) => ((ttl.as_millis() + 500) / 1000) as i64,
+            }),
+
+            Command::PTtl { key } => WorkResult::Integer(match store.pttl(&key) {
+                None => -2,
+                Some(None) => -1,
+                Some(Some(ttl)) => ttl.as_millis() as i64,
+            }),
+
+            Command::Append { key, value } => WorkResult::Integer(store.append(key, &value) as i64),
+
+            Command::Strlen { key } => WorkResult::Integer(store.strlen(&key) as i64),
+
+            Command::DelPattern { pattern } => {
+                WorkResult::Integer(store.del_matching(&String::from_utf8_lossy(&pattern)) as i64)
+            }
+
+            // Deliberately ties up this worker, to exercise backpressure and timeouts
+            Command::DebugSleep { ms } => {
+                std::thread::sleep(Duration::from_millis(ms));
+                WorkResult::Ok
+            }
+
+            // Access stats first: reading the value counts as an access
+            Command::DebugObject { key } => {
+                let access = store.idle_time(&key).zip(store.access_freq(&key));
+                match store.get_with_ttl(&key) {
+                    Some((value, expires_at)) => WorkResult::Value(super::debug_object(&value, expires_at, access)),
+                    None => WorkResult::Error(super::NO_SUCH_KEY_ERROR.to_string()),
+                }
+            }
+
+            Command::Dump { key } => match store.get_with_ttl(&key) {
+                Some((value, expires_at)) => WorkResult::Value(super::dump_payload(value, expires_at)),
+                None => WorkResult::Nil,
+            },
+
+            Command::Restore { key, blob, ttl_ms, replace } => match super::restore_payload(&blob, ttl_ms) {
+                Err(e) => WorkResult::Error(e),
+                Ok(None) => {
+                    if replace {
+                        store.del(&key);
+                    }
+                    WorkResult::Ok
+                }
+                Ok(Some((value, ttl))) => {
+                    let condition = if replace { SetCondition::Always } else { SetCondition::IfAbsent };
+                    match store.set_with(key, value, ttl, condition) {
+                        (true, _) => WorkResult::Ok,
+                        (false, _) => WorkResult::Error(super::BUSYKEY_ERROR.to_string()),
+                    }
+                }
+            },
+
+            Command::Type { key } => {
+                let kind = store.value_type(&key).map_or("none", |kind| kind.as_str());
+                WorkResult::Value(Bytes::from_static(kind.as_bytes()))
+            }
+
             Command::VAdd { key, vector } => {
                 // For VADD, we need a value. For now using empty value or key as value.
                 // The protocol command VAdd only has key and vector.
@@ -178,7 +651,26 @@ impl WorkerPool {
                 }
             }
 
-            Command::VSearch { vector, k } => {
+            Command::VAddBatch { entries } => {
+                let total = entries.len();
+                // Same key-as-value convention as VADD
+                let failed = vector_store
+                    .set_many(entries.into_iter().map(|(key, vector)| (key.clone(), vector, key)));
+                WorkResult::BatchAdded {
+                    added: (total - failed.len()) as u32,
+                    failed: failed.into_iter().map(|i| i as u32).collect(),
+                }
+            }
+
+            Command::VSearch { vector, k: _ } => {
+                let dimension = vector_store.config().dimension;
+                if vector.len() != dimension {
+                    return WorkResult::Error(format!(
+                        "Dimension mismatch: expected {}, got {}",
+                        dimension,
+                        vector.len()
+                    ));
+                }
                 let results = vector_store.semantic_get(&vector);
                 
                 // Return array of keys
@@ -193,6 +685,51 @@ impl WorkerPool {
         }
     }
 
+    fn incr_by(store: &ConcurrentStore, key: Bytes, delta: i64) -> WorkResult {
+        match store.incr_by(key, delta) {
+            Ok(value) => WorkResult::Integer(value),
+            Err(e) => WorkResult::Error(e.to_string()),
+        }
+    }
+
+    /// Execute an extended command against the store
+    fn execute_extended(store: &ConcurrentStore, cmd: ExtendedCommand) -> WorkResult {
+        match cmd {
+            // Reply: [next_cursor, key...]; an empty page with a nonzero cursor is normal
+            ExtendedCommand::Scan { cursor, pattern, count } => {
+                let pattern = pattern.map(|p| String::from_utf8_lossy(&p).into_owned());
+                let (next, keys) =
+                    store.scan(cursor, pattern.as_deref(), count as usize, SCAN_TIME_BUDGET);
+
+                let mut items = Vec::with_capacity(keys.len() + 1);
+                items.push(WorkResult::Value(Bytes::from(next.to_string())));
+                items.extend(keys.into_iter().map(WorkResult::Value));
+                WorkResult::Array(items)
+            }
+
+            ExtendedCommand::MGet { keys } => WorkResult::Values(store.get_many(&keys)),
+
+            ExtendedCommand::MSet { pairs } => {
+                for (key, value) in pairs {
+                    store.set(key, value, None);
+                }
+                WorkResult::Ok
+            }
+
+            ExtendedCommand::MDel { keys } => WorkResult::Integer(store.del_many(&keys) as i64),
+
+            ExtendedCommand::Incr { key } => Self::incr_by(store, key, 1),
+            ExtendedCommand::Decr { key } => Self::incr_by(store, key, -1),
+            ExtendedCommand::IncrBy { key, delta } => Self::incr_by(store, key, delta),
+            ExtendedCommand::DecrBy { key, delta } => match delta.checked_neg() {
+                Some(delta) => Self::incr_by(store, key, delta),
+                None => WorkResult::Error("ERR decrement would overflow".to_string()),
+            },
+
+            other => WorkResult::Error(format!("ERR unsupported command '{}'", other.name())),
+        }
+    }
+
     /// Wait for all workers to finish
     pub fn join(self) {
         for handle in self.handles {
@@ -205,3 +742,40 @@ impl WorkerPool {
         self.handles.len()
     }
 }
+
+/// Worker whose queue a keyed command goes to when routing by key, so the
+/// same key (or hash tag) always lands on the same worker
+pub fn route_key(key: &[u8], workers: usize) -> usize {
+    Slot::from_key(key).0 as usize % workers
+}
+
+/// Bytes a write may add to the store, for commands that carry a value or
+/// copy an existing one
+fn write_size(store: &ConcurrentStore, cmd: &Command) -> Option<usize> {
+    match cmd {
+        Command::Set { key, value, .. } | Command::GetSet { key, value } => {
+            Some(key.len() + value.len() + ENTRY_OVERHEAD)
+        }
+        Command::Append { value, .. } => Some(value.len()),
+        Command::Restore { key, blob, .. } => Some(key.len() + blob.len() + ENTRY_OVERHEAD),
+        Command::Copy { src, dst, .. } => Some(dst.len() + store.strlen(src) + ENTRY_OVERHEAD),
+        Command::Extended(ExtendedCommand::MSet { pairs }) => {
+            Some(pairs.iter().map(|(key, value)| key.len() + value.len() + ENTRY_OVERHEAD).sum())
+        }
+        _ => None,
+    }
+}
+
+/// Length of the largest value a write would leave stored, for commands that
+/// carry a value; APPEND counts the existing value it extends
+fn stored_value_len(store: &ConcurrentStore, cmd: &Command) -> Option<usize> {
+    match cmd {
+        Command::Set { value, .. } | Command::GetSet { value, .. } => Some(value.len()),
+        Command::Append { key, value } => Some(store.strlen(key) + value.len()),
+        Command::Restore { blob, .. } => Some(blob.len()),
+        Command::Extended(ExtendedCommand::MSet { pairs }) => {
+            pairs.iter().map(|(_, value)| value.len()).max()
+        }
+        _ => None,
+    }
+}
@@ -2,16 +2,202 @@
 //!
 //! Lock-free hashmap using DashMap for high-concurrency operations.
 
-use bytes::Bytes;
-use dashmap::DashMap;
-use std::sync::Arc;
+use bytes::{Bytes, BytesMut};
+use dashmap::mapref::entry::Entry as MapEntry;
+use dashmap::{DashMap, SharedValue};
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
 
+use crate::cluster::replication::{ReplicationEntry, ReplicationManager, ReplicationOp};
+use crate::pubsub::KeyspaceNotifier;
+use crate::security::acl::glob_match;
+
+use super::reclaim;
+
+/// Default wall-clock budget for a single SCAN call
+pub const SCAN_TIME_BUDGET: Duration = Duration::from_millis(5);
+
+/// SCAN cursors pack `[shard][log2 of the shard's buckets][bucket]`, with
+/// the bucket in the low `SCAN_BUCKET_BITS` bits
+const SCAN_BUCKET_BITS: u32 = 40;
+const SCAN_TABLE_BITS: u32 = 6;
+
+/// Buckets RANDOMKEY samples before falling back to a scan for a live key
+const RANDOM_KEY_PROBES: usize = 16;
+
+/// Entries `expire_sample_with` examines per key it's asked to sample, so
+/// a store with few TTLs isn't scanned end to end looking for them
+const EXPIRE_SAMPLE_PROBES: usize = 20;
+
+thread_local! {
+    static RNG: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+/// xorshift64 from a per-thread random seed; fast, not cryptographic
+pub(crate) fn random_u64() -> u64 {
+    RNG.with(|rng| {
+        let mut x = rng.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        rng.set(x);
+        x
+    })
+}
+
+/// Kind of value held by an entry, as reported by TYPE
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValueType {
+    /// Opaque bytes (also used for counters)
+    #[default]
+    String,
+}
+
+impl ValueType {
+    /// Name reported by TYPE
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValueType::String => "string",
+        }
+    }
+}
+
+/// Fixed per-entry cost counted by `memory_used`, on top of key and value bytes
+pub const ENTRY_OVERHEAD: usize = std::mem::size_of::<(Bytes, Entry)>();
+
+/// Values smaller than this are cheaper to free inline than to queue
+pub const LAZY_FREE_MIN_SIZE: usize = 64 * 1024;
+
+/// Approximate bytes held by one entry
+#[inline]
+fn entry_size(key: &[u8], value: &[u8]) -> usize {
+    key.len() + value.len() + ENTRY_OVERHEAD
+}
+
+/// Key count of one DashMap shard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShardStat {
+    pub shard: usize,
+    /// Entries held, including expired ones not yet reaped
+    pub keys: usize,
+}
+
+/// Spread of keys across shards, for spotting hotspots
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ShardDistribution {
+    pub max: usize,
+    pub min: usize,
+    pub stddev: f64,
+}
+
+impl ShardDistribution {
+    pub fn from_stats(stats: &[ShardStat]) -> Self {
+        if stats.is_empty() {
+            return Self::default();
+        }
+        let n = stats.len() as f64;
+        let mean = stats.iter().map(|s| s.keys as f64).sum::<f64>() / n;
+        let variance = stats.iter().map(|s| (s.keys as f64 - mean).powi(2)).sum::<f64>() / n;
+        Self {
+            max: stats.iter().map(|s| s.keys).max().unwrap_or(0),
+            min: stats.iter().map(|s| s.keys).min().unwrap_or(0),
+            stddev: variance.sqrt(),
+        }
+    }
+}
+
+/// Expand `$body!(table1, table2)` with write guards on two shards, taken
+/// in shard-index order so concurrent two-key operations can't deadlock.
+/// Both arguments name the same guard when the shards are equal.
+macro_rules! with_shard_pair {
+    ($store:expr, $shard1:expr, $shard2:expr, $body:ident) => {{
+        let shards = $store.inner.shards();
+        if $shard1 == $shard2 {
+            let mut shard = shards[$shard1].write();
+            $body!(shard, shard)
+        } else {
+            let (low, high) = ($shard1.min($shard2), $shard1.max($shard2));
+            let mut low_guard = shards[low].write();
+            let mut high_guard = shards[high].write();
+            if $shard1 == low {
+                $body!(low_guard, high_guard)
+            } else {
+                $body!(high_guard, low_guard)
+            }
+        }
+    }};
+}
+
+/// Source of entry versions; 0 is reserved for missing keys
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(1);
+
+fn next_version() -> u64 {
+    NEXT_VERSION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Reference point for `AccessStats` timestamps
+static ACCESS_EPOCH: OnceLock<Instant> = OnceLock::new();
+
+fn access_clock_us() -> u64 {
+    ACCESS_EPOCH.get_or_init(Instant::now).elapsed().as_micros() as u64
+}
+
+/// When a key was last read or written and how many times it has been
+/// accessed since its last write, updated under the shard's read lock
+#[derive(Debug)]
+pub struct AccessStats {
+    /// Microseconds since `ACCESS_EPOCH`
+    last_access_us: AtomicU64,
+    count: AtomicU64,
+}
+
+impl AccessStats {
+    fn new() -> Self {
+        Self {
+            last_access_us: AtomicU64::new(access_clock_us()),
+            count: AtomicU64::new(1),
+        }
+    }
+
+    #[inline]
+    fn touch(&self) {
+        self.last_access_us.store(access_clock_us(), Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn idle_time(&self) -> Duration {
+        let last = self.last_access_us.load(Ordering::Relaxed);
+        Duration::from_micros(access_clock_us().saturating_sub(last))
+    }
+
+    fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+impl Clone for AccessStats {
+    fn clone(&self) -> Self {
+        Self {
+            last_access_us: AtomicU64::new(self.last_access_us.load(Ordering::Relaxed)),
+            count: AtomicU64::new(self.count()),
+        }
+    }
+}
+
 /// Entry in the store with value and expiration
 #[derive(Debug, Clone)]
 pub struct Entry {
     pub value: Bytes,
     pub expires_at: Option<Instant>,
+    pub kind: ValueType,
+    /// Stamp that changes on every write to the key, checked by WATCH
+    pub version: u64,
+    /// Recency and frequency, reported by DEBUG OBJECT
+    pub access: AccessStats,
 }
 
 impl Entry {
@@ -19,6 +205,9 @@ impl Entry {
         Self {
             value,
             expires_at: ttl.map(|d| Instant::now() + d),
+            kind: ValueType::String,
+            version: next_version(),
+            access: AccessStats::new(),
         }
     }
 
@@ -27,6 +216,52 @@ impl Entry {
     }
 }
 
+/// Result of a read that tells expired keys apart from missing ones
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lookup {
+    /// The key's current value
+    Live(Bytes),
+    /// The key was still stored but its TTL had passed; the read removed it
+    Expired,
+    /// The key was never set, or has been deleted or reaped
+    Missing,
+}
+
+impl Lookup {
+    /// The value if live; expired and missing keys both read as None
+    pub fn live(self) -> Option<Bytes> {
+        match self {
+            Lookup::Live(value) => Some(value),
+            Lookup::Expired | Lookup::Missing => None,
+        }
+    }
+}
+
+/// Precondition for a conditional SET
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SetCondition {
+    /// Always write
+    #[default]
+    Always,
+    /// Only write if the key does not exist (NX)
+    IfAbsent,
+    /// Only write if the key already exists (XX)
+    IfPresent,
+}
+
+impl SetCondition {
+    /// Build from NX/XX flags (NX wins if both are set)
+    pub fn new(nx: bool, xx: bool) -> Self {
+        if nx {
+            SetCondition::IfAbsent
+        } else if xx {
+            SetCondition::IfPresent
+        } else {
+            SetCondition::Always
+        }
+    }
+}
+
 /// Lock-free concurrent in-memory key-value store
 /// 
 /// Uses DashMap for O(1) concurrent access without global locks.
@@ -35,6 +270,16 @@ impl Entry {
 #[derive(Debug, Clone)]
 pub struct ConcurrentStore {
     inner: Arc<DashMap<Bytes, Entry>>,
+    /// Approximate bytes held, see `memory_used`
+    memory: Arc<AtomicUsize>,
+    /// Bytes of deleted values still queued for the reclaim thread
+    reclaiming: Arc<AtomicUsize>,
+    /// Keyspace event publisher (None = notifications disabled)
+    notifier: Option<KeyspaceNotifier>,
+    /// Replication log for writes, with this store's database index
+    replication: Option<(Arc<ReplicationManager>, u32)>,
+    /// Free large deleted values on the reclaim thread, not under the shard lock
+    lazy_free: bool,
 }
 
 impl Default for ConcurrentStore {
@@ -48,6 +293,11 @@ impl ConcurrentStore {
     pub fn new() -> Self {
         Self {
             inner: Arc::new(DashMap::new()),
+            memory: Arc::new(AtomicUsize::new(0)),
+            reclaiming: Arc::new(AtomicUsize::new(0)),
+            notifier: None,
+            replication: None,
+            lazy_free: false,
         }
     }
 
@@ -55,33 +305,662 @@ impl ConcurrentStore {
     pub fn with_shard_amount(shard_amount: usize) -> Self {
         Self {
             inner: Arc::new(DashMap::with_shard_amount(shard_amount)),
+            memory: Arc::new(AtomicUsize::new(0)),
+            reclaiming: Arc::new(AtomicUsize::new(0)),
+            notifier: None,
+            replication: None,
+            lazy_free: false,
+        }
+    }
+
+    /// Publish keyspace events for writes through the given notifier
+    pub fn with_notifier(mut self, notifier: KeyspaceNotifier) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Record writes in a replication log as database `db`
+    pub fn with_replication(mut self, manager: Arc<ReplicationManager>, db: u32) -> Self {
+        self.replication = Some((manager, db));
+        self
+    }
+
+    /// A handle on the same keys that publishes no keyspace events and
+    /// records nothing for replicas, for internal writes such as health
+    /// probes
+    pub fn unobserved(&self) -> Self {
+        Self { notifier: None, replication: None, ..self.clone() }
+    }
+
+    /// Free large values removed by DEL and pattern deletes in the
+    /// background, as UNLINK always does
+    pub fn with_lazy_free(mut self, enabled: bool) -> Self {
+        self.lazy_free = enabled;
+        self
+    }
+
+    #[inline]
+    fn charge(&self, bytes: usize) {
+        self.memory.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn release(&self, bytes: usize) {
+        self.memory.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    /// Release a removed entry, handing values of at least
+    /// `LAZY_FREE_MIN_SIZE` bytes to the reclaim thread when `lazy`. Their
+    /// bytes leave `memory_used` at once, so eviction doesn't keep deleting
+    /// while the reclaimer catches up, and are counted by
+    /// `reclaim_pending` until dropped.
+    #[inline]
+    fn free(&self, key: &[u8], value: Bytes, lazy: bool) {
+        let size = entry_size(key, &value);
+        self.release(size);
+        if lazy && value.len() >= LAZY_FREE_MIN_SIZE {
+            reclaim::defer_free(value, size, self.reclaiming.clone());
         }
     }
 
+    /// Approximate bytes held by keys, values and per-entry overhead,
+    /// including expired entries not yet reaped
+    pub fn memory_used(&self) -> usize {
+        self.memory.load(Ordering::Relaxed)
+    }
+
+    /// Bytes of deleted values the reclaim thread has yet to free
+    pub fn reclaim_pending(&self) -> usize {
+        self.reclaiming.load(Ordering::Relaxed)
+    }
+
+    /// Whether writes need the key passed to `notify_write`
+    #[inline]
+    fn observes_writes(&self) -> bool {
+        self.notifier.is_some() || self.replication.is_some()
+    }
+
+    /// Publish write events for a key and record its new state for
+    /// replicas, if either is enabled
+    #[inline]
+    fn notify_write(&self, event: &'static str, key: &Bytes, ttl: Option<Duration>) {
+        if let Some(notifier) = &self.notifier {
+            notifier.notify(event, key);
+            if ttl.is_some() {
+                notifier.notify("expire", key);
+            }
+        }
+        if let Some((manager, db)) = &self.replication {
+            // Record the key's current state rather than the operation, so
+            // every kind of write replicates the same way. Recording under
+            // the shard lock keeps log order consistent with write order
+            // for the key.
+            match self.inner.entry(key.clone()) {
+                MapEntry::Occupied(entry) if !entry.get().is_expired() => {
+                    let entry = entry.get();
+                    let ttl = entry.expires_at.map(|at| at.saturating_duration_since(Instant::now()));
+                    manager.record(ReplicationOp::Set, ReplicationEntry::set_data(*db, key, &entry.value, ttl));
+                }
+                _ => {
+                    manager.record(ReplicationOp::Del, ReplicationEntry::del_data(*db, key));
+                }
+            }
+        }
+    }
+
+    /// Record that `key` expired, so replicas remove it on this node's
+    /// authority rather than by their own clocks. Call under the key's
+    /// shard lock so the log keeps write order.
+    #[inline]
+    fn record_expired(&self, key: &Bytes) {
+        if let Some((manager, db)) = &self.replication {
+            // Expire carries the same payload as Del
+            manager.record(ReplicationOp::Expire, ReplicationEntry::del_data(*db, key));
+        }
+    }
+
+    /// Remove `key` as expired: if its TTL has passed, or regardless when
+    /// `force`. Publishes "expired" and replicates the expiry; returns
+    /// whether a key was removed.
+    fn reap(&self, key: &Bytes, force: bool) -> bool {
+        let (key, entry) = match self.inner.entry(key.clone()) {
+            MapEntry::Occupied(entry) if force || entry.get().is_expired() => {
+                self.record_expired(entry.key());
+                entry.remove_entry()
+            }
+            _ => return false,
+        };
+        self.free(&key, entry.value, self.lazy_free);
+        if let Some(notifier) = &self.notifier {
+            notifier.notify("expired", &key);
+        }
+        true
+    }
+
+    /// Remove `key` because the leader expired it, whatever its TTL here
+    /// says. Returns whether the key existed.
+    pub fn expire_now(&self, key: &Bytes) -> bool {
+        self.reap(key, true)
+    }
+
     /// Get value by key, returns None if key doesn't exist or is expired
     #[inline]
     pub fn get(&self, key: &Bytes) -> Option<Bytes> {
-        self.inner.get(key).and_then(|entry| {
-            if entry.is_expired() {
-                None
-            } else {
-                Some(entry.value.clone())
+        self.lookup(key).live()
+    }
+
+    /// Read a key, telling an expired key that is still stored apart from
+    /// one that isn't there at all. Expired keys are removed by the read.
+    #[inline]
+    pub fn lookup(&self, key: &Bytes) -> Lookup {
+        match self.inner.get(key) {
+            None => return Lookup::Missing,
+            Some(entry) if !entry.is_expired() => {
+                entry.access.touch();
+                return Lookup::Live(entry.value.clone());
+            }
+            Some(_) => {}
+        }
+        // The read guard is gone; reap the lapsed key rather than leave it
+        // for the cleaner
+        self.reap(key, false);
+        Lookup::Expired
+    }
+
+    /// Get several values at once, in the order of `keys`; missing and
+    /// expired keys read as None.
+    ///
+    /// Keys are grouped by shard so each shard is read-locked once rather
+    /// than once per key, which matters for large MGETs.
+    pub fn get_many(&self, keys: &[Bytes]) -> Vec<Option<Bytes>> {
+        let shards = self.inner.shards();
+        let mut values = vec![None; keys.len()];
+        let mut expired = Vec::new();
+        for group in self.group_by_shard(keys).chunk_by(|a, b| a.0 == b.0) {
+            let shard = shards[group[0].0].read();
+            for &(_, hash, i) in group {
+                if let Some((_, entry)) = shard.get(hash, |(k, _)| *k == keys[i]) {
+                    let entry = entry.get();
+                    if entry.is_expired() {
+                        expired.push(i);
+                    } else {
+                        entry.access.touch();
+                        values[i] = Some(entry.value.clone());
+                    }
+                }
             }
+        }
+        for i in expired {
+            self.reap(&keys[i], false);
+        }
+        values
+    }
+
+    /// `(shard, hash, index into keys)` for each key, sorted by shard so
+    /// callers can lock each shard once per batch
+    fn group_by_shard(&self, keys: &[Bytes]) -> Vec<(usize, u64, usize)> {
+        let mut located: Vec<_> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let hash = self.inner.hash_usize(key);
+                (self.inner.determine_shard(hash), hash as u64, i)
+            })
+            .collect();
+        located.sort_unstable_by_key(|(shard, _, _)| *shard);
+        located
+    }
+
+    /// Get a live value along with its expiry deadline, if any
+    pub fn get_with_ttl(&self, key: &Bytes) -> Option<(Bytes, Option<Instant>)> {
+        self.inner.get(key).filter(|e| !e.is_expired()).map(|e| {
+            e.access.touch();
+            (e.value.clone(), e.expires_at)
         })
     }
 
+    /// Time since a live key was last read or written
+    pub fn idle_time(&self, key: &Bytes) -> Option<Duration> {
+        self.inner
+            .get(key)
+            .filter(|e| !e.is_expired())
+            .map(|e| e.access.idle_time())
+    }
+
+    /// Accesses to a live key since it was last written, counting the write
+    pub fn access_freq(&self, key: &Bytes) -> Option<u64> {
+        self.inner
+            .get(key)
+            .filter(|e| !e.is_expired())
+            .map(|e| e.access.count())
+    }
+
     /// Set key-value pair with optional TTL in seconds
     #[inline]
     pub fn set(&self, key: Bytes, value: Bytes, ttl_secs: Option<u64>) {
-        let ttl = ttl_secs.map(Duration::from_secs);
+        self.set_with_ttl(key, value, ttl_secs.map(Duration::from_secs));
+    }
+
+    /// Set key-value pair with an optional TTL of any precision
+    #[inline]
+    pub fn set_with_ttl(&self, key: Bytes, value: Bytes, ttl: Option<Duration>) {
+        let key_len = key.len();
+        self.charge(entry_size(&key, &value));
+        let entry = Entry::new(value, ttl);
+        let old = if self.observes_writes() {
+            let old = self.inner.insert(key.clone(), entry);
+            self.notify_write("set", &key, ttl);
+            old
+        } else {
+            self.inner.insert(key, entry)
+        };
+        if let Some(old) = old {
+            self.release(key_len + old.value.len() + ENTRY_OVERHEAD);
+        }
+    }
+
+    /// Conditionally set a key under the shard lock.
+    ///
+    /// Returns whether the write happened and the previous live value.
+    pub fn set_with(
+        &self,
+        key: Bytes,
+        value: Bytes,
+        ttl: Option<Duration>,
+        condition: SetCondition,
+    ) -> (bool, Option<Bytes>) {
+        let size = entry_size(&key, &value);
         let entry = Entry::new(value, ttl);
-        self.inner.insert(key, entry);
+        let notify_key = self.observes_writes().then(|| key.clone());
+
+        let result = match self.inner.entry(key) {
+            MapEntry::Occupied(mut occupied) => {
+                let old = if occupied.get().is_expired() {
+                    None
+                } else {
+                    Some(occupied.get().value.clone())
+                };
+                let allowed = match condition {
+                    SetCondition::Always => true,
+                    SetCondition::IfAbsent => old.is_none(),
+                    SetCondition::IfPresent => old.is_some(),
+                };
+                if allowed {
+                    self.charge(size);
+                    let replaced = occupied.insert(entry);
+                    self.release(entry_size(occupied.key(), &replaced.value));
+                }
+                (allowed, old)
+            }
+            MapEntry::Vacant(vacant) => {
+                if condition == SetCondition::IfPresent {
+                    return (false, None);
+                }
+                self.charge(size);
+                vacant.insert(entry);
+                (true, None)
+            }
+        };
+
+        if let (Some(key), true) = (notify_key, result.0) {
+            self.notify_write("set", &key, ttl);
+        }
+        result
+    }
+
+    /// Remove a key and return its live value in one shard-locked operation
+    pub fn get_del(&self, key: &Bytes) -> Option<Bytes> {
+        let (_, entry) = self.inner.remove(key)?;
+        self.release(entry_size(key, &entry.value));
+        self.notify_write("del", key, None);
+        if entry.is_expired() {
+            None
+        } else {
+            Some(entry.value)
+        }
+    }
+
+    /// Replace a key's value (clearing any TTL) and return the previous live value
+    pub fn get_set(&self, key: Bytes, value: Bytes) -> Option<Bytes> {
+        self.charge(entry_size(&key, &value));
+        let old = self.inner.insert(key.clone(), Entry::new(value, None));
+        if let Some(old) = &old {
+            self.release(entry_size(&key, &old.value));
+        }
+        self.notify_write("set", &key, None);
+        old.filter(|e| !e.is_expired()).map(|e| e.value)
+    }
+
+    /// Atomically exchange the values and TTLs of two keys.
+    ///
+    /// Both shard locks are held for the duration. If only one key exists
+    /// this is a move; if neither exists it's a no-op.
+    pub fn swap(&self, key1: &Bytes, key2: &Bytes) {
+        if key1 == key2 {
+            return;
+        }
+
+        let hash1 = self.inner.hash_usize(key1);
+        let hash2 = self.inner.hash_usize(key2);
+        let shard1 = self.inner.determine_shard(hash1);
+        let shard2 = self.inner.determine_shard(hash2);
+        let rehash = |(k, _): &(Bytes, _)| self.inner.hash_usize(k) as u64;
+
+        // Take both live entries out, then put each back under the other key
+        macro_rules! swap_in {
+            ($table1:expr, $table2:expr) => {{
+                let old1 = $table1.remove_entry(hash1 as u64, |(k, _)| k == key1);
+                let old2 = $table2.remove_entry(hash2 as u64, |(k, _)| k == key2);
+                for (k, v) in old1.iter().chain(old2.iter()) {
+                    self.release(entry_size(k, &v.get().value));
+                }
+                let old1 = old1.filter(|(_, v)| !v.get().is_expired());
+                let old2 = old2.filter(|(_, v)| !v.get().is_expired());
+                let moved = (old1.is_some(), old2.is_some());
+                if let Some((_, mut v)) = old2 {
+                    v.get_mut().version = next_version();
+                    self.charge(entry_size(key1, &v.get().value));
+                    $table1.insert(hash1 as u64, (key1.clone(), v), rehash);
+                }
+                if let Some((_, mut v)) = old1 {
+                    v.get_mut().version = next_version();
+                    self.charge(entry_size(key2, &v.get().value));
+                    $table2.insert(hash2 as u64, (key2.clone(), v), rehash);
+                }
+                moved
+            }};
+        }
+
+        let (had1, had2) = with_shard_pair!(self, shard1, shard2, swap_in);
+
+        for (key, now_has) in [(key1, had2), (key2, had1)] {
+            if now_has {
+                self.notify_write("set", key, None);
+            } else if had1 || had2 {
+                self.notify_write("del", key, None);
+            }
+        }
+    }
+
+    /// Atomically move a key's value and TTL to `dst`, replacing whatever
+    /// `dst` held. Returns false if `src` is missing.
+    pub fn rename(&self, src: &Bytes, dst: &Bytes) -> bool {
+        if src == dst {
+            return self.exists(src);
+        }
+
+        let src_hash = self.inner.hash_usize(src);
+        let dst_hash = self.inner.hash_usize(dst);
+        let src_shard = self.inner.determine_shard(src_hash);
+        let dst_shard = self.inner.determine_shard(dst_hash);
+        let rehash = |(k, _): &(Bytes, _)| self.inner.hash_usize(k) as u64;
+
+        macro_rules! rename_in {
+            ($src_table:expr, $dst_table:expr) => {{
+                match $src_table.remove_entry(src_hash as u64, |(k, _)| k == src) {
+                    Some((k, v)) if v.get().is_expired() => {
+                        // Reap it while we hold the lock
+                        self.release(entry_size(&k, &v.get().value));
+                        false
+                    }
+                    Some((k, mut v)) => {
+                        self.release(entry_size(&k, &v.get().value));
+                        if let Some((k, old)) = $dst_table.remove_entry(dst_hash as u64, |(k, _)| k == dst) {
+                            self.release(entry_size(&k, &old.get().value));
+                        }
+                        v.get_mut().version = next_version();
+                        self.charge(entry_size(dst, &v.get().value));
+                        $dst_table.insert(dst_hash as u64, (dst.clone(), v), rehash);
+                        true
+                    }
+                    None => false,
+                }
+            }};
+        }
+
+        let renamed = with_shard_pair!(self, src_shard, dst_shard, rename_in);
+        if renamed {
+            self.notify_write("rename_from", src, None);
+            self.notify_write("rename_to", dst, None);
+        }
+        renamed
+    }
+
+    /// Atomically copy a key's value and TTL to `dst`. Returns false if
+    /// `src` is missing, or if `dst` exists and `replace` is false.
+    pub fn copy(&self, src: &Bytes, dst: &Bytes, replace: bool) -> bool {
+        if src == dst {
+            return false;
+        }
+
+        let src_hash = self.inner.hash_usize(src);
+        let dst_hash = self.inner.hash_usize(dst);
+        let src_shard = self.inner.determine_shard(src_hash);
+        let dst_shard = self.inner.determine_shard(dst_hash);
+        let rehash = |(k, _): &(Bytes, _)| self.inner.hash_usize(k) as u64;
+
+        macro_rules! copy_in {
+            ($src_table:expr, $dst_table:expr) => {{
+                let source = $src_table
+                    .get(src_hash as u64, |(k, _)| k == src)
+                    .map(|(_, v)| v.get())
+                    .filter(|e| !e.is_expired())
+                    .map(|e| (e.value.clone(), e.expires_at));
+                let dst_live = $dst_table
+                    .get(dst_hash as u64, |(k, _)| k == dst)
+                    .is_some_and(|(_, v)| !v.get().is_expired());
+                match source {
+                    Some(_) if dst_live && !replace => false,
+                    Some((value, expires_at)) => {
+                        if let Some((k, old)) = $dst_table.remove_entry(dst_hash as u64, |(k, _)| k == dst) {
+                            self.release(entry_size(&k, &old.get().value));
+                        }
+                        self.charge(entry_size(dst, &value));
+                        let entry = Entry { expires_at, ..Entry::new(value, None) };
+                        $dst_table.insert(dst_hash as u64, (dst.clone(), SharedValue::new(entry)), rehash);
+                        true
+                    }
+                    None => false,
+                }
+            }};
+        }
+
+        let copied = with_shard_pair!(self, src_shard, dst_shard, copy_in);
+        if copied {
+            self.notify_write("copy_to", dst, None);
+        }
+        copied
+    }
+
+    /// Atomically add `delta` to an integer value (missing keys count as 0).
+    ///
+    /// Keeps the key's TTL. Fails if the value isn't an integer or the
+    /// result would overflow.
+    pub fn incr_by(&self, key: Bytes, delta: i64) -> Result<i64, &'static str> {
+        let notify_key = self.observes_writes().then(|| key.clone());
+        let result = match self.inner.entry(key) {
+            MapEntry::Occupied(mut occupied) if !occupied.get().is_expired() => {
+                let current = std::str::from_utf8(&occupied.get().value)
+                    .ok()
+                    .and_then(|s| s.parse::<i64>().ok())
+                    .ok_or("ERR value is not an integer or out of range")?;
+                let next = current
+                    .checked_add(delta)
+                    .ok_or("ERR increment or decrement would overflow")?;
+                let value = Bytes::from(next.to_string());
+                self.charge(value.len());
+                let entry = occupied.get_mut();
+                entry.version = next_version();
+                let old = std::mem::replace(&mut entry.value, value);
+                self.release(old.len());
+                next
+            }
+            MapEntry::Occupied(mut occupied) => {
+                let value = Bytes::from(delta.to_string());
+                self.charge(entry_size(occupied.key(), &value));
+                let old = occupied.insert(Entry::new(value, None));
+                self.release(entry_size(occupied.key(), &old.value));
+                delta
+            }
+            MapEntry::Vacant(vacant) => {
+                let value = Bytes::from(delta.to_string());
+                self.charge(entry_size(vacant.key(), &value));
+                vacant.insert(Entry::new(value, None));
+                delta
+            }
+        };
+        if let Some(key) = notify_key {
+            self.notify_write("incrby", &key, None);
+        }
+        Ok(result)
+    }
+
+    /// Append to a key's value under its shard lock, creating the key if
+    /// missing. Keeps the key's TTL. Returns the new length.
+    pub fn append(&self, key: Bytes, suffix: &[u8]) -> usize {
+        let notify_key = self.observes_writes().then(|| key.clone());
+        let len = match self.inner.entry(key) {
+            MapEntry::Occupied(mut occupied) if !occupied.get().is_expired() => {
+                let entry = occupied.get_mut();
+                let mut value = BytesMut::with_capacity(entry.value.len() + suffix.len());
+                value.extend_from_slice(&entry.value);
+                value.extend_from_slice(suffix);
+                entry.value = value.freeze();
+                entry.version = next_version();
+                self.charge(suffix.len());
+                entry.value.len()
+            }
+            MapEntry::Occupied(mut occupied) => {
+                self.charge(entry_size(occupied.key(), suffix));
+                let old = occupied.insert(Entry::new(Bytes::copy_from_slice(suffix), None));
+                self.release(entry_size(occupied.key(), &old.value));
+                suffix.len()
+            }
+            MapEntry::Vacant(vacant) => {
+                self.charge(entry_size(vacant.key(), suffix));
+                vacant.insert(Entry::new(Bytes::copy_from_slice(suffix), None));
+                suffix.len()
+            }
+        };
+        if let Some(key) = notify_key {
+            self.notify_write("append", &key, None);
+        }
+        len
+    }
+
+    /// Length of a key's value, 0 if missing or expired
+    pub fn strlen(&self, key: &Bytes) -> usize {
+        self.inner
+            .get(key)
+            .filter(|e| !e.is_expired())
+            .map_or(0, |e| e.value.len())
     }
 
     /// Delete key, returns true if key existed
     #[inline]
     pub fn del(&self, key: &Bytes) -> bool {
-        self.inner.remove(key).is_some()
+        self.remove(key, self.lazy_free)
+    }
+
+    /// Delete key like `del`, but free a large value in the background
+    /// instead of on this thread
+    pub fn unlink(&self, key: &Bytes) -> bool {
+        self.remove(key, true)
+    }
+
+    #[inline]
+    fn remove(&self, key: &Bytes, lazy: bool) -> bool {
+        let removed = self.inner.remove(key);
+        let existed = removed.is_some();
+        if let Some((_, entry)) = removed {
+            self.free(key, entry.value, lazy);
+        }
+        if existed {
+            self.notify_write("del", key, None);
+        }
+        existed
+    }
+
+    /// Delete several keys, returning how many live keys were removed.
+    ///
+    /// Keys are grouped by shard so each shard is write-locked once rather
+    /// than once per key. Expired keys are dropped too but not counted.
+    pub fn del_many(&self, keys: &[Bytes]) -> usize {
+        let shards = self.inner.shards();
+        let mut removed = Vec::new();
+        for group in self.group_by_shard(keys).chunk_by(|a, b| a.0 == b.0) {
+            let mut shard = shards[group[0].0].write();
+            for &(_, hash, i) in group {
+                if let Some((key, entry)) = shard.remove_entry(hash, |(k, _)| *k == keys[i]) {
+                    let entry = entry.into_inner();
+                    let live = !entry.is_expired();
+                    self.free(&key, entry.value, self.lazy_free);
+                    if live {
+                        removed.push(key);
+                    }
+                }
+            }
+        }
+        for key in &removed {
+            self.notify_write("del", key, None);
+        }
+        removed.len()
+    }
+
+    /// Delete every key matching a glob pattern, returning how many live
+    /// keys were removed. Expired matches are dropped too but not counted.
+    pub fn del_matching(&self, pattern: &str) -> usize {
+        let mut removed = Vec::new();
+        self.inner.retain(|key, entry| {
+            if !glob_match(pattern, key) {
+                return true;
+            }
+            if !entry.is_expired() {
+                removed.push(key.clone());
+            }
+            self.free(key, std::mem::take(&mut entry.value), self.lazy_free);
+            false
+        });
+        for key in &removed {
+            self.notify_write("del", key, None);
+        }
+        removed.len()
+    }
+
+    /// Set a live key's TTL, returns false if the key doesn't exist
+    pub fn pexpire(&self, key: &Bytes, ttl: Duration) -> bool {
+        let updated = match self.inner.get_mut(key) {
+            Some(mut entry) if !entry.is_expired() => {
+                entry.expires_at = Some(Instant::now() + ttl);
+                entry.version = next_version();
+                true
+            }
+            _ => false,
+        };
+        if updated {
+            self.notify_write("expire", key, None);
+        }
+        updated
+    }
+
+    /// Current version of a key, 0 if it is missing or expired.
+    ///
+    /// Any write to the key changes it. A key that is created and deleted
+    /// again between two reads looks unchanged.
+    pub fn version(&self, key: &Bytes) -> u64 {
+        self.inner
+            .get(key)
+            .filter(|e| !e.is_expired())
+            .map_or(0, |e| e.version)
+    }
+
+    /// Remaining TTL: None if the key is missing, Some(None) if it never expires
+    pub fn pttl(&self, key: &Bytes) -> Option<Option<Duration>> {
+        let entry = self.inner.get(key)?;
+        match entry.expires_at {
+            None => Some(None),
+            Some(at) => at.checked_duration_since(Instant::now()).map(Some),
+        }
     }
 
     /// Check if key exists and is not expired
@@ -93,39 +972,290 @@ impl ConcurrentStore {
             .unwrap_or(false)
     }
 
-    /// Get the number of keys (including expired - approximate)
+    /// Kind of value stored at a key, None if missing or expired
+    pub fn value_type(&self, key: &Bytes) -> Option<ValueType> {
+        self.inner
+            .get(key)
+            .filter(|e| !e.is_expired())
+            .map(|e| e.kind)
+    }
+
+    /// Number of entries, including expired ones not yet removed; only sums
+    /// shard sizes, see `live_len` for an exact count of live keys
     pub fn len(&self) -> usize {
         self.inner.len()
     }
 
+    /// Number of live keys, leaving out expired ones the cleaner hasn't
+    /// removed yet.
+    ///
+    /// Reads every entry, one shard read-locked at a time, so it costs
+    /// O(keys) where `len` costs O(shards). That's fine for DBSIZE and
+    /// periodic stats; keeping an exact counter instead would mean acting
+    /// on each key the moment it expires, which nothing does.
+    pub fn live_len(&self) -> usize {
+        let now = Instant::now();
+        self.inner.iter().filter(|entry| entry.expires_at.is_none_or(|at| at >= now)).count()
+    }
+
     /// Check if store is empty
     pub fn is_empty(&self) -> bool {
         self.inner.is_empty()
     }
 
-    /// Remove expired keys, returns count of removed keys
+    /// A randomly sampled live key, None if there are none.
+    ///
+    /// Picks a shard weighted by its size, then the first occupied bucket
+    /// from a random position in it, so the cost doesn't grow with the
+    /// keyspace. Keys after runs of empty buckets are somewhat favoured.
+    pub fn random_key(&self) -> Option<Bytes> {
+        let shards = self.inner.shards();
+        let lens: Vec<usize> = shards.iter().map(|shard| shard.read().len()).collect();
+        let total: usize = lens.iter().sum();
+        if total == 0 {
+            return None;
+        }
+
+        for _ in 0..RANDOM_KEY_PROBES {
+            let mut target = (random_u64() % total as u64) as usize;
+            let mut index = 0;
+            while target >= lens[index] {
+                target -= lens[index];
+                index += 1;
+            }
+            let shard = shards[index].read();
+            let buckets = shard.buckets();
+            let start = random_u64() as usize % buckets;
+            // SAFETY: every index is below `buckets()`, and the read guard
+            // keeps the table alive and unchanged while the bucket is read
+            let sampled = (0..buckets)
+                .map(|i| (start + i) % buckets)
+                .find(|&i| unsafe { shard.is_bucket_full(i) })
+                .map(|i| unsafe { shard.bucket(i).as_ref() });
+            if let Some((key, entry)) = sampled {
+                if !entry.get().is_expired() {
+                    return Some(key.clone());
+                }
+            }
+        }
+
+        // Mostly expired keys not yet reaped: find any live one
+        self.inner.iter().find(|entry| !entry.is_expired()).map(|entry| entry.key().clone())
+    }
+
+    /// Remove all keys
+    pub fn clear(&self) {
+        // Hold every shard while recording the flush, so no write can land
+        // after it in the log but before it in the store (or vice versa)
+        let mut shards: Vec<_> = self.inner.shards().iter().map(|shard| shard.write()).collect();
+        if let Some((manager, db)) = &self.replication {
+            manager.record(ReplicationOp::FlushDb, ReplicationEntry::flush_data(*db));
+        }
+        for shard in &mut shards {
+            for (key, entry) in shard.drain() {
+                self.release(entry_size(&key, &entry.get().value));
+            }
+        }
+    }
+
+    /// Remove expired keys, returns count of removed keys. Publishes an
+    /// `expired` keyspace event for each.
     pub fn cleanup_expired(&self) -> usize {
+        self.cleanup_expired_with(|_| {})
+    }
+
+    /// Like `cleanup_expired`, calling `on_expired` with each removed key.
+    ///
+    /// The callback runs while the key's shard is write-locked, so it must
+    /// not call back into this store.
+    pub fn cleanup_expired_with(&self, mut on_expired: impl FnMut(&Bytes)) -> usize {
         let mut removed = 0;
-        self.inner.retain(|_, entry| {
+        // Only filled (and allocated) when keyspace events are published
+        let mut expired = Vec::new();
+        self.inner.retain(|key, entry| {
             if entry.is_expired() {
+                self.release(entry_size(key, &entry.value));
+                self.record_expired(key);
                 removed += 1;
+                on_expired(key);
+                if self.notifier.is_some() {
+                    expired.push(key.clone());
+                }
                 false
             } else {
                 true
             }
         });
+        if let Some(notifier) = &self.notifier {
+            for key in &expired {
+                notifier.notify("expired", key);
+            }
+        }
         removed
     }
 
+    /// Look at up to `count` keys with a TTL, from a random bucket onward,
+    /// and remove those that have expired. Returns `(sampled, removed)`; a
+    /// high ratio means many expired keys likely remain. Gives up after
+    /// examining `count * EXPIRE_SAMPLE_PROBES` entries, so a store with
+    /// few TTLs may sample fewer keys.
+    ///
+    /// Unlike `cleanup_expired_with`, `on_expired` runs with no lock held.
+    pub fn expire_sample_with(&self, count: usize, mut on_expired: impl FnMut(&Bytes)) -> (usize, usize) {
+        let shards = self.inner.shards();
+        let max_examined = count.saturating_mul(EXPIRE_SAMPLE_PROBES);
+        let mut examined = 0;
+        let mut sampled = Vec::with_capacity(count);
+        let first = random_u64() as usize % shards.len();
+        for index in (0..shards.len()).map(|i| (first + i) % shards.len()) {
+            if sampled.len() >= count || examined >= max_examined {
+                break;
+            }
+            let shard = shards[index].read();
+            let buckets = shard.buckets();
+            let start = random_u64() as usize % buckets;
+            for bucket in (0..buckets).map(|i| (start + i) % buckets) {
+                if sampled.len() >= count || examined >= max_examined {
+                    break;
+                }
+                // SAFETY: every index is below `buckets()`, and the read guard
+                // keeps the table alive and unchanged while the bucket is read
+                if !unsafe { shard.is_bucket_full(bucket) } {
+                    continue;
+                }
+                let (key, entry) = unsafe { shard.bucket(bucket).as_ref() };
+                examined += 1;
+                if let Some(at) = entry.get().expires_at {
+                    sampled.push((key.clone(), at));
+                }
+            }
+        }
+
+        let now = Instant::now();
+        let mut removed = 0;
+        for (key, _) in sampled.iter().filter(|(_, at)| *at < now) {
+            if self.reap(key, false) {
+                removed += 1;
+                on_expired(key);
+            }
+        }
+        (sampled.len(), removed)
+    }
+
+    /// Incrementally iterate keys matching an optional glob pattern.
+    ///
+    /// Pass cursor 0 to start and the returned cursor to continue; a returned
+    /// cursor of 0 means iteration is complete. Each call stops after
+    /// examining `count` keys or once `budget` has elapsed, whichever comes
+    /// first, but always makes progress. With a selective pattern an empty
+    /// page with a nonzero cursor is normal: keep scanning.
+    ///
+    /// The cursor names a shard and a bucket in it, so resuming costs the
+    /// same wherever the cursor points. Keys present for the whole scan are
+    /// returned at least once: a shard that resized since the cursor was
+    /// issued is restarted, which may return some of its keys again. Keys
+    /// inserted or removed mid-scan may or may not be returned.
+    pub fn scan(
+        &self,
+        cursor: u64,
+        pattern: Option<&str>,
+        count: usize,
+        budget: Duration,
+    ) -> (u64, Vec<Bytes>) {
+        let start = Instant::now();
+        let count = count.max(1);
+        let shards = self.inner.shards();
+        let mut index = (cursor >> (SCAN_BUCKET_BITS + SCAN_TABLE_BITS)) as usize;
+        let table_bits = ((cursor >> SCAN_BUCKET_BITS) & ((1 << SCAN_TABLE_BITS) - 1)) as u32;
+        let mut bucket = (cursor & ((1 << SCAN_BUCKET_BITS) - 1)) as usize;
+        let mut examined = 0usize;
+        let mut visited = 0usize;
+        let mut keys = Vec::new();
+
+        while index < shards.len() {
+            let shard = shards[index].read();
+            let buckets = shard.buckets();
+            if bucket > 0 && buckets.trailing_zeros() != table_bits {
+                // Resizing moved this shard's keys between buckets
+                bucket = 0;
+            }
+            while bucket < buckets {
+                // SAFETY: `bucket` is below `buckets()`, and the read guard
+                // keeps the table alive and unchanged while it's read
+                if unsafe { shard.is_bucket_full(bucket) } {
+                    let (key, entry) = unsafe { shard.bucket(bucket).as_ref() };
+                    examined += 1;
+                    if !entry.get().is_expired()
+                        && pattern.is_none_or(|p| glob_match(p, key))
+                    {
+                        keys.push(key.clone());
+                    }
+                }
+                bucket += 1;
+                visited += 1;
+
+                // Check the clock every 64 buckets to keep the hot loop cheap
+                if examined >= count || (visited.is_multiple_of(64) && start.elapsed() >= budget) {
+                    if bucket < buckets {
+                        let next = (index as u64) << (SCAN_BUCKET_BITS + SCAN_TABLE_BITS)
+                            | (buckets.trailing_zeros() as u64) << SCAN_BUCKET_BITS
+                            | bucket as u64;
+                        return (next, keys);
+                    }
+                    // Finished this shard: resume at the start of the next
+                    let next = index + 1;
+                    if next < shards.len() {
+                        return ((next as u64) << (SCAN_BUCKET_BITS + SCAN_TABLE_BITS), keys);
+                    }
+                    return (0, keys);
+                }
+            }
+            index += 1;
+            bucket = 0;
+        }
+        (0, keys)
+    }
+
+    /// Visit every live key with its value and remaining TTL.
+    ///
+    /// Each shard is read-locked while it's visited, so writes to other
+    /// shards proceed; the result is not a point-in-time view across shards.
+    pub fn for_each_live(&self, mut f: impl FnMut(&Bytes, &Bytes, Option<Duration>)) {
+        let now = Instant::now();
+        for entry in self.inner.iter() {
+            let ttl = match entry.expires_at {
+                Some(at) if at <= now => continue,
+                Some(at) => Some(at - now),
+                None => None,
+            };
+            f(entry.key(), &entry.value, ttl);
+        }
+    }
+
     /// Get all keys (for debugging/testing)
     pub fn keys(&self) -> Vec<Bytes> {
         self.inner.iter().map(|r| r.key().clone()).collect()
     }
 
-    /// Get estimated shard count for diagnostics
+    /// Number of DashMap shards
     pub fn shards(&self) -> usize {
-        // DashMap 6.x: shards() is private, estimate based on CPU count
-        num_cpus::get() * 4
+        self.inner.shards().len()
+    }
+
+    /// Key count of every shard. Each shard is read-locked in turn, so the
+    /// counts aren't a point-in-time view across shards.
+    pub fn shard_stats(&self) -> Vec<ShardStat> {
+        self.inner
+            .shards()
+            .iter()
+            .enumerate()
+            .map(|(shard, table)| ShardStat { shard, keys: table.read().len() })
+            .collect()
+    }
+
+    /// Max/min/stddev of keys per shard
+    pub fn shard_distribution(&self) -> ShardDistribution {
+        ShardDistribution::from_stats(&self.shard_stats())
     }
 }
 
@@ -153,6 +1283,27 @@ mod tests {
         assert_eq!(store.get(&key), None);
     }
 
+    #[test]
+    fn test_live_len_excludes_lapsed_keys() {
+        let store = ConcurrentStore::with_shard_amount(4);
+        for i in 0..10 {
+            let key = Bytes::from(format!("short:{}", i));
+            store.set_with_ttl(key, Bytes::from_static(b"v"), Some(Duration::from_millis(10)));
+        }
+        for i in 0..5 {
+            let key = Bytes::from(format!("long:{}", i));
+            store.set_with_ttl(key, Bytes::from_static(b"v"), Some(Duration::from_secs(60)));
+            store.set(Bytes::from(format!("forever:{}", i)), Bytes::from_static(b"v"), None);
+        }
+        assert_eq!((store.len(), store.live_len()), (20, 20));
+
+        thread::sleep(Duration::from_millis(20));
+        // Expired but never read or swept: still stored, no longer counted
+        assert_eq!((store.len(), store.live_len()), (20, 10));
+        assert_eq!(store.cleanup_expired(), 10);
+        assert_eq!((store.len(), store.live_len()), (10, 10));
+    }
+
     #[test]
     fn test_ttl_expiration() {
         let store = ConcurrentStore::new();
@@ -211,4 +1362,485 @@ mod tests {
         assert_eq!(removed, 10);
         assert!(store.is_empty());
     }
+
+    #[test]
+    fn test_conditional_set() {
+        let store = ConcurrentStore::new();
+        let key = Bytes::from_static(b"key");
+
+        // XX on a missing key is a no-op
+        let (applied, old) = store.set_with(key.clone(), Bytes::from_static(b"a"), None, SetCondition::IfPresent);
+        assert!(!applied && old.is_none());
+        assert!(!store.exists(&key));
+
+        // NX on a missing key writes
+        let (applied, _) = store.set_with(key.clone(), Bytes::from_static(b"a"), None, SetCondition::IfAbsent);
+        assert!(applied);
+
+        // NX on an existing key is a no-op
+        let (applied, old) = store.set_with(key.clone(), Bytes::from_static(b"b"), None, SetCondition::IfAbsent);
+        assert!(!applied);
+        assert_eq!(old, Some(Bytes::from_static(b"a")));
+        assert_eq!(store.get(&key), Some(Bytes::from_static(b"a")));
+
+        // Plain set returns the replaced value
+        let (applied, old) = store.set_with(key.clone(), Bytes::from_static(b"c"), None, SetCondition::Always);
+        assert!(applied);
+        assert_eq!(old, Some(Bytes::from_static(b"a")));
+        assert_eq!(store.get(&key), Some(Bytes::from_static(b"c")));
+    }
+
+    #[test]
+    fn test_concurrent_nx() {
+        let store = ConcurrentStore::new();
+        let key = Bytes::from_static(b"lock");
+
+        let handles: Vec<_> = (0..16)
+            .map(|i| {
+                let s = store.clone();
+                let k = key.clone();
+                thread::spawn(move || {
+                    s.set_with(k, Bytes::from(format!("owner-{}", i)), None, SetCondition::IfAbsent).0
+                })
+            })
+            .collect();
+
+        let winners = handles.into_iter().map(|h| h.join().unwrap()).filter(|&won| won).count();
+        assert_eq!(winners, 1);
+    }
+
+    #[test]
+    fn test_get_set_and_get_del() {
+        let store = ConcurrentStore::new();
+        let key = Bytes::from_static(b"token");
+
+        assert_eq!(store.get_set(key.clone(), Bytes::from_static(b"a")), None);
+        assert_eq!(store.get_set(key.clone(), Bytes::from_static(b"b")), Some(Bytes::from_static(b"a")));
+        assert_eq!(store.get_del(&key), Some(Bytes::from_static(b"b")));
+        assert_eq!(store.get_del(&key), None);
+    }
+
+    #[test]
+    fn test_concurrent_get_del() {
+        let store = ConcurrentStore::new();
+        let key = Bytes::from_static(b"job");
+        store.set(key.clone(), Bytes::from_static(b"payload"), None);
+
+        let handles: Vec<_> = (0..32)
+            .map(|_| {
+                let s = store.clone();
+                let k = key.clone();
+                thread::spawn(move || s.get_del(&k))
+            })
+            .collect();
+
+        let seen = handles
+            .into_iter()
+            .filter_map(|h| h.join().unwrap())
+            .count();
+        assert_eq!(seen, 1);
+    }
+
+    #[test]
+    fn test_del_matching() {
+        let store = ConcurrentStore::new();
+        for key in ["session:1", "session:2", "session", "user:1"] {
+            store.set(Bytes::from(key), Bytes::from_static(b"v"), None);
+        }
+        store.set_with_ttl(Bytes::from_static(b"session:old"), Bytes::from_static(b"v"), Some(Duration::ZERO));
+
+        assert_eq!(store.del_matching("session:*"), 2);
+        assert_eq!(store.len(), 2);
+        assert!(store.exists(&Bytes::from_static(b"session")));
+        assert_eq!(store.del_matching("nothing:*"), 0);
+    }
+
+    #[test]
+    fn test_concurrent_append() {
+        let store = ConcurrentStore::new();
+        let key = Bytes::from_static(b"log");
+        store.set_with_ttl(key.clone(), Bytes::new(), Some(Duration::from_secs(60)));
+
+        let handles: Vec<_> = (0..16)
+            .map(|t| {
+                let s = store.clone();
+                let k = key.clone();
+                thread::spawn(move || {
+                    let line = format!("worker {} line\n", t);
+                    for _ in 0..100 {
+                        s.append(k.clone(), line.as_bytes());
+                    }
+                    line.len() * 100
+                })
+            })
+            .collect();
+        let expected: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+
+        assert_eq!(store.strlen(&key), expected);
+        assert!(matches!(store.pttl(&key), Some(Some(_))));
+        assert_eq!(store.strlen(&Bytes::from_static(b"missing")), 0);
+    }
+
+    #[test]
+    fn test_scan_rare_pattern_is_bounded() {
+        let store = ConcurrentStore::new();
+        for i in 0..20_000 {
+            store.set(Bytes::from(format!("common:{}", i)), Bytes::from_static(b"v"), None);
+        }
+        for i in 0..5 {
+            store.set(Bytes::from(format!("rare:{}", i)), Bytes::from_static(b"v"), None);
+        }
+
+        let budget = Duration::from_millis(2);
+        let mut cursor = 0;
+        let mut found = Vec::new();
+        loop {
+            let start = Instant::now();
+            let (next, keys) = store.scan(cursor, Some("rare:*"), 1000, budget);
+            assert!(start.elapsed() < Duration::from_millis(250));
+            assert!(next == 0 || next > cursor, "scan must make forward progress");
+
+            found.extend(keys);
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+
+        assert_eq!(found.len(), 5);
+
+        // A zero budget still makes progress on every call
+        let (next, _) = store.scan(0, Some("rare:*"), 1000, Duration::ZERO);
+        assert!(next > 0);
+    }
+
+    #[test]
+    fn test_scan_late_cursor_is_cheap() {
+        let store = ConcurrentStore::with_shard_amount(4);
+        for i in 0..100_000 {
+            store.set(Bytes::from(format!("key:{}", i)), Bytes::from_static(b"v"), None);
+        }
+
+        let mut cursor = 0;
+        let mut late = 0;
+        let mut found = Vec::new();
+        loop {
+            let (next, keys) = store.scan(cursor, None, 1000, Duration::from_secs(10));
+            found.extend(keys);
+            if next == 0 {
+                break;
+            }
+            late = next;
+            cursor = next;
+        }
+        found.sort();
+        found.dedup();
+        assert_eq!(found.len(), 100_000);
+
+        // Resuming near the end costs the same as resuming at the start;
+        // skipping to the cursor would walk ~99k entries per call
+        let start = Instant::now();
+        for _ in 0..100 {
+            let (_, keys) = store.scan(late, None, 10, Duration::from_secs(10));
+            assert!(keys.len() <= 10);
+        }
+        assert!(start.elapsed() < Duration::from_millis(100), "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn test_scan_survives_resize() {
+        let store = ConcurrentStore::with_shard_amount(4);
+        for i in 0..1000 {
+            store.set(Bytes::from(format!("old:{}", i)), Bytes::from_static(b"v"), None);
+        }
+
+        let (mut cursor, mut found) = store.scan(0, Some("old:*"), 300, Duration::from_secs(10));
+        // Grow every shard well past its capacity mid-scan
+        for i in 0..10_000 {
+            store.set(Bytes::from(format!("new:{}", i)), Bytes::from_static(b"v"), None);
+        }
+        while cursor != 0 {
+            let (next, keys) = store.scan(cursor, Some("old:*"), 300, Duration::from_secs(10));
+            found.extend(keys);
+            cursor = next;
+        }
+
+        found.sort();
+        found.dedup();
+        assert_eq!(found.len(), 1000);
+    }
+
+    #[test]
+    fn test_shard_stats_reflect_skew() {
+        let store = ConcurrentStore::with_shard_amount(8);
+        for i in 0..800 {
+            store.set(Bytes::from(format!("key:{}", i)), Bytes::from_static(b"v"), None);
+        }
+        let even = store.shard_distribution();
+        assert_eq!(store.shard_stats().iter().map(|s| s.keys).sum::<usize>(), 800);
+
+        // Shards hash the whole key, so a hot hash tag spreads out; model
+        // the hotspot by keeping only tagged keys that land in one shard
+        let hot = store.inner.determine_map(&Bytes::from_static(b"{hot}:0"));
+        let mut added = 0;
+        for i in 0.. {
+            let key = Bytes::from(format!("{{hot}}:{}", i));
+            if store.inner.determine_map(&key) == hot {
+                store.set(key, Bytes::from_static(b"v"), None);
+                added += 1;
+                if added == 800 {
+                    break;
+                }
+            }
+        }
+
+        let stats = store.shard_stats();
+        assert_eq!(stats.len(), 8);
+        let skewed = store.shard_distribution();
+        assert_eq!(skewed.max, stats[hot].keys);
+        assert!(skewed.max >= 800 && skewed.min < 200, "{:?}", skewed);
+        assert!(skewed.stddev > even.stddev * 4.0, "{:?} vs {:?}", skewed, even);
+    }
+
+    #[test]
+    fn test_del_many_counts_live_keys() {
+        let store = ConcurrentStore::with_shard_amount(4);
+        for i in 0..20 {
+            store.set(Bytes::from(format!("k{}", i)), Bytes::from_static(b"v"), None);
+        }
+        store.set_with_ttl(Bytes::from_static(b"expired"), Bytes::from_static(b"v"), Some(Duration::from_millis(1)));
+        std::thread::sleep(Duration::from_millis(5));
+
+        let mut keys: Vec<Bytes> = (0..20).step_by(2).map(|i| Bytes::from(format!("k{}", i))).collect();
+        keys.extend([Bytes::from_static(b"missing"), Bytes::from_static(b"expired"), Bytes::from_static(b"k0")]);
+
+        assert_eq!(store.del_many(&keys), 10);
+        assert_eq!(store.len(), 10);
+        assert!(store.exists(&Bytes::from_static(b"k1")) && !store.exists(&Bytes::from_static(b"k2")));
+        let live: usize = store.keys().iter().map(|k| entry_size(k, &Bytes::from_static(b"v"))).sum();
+        assert_eq!(store.memory_used(), live);
+    }
+
+    #[test]
+    fn test_get_many_matches_get() {
+        let store = ConcurrentStore::with_shard_amount(16);
+        for i in 0..2000 {
+            store.set(Bytes::from(format!("k{}", i)), Bytes::from(format!("v{}", i)), None);
+        }
+        store.set_with_ttl(Bytes::from_static(b"expired"), Bytes::from_static(b"v"), Some(Duration::from_millis(1)));
+        std::thread::sleep(Duration::from_millis(5));
+
+        // Every other key, in reverse, plus misses and a duplicate
+        let mut keys: Vec<Bytes> = (0..2000).rev().step_by(2).map(|i| Bytes::from(format!("k{}", i))).collect();
+        keys.extend([Bytes::from_static(b"missing"), Bytes::from_static(b"expired"), Bytes::from_static(b"k1999")]);
+
+        let expected: Vec<_> = keys.iter().map(|key| store.get(key)).collect();
+        assert_eq!(store.get_many(&keys), expected);
+        assert_eq!(store.get_many(&keys)[0], Some(Bytes::from_static(b"v1999")));
+        assert!(store.get_many(&[]).is_empty());
+
+        // One lock per shard touched instead of one per key
+        let locks = store.group_by_shard(&keys).chunk_by(|a, b| a.0 == b.0).count();
+        assert_eq!(locks, 16);
+    }
+
+    #[test]
+    #[ignore = "benchmark; run with --ignored"]
+    fn test_get_many_benchmark() {
+        use crate::observability::Benchmark;
+
+        let store = ConcurrentStore::with_shard_amount(64);
+        let keys: Vec<Bytes> = (0..1000).map(|i| Bytes::from(format!("key:{}", i))).collect();
+        for key in &keys {
+            store.set(key.clone(), Bytes::from_static(b"value"), None);
+        }
+
+        let per_key = Benchmark::new("mget per-key").iterations(200).run(|| {
+            std::hint::black_box(keys.iter().map(|key| store.get(key)).collect::<Vec<_>>());
+        });
+        let batched = Benchmark::new("mget batched").iterations(200).run(|| {
+            std::hint::black_box(store.get_many(&keys));
+        });
+        assert!(per_key.ops_per_sec > 0.0 && batched.ops_per_sec > 0.0);
+    }
+
+    #[test]
+    fn test_idle_time_and_access_freq() {
+        let store = ConcurrentStore::new();
+        let key = Bytes::from_static(b"key");
+        assert_eq!(store.idle_time(&key), None);
+
+        store.set(key.clone(), Bytes::from_static(b"v"), None);
+        let touched = Instant::now();
+        store.get(&key);
+        std::thread::sleep(Duration::from_millis(100));
+        let idle = store.idle_time(&key).unwrap();
+        assert!(idle >= Duration::from_millis(100) && idle <= touched.elapsed(), "{:?}", idle);
+        assert_eq!(store.access_freq(&key), Some(2));
+
+        // Reads reset the idle time; overwrites also reset the count
+        store.get(&key);
+        assert!(store.idle_time(&key).unwrap() < Duration::from_millis(50));
+        assert_eq!(store.access_freq(&key), Some(3));
+        store.set(key.clone(), Bytes::from_static(b"w"), None);
+        assert_eq!(store.access_freq(&key), Some(1));
+    }
+
+    #[test]
+    fn test_unlink_reclaims_in_background() {
+        let store = ConcurrentStore::new();
+        let key = Bytes::from_static(b"big");
+        store.set(key.clone(), Bytes::from(vec![0u8; 64 * 1024 * 1024]), None);
+        let small = Bytes::from_static(b"small");
+        store.set(small.clone(), Bytes::from_static(b"v"), None);
+        let remaining = entry_size(&small, b"v");
+
+        let started = Instant::now();
+        assert!(store.unlink(&key));
+        assert!(started.elapsed() < Duration::from_millis(50));
+        assert!(!store.exists(&key));
+        assert!(!store.unlink(&key));
+        // Uncharged at once; the backlog drains as the reclaimer frees it
+        assert_eq!(store.memory_used(), remaining);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while store.reclaim_pending() != 0 {
+            assert!(Instant::now() < deadline, "value never reclaimed");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        // Small values are freed inline
+        assert!(store.unlink(&small));
+        assert_eq!(store.memory_used(), 0);
+    }
+
+    #[test]
+    fn test_memory_accounting() {
+        let store = ConcurrentStore::new();
+        let key = Bytes::from_static(b"key");
+        assert_eq!(store.memory_used(), 0);
+
+        store.set(key.clone(), Bytes::from_static(b"small"), None);
+        assert_eq!(store.memory_used(), 3 + 5 + ENTRY_OVERHEAD);
+
+        // Overwrites swap the old value's size for the new one
+        store.set(key.clone(), Bytes::from(vec![0u8; 1000]), None);
+        assert_eq!(store.memory_used(), 3 + 1000 + ENTRY_OVERHEAD);
+        store.append(key.clone(), b"tail");
+        assert_eq!(store.memory_used(), 3 + 1004 + ENTRY_OVERHEAD);
+
+        store.set_with_ttl(Bytes::from_static(b"gone"), Bytes::from_static(b"v"), Some(Duration::from_millis(1)));
+        std::thread::sleep(Duration::from_millis(5));
+        store.cleanup_expired();
+        assert_eq!(store.memory_used(), 3 + 1004 + ENTRY_OVERHEAD);
+
+        store.swap(&key, &Bytes::from_static(b"longer-key"));
+        assert_eq!(store.memory_used(), 10 + 1004 + ENTRY_OVERHEAD);
+        assert!(store.del(&Bytes::from_static(b"longer-key")));
+        assert_eq!(store.memory_used(), 0);
+    }
+
+    #[test]
+    fn test_swap() {
+        let store = ConcurrentStore::new();
+        let active = Bytes::from_static(b"config:active");
+        let staging = Bytes::from_static(b"config:staging");
+
+        store.set(active.clone(), Bytes::from_static(b"blue"), None);
+        store.set(staging.clone(), Bytes::from_static(b"green"), Some(3600));
+        store.swap(&active, &staging);
+
+        assert_eq!(store.get(&active), Some(Bytes::from_static(b"green")));
+        assert_eq!(store.get(&staging), Some(Bytes::from_static(b"blue")));
+        assert!(store.inner.get(&active).unwrap().expires_at.is_some());
+        assert!(store.inner.get(&staging).unwrap().expires_at.is_none());
+
+        // Swapping with a missing key is a move
+        let missing = Bytes::from_static(b"config:old");
+        store.swap(&active, &missing);
+        assert!(!store.exists(&active));
+        assert_eq!(store.get(&missing), Some(Bytes::from_static(b"green")));
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn test_rename_preserves_ttl() {
+        let store = ConcurrentStore::new();
+        let src = Bytes::from_static(b"session:tmp");
+        let dst = Bytes::from_static(b"session:1");
+
+        store.set(src.clone(), Bytes::from_static(b"token"), Some(3600));
+        store.set(dst.clone(), Bytes::from_static(b"stale"), None);
+        assert!(store.rename(&src, &dst));
+
+        assert!(!store.exists(&src));
+        assert_eq!(store.get(&dst), Some(Bytes::from_static(b"token")));
+        assert!(store.pttl(&dst).unwrap().is_some());
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.memory_used(), entry_size(&dst, b"token"));
+
+        // A missing source fails and leaves the destination alone
+        assert!(!store.rename(&src, &dst));
+        assert_eq!(store.get(&dst), Some(Bytes::from_static(b"token")));
+    }
+
+    #[test]
+    fn test_copy_respects_replace() {
+        let store = ConcurrentStore::new();
+        let src = Bytes::from_static(b"template");
+        let dst = Bytes::from_static(b"draft");
+
+        store.set(src.clone(), Bytes::from_static(b"v1"), Some(3600));
+        assert!(store.copy(&src, &dst, false));
+        assert_eq!(store.get(&dst), Some(Bytes::from_static(b"v1")));
+        assert!(store.pttl(&dst).unwrap().is_some());
+
+        store.set(src.clone(), Bytes::from_static(b"v2"), None);
+        assert!(!store.copy(&src, &dst, false));
+        assert_eq!(store.get(&dst), Some(Bytes::from_static(b"v1")));
+        assert!(store.copy(&src, &dst, true));
+        assert_eq!(store.get(&dst), Some(Bytes::from_static(b"v2")));
+        assert_eq!(store.get(&src), Some(Bytes::from_static(b"v2")));
+
+        assert!(!store.copy(&Bytes::from_static(b"missing"), &dst, true));
+    }
+
+    #[test]
+    fn test_len_tracks_inserts_and_deletes() {
+        let store = ConcurrentStore::new();
+        assert_eq!(store.len(), 0);
+        for i in 0..10 {
+            store.set(Bytes::from(format!("key:{}", i)), Bytes::from_static(b"v"), None);
+        }
+        store.set(Bytes::from_static(b"key:0"), Bytes::from_static(b"again"), None);
+        assert_eq!(store.len(), 10);
+        store.del(&Bytes::from_static(b"key:3"));
+        store.del_many(&[Bytes::from_static(b"key:4"), Bytes::from_static(b"missing")]);
+        assert_eq!(store.len(), 8);
+    }
+
+    #[test]
+    fn test_random_key() {
+        let store = ConcurrentStore::new();
+        assert_eq!(store.random_key(), None);
+
+        let keys: Vec<Bytes> = (0..50).map(|i| Bytes::from(format!("key:{}", i))).collect();
+        for key in &keys {
+            store.set(key.clone(), Bytes::from_static(b"v"), None);
+        }
+        store.set_with_ttl(Bytes::from_static(b"gone"), Bytes::from_static(b"v"), Some(Duration::from_millis(1)));
+        std::thread::sleep(Duration::from_millis(5));
+
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..1000 {
+            let key = store.random_key().unwrap();
+            assert!(keys.contains(&key), "{:?}", key);
+            seen.insert(key);
+        }
+        // Sampling reaches most of the keyspace, not one corner of it
+        assert!(seen.len() > 40, "only saw {} keys", seen.len());
+
+        store.clear();
+        assert_eq!(store.random_key(), None);
+    }
 }
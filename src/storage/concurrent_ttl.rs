@@ -1,17 +1,58 @@
 //! Concurrent TTL Cleaner
 //!
 //! Background task that periodically removes expired keys from ConcurrentStore.
+//!
+//! By default every interval sweeps the whole store. In adaptive mode the
+//! cleaner instead samples keys with TTLs between sweeps, like Redis' active
+//! expire cycle: it keeps sampling while many of them turn out expired, and
+//! backs off towards the sweep interval while few do.
 
-use std::time::Duration;
+use bytes::Bytes;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::time::interval;
 use tracing::{debug, info};
 
 use super::ConcurrentStore;
+use crate::metrics::Metrics;
+
+/// Called with each key removed by a sweep
+pub type ExpiredCallback = Box<dyn Fn(&Bytes) + Send + Sync>;
+
+/// Tuning for the adaptive cleaner's sampling cycles
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveExpiry {
+    /// Keys with a TTL examined per sample
+    pub sample_size: usize,
+    /// Fraction of a sample found expired above which the cycle samples again
+    pub repeat_threshold: f64,
+    /// Wait between cycles while they keep finding expired keys; doubles
+    /// up to the sweep interval while they don't
+    pub min_delay: Duration,
+    /// Longest one cycle may keep resampling
+    pub cycle_budget: Duration,
+}
+
+impl Default for AdaptiveExpiry {
+    fn default() -> Self {
+        Self {
+            sample_size: 20,
+            repeat_threshold: 0.25,
+            min_delay: Duration::from_millis(100),
+            cycle_budget: Duration::from_millis(25),
+        }
+    }
+}
 
 /// Background TTL cleanup task for ConcurrentStore
 pub struct ConcurrentTtlCleaner {
     store: ConcurrentStore,
     interval: Duration,
+    /// Counts removed keys in `expired_total`
+    metrics: Option<Arc<Metrics>>,
+    on_expired: Option<ExpiredCallback>,
+    /// Sample between sweeps (None = sweep every interval only)
+    adaptive: Option<AdaptiveExpiry>,
 }
 
 impl ConcurrentTtlCleaner {
@@ -20,11 +61,72 @@ impl ConcurrentTtlCleaner {
         Self {
             store,
             interval: Duration::from_secs(interval_secs),
+            metrics: None,
+            on_expired: None,
+            adaptive: None,
+        }
+    }
+
+    /// Run sampling cycles between full sweeps, which then only happen
+    /// every interval as a backstop
+    pub fn with_adaptive(mut self, adaptive: AdaptiveExpiry) -> Self {
+        self.adaptive = Some(adaptive);
+        self
+    }
+
+    /// Count removed keys in these metrics
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Call `f` with each removed key; it runs under the key's shard lock,
+    /// so it must not touch the store
+    pub fn with_callback(mut self, f: impl Fn(&Bytes) + Send + Sync + 'static) -> Self {
+        self.on_expired = Some(Box::new(f));
+        self
+    }
+
+    /// Remove expired keys once, returning how many were removed
+    pub fn sweep(&self) -> usize {
+        let removed = match &self.on_expired {
+            Some(f) => self.store.cleanup_expired_with(|key| f(key)),
+            None => self.store.cleanup_expired(),
+        };
+        if let Some(metrics) = &self.metrics {
+            metrics.record_expired(removed as u64);
         }
+        removed
+    }
+
+    /// Sample keys with TTLs, removing expired ones, until a sample is
+    /// mostly live or the cycle budget runs out. Returns how many keys were
+    /// removed and whether the last sample was still over the threshold.
+    pub fn expire_cycle(&self, adaptive: &AdaptiveExpiry) -> (usize, bool) {
+        let start = Instant::now();
+        let mut removed = 0;
+        let hot = loop {
+            let (sampled, expired) = match &self.on_expired {
+                Some(f) => self.store.expire_sample_with(adaptive.sample_size, |key| f(key)),
+                None => self.store.expire_sample_with(adaptive.sample_size, |_| {}),
+            };
+            removed += expired;
+            let hot = sampled > 0 && expired as f64 > sampled as f64 * adaptive.repeat_threshold;
+            if !hot || start.elapsed() >= adaptive.cycle_budget {
+                break hot;
+            }
+        };
+        if let Some(metrics) = &self.metrics {
+            metrics.record_expired(removed as u64);
+        }
+        (removed, hot)
     }
 
     /// Run the cleaner (should be spawned as a task)
     pub async fn run(self) {
+        if let Some(adaptive) = self.adaptive {
+            return self.run_adaptive(adaptive).await;
+        }
         let mut ticker = interval(self.interval);
         info!(
             "Concurrent TTL cleaner started, interval: {:?}",
@@ -33,7 +135,37 @@ impl ConcurrentTtlCleaner {
 
         loop {
             ticker.tick().await;
-            let removed = self.store.cleanup_expired();
+            let removed = self.sweep();
+            if removed > 0 {
+                debug!(removed = removed, "Cleaned up expired keys");
+            }
+        }
+    }
+
+    /// Sampling cycles, sooner while they find expired keys, with a full
+    /// sweep every interval
+    async fn run_adaptive(self, adaptive: AdaptiveExpiry) {
+        info!(
+            "Concurrent TTL cleaner started, adaptive, sweep interval: {:?}",
+            self.interval
+        );
+
+        let mut delay = adaptive.min_delay;
+        let mut last_sweep = Instant::now();
+        loop {
+            tokio::time::sleep(delay).await;
+            let mut removed = 0;
+            if last_sweep.elapsed() >= self.interval {
+                removed += self.sweep();
+                last_sweep = Instant::now();
+            }
+            let (expired, hot) = self.expire_cycle(&adaptive);
+            removed += expired;
+            delay = if hot || expired > 0 {
+                adaptive.min_delay
+            } else {
+                (delay * 2).min(self.interval.max(adaptive.min_delay))
+            };
             if removed > 0 {
                 debug!(removed = removed, "Cleaned up expired keys");
             }
@@ -46,3 +178,83 @@ impl ConcurrentTtlCleaner {
         tokio::spawn(cleaner.run())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_sweep_reports_expired_keys() {
+        let store = ConcurrentStore::new();
+        for i in 0..3 {
+            let key = Bytes::from(format!("short:{}", i));
+            store.set_with_ttl(key, Bytes::from_static(b"v"), Some(Duration::from_millis(1)));
+        }
+        store.set_with_ttl(Bytes::from_static(b"long"), Bytes::from_static(b"v"), Some(Duration::from_secs(60)));
+        store.set(Bytes::from_static(b"forever"), Bytes::from_static(b"v"), None);
+        std::thread::sleep(Duration::from_millis(5));
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let metrics = Arc::new(Metrics::new());
+        let cleaner = ConcurrentTtlCleaner::new(store.clone(), 1)
+            .with_metrics(metrics.clone())
+            .with_callback({
+                let seen = seen.clone();
+                move |key| seen.lock().unwrap().push(key.clone())
+            });
+
+        assert_eq!(cleaner.sweep(), 3);
+        let mut seen = seen.lock().unwrap().clone();
+        seen.sort();
+        assert_eq!(seen, vec![
+            Bytes::from_static(b"short:0"),
+            Bytes::from_static(b"short:1"),
+            Bytes::from_static(b"short:2"),
+        ]);
+        assert_eq!(metrics.expired_total(), 3);
+        assert_eq!(store.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_cleaner_reclaims_between_sweeps() {
+        let store = ConcurrentStore::new();
+        for i in 0..2000 {
+            let key = Bytes::from(format!("short:{}", i));
+            store.set_with_ttl(key, Bytes::from_static(b"v"), Some(Duration::from_millis(20)));
+        }
+        for i in 0..100 {
+            store.set(Bytes::from(format!("forever:{}", i)), Bytes::from_static(b"v"), None);
+        }
+
+        // A fixed-interval cleaner sweeps on its first tick, before anything
+        // has expired, and then not again for a minute
+        let metrics = Arc::new(Metrics::new());
+        let cleaner = ConcurrentTtlCleaner::new(store.clone(), 60)
+            .with_metrics(metrics.clone())
+            .with_adaptive(AdaptiveExpiry { min_delay: Duration::from_millis(10), ..Default::default() });
+        let task = tokio::spawn(cleaner.run());
+
+        let start = Instant::now();
+        while store.len() > 100 {
+            assert!(start.elapsed() < Duration::from_secs(5), "{} keys left", store.len());
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(metrics.expired_total(), 2000);
+        assert!(store.get(&Bytes::from_static(b"forever:0")).is_some());
+        task.abort();
+    }
+
+    #[test]
+    fn test_expire_cycle_stops_once_sample_is_live() {
+        let store = ConcurrentStore::new();
+        for i in 0..100 {
+            store.set(Bytes::from(format!("forever:{}", i)), Bytes::from_static(b"v"), None);
+            let key = Bytes::from(format!("long:{}", i));
+            store.set_with_ttl(key, Bytes::from_static(b"v"), Some(Duration::from_secs(60)));
+        }
+        let cleaner = ConcurrentTtlCleaner::new(store.clone(), 1);
+        assert_eq!(cleaner.expire_cycle(&AdaptiveExpiry::default()), (0, false));
+        assert_eq!(store.len(), 200);
+    }
+}
@@ -0,0 +1,88 @@
+//! Logical Databases
+//!
+//! Fixed set of independent keyspaces selected per connection (`SELECT n`).
+
+use std::sync::Arc;
+
+use super::ConcurrentStore;
+
+/// Default number of logical databases
+pub const DEFAULT_DATABASES: usize = 16;
+
+/// Array of independent `ConcurrentStore`s indexed by database number
+#[derive(Debug, Clone)]
+pub struct Databases {
+    stores: Arc<[ConcurrentStore]>,
+}
+
+impl Databases {
+    /// Create `count` empty databases, each with the given shard count
+    pub fn new(count: usize, shard_amount: usize) -> Self {
+        Self::from_fn(count, |_| ConcurrentStore::with_shard_amount(shard_amount))
+    }
+
+    /// Create `count` databases using a constructor per index
+    pub fn from_fn(count: usize, f: impl FnMut(usize) -> ConcurrentStore) -> Self {
+        assert!(count > 0, "at least one database is required");
+        Self {
+            stores: (0..count).map(f).collect(),
+        }
+    }
+
+    /// Get the store for a database index
+    #[inline]
+    pub fn get(&self, db: usize) -> Option<&ConcurrentStore> {
+        self.stores.get(db)
+    }
+
+    /// Number of databases
+    pub fn len(&self) -> usize {
+        self.stores.len()
+    }
+
+    /// Check if there are no databases (never true once constructed)
+    pub fn is_empty(&self) -> bool {
+        self.stores.is_empty()
+    }
+
+    /// Iterate over all databases in index order
+    pub fn iter(&self) -> impl Iterator<Item = &ConcurrentStore> {
+        self.stores.iter()
+    }
+
+    /// Remove every key from every database
+    pub fn flush_all(&self) {
+        for store in self.iter() {
+            store.clear();
+        }
+    }
+}
+
+impl From<ConcurrentStore> for Databases {
+    fn from(store: ConcurrentStore) -> Self {
+        Self {
+            stores: Arc::from(vec![store]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    #[test]
+    fn test_databases_are_independent() {
+        let dbs = Databases::new(2, 4);
+        let key = Bytes::from_static(b"k");
+        dbs.get(0).unwrap().set(key.clone(), Bytes::from_static(b"zero"), None);
+        dbs.get(1).unwrap().set(key.clone(), Bytes::from_static(b"one"), None);
+
+        assert_eq!(dbs.get(0).unwrap().get(&key).unwrap().as_ref(), b"zero");
+        assert_eq!(dbs.get(1).unwrap().get(&key).unwrap().as_ref(), b"one");
+        assert!(dbs.get(2).is_none());
+
+        dbs.flush_all();
+        assert!(dbs.iter().all(|s| s.is_empty()));
+    }
+}
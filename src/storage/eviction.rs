@@ -7,12 +7,15 @@ use dashmap::DashMap;
 use std::collections::VecDeque;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::RwLock;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+use super::ConcurrentStore;
 
 /// Eviction policy
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum EvictionPolicy {
     /// No eviction (default)
+    #[default]
     None,
     /// Least Recently Used
     Lru,
@@ -20,14 +23,40 @@ pub enum EvictionPolicy {
     Lfu,
     /// Random eviction
     Random,
+    /// Least Recently Used among keys with a TTL
+    VolatileLru,
+    /// Least Frequently Used among keys with a TTL
+    VolatileLfu,
+    /// Random eviction among keys with a TTL
+    VolatileRandom,
+    /// Keys with a TTL, soonest to expire first
+    VolatileTtl,
 }
 
-impl Default for EvictionPolicy {
-    fn default() -> Self {
-        Self::None
+impl EvictionPolicy {
+    /// Whether only keys with a TTL may be evicted
+    pub fn is_volatile(&self) -> bool {
+        matches!(
+            self,
+            Self::VolatileLru | Self::VolatileLfu | Self::VolatileRandom | Self::VolatileTtl
+        )
     }
 }
 
+/// What a write does when `max_memory` is reached and the policy finds
+/// nothing to evict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OomAction {
+    /// Refuse the write with `OOM_ERROR` (Redis `noeviction`)
+    #[default]
+    Reject,
+    /// Evict any key, with or without a TTL, until the write fits
+    Evict,
+}
+
+/// Reply to a write refused by `OomAction::Reject`
+pub const OOM_ERROR: &str = "OOM command not allowed when used memory > 'max_memory'";
+
 /// Eviction configuration
 #[derive(Debug, Clone)]
 pub struct EvictionConfig {
@@ -39,6 +68,11 @@ pub struct EvictionConfig {
     pub policy: EvictionPolicy,
     /// Number of samples for random eviction
     pub sample_size: usize,
+    /// LFU access counts halve for each interval a key goes unused
+    /// (zero = counts never decay)
+    pub lfu_decay_interval: Duration,
+    /// What to do when the policy can't make room under `max_memory`
+    pub oom_action: OomAction,
 }
 
 impl Default for EvictionConfig {
@@ -48,6 +82,8 @@ impl Default for EvictionConfig {
             max_keys: 0,
             policy: EvictionPolicy::None,
             sample_size: 5,
+            lfu_decay_interval: Duration::from_secs(60),
+            oom_action: OomAction::Reject,
         }
     }
 }
@@ -67,6 +103,68 @@ impl EvictionConfig {
         self.policy = policy;
         self
     }
+
+    pub fn with_lfu_decay_interval(mut self, interval: Duration) -> Self {
+        self.lfu_decay_interval = interval;
+        self
+    }
+
+    pub fn with_oom_action(mut self, action: OomAction) -> Self {
+        self.oom_action = action;
+        self
+    }
+
+    /// Evict keys from `store` until `incoming` more bytes fit under
+    /// `max_memory`. Returns false if the write should be refused.
+    ///
+    /// Like Redis, policies approximate their ordering by sampling
+    /// `sample_size` random keys and evicting the best candidate: the
+    /// longest idle for LRU, the least accessed for LFU, the soonest to
+    /// expire for `VolatileTtl`. Volatile policies only sample keys with a
+    /// TTL. When that finds nothing, `oom_action` decides between refusing
+    /// and evicting any key.
+    pub fn make_room(&self, store: &ConcurrentStore, incoming: usize) -> bool {
+        if self.max_memory == 0 {
+            return true;
+        }
+        while store.memory_used() + incoming > self.max_memory {
+            let victim = self.sample_victim(store).or_else(|| match self.oom_action {
+                OomAction::Evict => store.random_key(),
+                OomAction::Reject => None,
+            });
+            match victim {
+                Some(key) => {
+                    store.del(&key);
+                }
+                None => return false,
+            }
+        }
+        true
+    }
+
+    fn sample_victim(&self, store: &ConcurrentStore) -> Option<Bytes> {
+        let volatile = self.policy.is_volatile();
+        // Sampling reads metadata only, so it doesn't count as an access
+        let mut sampled = (0..self.sample_size.max(1))
+            .filter_map(|_| store.random_key())
+            .filter(|key| !volatile || matches!(store.pttl(key), Some(Some(_))));
+        match self.policy {
+            EvictionPolicy::None => None,
+            EvictionPolicy::Random | EvictionPolicy::VolatileRandom => sampled.next(),
+            EvictionPolicy::Lru | EvictionPolicy::VolatileLru => sampled
+                .filter_map(|key| Some((store.idle_time(&key)?, key)))
+                .max_by_key(|(idle, _)| *idle)
+                .map(|(_, key)| key),
+            EvictionPolicy::Lfu | EvictionPolicy::VolatileLfu => sampled
+                .filter_map(|key| Some((store.access_freq(&key)?, key)))
+                .min_by_key(|(freq, _)| *freq)
+                .map(|(_, key)| key),
+            EvictionPolicy::VolatileTtl => sampled
+                .filter_map(|key| Some((store.pttl(&key)??, key)))
+                .min_by_key(|(remaining, _)| *remaining)
+                .map(|(_, key)| key),
+        }
+    }
 }
 
 /// Entry metadata for eviction tracking
@@ -78,6 +176,8 @@ pub struct EvictionMeta {
     pub access_count: u64,
     /// Approximate size in bytes
     pub size: usize,
+    /// When the key expires (None = persistent)
+    pub expires_at: Option<Instant>,
 }
 
 impl EvictionMeta {
@@ -86,12 +186,23 @@ impl EvictionMeta {
             last_access: Instant::now(),
             access_count: 1,
             size,
+            expires_at: None,
         }
     }
 
-    pub fn touch(&mut self) {
+    pub fn touch(&mut self, decay_interval: Duration) {
+        self.access_count = self.frequency(decay_interval).saturating_add(1);
         self.last_access = Instant::now();
-        self.access_count = self.access_count.saturating_add(1);
+    }
+
+    /// Access count halved once per `decay_interval` since the last access,
+    /// so keys that were hot long ago fall behind currently hot ones
+    pub fn frequency(&self, decay_interval: Duration) -> u64 {
+        if decay_interval.is_zero() {
+            return self.access_count;
+        }
+        let periods = self.last_access.elapsed().as_nanos() / decay_interval.as_nanos();
+        self.access_count.checked_shr(periods.min(64) as u32).unwrap_or(0)
     }
 }
 
@@ -103,6 +214,8 @@ pub struct LruManager {
     meta: DashMap<Bytes, EvictionMeta>,
     /// Current memory usage
     memory_used: AtomicUsize,
+    /// Store whose own accounting replaces the per-key sizes passed to `touch`
+    store: Option<ConcurrentStore>,
     /// Configuration
     config: EvictionConfig,
 }
@@ -113,15 +226,23 @@ impl LruManager {
             order: RwLock::new(VecDeque::new()),
             meta: DashMap::new(),
             memory_used: AtomicUsize::new(0),
+            store: None,
             config,
         }
     }
 
+    /// Check `max_memory` against the store's `memory_used` rather than
+    /// the sizes passed to `touch`
+    pub fn with_store(mut self, store: ConcurrentStore) -> Self {
+        self.store = Some(store);
+        self
+    }
+
     /// Record a key access (for LRU ordering)
     pub fn touch(&self, key: &Bytes, size: usize) {
         // Update metadata
         if let Some(mut entry) = self.meta.get_mut(key) {
-            entry.touch();
+            entry.touch(self.config.lfu_decay_interval);
         } else {
             self.meta.insert(key.clone(), EvictionMeta::new(size));
             self.memory_used.fetch_add(size, Ordering::Relaxed);
@@ -135,6 +256,14 @@ impl LruManager {
         order.push_back(key.clone());
     }
 
+    /// Record a key's expiry, for the volatile policies (None = persistent).
+    /// Not needed when a store is attached; its TTLs are used instead.
+    pub fn set_expiry(&self, key: &Bytes, expires_at: Option<Instant>) {
+        if let Some(mut entry) = self.meta.get_mut(key) {
+            entry.expires_at = expires_at;
+        }
+    }
+
     /// Record key removal
     pub fn remove(&self, key: &Bytes) {
         if let Some((_, meta)) = self.meta.remove(key) {
@@ -144,48 +273,98 @@ impl LruManager {
         order.retain(|k| k != key);
     }
 
+    /// Time since `key` was last touched
+    pub fn idle_time(&self, key: &Bytes) -> Option<Duration> {
+        self.meta.get(key).map(|meta| meta.last_access.elapsed())
+    }
+
+    /// `key`'s decayed access count, as ranked by the LFU policies
+    pub fn access_freq(&self, key: &Bytes) -> Option<u64> {
+        self.meta.get(key).map(|meta| meta.frequency(self.config.lfu_decay_interval))
+    }
+
     /// Check if eviction is needed
     pub fn needs_eviction(&self) -> bool {
         let key_count = self.meta.len();
-        let memory = self.memory_used.load(Ordering::Relaxed);
+        let memory = self.memory_used();
 
         (self.config.max_keys > 0 && key_count >= self.config.max_keys)
             || (self.config.max_memory > 0 && memory >= self.config.max_memory)
     }
 
     /// Get keys to evict (returns up to `count` keys)
+    /// Volatile policies only ever return keys with a TTL.
     pub fn get_eviction_candidates(&self, count: usize) -> Vec<Bytes> {
+        let volatile = self.config.policy.is_volatile();
         match self.config.policy {
             EvictionPolicy::None => Vec::new(),
-            EvictionPolicy::Lru => self.get_lru_candidates(count),
-            EvictionPolicy::Lfu => self.get_lfu_candidates(count),
-            EvictionPolicy::Random => self.get_random_candidates(count),
+            EvictionPolicy::Lru | EvictionPolicy::VolatileLru => self.get_lru_candidates(count, volatile),
+            EvictionPolicy::Lfu | EvictionPolicy::VolatileLfu => self.get_lfu_candidates(count, volatile),
+            EvictionPolicy::Random | EvictionPolicy::VolatileRandom => {
+                self.get_random_candidates(count, volatile)
+            }
+            EvictionPolicy::VolatileTtl => self.get_ttl_candidates(count),
         }
     }
 
-    fn get_lru_candidates(&self, count: usize) -> Vec<Bytes> {
+    /// When `key` expires, from the attached store if any
+    fn expiry(&self, key: &Bytes, meta: &EvictionMeta) -> Option<Instant> {
+        match &self.store {
+            Some(store) => store.get_with_ttl(key).and_then(|(_, expires_at)| expires_at),
+            None => meta.expires_at,
+        }
+    }
+
+    fn has_expiry(&self, key: &Bytes) -> bool {
+        self.meta.get(key).is_some_and(|meta| self.expiry(key, &meta).is_some())
+    }
+
+    fn get_lru_candidates(&self, count: usize, volatile: bool) -> Vec<Bytes> {
         let order = self.order.read().unwrap();
-        order.iter().take(count).cloned().collect()
+        order
+            .iter()
+            .filter(|k| !volatile || self.has_expiry(k))
+            .take(count)
+            .cloned()
+            .collect()
     }
 
-    fn get_lfu_candidates(&self, count: usize) -> Vec<Bytes> {
+    fn get_lfu_candidates(&self, count: usize, volatile: bool) -> Vec<Bytes> {
         let mut entries: Vec<_> = self
             .meta
             .iter()
-            .map(|r| (r.key().clone(), r.value().access_count))
+            .filter(|r| !volatile || self.expiry(r.key(), r.value()).is_some())
+            .map(|r| (r.key().clone(), r.value().frequency(self.config.lfu_decay_interval)))
             .collect();
         entries.sort_by_key(|(_, count)| *count);
         entries.into_iter().take(count).map(|(k, _)| k).collect()
     }
 
-    fn get_random_candidates(&self, count: usize) -> Vec<Bytes> {
-        let keys: Vec<_> = self.meta.iter().take(count).map(|r| r.key().clone()).collect();
-        keys
+    fn get_random_candidates(&self, count: usize, volatile: bool) -> Vec<Bytes> {
+        self.meta
+            .iter()
+            .filter(|r| !volatile || self.expiry(r.key(), r.value()).is_some())
+            .take(count)
+            .map(|r| r.key().clone())
+            .collect()
+    }
+
+    fn get_ttl_candidates(&self, count: usize) -> Vec<Bytes> {
+        let mut entries: Vec<_> = self
+            .meta
+            .iter()
+            .filter_map(|r| Some((r.key().clone(), self.expiry(r.key(), r.value())?)))
+            .collect();
+        entries.sort_by_key(|(_, expires_at)| *expires_at);
+        entries.into_iter().take(count).map(|(k, _)| k).collect()
     }
 
     /// Get current memory usage
     pub fn memory_used(&self) -> usize {
-        self.memory_used.load(Ordering::Relaxed)
+        match &self.store {
+            Some(store) => store.memory_used(),
+            None => self.memory_used.load(Ordering::Relaxed),
+        }
     }
 
     /// Get number of tracked keys
@@ -218,6 +397,20 @@ mod tests {
         assert_eq!(candidates[0].as_ref(), b"b");
     }
 
+    #[test]
+    fn test_idle_time_and_access_freq() {
+        let manager = LruManager::new(EvictionConfig::default().with_lfu_decay_interval(Duration::ZERO));
+        let key = Bytes::from_static(b"a");
+        assert_eq!(manager.idle_time(&key), None);
+
+        manager.touch(&key, 10);
+        manager.touch(&key, 10);
+        std::thread::sleep(Duration::from_millis(50));
+        let idle = manager.idle_time(&key).unwrap();
+        assert!(idle >= Duration::from_millis(50) && idle < Duration::from_millis(500), "{:?}", idle);
+        assert_eq!(manager.access_freq(&key), Some(2));
+    }
+
     #[test]
     fn test_needs_eviction() {
         let config = EvictionConfig::default()
@@ -231,4 +424,102 @@ mod tests {
         manager.touch(&Bytes::from_static(b"b"), 10);
         assert!(manager.needs_eviction());
     }
+
+    #[test]
+    fn test_make_room_ranks_samples_by_policy() {
+        for policy in [EvictionPolicy::Lru, EvictionPolicy::Lfu, EvictionPolicy::VolatileLru, EvictionPolicy::VolatileLfu] {
+            let store = ConcurrentStore::new();
+            for key in [&b"a"[..], b"b", b"c", b"d"] {
+                // "d" has no TTL, so only the plain policies could pick it
+                let ttl = (key != b"d").then_some(60);
+                store.set(Bytes::from_static(key), Bytes::from_static(b"v"), ttl);
+            }
+            std::thread::sleep(Duration::from_millis(5));
+            for key in [&b"a"[..], b"c", b"d"] {
+                for _ in 0..3 {
+                    store.get(&Bytes::from_static(key));
+                }
+            }
+
+            // Enough samples that every key is seen
+            let mut config = EvictionConfig::default().with_policy(policy).with_max_memory(store.memory_used());
+            config.sample_size = 64;
+            assert!(config.make_room(&store, 1), "{:?}", policy);
+            assert_eq!(store.len(), 3, "{:?}", policy);
+            assert!(!store.exists(&Bytes::from_static(b"b")), "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn test_make_room_with_lazy_free_evicts_only_what_it_needs() {
+        let store = ConcurrentStore::new().with_lazy_free(true);
+        let value = Bytes::from(vec![0u8; 256 * 1024]);
+        for i in 0..10 {
+            store.set(Bytes::from(format!("k{}", i)), value.clone(), None);
+        }
+
+        // Lazily freed bytes stop counting as soon as the key is gone, so
+        // one value's worth of room costs one key, not the whole database
+        let config = EvictionConfig::default()
+            .with_policy(EvictionPolicy::Random)
+            .with_max_memory(store.memory_used());
+        assert!(config.make_room(&store, store.memory_used() / 10));
+        assert_eq!(store.len(), 9);
+    }
+
+    #[test]
+    fn test_volatile_policies_skip_persistent_keys() {
+        let store = ConcurrentStore::new();
+        let now = Instant::now();
+        for (key, ttl) in [("persistent", None), ("later", Some(60)), ("sooner", Some(5))] {
+            store.set(Bytes::from(key), Bytes::from_static(b"v"), ttl);
+        }
+
+        for policy in [
+            EvictionPolicy::VolatileLru,
+            EvictionPolicy::VolatileLfu,
+            EvictionPolicy::VolatileRandom,
+            EvictionPolicy::VolatileTtl,
+        ] {
+            // Tracked both through an attached store and through set_expiry
+            let tracked = LruManager::new(EvictionConfig::default().with_policy(policy));
+            let attached = LruManager::new(EvictionConfig::default().with_policy(policy)).with_store(store.clone());
+            for manager in [&tracked, &attached] {
+                for key in ["persistent", "later", "sooner"] {
+                    manager.touch(&Bytes::from(key), 10);
+                }
+            }
+            tracked.set_expiry(&Bytes::from("later"), Some(now + Duration::from_secs(60)));
+            tracked.set_expiry(&Bytes::from("sooner"), Some(now + Duration::from_secs(5)));
+
+            for manager in [&tracked, &attached] {
+                let candidates = manager.get_eviction_candidates(3);
+                assert_eq!(candidates.len(), 2, "{:?}", policy);
+                assert!(!candidates.contains(&Bytes::from("persistent")), "{:?}", policy);
+                if policy == EvictionPolicy::VolatileTtl {
+                    assert_eq!(candidates[0], Bytes::from("sooner"));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_lfu_counts_decay() {
+        let config = EvictionConfig::default()
+            .with_policy(EvictionPolicy::Lfu)
+            .with_lfu_decay_interval(Duration::from_millis(100));
+        let manager = LruManager::new(config);
+        let (old, hot) = (Bytes::from_static(b"old"), Bytes::from_static(b"hot"));
+
+        for _ in 0..100 {
+            manager.touch(&old, 10);
+        }
+        // At least 4 idle intervals: 100 accesses count for at most 6
+        std::thread::sleep(Duration::from_millis(450));
+        for _ in 0..20 {
+            manager.touch(&hot, 10);
+        }
+
+        assert_eq!(manager.get_eviction_candidates(1), vec![old]);
+    }
 }
@@ -4,12 +4,18 @@
 
 mod concurrent_store;
 mod concurrent_ttl;
+mod databases;
 mod eviction;
+mod reclaim;
 mod store;
 mod ttl;
 
-pub use concurrent_store::ConcurrentStore;
-pub use concurrent_ttl::ConcurrentTtlCleaner;
-pub use eviction::{EvictionConfig, EvictionPolicy, LruManager};
+pub use concurrent_store::{
+    ConcurrentStore, Lookup, SetCondition, ShardDistribution, ShardStat, ValueType, ENTRY_OVERHEAD, LAZY_FREE_MIN_SIZE,
+    SCAN_TIME_BUDGET,
+};
+pub use concurrent_ttl::{AdaptiveExpiry, ConcurrentTtlCleaner, ExpiredCallback};
+pub use databases::{Databases, DEFAULT_DATABASES};
+pub use eviction::{EvictionConfig, EvictionPolicy, LruManager, OomAction, OOM_ERROR};
 pub use store::Store;
 pub use ttl::TtlCleaner;
@@ -0,0 +1,47 @@
+//! Background Reclaimer
+//!
+//! Frees deleted values off the caller's thread, for UNLINK and lazy-free
+//! deletes. Values are queued to a single process-wide thread, started on
+//! first use, which drops them and only then takes their bytes off the
+//! owning store's reclaim backlog.
+
+use bytes::Bytes;
+use crossbeam::channel::{self, Sender};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+
+/// A deleted value waiting to be freed
+struct Garbage {
+    value: Bytes,
+    /// Bytes to take off `backlog` once `value` is dropped
+    size: usize,
+    backlog: Arc<AtomicUsize>,
+}
+
+static RECLAIMER: OnceLock<Sender<Garbage>> = OnceLock::new();
+
+fn reclaimer() -> &'static Sender<Garbage> {
+    RECLAIMER.get_or_init(|| {
+        let (tx, rx) = channel::unbounded::<Garbage>();
+        thread::Builder::new()
+            .name("celrix-reclaim".to_string())
+            .spawn(move || {
+                for garbage in rx {
+                    drop(garbage.value);
+                    garbage.backlog.fetch_sub(garbage.size, Ordering::Relaxed);
+                }
+            })
+            .expect("Failed to spawn reclaim thread");
+        tx
+    })
+}
+
+/// Free `value` in the background, counting `size` bytes in `backlog`
+/// until it is dropped
+pub(crate) fn defer_free(value: Bytes, size: usize, backlog: Arc<AtomicUsize>) {
+    backlog.fetch_add(size, Ordering::Relaxed);
+    if let Err(channel::SendError(garbage)) = reclaimer().send(Garbage { value, size, backlog }) {
+        garbage.backlog.fetch_sub(garbage.size, Ordering::Relaxed);
+    }
+}
@@ -4,6 +4,8 @@
 
 use bytes::Bytes;
 use hashbrown::HashMap;
+
+use super::{Lookup, SetCondition};
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 
@@ -47,6 +49,17 @@ impl Store {
         }
     }
 
+    /// Read a key, telling an expired key that is still stored apart from
+    /// one that isn't there at all
+    pub fn lookup(&self, key: &Bytes) -> Lookup {
+        let map = self.inner.read().unwrap();
+        match map.get(key) {
+            None => Lookup::Missing,
+            Some(entry) if entry.is_expired() => Lookup::Expired,
+            Some(entry) => Lookup::Live(entry.value.clone()),
+        }
+    }
+
     /// Get value by key, returns None if key doesn't exist or is expired
     pub fn get(&self, key: &Bytes) -> Option<Bytes> {
         let map = self.inner.read().unwrap();
@@ -67,12 +80,171 @@ impl Store {
         map.insert(key, entry);
     }
 
+    /// Conditionally set a key, returning whether it was written and the
+    /// previous live value
+    pub fn set_with(
+        &self,
+        key: Bytes,
+        value: Bytes,
+        ttl: Option<Duration>,
+        condition: SetCondition,
+    ) -> (bool, Option<Bytes>) {
+        let mut map = self.inner.write().unwrap();
+        let old = map
+            .get(&key)
+            .filter(|e| !e.is_expired())
+            .map(|e| e.value.clone());
+        let allowed = match condition {
+            SetCondition::Always => true,
+            SetCondition::IfAbsent => old.is_none(),
+            SetCondition::IfPresent => old.is_some(),
+        };
+        if allowed {
+            map.insert(key, Entry::new(value, ttl));
+        }
+        (allowed, old)
+    }
+
+    /// Remove a key and return its live value
+    pub fn get_del(&self, key: &Bytes) -> Option<Bytes> {
+        let mut map = self.inner.write().unwrap();
+        map.remove(key).filter(|e| !e.is_expired()).map(|e| e.value)
+    }
+
+    /// Replace a key's value and return the previous live value
+    pub fn get_set(&self, key: Bytes, value: Bytes) -> Option<Bytes> {
+        let mut map = self.inner.write().unwrap();
+        map.insert(key, Entry::new(value, None))
+            .filter(|e| !e.is_expired())
+            .map(|e| e.value)
+    }
+
+    /// Append to a key's value, creating it if missing; keeps the TTL and
+    /// returns the new length
+    pub fn append(&self, key: Bytes, suffix: &[u8]) -> usize {
+        let mut map = self.inner.write().unwrap();
+        match map.get_mut(&key).filter(|e| !e.is_expired()) {
+            Some(entry) => {
+                let mut value = entry.value.to_vec();
+                value.extend_from_slice(suffix);
+                entry.value = Bytes::from(value);
+                entry.value.len()
+            }
+            None => {
+                map.insert(key, Entry::new(Bytes::copy_from_slice(suffix), None));
+                suffix.len()
+            }
+        }
+    }
+
+    /// Length of a key's value, 0 if missing or expired
+    pub fn strlen(&self, key: &Bytes) -> usize {
+        let map = self.inner.read().unwrap();
+        map.get(key).filter(|e| !e.is_expired()).map_or(0, |e| e.value.len())
+    }
+
+    /// Exchange the values and TTLs of two keys (a move if one is missing)
+    pub fn swap(&self, key1: &Bytes, key2: &Bytes) {
+        let mut map = self.inner.write().unwrap();
+        let old1 = map.remove(key1).filter(|e| !e.is_expired());
+        let old2 = map.remove(key2).filter(|e| !e.is_expired());
+        if let Some(entry) = old2 {
+            map.insert(key1.clone(), entry);
+        }
+        if let Some(entry) = old1 {
+            map.insert(key2.clone(), entry);
+        }
+    }
+
+    /// Move a key's value and TTL to `dst`, replacing it; false if `src` is missing
+    pub fn rename(&self, src: &Bytes, dst: &Bytes) -> bool {
+        let mut map = self.inner.write().unwrap();
+        match map.remove(src).filter(|e| !e.is_expired()) {
+            Some(entry) => {
+                map.insert(dst.clone(), entry);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Copy a key's value and TTL to `dst`; false if `src` is missing or
+    /// `dst` exists and `replace` is false
+    pub fn copy(&self, src: &Bytes, dst: &Bytes, replace: bool) -> bool {
+        let mut map = self.inner.write().unwrap();
+        let Some(entry) = map.get(src).filter(|e| !e.is_expired()).cloned() else {
+            return false;
+        };
+        let dst_live = map.get(dst).is_some_and(|e| !e.is_expired());
+        if src == dst || (dst_live && !replace) {
+            return false;
+        }
+        map.insert(dst.clone(), entry);
+        true
+    }
+
+    /// A randomly chosen live key, None if there are none
+    pub fn random_key(&self) -> Option<Bytes> {
+        let map = self.inner.read().unwrap();
+        let live = map.values().filter(|e| !e.is_expired()).count();
+        if live == 0 {
+            return None;
+        }
+        let index = (super::concurrent_store::random_u64() % live as u64) as usize;
+        map.iter().filter(|(_, e)| !e.is_expired()).nth(index).map(|(k, _)| k.clone())
+    }
+
     /// Delete key, returns true if key existed
     pub fn del(&self, key: &Bytes) -> bool {
         let mut map = self.inner.write().unwrap();
         map.remove(key).is_some()
     }
 
+    /// Delete every key matching a glob pattern, returning how many live
+    /// keys were removed
+    pub fn del_matching(&self, pattern: &str) -> usize {
+        let mut map = self.inner.write().unwrap();
+        let mut removed = 0;
+        map.retain(|key, entry| {
+            if !crate::security::acl::glob_match(pattern, key) {
+                return true;
+            }
+            if !entry.is_expired() {
+                removed += 1;
+            }
+            false
+        });
+        removed
+    }
+
+    /// Set a live key's TTL, returns false if the key doesn't exist
+    pub fn pexpire(&self, key: &Bytes, ttl: Duration) -> bool {
+        let mut map = self.inner.write().unwrap();
+        match map.get_mut(key) {
+            Some(entry) if !entry.is_expired() => {
+                entry.expires_at = Some(Instant::now() + ttl);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Get a live value along with its expiry deadline, if any
+    pub fn get_with_ttl(&self, key: &Bytes) -> Option<(Bytes, Option<Instant>)> {
+        let map = self.inner.read().unwrap();
+        map.get(key).filter(|e| !e.is_expired()).map(|e| (e.value.clone(), e.expires_at))
+    }
+
+    /// Remaining TTL: None if the key is missing, Some(None) if it never expires
+    pub fn pttl(&self, key: &Bytes) -> Option<Option<Duration>> {
+        let map = self.inner.read().unwrap();
+        let entry = map.get(key)?;
+        match entry.expires_at {
+            None => Some(None),
+            Some(at) => at.checked_duration_since(Instant::now()).map(Some),
+        }
+    }
+
     /// Check if key exists and is not expired
     pub fn exists(&self, key: &Bytes) -> bool {
         let map = self.inner.read().unwrap();
@@ -84,11 +256,24 @@ impl Store {
         self.inner.read().unwrap().len()
     }
 
+    /// Number of live keys, leaving out expired ones not yet removed;
+    /// reads every entry, unlike `len`
+    pub fn live_len(&self) -> usize {
+        let now = Instant::now();
+        let map = self.inner.read().unwrap();
+        map.values().filter(|entry| entry.expires_at.is_none_or(|at| at >= now)).count()
+    }
+
     /// Check if store is empty
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
+    /// Remove all keys
+    pub fn clear(&self) {
+        self.inner.write().unwrap().clear();
+    }
+
     /// Remove expired keys, returns count of removed keys
     pub fn cleanup_expired(&self) -> usize {
         let mut map = self.inner.write().unwrap();
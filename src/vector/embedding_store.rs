@@ -3,15 +3,21 @@
 //! Storage for vector embeddings keyed by cache keys.
 
 use bytes::Bytes;
-use dashmap::DashMap;
+use dashmap::{DashMap, SharedValue};
 use std::sync::Arc;
 use std::time::Instant;
 
+use super::quantize::QuantizedVector;
+use super::similarity::{best_available, DistanceMetric, SimdOps};
+
 /// An embedding entry with metadata
 #[derive(Debug, Clone)]
 pub struct EmbeddingEntry {
-    /// The embedding vector
+    /// The embedding vector; empty once a quantizing store has taken it,
+    /// see `vector()`
     pub embedding: Vec<f32>,
+    /// int8 form, set instead of `embedding` by a quantizing store
+    pub quantized: Option<QuantizedVector>,
     /// Associated cached value (optional)
     pub value: Option<Bytes>,
     /// Metadata/context
@@ -29,6 +35,7 @@ impl EmbeddingEntry {
             embedding,
             value: None,
             metadata: None,
+            quantized: None,
             created_at: now,
             last_accessed: now,
         }
@@ -51,7 +58,23 @@ impl EmbeddingEntry {
 
     /// Get embedding dimension
     pub fn dim(&self) -> usize {
-        self.embedding.len()
+        self.quantized.as_ref().map_or(self.embedding.len(), |q| q.len())
+    }
+
+    /// The embedding as f32, dequantized if stored as int8
+    pub fn vector(&self) -> Vec<f32> {
+        match &self.quantized {
+            Some(quantized) => quantized.dequantize(),
+            None => self.embedding.clone(),
+        }
+    }
+
+    /// Replace the f32 embedding with its int8 form
+    fn quantize(&mut self) {
+        if self.quantized.is_none() {
+            self.quantized = Some(QuantizedVector::new(&self.embedding));
+            self.embedding = Vec::new();
+        }
     }
 }
 
@@ -62,6 +85,12 @@ pub struct EmbeddingStore {
     embeddings: Arc<DashMap<Bytes, EmbeddingEntry>>,
     /// Expected embedding dimension
     dimension: usize,
+    /// Metric used by nearest-neighbour search
+    metric: DistanceMetric,
+    /// Store vectors int8-quantized instead of f32
+    quantize: bool,
+    /// Kernels used to score f32 vectors
+    ops: Arc<dyn SimdOps>,
 }
 
 impl EmbeddingStore {
@@ -70,9 +99,47 @@ impl EmbeddingStore {
         Self {
             embeddings: Arc::new(DashMap::new()),
             dimension,
+            metric: DistanceMetric::default(),
+            quantize: false,
+            ops: best_available(),
         }
     }
 
+    /// Store vectors int8-quantized, trading a little search accuracy for
+    /// ~4x less memory per vector (see the `quantize` module)
+    pub fn with_quantization(mut self) -> Self {
+        self.quantize = true;
+        self
+    }
+
+    /// Whether vectors are stored quantized
+    pub fn is_quantized(&self) -> bool {
+        self.quantize
+    }
+
+    /// Use `metric` for nearest-neighbour search
+    pub fn with_metric(mut self, metric: DistanceMetric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    /// Get the search metric
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    /// Score with `ops` instead of the fastest kernels this CPU supports,
+    /// e.g. `Scalar` for reproducible results across machines
+    pub fn with_simd_ops(mut self, ops: Arc<dyn SimdOps>) -> Self {
+        self.ops = ops;
+        self
+    }
+
+    /// Name of the kernels used for scoring
+    pub fn simd_ops(&self) -> &'static str {
+        self.ops.name()
+    }
+
     /// Get embedding dimension
     pub fn dimension(&self) -> usize {
         self.dimension
@@ -88,10 +155,47 @@ impl EmbeddingStore {
             ));
         }
         entry.touch();
+        if self.quantize {
+            entry.quantize();
+        }
         self.embeddings.insert(key, entry);
         Ok(())
     }
 
+    /// Store many embeddings, returning the indices of entries rejected for
+    /// a dimension mismatch. Entries are grouped by shard so each shard's
+    /// write lock is taken once per batch rather than once per entry.
+    pub fn set_many(&self, entries: Vec<(Bytes, EmbeddingEntry)>) -> Vec<usize> {
+        let mut failed = Vec::new();
+        let mut by_shard: Vec<Vec<(usize, Bytes, EmbeddingEntry)>> =
+            (0..self.embeddings.shards().len()).map(|_| Vec::new()).collect();
+        for (i, (key, mut entry)) in entries.into_iter().enumerate() {
+            if entry.dim() != self.dimension {
+                failed.push(i);
+                continue;
+            }
+            entry.touch();
+            if self.quantize {
+                entry.quantize();
+            }
+            let hash = self.embeddings.hash_usize(&key);
+            by_shard[self.embeddings.determine_shard(hash)].push((hash, key, entry));
+        }
+
+        let rehash = |(k, _): &(Bytes, _)| self.embeddings.hash_usize(k) as u64;
+        for (shard, batch) in self.embeddings.shards().iter().zip(by_shard) {
+            if batch.is_empty() {
+                continue;
+            }
+            let mut table = shard.write();
+            for (hash, key, entry) in batch {
+                table.remove_entry(hash as u64, |(k, _)| *k == key);
+                table.insert(hash as u64, (key, SharedValue::new(entry)), rehash);
+            }
+        }
+        failed
+    }
+
     /// Get an embedding
     pub fn get(&self, key: &Bytes) -> Option<EmbeddingEntry> {
         self.embeddings.get(key).map(|e| {
@@ -103,7 +207,7 @@ impl EmbeddingStore {
 
     /// Get just the vector
     pub fn get_vector(&self, key: &Bytes) -> Option<Vec<f32>> {
-        self.embeddings.get(key).map(|e| e.embedding.clone())
+        self.embeddings.get(key).map(|e| e.vector())
     }
 
     /// Delete an embedding
@@ -116,6 +220,11 @@ impl EmbeddingStore {
         self.embeddings.contains_key(key)
     }
 
+    /// Remove every embedding
+    pub fn clear(&self) {
+        self.embeddings.clear();
+    }
+
     /// Get number of stored embeddings
     pub fn len(&self) -> usize {
         self.embeddings.len()
@@ -126,16 +235,40 @@ impl EmbeddingStore {
         self.embeddings.is_empty()
     }
 
-    /// Find K nearest neighbors to query embedding
+    /// Find K nearest neighbors to query embedding, closest first. Scores and
+    /// `threshold` are in the store's metric: for Euclidean the threshold is
+    /// a maximum distance, otherwise a minimum score
     pub fn find_nearest(&self, query: &[f32], k: usize, threshold: f32) -> Vec<(Bytes, f32)> {
-        use super::similarity::cosine_similarity;
+        self.find_nearest_filtered(query, k, threshold, |_| true)
+    }
+
+    /// Find K nearest neighbors among entries accepted by `filter`; the filter
+    /// runs during the scan, so up to K eligible results are still returned.
+    /// A query of the wrong dimension matches nothing.
+    pub fn find_nearest_filtered(
+        &self,
+        query: &[f32],
+        k: usize,
+        threshold: f32,
+        filter: impl Fn(&EmbeddingEntry) -> bool,
+    ) -> Vec<(Bytes, f32)> {
+        if query.len() != self.dimension {
+            return Vec::new();
+        }
+        // Query-side terms for scoring quantized entries
+        let query_sum: f32 = query.iter().sum();
+        let query_norm_sq: f32 = query.iter().map(|x| x * x).sum();
 
         let mut results: Vec<(Bytes, f32)> = self
             .embeddings
             .iter()
+            .filter(|entry| filter(entry.value()))
             .filter_map(|entry| {
-                let sim = cosine_similarity(query, &entry.embedding);
-                if sim >= threshold {
+                let sim = match &entry.quantized {
+                    Some(q) => self.metric.score_from_dot(q.dot(query, query_sum), query_norm_sq, q.norm_sq()),
+                    None => self.metric.score_with(self.ops.as_ref(), query, &entry.embedding),
+                };
+                if self.metric.within(sim, threshold) {
                     Some((entry.key().clone(), sim))
                 } else {
                     None
@@ -143,8 +276,8 @@ impl EmbeddingStore {
             })
             .collect();
 
-        // Sort by similarity (descending)
-        results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        // Sort closest first
+        results.sort_by(|a, b| self.metric.compare(a.1, b.1));
 
         // Take top K
         results.truncate(k);
@@ -160,6 +293,7 @@ impl EmbeddingStore {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vector::{random_vector, Scalar};
 
     #[test]
     fn test_embedding_store() {
@@ -184,6 +318,12 @@ mod tests {
 
         let result = store.set(Bytes::from_static(b"key1"), entry);
         assert!(result.is_err());
+
+        // Queries of the wrong length match nothing rather than being scored
+        store.set(Bytes::from_static(b"key1"), EmbeddingEntry::new(vec![1.0; 4])).unwrap();
+        assert_eq!(store.find_nearest(&[1.0; 4], 1, 0.0).len(), 1);
+        assert!(store.find_nearest(&[1.0; 16], 1, 0.0).is_empty());
+        assert!(store.find_nearest(&[1.0; 2], 1, 0.0).is_empty());
     }
 
     #[test]
@@ -215,4 +355,93 @@ mod tests {
         assert_eq!(results.len(), 2);
         assert_eq!(results[0].0.as_ref(), b"a"); // Most similar
     }
+
+    #[test]
+    fn test_quantized_store() {
+        let store = EmbeddingStore::new(3).with_quantization();
+        store.set(Bytes::from_static(b"a"), EmbeddingEntry::new(vec![1.0, 0.0, 0.0])).unwrap();
+        store.set(Bytes::from_static(b"b"), EmbeddingEntry::new(vec![0.6, 0.8, 0.0])).unwrap();
+
+        let entry = store.get(&Bytes::from_static(b"a")).unwrap();
+        assert!(entry.embedding.is_empty());
+        assert_eq!(entry.dim(), 3);
+
+        let results = store.find_nearest(&[0.9, 0.1, 0.0], 2, 0.5);
+        assert_eq!(results[0].0.as_ref(), b"a");
+        assert!((results[0].1 - 0.9939).abs() < 0.01);
+        assert!(store.set(Bytes::from_static(b"c"), EmbeddingEntry::new(vec![1.0])).is_err());
+    }
+
+    #[test]
+    fn test_set_many() {
+        let store = EmbeddingStore::new(2);
+        store.set(Bytes::from_static(b"k0"), EmbeddingEntry::new(vec![0.0, 0.0])).unwrap();
+
+        let mut entries: Vec<_> = (0..100)
+            .map(|i| (Bytes::from(format!("k{}", i)), EmbeddingEntry::new(vec![i as f32, 1.0])))
+            .collect();
+        entries[7].1 = EmbeddingEntry::new(vec![1.0]);
+        assert_eq!(store.set_many(entries), vec![7]);
+
+        assert_eq!(store.len(), 99);
+        // Existing keys are overwritten
+        assert_eq!(store.get_vector(&Bytes::from_static(b"k0")), Some(vec![0.0, 1.0]));
+        assert!(!store.exists(&Bytes::from_static(b"k7")));
+        assert_eq!(store.get_vector(&Bytes::from_static(b"k99")), Some(vec![99.0, 1.0]));
+    }
+
+    #[test]
+    fn test_find_nearest_per_metric() {
+        let entries: [(&'static [u8], [f32; 2]); 3] = [
+            (b"small", [0.5, 0.0]),
+            (b"large", [4.0, 1.0]),
+            (b"away", [0.0, 1.0]),
+        ];
+        let ranked = |metric, threshold| {
+            let store = EmbeddingStore::new(2).with_metric(metric);
+            for (key, v) in entries {
+                store.set(Bytes::from_static(key), EmbeddingEntry::new(v.to_vec())).unwrap();
+            }
+            store
+                .find_nearest(&[1.0, 0.0], 3, threshold)
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>()
+        };
+
+        // Direction only: "small" points exactly along the query
+        assert_eq!(ranked(DistanceMetric::Cosine, 0.5), vec!["small", "large"]);
+        // Magnitude counts: "large" has the biggest projection
+        assert_eq!(ranked(DistanceMetric::DotProduct, 0.1), vec!["large", "small"]);
+        // Smaller is closer, threshold is a max distance
+        assert_eq!(ranked(DistanceMetric::Euclidean, 1.5), vec!["small", "away"]);
+    }
+
+    #[test]
+    fn test_scalar_and_simd_stores_agree() {
+        let mut seed = 11u64;
+        let mut random = |dim| random_vector(&mut seed, dim);
+        let vectors: Vec<_> = (0..50).map(|_| random(67)).collect();
+        let query = random(67);
+
+        for metric in [DistanceMetric::Cosine, DistanceMetric::DotProduct, DistanceMetric::Euclidean] {
+            let scalar = EmbeddingStore::new(67).with_metric(metric).with_simd_ops(Arc::new(Scalar));
+            let best = EmbeddingStore::new(67).with_metric(metric);
+            assert_eq!(scalar.simd_ops(), "scalar");
+            for (i, v) in vectors.iter().enumerate() {
+                let key = Bytes::from(format!("v{}", i));
+                scalar.set(key.clone(), EmbeddingEntry::new(v.clone())).unwrap();
+                best.set(key, EmbeddingEntry::new(v.clone())).unwrap();
+            }
+
+            let threshold = if metric == DistanceMetric::Euclidean { f32::MAX } else { f32::MIN };
+            let expected = scalar.find_nearest(&query, 5, threshold);
+            let actual = best.find_nearest(&query, 5, threshold);
+            assert_eq!(expected.len(), 5);
+            for ((ek, es), (ak, as_)) in expected.iter().zip(&actual) {
+                assert_eq!(ek, ak, "{:?} via {}", metric, best.simd_ops());
+                assert!((es - as_).abs() <= 1e-4 * es.abs().max(1.0));
+            }
+        }
+    }
 }
@@ -5,7 +5,20 @@
 mod embedding_store;
 mod similarity;
 mod semantic;
+mod quantize;
 
 pub use embedding_store::{EmbeddingStore, EmbeddingEntry};
-pub use similarity::{cosine_similarity, dot_product, euclidean_distance, SimdOps};
-pub use semantic::{SemanticCache, SemanticResult};
+pub use similarity::{best_available, cosine_similarity, dot_product, DistanceMetric, euclidean_distance, Scalar, Simd, SimdOps};
+pub use quantize::QuantizedVector;
+pub use semantic::{SemanticCache, SemanticCacheConfig, SemanticResult};
+
+/// Deterministic vectors in [-1, 1] from a simple LCG, for tests
+#[cfg(test)]
+pub(crate) fn random_vector(seed: &mut u64, dim: usize) -> Vec<f32> {
+    (0..dim)
+        .map(|_| {
+            *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((*seed >> 40) as f32 / (1u64 << 24) as f32) * 2.0 - 1.0
+        })
+        .collect()
+}
@@ -0,0 +1,113 @@
+//! Scalar Quantization
+//!
+//! int8 storage for embeddings, cutting memory per vector by ~4x.
+//!
+//! Each vector is quantized independently with min/max scaling, so the
+//! per-component error is at most `(max - min) / 510`. For typical
+//! 384–1536 dimension embeddings cosine scores stay within ~0.005 of the
+//! exact f32 value; recall@k drops only where neighbours are separated by
+//! less than that, i.e. near-ties may swap order.
+
+/// An int8-quantized vector with its per-vector scale and offset
+#[derive(Debug, Clone)]
+pub struct QuantizedVector {
+    data: Vec<i8>,
+    /// Width of one quantization step
+    scale: f32,
+    /// Value of the lowest step (the original minimum)
+    offset: f32,
+    /// Squared norm of the dequantized vector, cached for scoring
+    norm_sq: f32,
+}
+
+impl QuantizedVector {
+    pub fn new(vector: &[f32]) -> Self {
+        let min = vector.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = vector.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let (offset, scale) = if vector.is_empty() { (0.0, 0.0) } else { (min, (max - min) / 255.0) };
+
+        let data = vector
+            .iter()
+            .map(|&x| {
+                let step = if scale > 0.0 { ((x - offset) / scale).round() } else { 0.0 };
+                (step.clamp(0.0, 255.0) - 128.0) as i8
+            })
+            .collect();
+        let mut quantized = Self { data, scale, offset, norm_sq: 0.0 };
+        quantized.norm_sq = quantized.dequantize().iter().map(|x| x * x).sum();
+        quantized
+    }
+
+    /// Number of components
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Squared norm of the dequantized vector
+    pub fn norm_sq(&self) -> f32 {
+        self.norm_sq
+    }
+
+    /// Reconstruct an approximate f32 vector
+    pub fn dequantize(&self) -> Vec<f32> {
+        self.data.iter().map(|&q| self.value(q)).collect()
+    }
+
+    #[inline]
+    fn value(&self, q: i8) -> f32 {
+        (q as f32 + 128.0) * self.scale + self.offset
+    }
+
+    /// Dot product with an f32 query without dequantizing; `query_sum` is
+    /// the sum of the query's components, shared across comparisons
+    pub fn dot(&self, query: &[f32], query_sum: f32) -> f32 {
+        debug_assert_eq!(query.len(), self.data.len(), "Vector dimensions must match");
+        // sum(a * (s * (q + 128) + m)) = s * sum(a * (q + 128)) + m * sum(a)
+        let steps: f32 = query
+            .iter()
+            .zip(&self.data)
+            .map(|(a, &q)| a * (q as f32 + 128.0))
+            .sum();
+        self.scale * steps + self.offset * query_sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::{cosine_similarity, random_vector};
+
+    #[test]
+    fn test_quantized_cosine_close_to_exact() {
+        let mut seed = 42;
+        for _ in 0..50 {
+            let a = random_vector(&mut seed, 384);
+            let b = random_vector(&mut seed, 384);
+            let quantized = QuantizedVector::new(&b);
+
+            let dot = quantized.dot(&a, a.iter().sum());
+            let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+            let approx = dot / (norm_a * quantized.norm_sq().sqrt());
+            let exact = cosine_similarity(&a, &b);
+            assert!((approx - exact).abs() < 0.01, "exact {} vs quantized {}", exact, approx);
+
+            let max_err = b
+                .iter()
+                .zip(quantized.dequantize())
+                .map(|(x, y)| (x - y).abs())
+                .fold(0.0, f32::max);
+            assert!(max_err <= 2.0 / 510.0 + 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_constant_vector() {
+        let quantized = QuantizedVector::new(&[0.5; 8]);
+        assert_eq!(quantized.dequantize(), vec![0.5; 8]);
+        assert!(QuantizedVector::new(&[]).is_empty());
+    }
+}
@@ -3,8 +3,11 @@
 //! Cache with semantic similarity lookup for AI/LLM responses.
 
 use bytes::Bytes;
+use std::borrow::Cow;
+use std::time::{Duration, Instant};
 
 use super::embedding_store::{EmbeddingEntry, EmbeddingStore};
+use super::similarity::{normalize_vector, DistanceMetric};
 
 /// Result of semantic cache lookup
 #[derive(Debug, Clone)]
@@ -13,29 +16,51 @@ pub struct SemanticResult {
     pub key: Bytes,
     /// The cached value
     pub value: Option<Bytes>,
-    /// Similarity score
+    /// Score under the cache's metric (a distance for Euclidean)
     pub similarity: f32,
     /// Original metadata
     pub metadata: Option<String>,
+    /// Entry is past its fresh window; serve it but refresh in the background
+    pub stale: bool,
 }
 
 /// Semantic cache configuration
 #[derive(Debug, Clone)]
 pub struct SemanticCacheConfig {
-    /// Minimum similarity threshold for cache hits
+    /// Threshold for cache hits: a minimum score, or a maximum distance
+    /// for `DistanceMetric::Euclidean`
     pub similarity_threshold: f32,
+    /// How embeddings are compared
+    pub metric: DistanceMetric,
     /// Maximum number of results to return
     pub max_results: usize,
     /// Embedding dimension
     pub dimension: usize,
+    /// Store embeddings int8-quantized instead of f32
+    pub quantize: bool,
+    /// L2-normalize embeddings on insert. Normalized vectors compared by
+    /// dot product score exactly their cosine similarity, so with
+    /// `DistanceMetric::Cosine` the search switches to the cheaper dot
+    /// product (normalizing the query once per lookup). Zero vectors are
+    /// rejected.
+    pub normalize_on_insert: bool,
+    /// How long entries are fresh (None = never go stale)
+    pub ttl: Option<Duration>,
+    /// How long after `ttl` entries are still served, flagged stale
+    pub stale_ttl: Duration,
 }
 
 impl Default for SemanticCacheConfig {
     fn default() -> Self {
         Self {
             similarity_threshold: 0.85,
+            metric: DistanceMetric::Cosine,
             max_results: 5,
             dimension: 1536, // OpenAI ada-002 dimension
+            quantize: false,
+            normalize_on_insert: false,
+            ttl: None,
+            stale_ttl: Duration::ZERO,
         }
     }
 }
@@ -46,6 +71,11 @@ impl SemanticCacheConfig {
         self
     }
 
+    pub fn with_metric(mut self, metric: DistanceMetric) -> Self {
+        self.metric = metric;
+        self
+    }
+
     pub fn with_dimension(mut self, dim: usize) -> Self {
         self.dimension = dim;
         self
@@ -55,6 +85,28 @@ impl SemanticCacheConfig {
         self.max_results = max;
         self
     }
+
+    /// Store embeddings int8-quantized to save memory
+    pub fn with_quantization(mut self) -> Self {
+        self.quantize = true;
+        self
+    }
+
+    /// L2-normalize embeddings when they're stored
+    pub fn with_normalize_on_insert(mut self) -> Self {
+        self.normalize_on_insert = true;
+        self
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn with_stale_ttl(mut self, stale_ttl: Duration) -> Self {
+        self.stale_ttl = stale_ttl;
+        self
+    }
 }
 
 /// Semantic cache for AI/LLM query caching
@@ -69,10 +121,16 @@ pub struct SemanticCache {
 impl SemanticCache {
     /// Create a new semantic cache
     pub fn new(config: SemanticCacheConfig) -> Self {
-        Self {
-            store: EmbeddingStore::new(config.dimension),
-            config,
+        let metric = match config.metric {
+            // Cosine over unit vectors is their dot product
+            DistanceMetric::Cosine if config.normalize_on_insert => DistanceMetric::DotProduct,
+            metric => metric,
+        };
+        let mut store = EmbeddingStore::new(config.dimension).with_metric(metric);
+        if config.quantize {
+            store = store.with_quantization();
         }
+        Self { store, config }
     }
 
     /// Create with default configuration
@@ -88,53 +146,152 @@ impl SemanticCache {
         value: Bytes,
         metadata: Option<String>,
     ) -> Result<(), String> {
-        let mut entry = EmbeddingEntry::new(embedding).with_value(value);
+        let mut entry = EmbeddingEntry::new(self.prepare(embedding)?).with_value(value);
         if let Some(m) = metadata {
             entry = entry.with_metadata(m);
         }
         self.store.set(key, entry)
     }
 
-    /// Get exact match by key
+    /// Normalize an embedding for storage if configured to
+    fn prepare(&self, mut embedding: Vec<f32>) -> Result<Vec<f32>, String> {
+        if self.config.normalize_on_insert {
+            if embedding.iter().all(|&x| x == 0.0) {
+                return Err("Cannot normalize a zero vector".to_string());
+            }
+            normalize_vector(&mut embedding);
+        }
+        Ok(embedding)
+    }
+
+    /// The query as the store should see it: normalized when stored vectors
+    /// are, so cosine-by-dot-product scores match plain cosine
+    fn query<'a>(&self, query: &'a [f32]) -> Cow<'a, [f32]> {
+        if self.config.normalize_on_insert && self.config.metric == DistanceMetric::Cosine {
+            let mut normalized = query.to_vec();
+            normalize_vector(&mut normalized);
+            Cow::Owned(normalized)
+        } else {
+            Cow::Borrowed(query)
+        }
+    }
+
+    /// Store many embeddings with their values, returning the indices of
+    /// entries rejected for a dimension mismatch
+    pub fn set_many(&self, entries: impl IntoIterator<Item = (Bytes, Vec<f32>, Bytes)>) -> Vec<usize> {
+        let mut rejected = Vec::new();
+        // Batch index of each entry handed to the store
+        let mut indices = Vec::new();
+        let mut accepted = Vec::new();
+        for (i, (key, embedding, value)) in entries.into_iter().enumerate() {
+            match self.prepare(embedding) {
+                Ok(embedding) => {
+                    indices.push(i);
+                    accepted.push((key, EmbeddingEntry::new(embedding).with_value(value)));
+                }
+                Err(_) => rejected.push(i),
+            }
+        }
+        rejected.extend(self.store.set_many(accepted).into_iter().map(|i| indices[i]));
+        rejected.sort_unstable();
+        rejected
+    }
+
+    /// Staleness of an entry created at `created_at`:
+    /// `Some(false)` fresh, `Some(true)` stale, `None` expired
+    fn staleness(&self, created_at: Instant) -> Option<bool> {
+        let ttl = match self.config.ttl {
+            Some(ttl) => ttl,
+            None => return Some(false),
+        };
+        let age = created_at.elapsed();
+        if age < ttl {
+            Some(false)
+        } else if age < ttl + self.config.stale_ttl {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    /// Get exact match by key, scored as a query for its own embedding
+    /// would be (1.0 for cosine, 0.0 for Euclidean)
     pub fn get(&self, key: &Bytes) -> Option<SemanticResult> {
-        self.store.get(key).map(|entry| SemanticResult {
+        let entry = self.store.get(key)?;
+        let stale = match self.staleness(entry.created_at) {
+            Some(stale) => stale,
+            None => {
+                self.store.del(key);
+                return None;
+            }
+        };
+        let vector = entry.vector();
+        Some(SemanticResult {
             key: key.clone(),
             value: entry.value,
-            similarity: 1.0,
+            similarity: self.store.metric().score(&vector, &vector),
             metadata: entry.metadata,
+            stale,
         })
     }
 
     /// Semantic lookup by embedding similarity
     pub fn semantic_get(&self, query_embedding: &[f32]) -> Vec<SemanticResult> {
-        let nearest = self.store.find_nearest(
-            query_embedding,
-            self.config.max_results,
+        self.semantic_get_filtered(query_embedding, |_| true)
+    }
+
+    /// Semantic lookup restricted to entries accepted by `predicate`, e.g. to
+    /// scope a shared cache by tenant or model through the entry metadata
+    pub fn semantic_get_filtered(
+        &self,
+        query_embedding: &[f32],
+        predicate: impl Fn(&EmbeddingEntry) -> bool,
+    ) -> Vec<SemanticResult> {
+        self.nearest(query_embedding, self.config.max_results, predicate)
+    }
+
+    /// Up to `k` live entries accepted by `predicate` whose score passes
+    /// the threshold, closest first. Every lookup goes through here, and
+    /// the store compares and orders scores with `DistanceMetric::within`
+    /// and `compare`, so all lookups agree on what counts as a match.
+    fn nearest(
+        &self,
+        query_embedding: &[f32],
+        k: usize,
+        predicate: impl Fn(&EmbeddingEntry) -> bool,
+    ) -> Vec<SemanticResult> {
+        // Expired entries are skipped in the scan too, so they don't use up result slots
+        let nearest = self.store.find_nearest_filtered(
+            &self.query(query_embedding),
+            k,
             self.config.similarity_threshold,
+            |entry| self.staleness(entry.created_at).is_some() && predicate(entry),
         );
 
         nearest
             .into_iter()
             .filter_map(|(key, similarity)| {
-                self.store.get(&key).map(|entry| SemanticResult {
+                let entry = self.store.get(&key)?;
+                let stale = self.staleness(entry.created_at)?;
+                Some(SemanticResult {
                     key,
                     value: entry.value,
                     similarity,
                     metadata: entry.metadata,
+                    stale,
                 })
             })
             .collect()
     }
 
-    /// Check if there's a semantic match above threshold
+    /// Check if any live entry is within the threshold
     pub fn has_semantic_match(&self, query_embedding: &[f32]) -> bool {
-        let nearest = self.store.find_nearest(query_embedding, 1, self.config.similarity_threshold);
-        !nearest.is_empty()
+        self.best_match(query_embedding).is_some()
     }
 
     /// Get the best semantic match
     pub fn best_match(&self, query_embedding: &[f32]) -> Option<SemanticResult> {
-        self.semantic_get(query_embedding).into_iter().next()
+        self.nearest(query_embedding, 1, |_| true).into_iter().next()
     }
 
     /// Delete by key
@@ -142,6 +299,11 @@ impl SemanticCache {
         self.store.del(key)
     }
 
+    /// Remove every entry
+    pub fn clear(&self) {
+        self.store.clear();
+    }
+
     /// Get cache size
     pub fn len(&self) -> usize {
         self.store.len()
@@ -251,4 +413,137 @@ mod tests {
         let results = cache.semantic_get(&[0.0, 1.0, 0.0]);
         assert!(results.is_empty());
     }
+
+    #[test]
+    fn test_semantic_get_filtered() {
+        let cache = SemanticCache::new(
+            SemanticCacheConfig::default()
+                .with_dimension(3)
+                .with_threshold(0.5)
+                .with_max_results(2),
+        );
+        let entries: [(&'static [u8], [f32; 3], &str); 4] = [
+            (b"b1", [1.0, 0.0, 0.0], "tenant=B"),
+            (b"b2", [0.99, 0.05, 0.0], "tenant=B"),
+            (b"a1", [0.9, 0.3, 0.0], "tenant=A"),
+            (b"a2", [0.8, 0.5, 0.0], "tenant=A"),
+        ];
+        for (key, embedding, tenant) in entries {
+            cache
+                .set(
+                    Bytes::from_static(key),
+                    embedding.to_vec(),
+                    Bytes::from_static(b"r"),
+                    Some(tenant.to_string()),
+                )
+                .unwrap();
+        }
+
+        // Tenant B's entries are closer, but A still gets its full top-k
+        let query = [1.0, 0.0, 0.0];
+        let results = cache.semantic_get_filtered(&query, |e| e.metadata.as_deref() == Some("tenant=A"));
+        let keys: Vec<&[u8]> = results.iter().map(|r| r.key.as_ref()).collect();
+        assert_eq!(keys, vec![b"a1".as_ref(), b"a2".as_ref()]);
+
+        let unfiltered = cache.semantic_get(&query);
+        assert!(unfiltered.iter().all(|r| r.metadata.as_deref() == Some("tenant=B")));
+    }
+
+    #[test]
+    fn test_anti_correlated_vectors_never_match() {
+        let base = SemanticCacheConfig::default().with_dimension(3).with_threshold(0.5);
+        let ttl = base.clone().with_ttl(Duration::from_millis(20)).with_stale_ttl(Duration::ZERO);
+        for config in [base.clone(), base.clone().with_normalize_on_insert(), base.with_quantization()] {
+            let cache = SemanticCache::new(config);
+            cache.set(Bytes::from_static(b"opposite"), vec![-1.0, -0.1, 0.0], Bytes::new(), None).unwrap();
+            let query = [1.0, 0.1, 0.0];
+            assert!(cache.semantic_get(&query).is_empty());
+            assert!(cache.best_match(&query).is_none());
+            assert!(!cache.has_semantic_match(&query));
+            // Its own direction still matches, scored like an exact get
+            let exact = cache.best_match(&[-1.0, -0.1, 0.0]).unwrap();
+            assert!((exact.similarity - cache.get(&Bytes::from_static(b"opposite")).unwrap().similarity).abs() < 0.02);
+        }
+
+        // Expired entries don't count as matches for any lookup
+        let cache = SemanticCache::new(ttl);
+        cache.set(Bytes::from_static(b"q"), vec![1.0, 0.0, 0.0], Bytes::new(), None).unwrap();
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(!cache.has_semantic_match(&[1.0, 0.0, 0.0]));
+        assert!(cache.semantic_get(&[1.0, 0.0, 0.0]).is_empty());
+    }
+
+    #[test]
+    fn test_euclidean_cache() {
+        let cache = SemanticCache::new(
+            SemanticCacheConfig::default()
+                .with_dimension(2)
+                .with_metric(DistanceMetric::Euclidean)
+                .with_threshold(1.0),
+        );
+        cache.set(Bytes::from_static(b"near"), vec![1.0, 1.0], Bytes::from_static(b"r1"), None).unwrap();
+        cache.set(Bytes::from_static(b"far"), vec![5.0, 5.0], Bytes::from_static(b"r2"), None).unwrap();
+
+        let best = cache.best_match(&[1.2, 1.0]).unwrap();
+        assert_eq!(best.key.as_ref(), b"near");
+        assert_eq!(cache.get(&Bytes::from_static(b"near")).unwrap().similarity, 0.0);
+        assert!(best.similarity < 0.3);
+        assert!(cache.has_semantic_match(&[4.5, 5.0]));
+        // Same direction as both entries but too far from either
+        assert!(!cache.has_semantic_match(&[3.0, 3.0]));
+    }
+
+    #[test]
+    fn test_normalize_on_insert_matches_cosine() {
+        let config = SemanticCacheConfig::default().with_dimension(3).with_threshold(0.0).with_max_results(10);
+        let cosine = SemanticCache::new(config.clone());
+        let normalized = SemanticCache::new(config.with_normalize_on_insert());
+
+        let vectors = [[3.0, 0.5, 0.0], [10.0, 9.0, 1.0], [0.2, 0.1, 0.3], [5.0, -1.0, 2.0], [0.01, 0.0, 0.0]];
+        for (i, v) in vectors.iter().enumerate() {
+            let key = Bytes::from(format!("q{}", i));
+            cosine.set(key.clone(), v.to_vec(), Bytes::new(), None).unwrap();
+            normalized.set(key, v.to_vec(), Bytes::new(), None).unwrap();
+        }
+        assert!(normalized.set(Bytes::from_static(b"zero"), vec![0.0; 3], Bytes::new(), None).is_err());
+
+        let query = [4.0, 1.0, 0.5];
+        let expected = cosine.semantic_get(&query);
+        let actual = normalized.semantic_get(&query);
+        assert_eq!(expected.len(), actual.len());
+        for (e, a) in expected.iter().zip(&actual) {
+            assert_eq!(e.key, a.key);
+            assert!((e.similarity - a.similarity).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_stale_while_revalidate() {
+        // Entries are stale as soon as they're written, for 50ms
+        let config = SemanticCacheConfig::default()
+            .with_dimension(3)
+            .with_ttl(Duration::ZERO)
+            .with_stale_ttl(Duration::from_millis(50));
+        let cache = SemanticCache::new(config);
+        let key = Bytes::from_static(b"q1");
+        cache
+            .set(key.clone(), vec![1.0, 0.0, 0.0], Bytes::from_static(b"r1"), None)
+            .unwrap();
+
+        let result = cache.get(&key).unwrap();
+        assert!(result.stale);
+        assert_eq!(result.value, Some(Bytes::from_static(b"r1")));
+        assert!(cache.best_match(&[1.0, 0.0, 0.0]).unwrap().stale);
+
+        std::thread::sleep(Duration::from_millis(80));
+        assert!(cache.best_match(&[1.0, 0.0, 0.0]).is_none());
+        assert!(cache.get(&key).is_none());
+
+        // Without a TTL entries are always fresh
+        let cache = create_test_cache();
+        cache
+            .set(key.clone(), vec![1.0, 0.0, 0.0], Bytes::from_static(b"r1"), None)
+            .unwrap();
+        assert!(!cache.get(&key).unwrap().stale);
+    }
 }
@@ -2,32 +2,243 @@
 //!
 //! SIMD-accelerated similarity computations.
 
-/// SIMD operations trait for vectors
-pub trait SimdOps {
-    fn dot(&self, other: &Self) -> f32;
-    fn magnitude(&self) -> f32;
-    fn normalize(&mut self);
+use std::sync::Arc;
+
+/// Vector kernels behind similarity search. `Scalar` is portable and
+/// deterministic; `Simd` uses AVX2/FMA where the CPU has them. Pick one with
+/// `best_available()`, or force `Scalar` for reproducible results.
+pub trait SimdOps: Send + Sync {
+    /// Implementation name, for logs and benchmarks
+    fn name(&self) -> &'static str;
+
+    /// Dot product of two equal-length vectors
+    fn dot(&self, a: &[f32], b: &[f32]) -> f32;
+
+    /// Cosine similarity in [-1, 1]; 0 if either vector is all zeros
+    fn cosine(&self, a: &[f32], b: &[f32]) -> f32;
+
+    /// Euclidean (L2) distance
+    fn l2(&self, a: &[f32], b: &[f32]) -> f32;
+}
+
+/// Plain Rust kernels, available on every target
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Scalar;
+
+impl SimdOps for Scalar {
+    fn name(&self) -> &'static str {
+        "scalar"
+    }
+
+    #[inline]
+    fn dot(&self, a: &[f32], b: &[f32]) -> f32 {
+        dot_product(a, b)
+    }
+
+    #[inline]
+    fn cosine(&self, a: &[f32], b: &[f32]) -> f32 {
+        cosine_similarity(a, b)
+    }
+
+    #[inline]
+    fn l2(&self, a: &[f32], b: &[f32]) -> f32 {
+        euclidean_distance(a, b)
+    }
+}
+
+/// AVX2/FMA kernels; only constructible on a CPU that supports them
+#[derive(Debug, Clone, Copy)]
+pub struct Simd {
+    _detected: (),
+}
+
+impl Simd {
+    /// The AVX2 kernels, or None if this CPU lacks AVX2 or FMA
+    pub fn new() -> Option<Self> {
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+            return Some(Self { _detected: () });
+        }
+        None
+    }
+}
+
+impl SimdOps for Simd {
+    fn name(&self) -> &'static str {
+        "avx2"
+    }
+
+    #[inline]
+    fn dot(&self, a: &[f32], b: &[f32]) -> f32 {
+        // The kernels load `b` for as many lanes as `a` has
+        assert_eq!(a.len(), b.len(), "Vector dimensions must match");
+        // SAFETY: `Simd::new` only succeeds once AVX2 and FMA are detected,
+        // and the lengths match
+        #[cfg(target_arch = "x86_64")]
+        return unsafe { avx2::dot(a, b) };
+        #[cfg(not(target_arch = "x86_64"))]
+        Scalar.dot(a, b)
+    }
+
+    #[inline]
+    fn cosine(&self, a: &[f32], b: &[f32]) -> f32 {
+        assert_eq!(a.len(), b.len(), "Vector dimensions must match");
+        #[cfg(target_arch = "x86_64")]
+        let (dot, a_norm_sq, b_norm_sq) = unsafe { avx2::dot_and_norms(a, b) };
+        #[cfg(not(target_arch = "x86_64"))]
+        let (dot, a_norm_sq, b_norm_sq) = (Scalar.dot(a, b), Scalar.dot(a, a), Scalar.dot(b, b));
+        DistanceMetric::Cosine.score_from_dot(dot, a_norm_sq, b_norm_sq)
+    }
+
+    #[inline]
+    fn l2(&self, a: &[f32], b: &[f32]) -> f32 {
+        assert_eq!(a.len(), b.len(), "Vector dimensions must match");
+        #[cfg(target_arch = "x86_64")]
+        return unsafe { avx2::l2_sq(a, b) }.sqrt();
+        #[cfg(not(target_arch = "x86_64"))]
+        Scalar.l2(a, b)
+    }
+}
+
+/// The fastest kernels this CPU supports: `Simd` with AVX2, else `Scalar`
+pub fn best_available() -> Arc<dyn SimdOps> {
+    select(Simd::new())
+}
+
+fn select(simd: Option<Simd>) -> Arc<dyn SimdOps> {
+    match simd {
+        Some(simd) => Arc::new(simd),
+        None => Arc::new(Scalar),
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    //! Callers must have checked for AVX2 and FMA (see `Simd::new`)
+
+    use std::arch::x86_64::*;
+
+    const LANES: usize = 8;
+
+    #[target_feature(enable = "avx2,fma")]
+    pub(super) unsafe fn dot(a: &[f32], b: &[f32]) -> f32 {
+        let chunks = a.len() / LANES;
+        let mut acc = _mm256_setzero_ps();
+        for i in 0..chunks {
+            let x = _mm256_loadu_ps(a.as_ptr().add(i * LANES));
+            let y = _mm256_loadu_ps(b.as_ptr().add(i * LANES));
+            acc = _mm256_fmadd_ps(x, y, acc);
+        }
+        let tail: f32 = (chunks * LANES..a.len()).map(|i| a[i] * b[i]).sum();
+        sum(acc) + tail
+    }
+
+    /// a·b, |a|² and |b|² in a single pass
+    #[target_feature(enable = "avx2,fma")]
+    pub(super) unsafe fn dot_and_norms(a: &[f32], b: &[f32]) -> (f32, f32, f32) {
+        let chunks = a.len() / LANES;
+        let (mut ab, mut aa, mut bb) = (_mm256_setzero_ps(), _mm256_setzero_ps(), _mm256_setzero_ps());
+        for i in 0..chunks {
+            let x = _mm256_loadu_ps(a.as_ptr().add(i * LANES));
+            let y = _mm256_loadu_ps(b.as_ptr().add(i * LANES));
+            ab = _mm256_fmadd_ps(x, y, ab);
+            aa = _mm256_fmadd_ps(x, x, aa);
+            bb = _mm256_fmadd_ps(y, y, bb);
+        }
+        let (mut dot, mut a_norm_sq, mut b_norm_sq) = (sum(ab), sum(aa), sum(bb));
+        for i in chunks * LANES..a.len() {
+            dot += a[i] * b[i];
+            a_norm_sq += a[i] * a[i];
+            b_norm_sq += b[i] * b[i];
+        }
+        (dot, a_norm_sq, b_norm_sq)
+    }
+
+    /// Squared L2 distance
+    #[target_feature(enable = "avx2,fma")]
+    pub(super) unsafe fn l2_sq(a: &[f32], b: &[f32]) -> f32 {
+        let chunks = a.len() / LANES;
+        let mut acc = _mm256_setzero_ps();
+        for i in 0..chunks {
+            let x = _mm256_loadu_ps(a.as_ptr().add(i * LANES));
+            let y = _mm256_loadu_ps(b.as_ptr().add(i * LANES));
+            let d = _mm256_sub_ps(x, y);
+            acc = _mm256_fmadd_ps(d, d, acc);
+        }
+        let tail: f32 = (chunks * LANES..a.len()).map(|i| (a[i] - b[i]).powi(2)).sum();
+        sum(acc) + tail
+    }
+
+    /// Horizontal sum of the eight lanes
+    #[target_feature(enable = "avx2,fma")]
+    unsafe fn sum(v: __m256) -> f32 {
+        let quad = _mm_add_ps(_mm256_castps256_ps128(v), _mm256_extractf128_ps(v, 1));
+        let pair = _mm_add_ps(quad, _mm_movehl_ps(quad, quad));
+        let single = _mm_add_ss(pair, _mm_shuffle_ps(pair, pair, 1));
+        _mm_cvtss_f32(single)
+    }
 }
 
-impl SimdOps for Vec<f32> {
+/// How embeddings are compared in nearest-neighbour search
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DistanceMetric {
+    /// Cosine similarity; higher is closer, threshold is a minimum
+    #[default]
+    Cosine,
+    /// Raw dot product; higher is closer, threshold is a minimum
+    DotProduct,
+    /// L2 distance; lower is closer, threshold is a maximum
+    Euclidean,
+}
+
+impl DistanceMetric {
+    /// Score `b` against `a` under this metric
     #[inline]
-    fn dot(&self, other: &Self) -> f32 {
-        dot_product(self, other)
+    pub fn score(&self, a: &[f32], b: &[f32]) -> f32 {
+        self.score_with(&Scalar, a, b)
     }
 
+    /// Score `b` against `a` using the given kernels
     #[inline]
-    fn magnitude(&self) -> f32 {
-        self.iter().map(|x| x * x).sum::<f32>().sqrt()
+    pub fn score_with(&self, ops: &dyn SimdOps, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            DistanceMetric::Cosine => ops.cosine(a, b),
+            DistanceMetric::DotProduct => ops.dot(a, b),
+            DistanceMetric::Euclidean => ops.l2(a, b),
+        }
     }
 
-    fn normalize(&mut self) {
-        let mag = self.magnitude();
-        if mag > 0.0 {
-            for x in self.iter_mut() {
-                *x /= mag;
+    /// Score from a precomputed dot product and squared norms, for vectors
+    /// that aren't stored as plain f32 slices
+    #[inline]
+    pub fn score_from_dot(&self, dot: f32, a_norm_sq: f32, b_norm_sq: f32) -> f32 {
+        match self {
+            DistanceMetric::Cosine => {
+                let denom = (a_norm_sq * b_norm_sq).sqrt();
+                if denom > 0.0 { dot / denom } else { 0.0 }
             }
+            DistanceMetric::DotProduct => dot,
+            DistanceMetric::Euclidean => (a_norm_sq - 2.0 * dot + b_norm_sq).max(0.0).sqrt(),
+        }
+    }
+
+    /// Whether a score is close enough given `threshold`
+    #[inline]
+    pub fn within(&self, score: f32, threshold: f32) -> bool {
+        match self {
+            DistanceMetric::Euclidean => score <= threshold,
+            _ => score >= threshold,
         }
     }
+
+    /// Order scores closest-first
+    pub fn compare(&self, a: f32, b: f32) -> std::cmp::Ordering {
+        let ordering = match self {
+            DistanceMetric::Euclidean => a.partial_cmp(&b),
+            _ => b.partial_cmp(&a),
+        };
+        ordering.unwrap_or(std::cmp::Ordering::Equal)
+    }
 }
 
 /// Compute dot product of two vectors
@@ -92,7 +303,6 @@ pub fn euclidean_distance(a: &[f32], b: &[f32]) -> f32 {
 }
 
 /// Normalize a vector in place
-#[allow(dead_code)]
 pub fn normalize_vector(v: &mut [f32]) {
     let mag: f32 = v.iter().map(|x| x * x).sum::<f32>().sqrt();
     if mag > 0.0 {
@@ -113,6 +323,7 @@ pub fn normalized(v: &[f32]) -> Vec<f32> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vector::random_vector;
 
     #[test]
     fn test_dot_product() {
@@ -149,6 +360,48 @@ mod tests {
         assert!((euclidean_distance(&a, &b) - 5.0).abs() < 1e-6);
     }
 
+    #[test]
+    fn test_metric_ordering() {
+        assert!(DistanceMetric::Euclidean.within(0.5, 1.0));
+        assert!(!DistanceMetric::Euclidean.within(1.5, 1.0));
+        assert!(DistanceMetric::Cosine.within(0.9, 0.8));
+        assert!(DistanceMetric::Euclidean.compare(0.1, 0.2).is_lt());
+        assert!(DistanceMetric::DotProduct.compare(0.1, 0.2).is_gt());
+    }
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        let Some(simd) = Simd::new() else {
+            return; // no AVX2 on this machine
+        };
+        let close = |x: f32, y: f32| (x - y).abs() <= 1e-4 * x.abs().max(1.0);
+        let mut seed = 7;
+        // Odd lengths exercise the non-vectorized tail
+        for dim in [1, 7, 8, 9, 31, 384, 1001] {
+            let a = random_vector(&mut seed, dim);
+            let b = random_vector(&mut seed, dim);
+            assert!(close(simd.dot(&a, &b), Scalar.dot(&a, &b)), "dot, dim {}", dim);
+            assert!(close(simd.cosine(&a, &b), Scalar.cosine(&a, &b)), "cosine, dim {}", dim);
+            assert!(close(simd.l2(&a, &b), Scalar.l2(&a, &b)), "l2, dim {}", dim);
+        }
+        assert_eq!(simd.cosine(&[0.0; 16], &[1.0; 16]), 0.0);
+
+        // A longer `a` must not read past the end of `b`
+        assert!(std::panic::catch_unwind(|| simd.dot(&[1.0; 16], &[1.0; 8])).is_err());
+        assert!(std::panic::catch_unwind(|| simd.cosine(&[1.0; 16], &[1.0; 8])).is_err());
+        assert!(std::panic::catch_unwind(|| simd.l2(&[1.0; 16], &[1.0; 8])).is_err());
+    }
+
+    #[test]
+    fn test_best_available() {
+        assert_eq!(select(None).name(), "scalar");
+        #[cfg(target_arch = "x86_64")]
+        let expected = if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") { "avx2" } else { "scalar" };
+        #[cfg(not(target_arch = "x86_64"))]
+        let expected = "scalar";
+        assert_eq!(best_available().name(), expected);
+    }
+
     #[test]
     fn test_normalize() {
         let v = vec![3.0, 4.0, 0.0];